@@ -0,0 +1,123 @@
+/* C ABI for the webcrypto crate, built with `--features cdylib`. */
+#ifndef WEBCRYPTO_H
+#define WEBCRYPTO_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define WEBCRYPTO_USAGE_ENCRYPT (1u << 0)
+#define WEBCRYPTO_USAGE_DECRYPT (1u << 1)
+#define WEBCRYPTO_USAGE_SIGN (1u << 2)
+#define WEBCRYPTO_USAGE_VERIFY (1u << 3)
+#define WEBCRYPTO_USAGE_WRAP_KEY (1u << 4)
+#define WEBCRYPTO_USAGE_UNWRAP_KEY (1u << 5)
+#define WEBCRYPTO_USAGE_DERIVE_KEY (1u << 6)
+#define WEBCRYPTO_USAGE_DERIVE_BITS (1u << 7)
+#define WEBCRYPTO_USAGE_ENCAPSULATE_BITS (1u << 8)
+#define WEBCRYPTO_USAGE_DECAPSULATE_BITS (1u << 9)
+
+typedef enum {
+  WEBCRYPTO_OK = 0,
+  WEBCRYPTO_NULL_POINTER = 1,
+  WEBCRYPTO_INVALID_ARGUMENT = 2,
+  WEBCRYPTO_OPERATION_ERROR = 3,
+  WEBCRYPTO_NOT_SUPPORTED_ERROR = 4,
+  WEBCRYPTO_SYNTAX_ERROR = 5,
+  WEBCRYPTO_DATA_ERROR = 6,
+  WEBCRYPTO_INVALID_ACCESS_ERROR = 7,
+  WEBCRYPTO_QUOTA_EXCEEDED_ERROR = 8,
+  WEBCRYPTO_STORAGE_ERROR = 9,
+  WEBCRYPTO_WEAK_KEY_ERROR = 10,
+} WebCryptoStatus;
+
+typedef struct WebCryptoStorage WebCryptoStorage;
+typedef struct WebCryptoContext WebCryptoContext;
+typedef struct WebCryptoKey WebCryptoKey;
+
+/* Bytes owned by the library, release with webcrypto_buffer_free. */
+typedef struct {
+  uint8_t *data;
+  size_t len;
+} WebCryptoBuffer;
+
+WebCryptoStatus webcrypto_storage_open_file(const char *path,
+                                            WebCryptoStorage **storage);
+void webcrypto_storage_free(WebCryptoStorage *storage);
+
+WebCryptoContext *webcrypto_context_new(void);
+/* Takes ownership of `storage`. */
+WebCryptoContext *webcrypto_context_new_with_storage(WebCryptoStorage *storage);
+void webcrypto_context_free(WebCryptoContext *ctx);
+void webcrypto_key_free(WebCryptoKey *key);
+void webcrypto_buffer_free(WebCryptoBuffer buf);
+
+WebCryptoStatus webcrypto_get_random_values(WebCryptoContext *ctx,
+                                            uint8_t *out, size_t len);
+WebCryptoStatus webcrypto_random_uuid(WebCryptoContext *ctx,
+                                      WebCryptoBuffer *out);
+
+WebCryptoStatus webcrypto_generate_key_rsa(
+    WebCryptoContext *ctx, const char *name, size_t modulus_length,
+    const uint8_t *public_exponent, size_t public_exponent_len,
+    const char *hash, bool extractable, uint32_t usages,
+    WebCryptoKey **public_key, WebCryptoKey **private_key);
+/* `length` is in bits: 128, 192 or 256. */
+WebCryptoStatus webcrypto_generate_key_aes(WebCryptoContext *ctx,
+                                           const char *name, size_t length,
+                                           bool extractable, uint32_t usages,
+                                           WebCryptoKey **key);
+/* `length` is in bits, 0 for the block size of `hash`. */
+WebCryptoStatus webcrypto_generate_key_hmac(WebCryptoContext *ctx,
+                                            const char *hash, size_t length,
+                                            bool extractable, uint32_t usages,
+                                            WebCryptoKey **key);
+
+WebCryptoStatus webcrypto_import_key(WebCryptoContext *ctx, const char *format,
+                                     const uint8_t *key_data,
+                                     size_t key_data_len,
+                                     const char *key_algorithm,
+                                     const char *hash, bool extractable,
+                                     uint32_t usages, WebCryptoKey **key);
+
+WebCryptoStatus webcrypto_sign(WebCryptoContext *ctx, const char *name,
+                               size_t salt_length, const WebCryptoKey *key,
+                               const uint8_t *data, size_t data_len,
+                               WebCryptoBuffer *out);
+WebCryptoStatus webcrypto_verify(WebCryptoContext *ctx, const char *name,
+                                 size_t salt_length, const WebCryptoKey *key,
+                                 const uint8_t *signature,
+                                 size_t signature_len, const uint8_t *data,
+                                 size_t data_len, bool *valid);
+
+WebCryptoStatus webcrypto_encrypt(WebCryptoContext *ctx, const char *name,
+                                  const uint8_t *iv, size_t iv_len,
+                                  const WebCryptoKey *key, const uint8_t *data,
+                                  size_t data_len, WebCryptoBuffer *out);
+WebCryptoStatus webcrypto_decrypt(WebCryptoContext *ctx, const char *name,
+                                  const uint8_t *iv, size_t iv_len,
+                                  const WebCryptoKey *key, const uint8_t *data,
+                                  size_t data_len, WebCryptoBuffer *out);
+
+WebCryptoStatus webcrypto_wrap_key(WebCryptoContext *ctx, const char *format,
+                                   const WebCryptoKey *key,
+                                   const WebCryptoKey *wrapping_key,
+                                   const char *wrap_algorithm,
+                                   const uint8_t *iv, size_t iv_len,
+                                   WebCryptoBuffer *out);
+WebCryptoStatus webcrypto_unwrap_key(
+    WebCryptoContext *ctx, const char *format, const uint8_t *wrapped_key,
+    size_t wrapped_key_len, const WebCryptoKey *unwrapping_key,
+    const char *unwrap_algorithm, const uint8_t *iv, size_t iv_len,
+    const char *key_algorithm, const char *hash, bool extractable,
+    uint32_t usages, WebCryptoKey **key);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* WEBCRYPTO_H */
@@ -0,0 +1,228 @@
+//! Key ceremonies: standing up signing infrastructure from a declarative
+//! plan, with [`Context::run_ceremony`].
+//!
+//! A ceremony generates a key encryption key (KEK), splits it into
+//! Shamir shares for its custodians, generates the signing keys and wraps
+//! them under the KEK, and records every step. The KEK and the signing
+//! private keys never leave the ceremony unprotected: the report carries
+//! the shares, the wrapped private keys and the public keys only.
+//!
+//! [`Context::run_ceremony`]: crate::Context::run_ceremony
+mod shamir;
+
+use rand::CryptoRng;
+use rand::RngCore;
+
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::storage::InMemoryVault;
+use crate::subtle::AesKeyGenParams;
+use crate::subtle::AlgorithmIdentifer;
+use crate::subtle::CryptoKeyOrPair;
+use crate::subtle::EncryptParams;
+use crate::subtle::KeyGenParams;
+use crate::subtle::KeyUsage;
+use crate::subtle::SubtleCrypto;
+use crate::WebCryptoError;
+
+/// The KEK check value prefix, separating it from other hashes of keys.
+const CHECK_VALUE_CONTEXT: &[u8] = b"webcrypto ceremony check value\0";
+
+/// A signing key for the ceremony to generate.
+#[derive(Clone)]
+pub struct SigningKeySpec {
+  /// A name unique within the plan, eg. "root-2026".
+  pub label: String,
+  /// Parameters of an algorithm generating key pairs with the Sign usage,
+  /// eg. ECDSA or RSA-PSS.
+  pub algorithm: KeyGenParams,
+}
+
+#[derive(Clone)]
+pub struct CeremonyPlan {
+  /// The name of the ceremony, the first entry of the log.
+  pub name: String,
+  /// The length in bits of the AES-KWP key encryption key, 128, 192 or
+  /// 256.
+  pub kek_length: usize,
+  /// The number of KEK shares, one per custodian, at most 255.
+  pub shares: u8,
+  /// The number of shares recovering the KEK, at least 2 and at most
+  /// `shares`.
+  pub threshold: u8,
+  pub signing_keys: Vec<SigningKeySpec>,
+}
+
+impl CeremonyPlan {
+  fn check(&self) -> Result<(), WebCryptoError> {
+    let unique = self.signing_keys.iter().enumerate().all(|(i, key)| {
+      self.signing_keys[..i]
+        .iter()
+        .all(|other| other.label != key.label)
+    });
+    if ![128, 192, 256].contains(&self.kek_length)
+      || self.threshold < 2
+      || self.threshold > self.shares
+      || !unique
+    {
+      return Err(WebCryptoError::SyntaxError);
+    }
+
+    Ok(())
+  }
+}
+
+/// A share of the KEK, the value of the sharing polynomial at `index`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Share {
+  /// The x-coordinate, from 1 to 255.
+  pub index: u8,
+  pub value: Vec<u8>,
+}
+
+/// The outcome of generating one signing key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttestationRecord {
+  pub label: String,
+  /// The algorithm name, eg. "ECDSA".
+  pub algorithm: &'static str,
+  /// The public key as SPKI.
+  pub public_key: Vec<u8>,
+  /// The SHA-256 fingerprint of `public_key`.
+  pub fingerprint: Vec<u8>,
+  /// The private key as PKCS#8, wrapped under the KEK with AES-KWP.
+  pub wrapped_private_key: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CeremonyReport {
+  /// One share per custodian, to hand out and delete from the report.
+  pub shares: Vec<Share>,
+  /// Identifies the KEK without revealing it, to check a recombined KEK.
+  pub kek_check_value: Vec<u8>,
+  pub records: Vec<AttestationRecord>,
+  /// Every step in order, naming each key by its fingerprint.
+  pub log: Vec<String>,
+  /// The SHA-256 of the log lines, each followed by a newline, for the
+  /// witnesses to sign or publish.
+  pub log_digest: Vec<u8>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The check value of the KEK `kek`.
+pub fn kek_check_value(kek: &[u8]) -> Vec<u8> {
+  let mut hasher = Sha256::new();
+  hasher.update(CHECK_VALUE_CONTEXT);
+  hasher.update(kek);
+  hasher.finalize()[..8].to_vec()
+}
+
+/// Recover the KEK from at least the threshold number of `shares`, to
+/// import it as a raw "AES-KWP" key and unwrap the signing keys. Compare
+/// its `kek_check_value` with the one of the report: too few shares
+/// recover a wrong key without failing.
+pub fn combine_shares(shares: &[Share]) -> Result<Vec<u8>, WebCryptoError> {
+  shamir::combine(shares)
+}
+
+/// Run `plan` on a scratch storage, so no key of the ceremony reaches the
+/// storage of the caller, and return the report once every step
+/// succeeded. A failed step aborts the ceremony without a report.
+pub(crate) fn run<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  plan: &CeremonyPlan,
+) -> Result<CeremonyReport, WebCryptoError> {
+  plan.check()?;
+  let mut subtle = SubtleCrypto::new(rng, InMemoryVault::new());
+  let mut log = vec![format!("ceremony {:?}", plan.name)];
+
+  let kek = match subtle.generate_key(
+    KeyGenParams::AesKeyGenParams(AesKeyGenParams {
+      name: "AES-KWP",
+      length: plan.kek_length,
+    }),
+    true,
+    vec![KeyUsage::WrapKey],
+  )? {
+    CryptoKeyOrPair::CryptoKey(key) => key,
+    CryptoKeyOrPair::CryptoKeyPair(_) => {
+      return Err(WebCryptoError::OperationError)
+    }
+  };
+  let mut kek_bytes = subtle.export_key("raw", &kek)?;
+  let kek_check_value = kek_check_value(&kek_bytes);
+  log.push(format!(
+    "generate {}-bit AES-KWP key encryption key, check value {}",
+    plan.kek_length,
+    hex(&kek_check_value)
+  ));
+
+  let shares =
+    shamir::split(&mut subtle.rng, &kek_bytes, plan.threshold, plan.shares);
+  kek_bytes.fill(0);
+  log.push(format!(
+    "split key encryption key into {} shares, threshold {}",
+    plan.shares, plan.threshold
+  ));
+
+  let mut records = Vec::with_capacity(plan.signing_keys.len());
+  for spec in &plan.signing_keys {
+    let key = match subtle.generate_key(
+      spec.algorithm,
+      true,
+      vec![KeyUsage::Sign, KeyUsage::Verify],
+    )? {
+      CryptoKeyOrPair::CryptoKeyPair(key) => key,
+      CryptoKeyOrPair::CryptoKey(_) => {
+        return Err(WebCryptoError::NotSupportedError);
+      }
+    };
+    let algorithm = key.private_key.algorithm.name();
+    let public_key = subtle.export_key("spki", &key.public_key)?;
+    let fingerprint = Sha256::digest(&public_key).to_vec();
+    log.push(format!(
+      "generate {} key {:?}, SPKI SHA-256 {}",
+      algorithm,
+      spec.label,
+      hex(&fingerprint)
+    ));
+
+    let wrapped_private_key = subtle.wrap_key(
+      "pkcs8",
+      &key.private_key,
+      &kek,
+      EncryptParams::AlgorithmIdentifer(AlgorithmIdentifer { name: "AES-KWP" }),
+    )?;
+    log.push(format!(
+      "wrap private key {:?} under the key encryption key, SHA-256 {}",
+      spec.label,
+      hex(&Sha256::digest(&wrapped_private_key))
+    ));
+
+    records.push(AttestationRecord {
+      label: spec.label.clone(),
+      algorithm,
+      public_key,
+      fingerprint,
+      wrapped_private_key,
+    });
+  }
+
+  let mut hasher = Sha256::new();
+  for line in &log {
+    hasher.update(line.as_bytes());
+    hasher.update(b"\n");
+  }
+
+  Ok(CeremonyReport {
+    shares,
+    kek_check_value,
+    records,
+    log,
+    log_digest: hasher.finalize().to_vec(),
+  })
+}
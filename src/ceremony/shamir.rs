@@ -0,0 +1,113 @@
+// Shamir secret sharing over GF(2^8), byte by byte, with the AES
+// reduction polynomial x^8 + x^4 + x^3 + x + 1.
+
+use rand::RngCore;
+
+use super::Share;
+use crate::WebCryptoError;
+
+/// Multiply in GF(2^8) without branching on the operands.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+  let mut product = 0;
+  for _ in 0..8 {
+    product ^= a & 0u8.wrapping_sub(b & 1);
+    let carry = 0u8.wrapping_sub(a >> 7);
+    a = (a << 1) ^ (0x1b & carry);
+    b >>= 1;
+  }
+  product
+}
+
+/// The inverse of `a`, a^254, for nonzero `a`.
+fn inv(a: u8) -> u8 {
+  let mut result = 1;
+  let mut power = a;
+  let mut exponent = 254u8;
+  while exponent > 0 {
+    if exponent & 1 == 1 {
+      result = mul(result, power);
+    }
+    power = mul(power, power);
+    exponent >>= 1;
+  }
+  result
+}
+
+/// Split `secret` into `shares` shares at x = 1, 2, ..., any `threshold`
+/// of which recover it.
+pub(crate) fn split<R: RngCore>(
+  rng: &mut R,
+  secret: &[u8],
+  threshold: u8,
+  shares: u8,
+) -> Vec<Share> {
+  let mut result = (1..=shares)
+    .map(|index| Share {
+      index,
+      value: Vec::with_capacity(secret.len()),
+    })
+    .collect::<Vec<_>>();
+
+  let mut coefficients = vec![0; threshold as usize];
+  for &byte in secret {
+    coefficients[0] = byte;
+    rng.fill_bytes(&mut coefficients[1..]);
+
+    for share in &mut result {
+      // Horner's rule.
+      let y = coefficients
+        .iter()
+        .rev()
+        .fold(0, |y, &coefficient| mul(y, share.index) ^ coefficient);
+      share.value.push(y);
+    }
+  }
+  coefficients.fill(0);
+
+  result
+}
+
+/// Recover the secret from `shares` by Lagrange interpolation at x = 0.
+/// With fewer shares than the threshold the result is garbage, not an
+/// error.
+pub(crate) fn combine(shares: &[Share]) -> Result<Vec<u8>, WebCryptoError> {
+  let length = shares
+    .first()
+    .ok_or(WebCryptoError::OperationError)?
+    .value
+    .len();
+  let distinct = shares.iter().enumerate().all(|(i, share)| {
+    share.index != 0
+      && share.value.len() == length
+      && shares[..i].iter().all(|other| other.index != share.index)
+  });
+  if !distinct {
+    return Err(WebCryptoError::DataError);
+  }
+
+  let weights = shares
+    .iter()
+    .map(|share| {
+      shares
+        .iter()
+        .filter(|other| other.index != share.index)
+        .fold(1, |weight, other| {
+          // x_j / (x_j - x_i), subtraction being XOR.
+          mul(weight, mul(other.index, inv(other.index ^ share.index)))
+        })
+    })
+    .collect::<Vec<_>>();
+
+  Ok(
+    (0..length)
+      .map(|i| {
+        shares
+          .iter()
+          .zip(&weights)
+          .fold(0, |secret, (share, &weight)| {
+            secret ^ mul(share.value[i], weight)
+          })
+      })
+      .collect(),
+  )
+}
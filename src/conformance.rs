@@ -0,0 +1,358 @@
+//! A conformance matrix of the key import and export subsystem: every
+//! key format for every algorithm, exported from fixed keys.
+//!
+//! Each entry records whether the export is supported and, if it is,
+//! whether importing the exported bytes and exporting them again gives
+//! the same bytes. The matrix is kept in `testdata/conformance`, and the
+//! crate tests compare it byte for byte, so a refactor changing an
+//! encoding, or breaking a round trip, shows up as a fixture diff.
+//!
+//! Regenerate the fixtures after an intended change with
+//! `UPDATE_CONFORMANCE_FIXTURES=1 cargo test --all-features`, and review
+//! the diff.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand::rngs::OsRng;
+
+use crate::storage::InMemoryVault;
+use crate::subtle::AlgorithmIdentifer;
+use crate::subtle::CryptoKey;
+use crate::subtle::DhGroup;
+use crate::subtle::DhKeyImportParams;
+use crate::subtle::EcKeyImportParams;
+use crate::subtle::HashAlgorithmIdentifer;
+use crate::subtle::HmacImportParams;
+use crate::subtle::ImportParams;
+use crate::subtle::KeyType;
+use crate::subtle::KeyUsage;
+use crate::subtle::NamedCurve;
+use crate::subtle::RsaHashedImportParams;
+use crate::subtle::SubtleCrypto;
+
+/// Every format keys may be exported in.
+pub const FORMATS: &[&str] = &[
+  "raw",
+  "raw-compressed",
+  "raw-private",
+  "pkcs8",
+  "spki",
+  "jwk",
+];
+
+/// The name of the file listing the entries of the matrix.
+pub const MANIFEST: &str = "MANIFEST";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outcome {
+  /// The key cannot be exported in the format.
+  Unsupported,
+  /// The export cannot be imported again, or exports differently.
+  OneWay,
+  /// Importing the export and exporting it again gives the same bytes.
+  RoundTrip,
+}
+
+impl Outcome {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Outcome::Unsupported => "unsupported",
+      Outcome::OneWay => "one-way",
+      Outcome::RoundTrip => "round-trip",
+    }
+  }
+}
+
+/// One cell of the matrix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Artifact {
+  /// The key, its type and the format, eg. "ecdsa-p256.public.spki",
+  /// also the name of the fixture file holding `data`.
+  pub name: String,
+  pub outcome: Outcome,
+  /// The exported key, empty when unsupported.
+  pub data: Vec<u8>,
+}
+
+/// A fixed key the matrix is exported from.
+struct Source {
+  name: &'static str,
+  format: &'static str,
+  data: &'static [u8],
+  params: ImportParams,
+  private_usages: &'static [KeyUsage],
+  /// The usages of the public key, `None` for secret keys.
+  public_usages: Option<&'static [KeyUsage]>,
+}
+
+fn sources() -> Vec<Source> {
+  let hash = HashAlgorithmIdentifer { name: "SHA-256" };
+  let rsa = |name| {
+    ImportParams::RsaHashedImportParams(RsaHashedImportParams { name, hash })
+  };
+  let ec = |name, named_curve| {
+    ImportParams::EcKeyImportParams(EcKeyImportParams { name, named_curve })
+  };
+  let identifier =
+    |name| ImportParams::AlgorithmIdentifer(AlgorithmIdentifer { name });
+
+  #[allow(unused_mut)]
+  let mut sources = vec![
+    Source {
+      name: "rsassa-pkcs1-v1_5-sha256",
+      format: "pkcs8",
+      data: include_bytes!("../testdata/rsa1024.pk8"),
+      params: rsa("RSASSA-PKCS1-v1_5"),
+      private_usages: &[KeyUsage::Sign],
+      public_usages: Some(&[KeyUsage::Verify]),
+    },
+    Source {
+      name: "rsa-oaep-sha256",
+      format: "pkcs8",
+      data: include_bytes!("../testdata/rsa1024.pk8"),
+      params: rsa("RSA-OAEP"),
+      private_usages: &[KeyUsage::Decrypt],
+      public_usages: Some(&[KeyUsage::Encrypt]),
+    },
+    Source {
+      name: "ecdsa-p256",
+      format: "pkcs8",
+      data: include_bytes!("../testdata/p256.pk8"),
+      params: ec("ECDSA", NamedCurve::P256),
+      private_usages: &[KeyUsage::Sign],
+      public_usages: Some(&[KeyUsage::Verify]),
+    },
+    Source {
+      name: "ecdh-p384",
+      format: "pkcs8",
+      data: include_bytes!("../testdata/p384.pk8"),
+      params: ec("ECDH", NamedCurve::P384),
+      private_usages: &[KeyUsage::DeriveBits],
+      public_usages: Some(&[]),
+    },
+    Source {
+      name: "dh-modp2048",
+      format: "raw-private",
+      data: &[0x5a; 256],
+      params: ImportParams::DhKeyImportParams(DhKeyImportParams {
+        name: "DH",
+        group: DhGroup::Modp2048,
+      }),
+      private_usages: &[KeyUsage::DeriveBits],
+      // `get_public_key` does not support DH.
+      public_usages: None,
+    },
+    Source {
+      name: "aes-gcm-256",
+      format: "raw",
+      data: &[0x11; 32],
+      params: identifier("AES-GCM"),
+      private_usages: &[KeyUsage::Encrypt, KeyUsage::Decrypt],
+      public_usages: None,
+    },
+    Source {
+      name: "aes-kw-128",
+      format: "raw",
+      data: &[0x22; 16],
+      params: identifier("AES-KW"),
+      private_usages: &[KeyUsage::WrapKey, KeyUsage::UnwrapKey],
+      public_usages: None,
+    },
+    Source {
+      name: "hmac-sha256",
+      format: "raw",
+      data: &[0x33; 32],
+      params: ImportParams::HmacImportParams(HmacImportParams {
+        name: "HMAC",
+        hash,
+        length: None,
+      }),
+      private_usages: &[KeyUsage::Sign, KeyUsage::Verify],
+      public_usages: None,
+    },
+    Source {
+      name: "chacha20-poly1305",
+      format: "raw",
+      data: &[0x44; 32],
+      params: identifier("ChaCha20-Poly1305"),
+      private_usages: &[KeyUsage::Encrypt, KeyUsage::Decrypt],
+      public_usages: None,
+    },
+    Source {
+      name: "ed25519",
+      format: "pkcs8",
+      data: include_bytes!("../testdata/ed25519_rfc8032.pk8"),
+      params: identifier("Ed25519"),
+      private_usages: &[KeyUsage::Sign],
+      public_usages: Some(&[KeyUsage::Verify]),
+    },
+  ];
+  #[cfg(feature = "k256")]
+  sources.push(Source {
+    name: "ecdsa-secp256k1",
+    format: "pkcs8",
+    data: include_bytes!("../testdata/k256.pk8"),
+    params: ec("ECDSA", NamedCurve::K256),
+    private_usages: &[KeyUsage::Sign],
+    public_usages: Some(&[KeyUsage::Verify]),
+  });
+  #[cfg(feature = "ed448")]
+  sources.push(Source {
+    name: "ed448",
+    format: "pkcs8",
+    data: include_bytes!("../testdata/ed448_rfc8032.pk8"),
+    params: identifier("Ed448"),
+    private_usages: &[KeyUsage::Sign],
+    public_usages: Some(&[KeyUsage::Verify]),
+  });
+  #[cfg(feature = "x448")]
+  sources.push(Source {
+    name: "x448",
+    format: "pkcs8",
+    data: include_bytes!("../testdata/x448_rfc7748.pk8"),
+    params: identifier("X448"),
+    private_usages: &[KeyUsage::DeriveBits],
+    public_usages: Some(&[]),
+  });
+  sources
+}
+
+type Subtle = SubtleCrypto<OsRng, InMemoryVault>;
+
+/// Export `key` in `format`, then import and export it again.
+fn artifact(
+  subtle: &mut Subtle,
+  name: String,
+  format: &str,
+  key: &CryptoKey<usize>,
+  params: ImportParams,
+  usages: &[KeyUsage],
+) -> Artifact {
+  let data = match subtle.export_key(format, key) {
+    Ok(data) => data,
+    Err(_) => {
+      return Artifact {
+        name,
+        outcome: Outcome::Unsupported,
+        data: vec![],
+      }
+    }
+  };
+
+  let again = subtle
+    .import_key(format, &data, params, true, usages.to_vec())
+    .and_then(|key| subtle.export_key(format, &key));
+  let outcome = match again {
+    Ok(again) if again == data => Outcome::RoundTrip,
+    _ => Outcome::OneWay,
+  };
+  Artifact {
+    name,
+    outcome,
+    data,
+  }
+}
+
+/// The matrix for the algorithms of the enabled features, in a fixed
+/// order.
+pub fn artifacts() -> Vec<Artifact> {
+  let mut subtle = SubtleCrypto::new(OsRng, InMemoryVault::new());
+  let mut artifacts = vec![];
+
+  for source in sources() {
+    let key = subtle
+      .import_key(
+        source.format,
+        source.data,
+        source.params,
+        true,
+        source.private_usages.to_vec(),
+      )
+      .expect("conformance source key");
+    let public_key = source.public_usages.map(|usages| {
+      subtle
+        .get_public_key(&key, usages.to_vec())
+        .expect("conformance public key")
+    });
+
+    let type_ = match key.type_ {
+      KeyType::Secret => "secret",
+      _ => "private",
+    };
+    for format in FORMATS {
+      let name = format!("{}.{}.{}", source.name, type_, format);
+      artifacts.push(artifact(
+        &mut subtle,
+        name,
+        format,
+        &key,
+        source.params,
+        source.private_usages,
+      ));
+    }
+    if let (Some(public_key), Some(usages)) = (public_key, source.public_usages)
+    {
+      for format in FORMATS {
+        let name = format!("{}.public.{}", source.name, format);
+        artifacts.push(artifact(
+          &mut subtle,
+          name,
+          format,
+          &public_key,
+          source.params,
+          usages,
+        ));
+      }
+    }
+  }
+
+  artifacts
+}
+
+/// The manifest of `artifacts`: one line per entry, its name and its
+/// outcome.
+pub fn manifest(artifacts: &[Artifact]) -> String {
+  artifacts
+    .iter()
+    .map(|artifact| format!("{} {}\n", artifact.name, artifact.outcome.name()))
+    .collect()
+}
+
+/// Write the manifest and the exported keys of `artifacts` to `dir`,
+/// replacing the fixtures there.
+pub fn write_fixtures(dir: &Path, artifacts: &[Artifact]) -> io::Result<()> {
+  if dir.exists() {
+    fs::remove_dir_all(dir)?;
+  }
+  fs::create_dir_all(dir)?;
+  fs::write(dir.join(MANIFEST), manifest(artifacts))?;
+  for artifact in artifacts {
+    if artifact.outcome != Outcome::Unsupported {
+      fs::write(dir.join(&artifact.name), &artifact.data)?;
+    }
+  }
+  Ok(())
+}
+
+/// The names of the entries of `artifacts` that differ from the
+/// fixtures in `dir`, in their outcome or their bytes. Entries missing
+/// from the manifest differ too.
+pub fn check_fixtures(
+  dir: &Path,
+  artifacts: &[Artifact],
+) -> io::Result<Vec<String>> {
+  let manifest = fs::read_to_string(dir.join(MANIFEST))?;
+  let mut differ = vec![];
+  for artifact in artifacts {
+    let line = format!("{} {}", artifact.name, artifact.outcome.name());
+    let same = manifest.lines().any(|expected| expected == line)
+      && (artifact.outcome == Outcome::Unsupported
+        || fs::read(dir.join(&artifact.name))? == artifact.data);
+    if !same {
+      differ.push(artifact.name.clone());
+    }
+  }
+  Ok(differ)
+}
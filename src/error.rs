@@ -0,0 +1,53 @@
+use std::fmt;
+
+use crate::subtle::WeakRsaKey;
+
+/// The error of a failed operation, named after the `DOMException` the
+/// Web Crypto API throws in its place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebCryptoError {
+  /// The algorithm, a parameter or a key format is not supported.
+  NotSupportedError,
+  /// A parameter or a key usage is invalid for the algorithm.
+  SyntaxError,
+  /// The operation failed, eg. a ciphertext failed to authenticate.
+  OperationError,
+  /// The key data or the input is malformed.
+  DataError,
+  /// The key cannot be used for the operation: the wrong algorithm, key
+  /// type or usages, or it is not extractable.
+  InvalidAccessError,
+  /// A limit was exceeded, eg. the length of `get_random_values`.
+  QuotaExceededError,
+  /// An imported RSA key is weak, for the reason given. Named
+  /// "DataError".
+  WeakRsaKey(WeakRsaKey),
+}
+
+impl WebCryptoError {
+  /// The name of the matching `DOMException`, eg. "OperationError".
+  pub fn name(&self) -> &'static str {
+    match self {
+      WebCryptoError::NotSupportedError => "NotSupportedError",
+      WebCryptoError::SyntaxError => "SyntaxError",
+      WebCryptoError::OperationError => "OperationError",
+      WebCryptoError::DataError => "DataError",
+      WebCryptoError::InvalidAccessError => "InvalidAccessError",
+      WebCryptoError::QuotaExceededError => "QuotaExceededError",
+      WebCryptoError::WeakRsaKey(_) => "DataError",
+    }
+  }
+}
+
+impl fmt::Display for WebCryptoError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      WebCryptoError::WeakRsaKey(reason) => {
+        write!(f, "{}: {}", self.name(), reason)
+      }
+      _ => f.write_str(self.name()),
+    }
+  }
+}
+
+impl std::error::Error for WebCryptoError {}
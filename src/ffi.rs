@@ -0,0 +1,1088 @@
+//! C ABI, enabled with the `ffi` feature. See `include/webcrypto.h`.
+//!
+//! Conventions:
+//!
+//! - Every function returns a `WebCryptoStatus`; results are written
+//!   through out pointers.
+//! - Contexts and keys are opaque heap objects owned by the caller and
+//!   released with `webcrypto_context_free` / `webcrypto_key_free`.
+//! - Input bytes are passed as a pointer and a length. Output bytes are
+//!   returned in a `WebCryptoBuffer` allocated by this library, which must
+//!   be released with `webcrypto_buffer_free`.
+//! - Algorithm names are NUL-terminated UTF-8 strings and key usages a
+//!   bitmask of `WEBCRYPTO_USAGE_*` flags.
+//!
+//! Key material is kept in the storage owned by the context, an
+//! in-memory vault unless the context is created with
+//! `webcrypto_context_new_with_storage`, so keys must only be used with
+//! the context that created them.
+//!
+//! Build the shared library with the `cdylib` feature:
+//!
+//! ```text
+//! cargo rustc --lib --release --features cdylib --crate-type cdylib
+//! ```
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use crate::storage::Backend;
+use crate::subtle;
+use crate::Context;
+use crate::WebCryptoError;
+
+pub const WEBCRYPTO_USAGE_ENCRYPT: u32 = 1 << 0;
+pub const WEBCRYPTO_USAGE_DECRYPT: u32 = 1 << 1;
+pub const WEBCRYPTO_USAGE_SIGN: u32 = 1 << 2;
+pub const WEBCRYPTO_USAGE_VERIFY: u32 = 1 << 3;
+pub const WEBCRYPTO_USAGE_WRAP_KEY: u32 = 1 << 4;
+pub const WEBCRYPTO_USAGE_UNWRAP_KEY: u32 = 1 << 5;
+pub const WEBCRYPTO_USAGE_DERIVE_KEY: u32 = 1 << 6;
+pub const WEBCRYPTO_USAGE_DERIVE_BITS: u32 = 1 << 7;
+pub const WEBCRYPTO_USAGE_ENCAPSULATE_BITS: u32 = 1 << 8;
+pub const WEBCRYPTO_USAGE_DECAPSULATE_BITS: u32 = 1 << 9;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WebCryptoStatus {
+  Ok = 0,
+  /// A required pointer argument was null.
+  NullPointer = 1,
+  /// An argument could not be decoded, eg. an unknown algorithm name.
+  InvalidArgument = 2,
+  /// The operation itself failed, eg. a ciphertext failed to
+  /// authenticate or a key could not be stored.
+  OperationError = 3,
+  /// The algorithm, a parameter or a key format is not supported.
+  NotSupportedError = 4,
+  /// A parameter or a key usage is invalid for the algorithm.
+  SyntaxError = 5,
+  /// The key data or the input is malformed.
+  DataError = 6,
+  /// The key cannot be used for the operation.
+  InvalidAccessError = 7,
+  /// A limit was exceeded, eg. the length of `get_random_values`.
+  QuotaExceededError = 8,
+  /// The key storage could not be opened.
+  StorageError = 9,
+  /// An imported RSA key is weak, eg. a ROCA modulus or a modulus shorter
+  /// than the context requires.
+  WeakKeyError = 10,
+}
+
+impl From<WebCryptoError> for WebCryptoStatus {
+  fn from(error: WebCryptoError) -> Self {
+    match error {
+      WebCryptoError::NotSupportedError => WebCryptoStatus::NotSupportedError,
+      WebCryptoError::SyntaxError => WebCryptoStatus::SyntaxError,
+      WebCryptoError::OperationError => WebCryptoStatus::OperationError,
+      WebCryptoError::DataError => WebCryptoStatus::DataError,
+      WebCryptoError::InvalidAccessError => WebCryptoStatus::InvalidAccessError,
+      WebCryptoError::QuotaExceededError => WebCryptoStatus::QuotaExceededError,
+      WebCryptoError::WeakRsaKey(_) => WebCryptoStatus::WeakKeyError,
+    }
+  }
+}
+
+/// Opaque handle to a key storage, owned by the caller until handed to
+/// `webcrypto_context_new_with_storage`.
+pub struct WebCryptoStorage(Backend);
+
+/// Opaque handle to a `Context`.
+pub struct WebCryptoContext(Context<rand::rngs::OsRng, Backend>);
+
+/// Opaque handle to a `CryptoKey`.
+pub struct WebCryptoKey(subtle::CryptoKey<usize>);
+
+/// Bytes owned by this library.
+#[repr(C)]
+pub struct WebCryptoBuffer {
+  pub data: *mut u8,
+  pub len: usize,
+}
+
+impl From<Vec<u8>> for WebCryptoBuffer {
+  fn from(bytes: Vec<u8>) -> Self {
+    let len = bytes.len();
+    let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+    WebCryptoBuffer { data, len }
+  }
+}
+
+macro_rules! try_status {
+  ($expr:expr, $status:ident) => {
+    match $expr {
+      Some(value) => value,
+      None => return WebCryptoStatus::$status,
+    }
+  };
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+  if len == 0 {
+    Some(&[])
+  } else if data.is_null() {
+    None
+  } else {
+    Some(slice::from_raw_parts(data, len))
+  }
+}
+
+unsafe fn string<'a>(s: *const c_char) -> Option<&'a str> {
+  if s.is_null() {
+    return None;
+  }
+
+  CStr::from_ptr(s).to_str().ok()
+}
+
+unsafe fn algorithm_name(s: *const c_char) -> Option<&'static str> {
+  subtle::algorithm_name(string(s)?)
+}
+
+fn key_usages(usages: u32) -> Vec<subtle::KeyUsage> {
+  [
+    (WEBCRYPTO_USAGE_ENCRYPT, subtle::KeyUsage::Encrypt),
+    (WEBCRYPTO_USAGE_DECRYPT, subtle::KeyUsage::Decrypt),
+    (WEBCRYPTO_USAGE_SIGN, subtle::KeyUsage::Sign),
+    (WEBCRYPTO_USAGE_VERIFY, subtle::KeyUsage::Verify),
+    (WEBCRYPTO_USAGE_WRAP_KEY, subtle::KeyUsage::WrapKey),
+    (WEBCRYPTO_USAGE_UNWRAP_KEY, subtle::KeyUsage::UnwrapKey),
+    (WEBCRYPTO_USAGE_DERIVE_KEY, subtle::KeyUsage::DeriveKey),
+    (WEBCRYPTO_USAGE_DERIVE_BITS, subtle::KeyUsage::DeriveBits),
+    (
+      WEBCRYPTO_USAGE_ENCAPSULATE_BITS,
+      subtle::KeyUsage::EncapsulateBits,
+    ),
+    (
+      WEBCRYPTO_USAGE_DECAPSULATE_BITS,
+      subtle::KeyUsage::DecapsulateBits,
+    ),
+  ]
+  .into_iter()
+  .filter(|(flag, _)| usages & flag != 0)
+  .map(|(_, usage)| usage)
+  .collect()
+}
+
+fn sign_params(name: &'static str, salt_length: usize) -> subtle::SignParams {
+  match name {
+    "RSA-PSS" => subtle::SignParams::RsaPssParams(subtle::RsaPssParams {
+      name,
+      salt_length: Some(salt_length),
+    }),
+    _ => subtle::SignParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+      name,
+    }),
+  }
+}
+
+fn encrypt_params(name: &'static str, iv: &[u8]) -> subtle::EncryptParams {
+  match name {
+    "AES-GCM" => subtle::EncryptParams::AesGcmParams(subtle::AesGcmParams {
+      name,
+      iv: iv.to_vec(),
+      additional_data: vec![],
+      tag_length: None,
+      key_commitment: false,
+    }),
+    "RSA-OAEP" => subtle::EncryptParams::RsaOaepParams(subtle::RsaOaepParams {
+      name,
+      label: vec![],
+    }),
+    "ChaCha20-Poly1305" => subtle::EncryptParams::ChaCha20Poly1305Params(
+      subtle::ChaCha20Poly1305Params {
+        name,
+        iv: iv.to_vec(),
+      },
+    ),
+    "XChaCha20-Poly1305" => subtle::EncryptParams::XChaCha20Poly1305Params(
+      subtle::XChaCha20Poly1305Params {
+        name,
+        iv: iv.to_vec(),
+      },
+    ),
+    _ => {
+      subtle::EncryptParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+        name,
+      })
+    }
+  }
+}
+
+unsafe fn import_params(
+  key_algorithm: *const c_char,
+  hash: *const c_char,
+) -> Option<subtle::ImportParams> {
+  let name = algorithm_name(key_algorithm)?;
+  Some(match name {
+    "HMAC" => {
+      subtle::ImportParams::HmacImportParams(subtle::HmacImportParams {
+        name,
+        hash: subtle::HashAlgorithmIdentifer {
+          name: algorithm_name(hash)?,
+        },
+        length: None,
+      })
+    }
+    "RSASSA-PKCS1-v1_5" | "RSA-PSS" | "RSA-OAEP" => {
+      subtle::ImportParams::RsaHashedImportParams(
+        subtle::RsaHashedImportParams {
+          name,
+          hash: subtle::HashAlgorithmIdentifer {
+            name: algorithm_name(hash)?,
+          },
+        },
+      )
+    }
+    _ => subtle::ImportParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+      name,
+    }),
+  })
+}
+
+unsafe fn write_key(
+  out: *mut *mut WebCryptoKey,
+  key: subtle::CryptoKey<usize>,
+) {
+  *out = Box::into_raw(Box::new(WebCryptoKey(key)));
+}
+
+/// Open the vault file at `path`, creating it if it does not exist.
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string and `storage` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_storage_open_file(
+  path: *const c_char,
+  storage: *mut *mut WebCryptoStorage,
+) -> WebCryptoStatus {
+  if path.is_null() || storage.is_null() {
+    return WebCryptoStatus::NullPointer;
+  }
+  let path = try_status!(string(path), InvalidArgument);
+
+  match Backend::open(Some(path)) {
+    Ok(backend) => {
+      *storage = Box::into_raw(Box::new(WebCryptoStorage(backend)));
+      WebCryptoStatus::Ok
+    }
+    Err(_) => WebCryptoStatus::StorageError,
+  }
+}
+
+/// # Safety
+///
+/// `storage` must be null or a storage returned by this library that has
+/// not been freed or handed to a context yet.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_storage_free(
+  storage: *mut WebCryptoStorage,
+) {
+  if !storage.is_null() {
+    drop(Box::from_raw(storage));
+  }
+}
+
+/// Create a new context using the operating system RNG and an in-memory
+/// vault.
+#[no_mangle]
+pub extern "C" fn webcrypto_context_new() -> *mut WebCryptoContext {
+  Box::into_raw(Box::new(WebCryptoContext(Context::new(
+    rand::rngs::OsRng,
+    Backend::Memory(Default::default()),
+  ))))
+}
+
+/// Create a new context using the operating system RNG, keeping keys in
+/// `storage`. The context takes ownership of `storage`. Returns null when
+/// `storage` is null.
+///
+/// # Safety
+///
+/// `storage` must be null or a storage returned by this library that has
+/// not been freed or handed to a context yet.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_context_new_with_storage(
+  storage: *mut WebCryptoStorage,
+) -> *mut WebCryptoContext {
+  if storage.is_null() {
+    return ptr::null_mut();
+  }
+
+  let storage = Box::from_raw(storage);
+  Box::into_raw(Box::new(WebCryptoContext(Context::new(
+    rand::rngs::OsRng,
+    storage.0,
+  ))))
+}
+
+/// # Safety
+///
+/// `ctx` must be null or a pointer returned by `webcrypto_context_new`
+/// that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_context_free(ctx: *mut WebCryptoContext) {
+  if !ctx.is_null() {
+    drop(Box::from_raw(ctx));
+  }
+}
+
+/// # Safety
+///
+/// `key` must be null or a key returned by this library that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_key_free(key: *mut WebCryptoKey) {
+  if !key.is_null() {
+    drop(Box::from_raw(key));
+  }
+}
+
+/// # Safety
+///
+/// `buf` must have been returned by this library and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_buffer_free(buf: WebCryptoBuffer) {
+  if !buf.data.is_null() {
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+      buf.data, buf.len,
+    )));
+  }
+}
+
+/// Fills `out` with `len` random bytes, at most 65536.
+///
+/// # Safety
+///
+/// `ctx` must be a live context and `out` must be valid for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_get_random_values(
+  ctx: *mut WebCryptoContext,
+  out: *mut u8,
+  len: usize,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  if len == 0 {
+    return WebCryptoStatus::Ok;
+  }
+  if out.is_null() {
+    return WebCryptoStatus::NullPointer;
+  }
+
+  match ctx.0.get_random_values(slice::from_raw_parts_mut(out, len)) {
+    Ok(()) => WebCryptoStatus::Ok,
+    Err(err) => err.into(),
+  }
+}
+
+/// Writes the 36 byte textual form of a random UUID into `out`.
+///
+/// # Safety
+///
+/// `ctx` must be a live context and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_random_uuid(
+  ctx: *mut WebCryptoContext,
+  out: *mut WebCryptoBuffer,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  let out = try_status!(out.as_mut(), NullPointer);
+
+  *out = ctx.0.random_uuid().into_bytes().into();
+  WebCryptoStatus::Ok
+}
+
+/// Generate an RSA key pair for `name` ("RSASSA-PKCS1-v1_5", "RSA-PSS"
+/// or "RSA-OAEP").
+///
+/// # Safety
+///
+/// `ctx` must be a live context, `name` and `hash` NUL-terminated
+/// strings, `public_exponent` valid for `public_exponent_len` bytes and
+/// `public_key` / `private_key` valid pointers.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn webcrypto_generate_key_rsa(
+  ctx: *mut WebCryptoContext,
+  name: *const c_char,
+  modulus_length: usize,
+  public_exponent: *const u8,
+  public_exponent_len: usize,
+  hash: *const c_char,
+  extractable: bool,
+  usages: u32,
+  public_key: *mut *mut WebCryptoKey,
+  private_key: *mut *mut WebCryptoKey,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  if public_key.is_null() || private_key.is_null() {
+    return WebCryptoStatus::NullPointer;
+  }
+
+  let name = try_status!(algorithm_name(name), InvalidArgument);
+  let hash = try_status!(algorithm_name(hash), InvalidArgument);
+  let public_exponent = try_status!(
+    bytes(public_exponent, public_exponent_len)
+      .and_then(|exp| subtle::PublicExponent::from_be_bytes(exp).ok()),
+    InvalidArgument
+  );
+
+  let params = subtle::RsaHashedKeyGenParams {
+    name,
+    hash: subtle::HashAlgorithmIdentifer { name: hash },
+    modulus_length,
+    public_exponent,
+  };
+
+  match ctx.0.subtle.generate_key(
+    params.into(),
+    extractable,
+    key_usages(usages),
+  ) {
+    Ok(subtle::CryptoKeyOrPair::CryptoKeyPair(pair)) => {
+      write_key(public_key, pair.public_key);
+      write_key(private_key, pair.private_key);
+      WebCryptoStatus::Ok
+    }
+    Ok(_) => WebCryptoStatus::OperationError,
+    Err(err) => err.into(),
+  }
+}
+
+/// Generate an AES key for `name` ("AES-CTR", "AES-CBC", "AES-GCM",
+/// "AES-KW", "AES-KWP" or "AES-CMAC"), of `length` bits.
+///
+/// # Safety
+///
+/// `ctx` must be a live context, `name` a NUL-terminated string and
+/// `key` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_generate_key_aes(
+  ctx: *mut WebCryptoContext,
+  name: *const c_char,
+  length: usize,
+  extractable: bool,
+  usages: u32,
+  key: *mut *mut WebCryptoKey,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  if key.is_null() {
+    return WebCryptoStatus::NullPointer;
+  }
+
+  let name = try_status!(algorithm_name(name), InvalidArgument);
+  let params = subtle::AesKeyGenParams { name, length };
+
+  match ctx.0.subtle.generate_key(
+    params.into(),
+    extractable,
+    key_usages(usages),
+  ) {
+    Ok(subtle::CryptoKeyOrPair::CryptoKey(secret)) => {
+      write_key(key, secret);
+      WebCryptoStatus::Ok
+    }
+    Ok(_) => WebCryptoStatus::OperationError,
+    Err(err) => err.into(),
+  }
+}
+
+/// Generate an HMAC key using `hash`. `length` is in bits, or 0 for the
+/// block size of `hash`.
+///
+/// # Safety
+///
+/// `ctx` must be a live context, `hash` a NUL-terminated string and
+/// `key` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_generate_key_hmac(
+  ctx: *mut WebCryptoContext,
+  hash: *const c_char,
+  length: usize,
+  extractable: bool,
+  usages: u32,
+  key: *mut *mut WebCryptoKey,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  if key.is_null() {
+    return WebCryptoStatus::NullPointer;
+  }
+
+  let hash = try_status!(algorithm_name(hash), InvalidArgument);
+  let params = subtle::HmacKeyGenParams {
+    name: "HMAC",
+    hash: subtle::HashAlgorithmIdentifer { name: hash },
+    length: (length != 0).then_some(length),
+  };
+
+  match ctx.0.subtle.generate_key(
+    params.into(),
+    extractable,
+    key_usages(usages),
+  ) {
+    Ok(subtle::CryptoKeyOrPair::CryptoKey(secret)) => {
+      write_key(key, secret);
+      WebCryptoStatus::Ok
+    }
+    Ok(_) => WebCryptoStatus::OperationError,
+    Err(err) => err.into(),
+  }
+}
+
+/// Import `key_data` in `format` as a new key for `key_algorithm`.
+/// `hash` is required for "HMAC" and the RSA algorithms and ignored
+/// otherwise.
+///
+/// # Safety
+///
+/// `ctx` must be a live context, `format` and `key_algorithm`
+/// NUL-terminated strings, `hash` null or a NUL-terminated string,
+/// `key_data` valid for `key_data_len` bytes and `key` a valid pointer.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn webcrypto_import_key(
+  ctx: *mut WebCryptoContext,
+  format: *const c_char,
+  key_data: *const u8,
+  key_data_len: usize,
+  key_algorithm: *const c_char,
+  hash: *const c_char,
+  extractable: bool,
+  usages: u32,
+  key: *mut *mut WebCryptoKey,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  if key.is_null() {
+    return WebCryptoStatus::NullPointer;
+  }
+
+  let key_data = try_status!(bytes(key_data, key_data_len), NullPointer);
+  let format = try_status!(string(format), InvalidArgument);
+  let import_params =
+    try_status!(import_params(key_algorithm, hash), InvalidArgument);
+
+  match ctx.0.subtle.import_key(
+    format,
+    key_data,
+    import_params,
+    extractable,
+    key_usages(usages),
+  ) {
+    Ok(imported) => {
+      write_key(key, imported);
+      WebCryptoStatus::Ok
+    }
+    Err(err) => err.into(),
+  }
+}
+
+/// Sign `data` with `key`. `salt_length` is only used by "RSA-PSS".
+///
+/// # Safety
+///
+/// `ctx` and `key` must be live, `name` a NUL-terminated string, `data`
+/// valid for `data_len` bytes and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn webcrypto_sign(
+  ctx: *mut WebCryptoContext,
+  name: *const c_char,
+  salt_length: usize,
+  key: *const WebCryptoKey,
+  data: *const u8,
+  data_len: usize,
+  out: *mut WebCryptoBuffer,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  let key = try_status!(key.as_ref(), NullPointer);
+  let out = try_status!(out.as_mut(), NullPointer);
+  let data = try_status!(bytes(data, data_len), NullPointer);
+  let name = try_status!(algorithm_name(name), InvalidArgument);
+
+  match ctx
+    .0
+    .subtle
+    .sign(sign_params(name, salt_length), &key.0, data)
+  {
+    Ok(signature) => {
+      *out = signature.into();
+      WebCryptoStatus::Ok
+    }
+    Err(err) => err.into(),
+  }
+}
+
+/// Verify `signature` over `data` with `key`, writing the outcome to
+/// `valid`.
+///
+/// # Safety
+///
+/// `ctx` and `key` must be live, `name` a NUL-terminated string,
+/// `signature` and `data` valid for their lengths and `valid` a valid
+/// pointer.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn webcrypto_verify(
+  ctx: *mut WebCryptoContext,
+  name: *const c_char,
+  salt_length: usize,
+  key: *const WebCryptoKey,
+  signature: *const u8,
+  signature_len: usize,
+  data: *const u8,
+  data_len: usize,
+  valid: *mut bool,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  let key = try_status!(key.as_ref(), NullPointer);
+  let valid = try_status!(valid.as_mut(), NullPointer);
+  let signature = try_status!(bytes(signature, signature_len), NullPointer);
+  let data = try_status!(bytes(data, data_len), NullPointer);
+  let name = try_status!(algorithm_name(name), InvalidArgument);
+
+  match ctx.0.subtle.verify(
+    sign_params(name, salt_length),
+    &key.0,
+    signature,
+    data,
+  ) {
+    Ok(result) => {
+      *valid = result;
+      WebCryptoStatus::Ok
+    }
+    Err(err) => err.into(),
+  }
+}
+
+/// Encrypt `data` with `key`. `iv` is required for "AES-GCM",
+/// "ChaCha20-Poly1305" and "XChaCha20-Poly1305" and ignored otherwise.
+///
+/// # Safety
+///
+/// `ctx` and `key` must be live, `name` a NUL-terminated string, `iv`
+/// and `data` valid for their lengths and `out` a valid pointer.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn webcrypto_encrypt(
+  ctx: *mut WebCryptoContext,
+  name: *const c_char,
+  iv: *const u8,
+  iv_len: usize,
+  key: *const WebCryptoKey,
+  data: *const u8,
+  data_len: usize,
+  out: *mut WebCryptoBuffer,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  let key = try_status!(key.as_ref(), NullPointer);
+  let out = try_status!(out.as_mut(), NullPointer);
+  let iv = try_status!(bytes(iv, iv_len), NullPointer);
+  let data = try_status!(bytes(data, data_len), NullPointer);
+  let name = try_status!(algorithm_name(name), InvalidArgument);
+
+  match ctx.0.subtle.encrypt(encrypt_params(name, iv), &key.0, data) {
+    Ok(ciphertext) => {
+      *out = ciphertext.into();
+      WebCryptoStatus::Ok
+    }
+    Err(err) => err.into(),
+  }
+}
+
+/// Decrypt `data` with `key`. `iv` is required for "AES-GCM",
+/// "ChaCha20-Poly1305" and "XChaCha20-Poly1305" and ignored otherwise.
+///
+/// # Safety
+///
+/// `ctx` and `key` must be live, `name` a NUL-terminated string, `iv`
+/// and `data` valid for their lengths and `out` a valid pointer.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn webcrypto_decrypt(
+  ctx: *mut WebCryptoContext,
+  name: *const c_char,
+  iv: *const u8,
+  iv_len: usize,
+  key: *const WebCryptoKey,
+  data: *const u8,
+  data_len: usize,
+  out: *mut WebCryptoBuffer,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  let key = try_status!(key.as_ref(), NullPointer);
+  let out = try_status!(out.as_mut(), NullPointer);
+  let iv = try_status!(bytes(iv, iv_len), NullPointer);
+  let data = try_status!(bytes(data, data_len), NullPointer);
+  let name = try_status!(algorithm_name(name), InvalidArgument);
+
+  match ctx.0.subtle.decrypt(encrypt_params(name, iv), &key.0, data) {
+    Ok(plaintext) => {
+      *out = plaintext.into();
+      WebCryptoStatus::Ok
+    }
+    Err(err) => err.into(),
+  }
+}
+
+/// Wrap `key` with `wrapping_key` using `wrap_algorithm`. `iv` is
+/// required for "AES-GCM" and the ChaCha20-Poly1305 algorithms and ignored
+/// otherwise.
+///
+/// # Safety
+///
+/// `ctx`, `key` and `wrapping_key` must be live, `format` and
+/// `wrap_algorithm` NUL-terminated strings, `iv` valid for `iv_len`
+/// bytes and `out` a valid pointer.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn webcrypto_wrap_key(
+  ctx: *mut WebCryptoContext,
+  format: *const c_char,
+  key: *const WebCryptoKey,
+  wrapping_key: *const WebCryptoKey,
+  wrap_algorithm: *const c_char,
+  iv: *const u8,
+  iv_len: usize,
+  out: *mut WebCryptoBuffer,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  let key = try_status!(key.as_ref(), NullPointer);
+  let wrapping_key = try_status!(wrapping_key.as_ref(), NullPointer);
+  let out = try_status!(out.as_mut(), NullPointer);
+  let iv = try_status!(bytes(iv, iv_len), NullPointer);
+  let format = try_status!(string(format), InvalidArgument);
+  let name = try_status!(algorithm_name(wrap_algorithm), InvalidArgument);
+
+  match ctx.0.subtle.wrap_key(
+    format,
+    &key.0,
+    &wrapping_key.0,
+    encrypt_params(name, iv),
+  ) {
+    Ok(wrapped) => {
+      *out = wrapped.into();
+      WebCryptoStatus::Ok
+    }
+    Err(err) => err.into(),
+  }
+}
+
+/// Unwrap `wrapped_key` into a new key for `key_algorithm`. `iv` is
+/// required for "AES-GCM" and the ChaCha20-Poly1305 algorithms, and
+/// `hash` for "HMAC" and the RSA algorithms; both are ignored otherwise.
+///
+/// # Safety
+///
+/// `ctx` and `unwrapping_key` must be live, `format`, `unwrap_algorithm`
+/// and `key_algorithm` NUL-terminated strings, `hash` null or a
+/// NUL-terminated string, `wrapped_key` and `iv` valid for their
+/// lengths and `key` a valid pointer.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn webcrypto_unwrap_key(
+  ctx: *mut WebCryptoContext,
+  format: *const c_char,
+  wrapped_key: *const u8,
+  wrapped_key_len: usize,
+  unwrapping_key: *const WebCryptoKey,
+  unwrap_algorithm: *const c_char,
+  iv: *const u8,
+  iv_len: usize,
+  key_algorithm: *const c_char,
+  hash: *const c_char,
+  extractable: bool,
+  usages: u32,
+  key: *mut *mut WebCryptoKey,
+) -> WebCryptoStatus {
+  let ctx = try_status!(ctx.as_mut(), NullPointer);
+  let unwrapping_key = try_status!(unwrapping_key.as_ref(), NullPointer);
+  if key.is_null() {
+    return WebCryptoStatus::NullPointer;
+  }
+
+  let wrapped_key =
+    try_status!(bytes(wrapped_key, wrapped_key_len), NullPointer);
+  let iv = try_status!(bytes(iv, iv_len), NullPointer);
+  let format = try_status!(string(format), InvalidArgument);
+  let unwrap_name =
+    try_status!(algorithm_name(unwrap_algorithm), InvalidArgument);
+  let import_params =
+    try_status!(import_params(key_algorithm, hash), InvalidArgument);
+
+  match ctx.0.subtle.unwrap_key(
+    format,
+    wrapped_key,
+    &unwrapping_key.0,
+    encrypt_params(unwrap_name, iv),
+    import_params,
+    extractable,
+    key_usages(usages),
+  ) {
+    Ok(unwrapped) => {
+      write_key(key, unwrapped);
+      WebCryptoStatus::Ok
+    }
+    Err(err) => err.into(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_wrap_unwrap_key() {
+    unsafe {
+      let ctx = webcrypto_context_new();
+
+      let mut kek = ptr::null_mut();
+      let status = webcrypto_generate_key_aes(
+        ctx,
+        c"AES-KW".as_ptr(),
+        128,
+        false,
+        WEBCRYPTO_USAGE_WRAP_KEY | WEBCRYPTO_USAGE_UNWRAP_KEY,
+        &mut kek,
+      );
+      assert_eq!(status, WebCryptoStatus::Ok);
+
+      let mut key = ptr::null_mut();
+      let status = webcrypto_generate_key_hmac(
+        ctx,
+        c"SHA-256".as_ptr(),
+        256,
+        true,
+        WEBCRYPTO_USAGE_SIGN | WEBCRYPTO_USAGE_VERIFY,
+        &mut key,
+      );
+      assert_eq!(status, WebCryptoStatus::Ok);
+
+      let mut wrapped = WebCryptoBuffer {
+        data: ptr::null_mut(),
+        len: 0,
+      };
+      let status = webcrypto_wrap_key(
+        ctx,
+        c"raw".as_ptr(),
+        key,
+        kek,
+        c"AES-KW".as_ptr(),
+        ptr::null(),
+        0,
+        &mut wrapped,
+      );
+      assert_eq!(status, WebCryptoStatus::Ok);
+      assert_eq!(wrapped.len, 40);
+
+      let mut unwrapped = ptr::null_mut();
+      let status = webcrypto_unwrap_key(
+        ctx,
+        c"raw".as_ptr(),
+        wrapped.data,
+        wrapped.len,
+        kek,
+        c"AES-KW".as_ptr(),
+        ptr::null(),
+        0,
+        c"HMAC".as_ptr(),
+        c"SHA-256".as_ptr(),
+        true,
+        WEBCRYPTO_USAGE_SIGN,
+        &mut unwrapped,
+      );
+      assert_eq!(status, WebCryptoStatus::Ok);
+      assert!((*unwrapped).0.usages == vec![subtle::KeyUsage::Sign]);
+
+      webcrypto_buffer_free(wrapped);
+      webcrypto_key_free(unwrapped);
+      webcrypto_key_free(key);
+      webcrypto_key_free(kek);
+      webcrypto_context_free(ctx);
+    }
+  }
+
+  #[test]
+  fn test_encrypt_decrypt() {
+    unsafe {
+      let ctx = webcrypto_context_new();
+
+      let mut key = ptr::null_mut();
+      let status = webcrypto_generate_key_aes(
+        ctx,
+        c"AES-GCM".as_ptr(),
+        256,
+        false,
+        WEBCRYPTO_USAGE_ENCRYPT | WEBCRYPTO_USAGE_DECRYPT,
+        &mut key,
+      );
+      assert_eq!(status, WebCryptoStatus::Ok);
+
+      let iv = [0u8; 12];
+      let data = b"Hello, world!";
+      let mut ciphertext = WebCryptoBuffer {
+        data: ptr::null_mut(),
+        len: 0,
+      };
+      let status = webcrypto_encrypt(
+        ctx,
+        c"AES-GCM".as_ptr(),
+        iv.as_ptr(),
+        iv.len(),
+        key,
+        data.as_ptr(),
+        data.len(),
+        &mut ciphertext,
+      );
+      assert_eq!(status, WebCryptoStatus::Ok);
+      assert_eq!(ciphertext.len, data.len() + 16);
+
+      let mut plaintext = WebCryptoBuffer {
+        data: ptr::null_mut(),
+        len: 0,
+      };
+      let status = webcrypto_decrypt(
+        ctx,
+        c"AES-GCM".as_ptr(),
+        iv.as_ptr(),
+        iv.len(),
+        key,
+        ciphertext.data,
+        ciphertext.len,
+        &mut plaintext,
+      );
+      assert_eq!(status, WebCryptoStatus::Ok);
+      assert_eq!(slice::from_raw_parts(plaintext.data, plaintext.len), data);
+
+      webcrypto_buffer_free(plaintext);
+      webcrypto_buffer_free(ciphertext);
+      webcrypto_key_free(key);
+      webcrypto_context_free(ctx);
+    }
+  }
+
+  #[test]
+  fn test_invalid_arguments() {
+    unsafe {
+      let ctx = webcrypto_context_new();
+      let mut key = ptr::null_mut();
+
+      let status = webcrypto_generate_key_aes(
+        ctx,
+        c"AES-XYZ".as_ptr(),
+        128,
+        false,
+        0,
+        &mut key,
+      );
+      assert_eq!(status, WebCryptoStatus::InvalidArgument);
+
+      let status =
+        webcrypto_get_random_values(ptr::null_mut(), ptr::null_mut(), 0);
+      assert_eq!(status, WebCryptoStatus::NullPointer);
+
+      webcrypto_context_free(ctx);
+    }
+  }
+
+  #[test]
+  fn test_storage() {
+    let path = std::env::temp_dir()
+      .join(format!("webcrypto-ffi-{}.vault", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+
+    unsafe {
+      let mut storage = ptr::null_mut();
+      let status = webcrypto_storage_open_file(c_path.as_ptr(), &mut storage);
+      assert_eq!(status, WebCryptoStatus::Ok);
+      let ctx = webcrypto_context_new_with_storage(storage);
+
+      let mut key = ptr::null_mut();
+      let status = webcrypto_import_key(
+        ctx,
+        c"raw".as_ptr(),
+        [7u8; 16].as_ptr(),
+        16,
+        c"AES-GCM".as_ptr(),
+        ptr::null(),
+        false,
+        WEBCRYPTO_USAGE_ENCRYPT,
+        &mut key,
+      );
+      assert_eq!(status, WebCryptoStatus::Ok);
+      webcrypto_key_free(key);
+      webcrypto_context_free(ctx);
+
+      // The key reached the file.
+      let vault = crate::storage::FileVault::open(&path).unwrap();
+      assert!(crate::storage::KeyStorage::get(&vault, 0).unwrap() == &[7; 16]);
+
+      let dir =
+        std::ffi::CString::new(std::env::temp_dir().to_str().unwrap()).unwrap();
+      let mut storage = ptr::null_mut();
+      let status = webcrypto_storage_open_file(dir.as_ptr(), &mut storage);
+      assert_eq!(status, WebCryptoStatus::StorageError);
+      assert!(webcrypto_context_new_with_storage(ptr::null_mut()).is_null());
+    }
+
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_error_status() {
+    unsafe {
+      let ctx = webcrypto_context_new();
+      let mut key = ptr::null_mut();
+
+      let import = |format: &std::ffi::CStr, key_data: &[u8], key| {
+        webcrypto_import_key(
+          ctx,
+          format.as_ptr(),
+          key_data.as_ptr(),
+          key_data.len(),
+          c"AES-GCM".as_ptr(),
+          ptr::null(),
+          false,
+          WEBCRYPTO_USAGE_ENCRYPT,
+          key,
+        )
+      };
+      assert_eq!(
+        import(c"raw", &[0; 5], &mut key),
+        WebCryptoStatus::DataError
+      );
+      assert_eq!(
+        import(c"spki", &[0; 16], &mut key),
+        WebCryptoStatus::NotSupportedError
+      );
+      assert_eq!(import(c"raw", &[0; 16], &mut key), WebCryptoStatus::Ok);
+
+      // The key may only encrypt.
+      let mut out = WebCryptoBuffer {
+        data: ptr::null_mut(),
+        len: 0,
+      };
+      let status = webcrypto_decrypt(
+        ctx,
+        c"AES-GCM".as_ptr(),
+        [0u8; 12].as_ptr(),
+        12,
+        key,
+        [0u8; 16].as_ptr(),
+        16,
+        &mut out,
+      );
+      assert_eq!(status, WebCryptoStatus::InvalidAccessError);
+
+      let mut values = vec![0u8; 65537];
+      let status =
+        webcrypto_get_random_values(ctx, values.as_mut_ptr(), values.len());
+      assert_eq!(status, WebCryptoStatus::QuotaExceededError);
+
+      webcrypto_key_free(key);
+      webcrypto_context_free(ctx);
+    }
+  }
+}
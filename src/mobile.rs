@@ -0,0 +1,742 @@
+//! UniFFI bindings for Swift and Kotlin, enabled with the `uniffi`
+//! feature.
+//!
+//! Generate the foreign bindings from the built library with
+//! `uniffi-bindgen`:
+//!
+//! ```text
+//! cargo build --release --features uniffi
+//! uniffi-bindgen generate --library target/release/libwebcrypto.so \
+//!   --language swift --out-dir out
+//! ```
+//!
+//! Key material is persisted through a [`PlatformKeyStore`] implemented
+//! on the foreign side, eg. on top of the iOS Keychain or the Android
+//! Keystore. Non-extractable ECDSA P-256 keys can instead be created in
+//! the Secure Enclave or a StrongBox through a [`HardwareKeyStore`]. Both
+//! are interfaces the app implements: this crate ships no backend for
+//! either.
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use p256::pkcs8::EncodePublicKey;
+use rand::rngs::OsRng;
+
+use crate::storage::GeneratedKey;
+use crate::storage::KeyMaterial;
+use crate::storage::KeyOperation;
+use crate::storage::KeyStorage;
+use crate::subtle;
+use crate::Context;
+
+#[derive(Debug, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum WebCryptoError {
+  /// An algorithm name or parameter was not recognized.
+  InvalidArgument,
+  /// The operation failed.
+  OperationError,
+}
+
+impl fmt::Display for WebCryptoError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      WebCryptoError::InvalidArgument => write!(f, "Invalid argument"),
+      WebCryptoError::OperationError => write!(f, "The operation failed"),
+    }
+  }
+}
+
+fn operation_error(_: crate::WebCryptoError) -> WebCryptoError {
+  WebCryptoError::OperationError
+}
+
+fn algorithm_name(name: &str) -> Result<&'static str, WebCryptoError> {
+  subtle::algorithm_name(name).ok_or(WebCryptoError::InvalidArgument)
+}
+
+/// Persistent key storage provided by the host platform.
+///
+/// Implementations should keep the material in the platform keystore
+/// and return a stable handle for it, below 2^63.
+#[uniffi::export(with_foreign)]
+pub trait PlatformKeyStore: Send + Sync {
+  /// Persist `key` and return its handle.
+  fn store(&self, key: Vec<u8>) -> u64;
+
+  /// Load the key stored under `handle`.
+  fn get(&self, handle: u64) -> Option<Vec<u8>>;
+}
+
+/// P-256 key pairs created inside secure hardware provided by the host
+/// platform, which signs with them itself.
+///
+/// On iOS, create keys with `SecKeyCreateRandomKey` and
+/// `kSecAttrTokenIDSecureEnclave`, and sign with
+/// `.ecdsaSignatureDigestX962`. On Android, generate them in the
+/// Keystore with `setIsStrongBoxBacked(true)` and `DIGEST_NONE`, and sign
+/// with `NONEwithECDSA`. Handles are below 2^63, and need not differ
+/// from the [`PlatformKeyStore`] ones.
+#[uniffi::export(with_foreign)]
+pub trait HardwareKeyStore: Send + Sync {
+  /// Create a key pair, `None` if the hardware is unavailable.
+  fn generate_p256(&self) -> Option<HardwareKey>;
+
+  /// Sign the hashed message `digest` with the key of `handle`, returning
+  /// a DER encoded ECDSA signature.
+  fn sign_digest(&self, handle: u64, digest: Vec<u8>) -> Option<Vec<u8>>;
+}
+
+#[derive(uniffi::Record)]
+pub struct HardwareKey {
+  /// The handle of the key pair, below 2^63.
+  pub handle: u64,
+  /// The public key, as an uncompressed point like
+  /// `SecKeyCopyExternalRepresentation` returns, or as a
+  /// SubjectPublicKeyInfo like `PublicKey.getEncoded` returns.
+  pub public_key: Vec<u8>,
+}
+
+/// A `KeyStorage` adaptor writing through to a [`PlatformKeyStore`].
+///
+/// Keys created by this context are also cached in memory, since
+/// `KeyStorage::get` hands out borrowed material.
+pub struct PlatformVault {
+  keystore: Arc<dyn PlatformKeyStore>,
+  keys: HashMap<u64, KeyMaterial>,
+  hardware: Option<Arc<dyn HardwareKeyStore>>,
+}
+
+/// Set in the handles of the keys held by the [`HardwareKeyStore`], which
+/// keeps them apart from the [`PlatformKeyStore`] handles.
+const HARDWARE_HANDLE: u64 = 1 << 63;
+
+impl PlatformVault {
+  pub fn new(keystore: Arc<dyn PlatformKeyStore>) -> Self {
+    PlatformVault {
+      keystore,
+      keys: HashMap::new(),
+      hardware: None,
+    }
+  }
+
+  /// A vault creating non-extractable ECDSA P-256 keys in `hardware`.
+  /// Other keys are stored in `keystore`.
+  pub fn with_hardware_keys(
+    keystore: Arc<dyn PlatformKeyStore>,
+    hardware: Arc<dyn HardwareKeyStore>,
+  ) -> Self {
+    PlatformVault {
+      hardware: Some(hardware),
+      ..PlatformVault::new(keystore)
+    }
+  }
+}
+
+/// The SubjectPublicKeyInfo of the public key of a [`HardwareKey`].
+fn hardware_public_key(
+  public_key: &[u8],
+) -> Result<Vec<u8>, crate::WebCryptoError> {
+  let spki = match p256::PublicKey::from_sec1_bytes(public_key) {
+    Ok(point) => point
+      .to_public_key_der()
+      .map_err(|_| crate::WebCryptoError::OperationError)?
+      .as_bytes()
+      .to_vec(),
+    Err(_) => public_key.to_vec(),
+  };
+  Ok(spki)
+}
+
+impl KeyStorage for PlatformVault {
+  type Handle = u64;
+
+  fn store(&mut self, key: KeyMaterial) -> u64 {
+    self
+      .try_store(key)
+      .expect("platform keystore handles are below 2^63")
+  }
+
+  fn try_store(&mut self, key: KeyMaterial) -> io::Result<u64> {
+    let handle = self.keystore.store(key.0.clone());
+    if handle & HARDWARE_HANDLE != 0 {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "platform keystore handle out of range",
+      ));
+    }
+    self.keys.insert(handle, key);
+    Ok(handle)
+  }
+
+  fn get(&self, handle: u64) -> Option<&KeyMaterial> {
+    self.keys.get(&handle)
+  }
+
+  fn resident_bytes(&self) -> Option<usize> {
+    Some(self.keys.values().map(|key| key.0.len()).sum())
+  }
+
+  fn generate(
+    &mut self,
+    algorithm: &subtle::KeyGenParams,
+  ) -> Option<Result<GeneratedKey<u64>, crate::WebCryptoError>> {
+    let hardware = self.hardware.as_ref()?;
+    match algorithm {
+      subtle::KeyGenParams::EcKeyGenParams(params)
+        if params.name == "ECDSA"
+          && params.named_curve == subtle::NamedCurve::P256 => {}
+      _ => return None,
+    }
+
+    // Not falling back to memory: the app asked for hardware keys.
+    let key = match hardware.generate_p256() {
+      Some(key) if key.handle & HARDWARE_HANDLE == 0 => key,
+      _ => return Some(Err(crate::WebCryptoError::OperationError)),
+    };
+    Some(
+      hardware_public_key(&key.public_key).map(|public_key| GeneratedKey {
+        handle: key.handle | HARDWARE_HANDLE,
+        public_key: Some(public_key),
+      }),
+    )
+  }
+
+  fn perform(
+    &mut self,
+    handle: u64,
+    _algorithm: &subtle::Algorithm,
+    operation: KeyOperation<'_>,
+  ) -> Option<Result<Vec<u8>, crate::WebCryptoError>> {
+    if handle & HARDWARE_HANDLE == 0 {
+      return None;
+    }
+    let hardware = self.hardware.as_ref()?;
+
+    Some(match operation {
+      KeyOperation::Sign {
+        params: subtle::SignParams::EcdsaParams(params),
+        digest,
+        ..
+      } if !params.deterministic => hardware
+        .sign_digest(handle & !HARDWARE_HANDLE, digest.to_vec())
+        .ok_or(crate::WebCryptoError::OperationError)
+        .and_then(|der| {
+          subtle::EcdsaSignature::from_der(subtle::NamedCurve::P256, &der)
+        })
+        .map(|signature| signature.to_raw()),
+      _ => Err(crate::WebCryptoError::NotSupportedError),
+    })
+  }
+}
+
+#[derive(uniffi::Enum)]
+pub enum KeyUsage {
+  Encrypt,
+  Decrypt,
+  Sign,
+  Verify,
+  WrapKey,
+  UnwrapKey,
+  DeriveKey,
+  DeriveBits,
+  EncapsulateBits,
+  DecapsulateBits,
+}
+
+impl From<KeyUsage> for subtle::KeyUsage {
+  fn from(usage: KeyUsage) -> Self {
+    match usage {
+      KeyUsage::Encrypt => subtle::KeyUsage::Encrypt,
+      KeyUsage::Decrypt => subtle::KeyUsage::Decrypt,
+      KeyUsage::Sign => subtle::KeyUsage::Sign,
+      KeyUsage::Verify => subtle::KeyUsage::Verify,
+      KeyUsage::WrapKey => subtle::KeyUsage::WrapKey,
+      KeyUsage::UnwrapKey => subtle::KeyUsage::UnwrapKey,
+      KeyUsage::DeriveKey => subtle::KeyUsage::DeriveKey,
+      KeyUsage::DeriveBits => subtle::KeyUsage::DeriveBits,
+      KeyUsage::EncapsulateBits => subtle::KeyUsage::EncapsulateBits,
+      KeyUsage::DecapsulateBits => subtle::KeyUsage::DecapsulateBits,
+    }
+  }
+}
+
+fn key_usages(usages: Vec<KeyUsage>) -> Vec<subtle::KeyUsage> {
+  usages.into_iter().map(Into::into).collect()
+}
+
+/// A `CryptoKey` handed out to Swift or Kotlin.
+#[derive(uniffi::Object)]
+pub struct CryptoKey(subtle::CryptoKey<u64>);
+
+#[uniffi::export]
+impl CryptoKey {
+  pub fn extractable(&self) -> bool {
+    self.0.extractable
+  }
+
+  pub fn algorithm(&self) -> String {
+    self.0.algorithm.name().to_string()
+  }
+
+  /// The platform keystore handle of the key material. Keys held by the
+  /// [`HardwareKeyStore`] have its handle with the top bit set.
+  pub fn handle(&self) -> u64 {
+    self.0.handle()
+  }
+}
+
+#[derive(uniffi::Record)]
+pub struct CryptoKeyPair {
+  pub public_key: Arc<CryptoKey>,
+  pub private_key: Arc<CryptoKey>,
+}
+
+/// A WebCrypto context backed by the operating system RNG and a
+/// platform keystore.
+#[derive(uniffi::Object)]
+pub struct WebCrypto(Mutex<Context<OsRng, PlatformVault>>);
+
+impl WebCrypto {
+  fn context(
+    &self,
+  ) -> std::sync::MutexGuard<'_, Context<OsRng, PlatformVault>> {
+    self.0.lock().unwrap()
+  }
+}
+
+#[uniffi::export]
+impl WebCrypto {
+  #[uniffi::constructor]
+  pub fn new(keystore: Arc<dyn PlatformKeyStore>) -> Arc<Self> {
+    let storage = PlatformVault::new(keystore);
+    Arc::new(WebCrypto(Mutex::new(Context::new(OsRng, storage))))
+  }
+
+  /// A context creating non-extractable ECDSA P-256 keys in `hardware`.
+  #[uniffi::constructor]
+  pub fn with_hardware_keys(
+    keystore: Arc<dyn PlatformKeyStore>,
+    hardware: Arc<dyn HardwareKeyStore>,
+  ) -> Arc<Self> {
+    let storage = PlatformVault::with_hardware_keys(keystore, hardware);
+    Arc::new(WebCrypto(Mutex::new(Context::new(OsRng, storage))))
+  }
+
+  pub fn get_random_values(
+    &self,
+    length: u32,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let mut bytes = vec![0u8; length as usize];
+    self
+      .context()
+      .get_random_values(&mut bytes)
+      .map_err(|_| WebCryptoError::InvalidArgument)?;
+    Ok(bytes)
+  }
+
+  pub fn random_uuid(&self) -> String {
+    self.context().random_uuid()
+  }
+
+  /// Generate an RSA key pair for `name` ("RSASSA-PKCS1-v1_5",
+  /// "RSA-PSS" or "RSA-OAEP").
+  pub fn generate_key_rsa(
+    &self,
+    name: String,
+    modulus_length: u32,
+    public_exponent: Vec<u8>,
+    hash: String,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKeyPair, WebCryptoError> {
+    let params = subtle::RsaHashedKeyGenParams {
+      name: algorithm_name(&name)?,
+      hash: subtle::HashAlgorithmIdentifer {
+        name: algorithm_name(&hash)?,
+      },
+      modulus_length: modulus_length as usize,
+      public_exponent: subtle::PublicExponent::from_be_bytes(&public_exponent)
+        .map_err(|_| WebCryptoError::InvalidArgument)?,
+    };
+
+    match self
+      .context()
+      .subtle
+      .generate_key(params.into(), extractable, key_usages(usages))
+      .map_err(operation_error)?
+    {
+      subtle::CryptoKeyOrPair::CryptoKeyPair(pair) => Ok(CryptoKeyPair {
+        public_key: Arc::new(CryptoKey(pair.public_key)),
+        private_key: Arc::new(CryptoKey(pair.private_key)),
+      }),
+      subtle::CryptoKeyOrPair::CryptoKey(_) => unreachable!(),
+    }
+  }
+
+  /// Generate an EC key pair for `name` ("ECDSA" or "ECDH") on
+  /// `named_curve` ("P-256", "P-384" or "P-521").
+  pub fn generate_key_ec(
+    &self,
+    name: String,
+    named_curve: String,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKeyPair, WebCryptoError> {
+    let named_curve = match named_curve.as_str() {
+      "P-256" => subtle::NamedCurve::P256,
+      "P-384" => subtle::NamedCurve::P384,
+      "P-521" => subtle::NamedCurve::P521,
+      _ => return Err(WebCryptoError::InvalidArgument),
+    };
+    let params = subtle::EcKeyGenParams {
+      name: algorithm_name(&name)?,
+      named_curve,
+    };
+
+    match self
+      .context()
+      .subtle
+      .generate_key(params.into(), extractable, key_usages(usages))
+      .map_err(operation_error)?
+    {
+      subtle::CryptoKeyOrPair::CryptoKeyPair(pair) => Ok(CryptoKeyPair {
+        public_key: Arc::new(CryptoKey(pair.public_key)),
+        private_key: Arc::new(CryptoKey(pair.private_key)),
+      }),
+      subtle::CryptoKeyOrPair::CryptoKey(_) => unreachable!(),
+    }
+  }
+
+  /// Generate an AES key for `name` ("AES-CTR", "AES-CBC", "AES-GCM",
+  /// "AES-KW", "AES-KWP" or "AES-CMAC"). `length` is in bits.
+  pub fn generate_key_aes(
+    &self,
+    name: String,
+    length: u32,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<Arc<CryptoKey>, WebCryptoError> {
+    let params = subtle::AesKeyGenParams {
+      name: algorithm_name(&name)?,
+      length: length as usize,
+    };
+    self.generate_secret_key(params.into(), extractable, usages)
+  }
+
+  /// Generate an HMAC key using `hash`. `length` is in bits and defaults
+  /// to the block size of `hash`.
+  pub fn generate_key_hmac(
+    &self,
+    hash: String,
+    length: Option<u32>,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<Arc<CryptoKey>, WebCryptoError> {
+    let params = subtle::HmacKeyGenParams {
+      name: "HMAC",
+      hash: subtle::HashAlgorithmIdentifer {
+        name: algorithm_name(&hash)?,
+      },
+      length: length.map(|length| length as usize),
+    };
+    self.generate_secret_key(params.into(), extractable, usages)
+  }
+
+  /// Sign `data` with `key`. `salt_length` is only used by "RSA-PSS" and
+  /// defaults to the digest length. "ECDSA" signs SHA-256 digests.
+  pub fn sign(
+    &self,
+    name: String,
+    salt_length: Option<u32>,
+    key: Arc<CryptoKey>,
+    data: Vec<u8>,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let params = sign_params(algorithm_name(&name)?, salt_length);
+    self
+      .context()
+      .subtle
+      .sign(params, &key.0, &data)
+      .map_err(operation_error)
+  }
+
+  pub fn verify(
+    &self,
+    name: String,
+    salt_length: Option<u32>,
+    key: Arc<CryptoKey>,
+    signature: Vec<u8>,
+    data: Vec<u8>,
+  ) -> Result<bool, WebCryptoError> {
+    let params = sign_params(algorithm_name(&name)?, salt_length);
+    self
+      .context()
+      .subtle
+      .verify(params, &key.0, &signature, &data)
+      .map_err(operation_error)
+  }
+
+  /// Encrypt `data` with `key`. `iv` is only used by "AES-GCM",
+  /// "ChaCha20-Poly1305" and "XChaCha20-Poly1305".
+  pub fn encrypt(
+    &self,
+    name: String,
+    iv: Vec<u8>,
+    key: Arc<CryptoKey>,
+    data: Vec<u8>,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let params = encrypt_params(algorithm_name(&name)?, iv);
+    self
+      .context()
+      .subtle
+      .encrypt(params, &key.0, &data)
+      .map_err(operation_error)
+  }
+
+  pub fn decrypt(
+    &self,
+    name: String,
+    iv: Vec<u8>,
+    key: Arc<CryptoKey>,
+    data: Vec<u8>,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let params = encrypt_params(algorithm_name(&name)?, iv);
+    self
+      .context()
+      .subtle
+      .decrypt(params, &key.0, &data)
+      .map_err(operation_error)
+  }
+}
+
+impl WebCrypto {
+  fn generate_secret_key(
+    &self,
+    params: subtle::KeyGenParams,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<Arc<CryptoKey>, WebCryptoError> {
+    match self
+      .context()
+      .subtle
+      .generate_key(params, extractable, key_usages(usages))
+      .map_err(operation_error)?
+    {
+      subtle::CryptoKeyOrPair::CryptoKey(key) => Ok(Arc::new(CryptoKey(key))),
+      subtle::CryptoKeyOrPair::CryptoKeyPair(_) => unreachable!(),
+    }
+  }
+}
+
+fn sign_params(
+  name: &'static str,
+  salt_length: Option<u32>,
+) -> subtle::SignParams {
+  match name {
+    "RSA-PSS" => subtle::SignParams::RsaPssParams(subtle::RsaPssParams {
+      name,
+      salt_length: salt_length.map(|salt_length| salt_length as usize),
+    }),
+    "ECDSA" => subtle::SignParams::EcdsaParams(subtle::EcdsaParams {
+      name,
+      hash: subtle::HashAlgorithmIdentifer { name: "SHA-256" },
+      deterministic: false,
+      low_s: false,
+    }),
+    _ => subtle::SignParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+      name,
+    }),
+  }
+}
+
+fn encrypt_params(name: &'static str, iv: Vec<u8>) -> subtle::EncryptParams {
+  match name {
+    "AES-GCM" => subtle::EncryptParams::AesGcmParams(subtle::AesGcmParams {
+      name,
+      iv,
+      additional_data: vec![],
+      tag_length: None,
+      key_commitment: false,
+    }),
+    "RSA-OAEP" => subtle::EncryptParams::RsaOaepParams(subtle::RsaOaepParams {
+      name,
+      label: vec![],
+    }),
+    "ChaCha20-Poly1305" => subtle::EncryptParams::ChaCha20Poly1305Params(
+      subtle::ChaCha20Poly1305Params { name, iv },
+    ),
+    "XChaCha20-Poly1305" => subtle::EncryptParams::XChaCha20Poly1305Params(
+      subtle::XChaCha20Poly1305Params { name, iv },
+    ),
+    _ => {
+      subtle::EncryptParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+        name,
+      })
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Default)]
+  struct MemoryKeyStore(Mutex<Vec<Vec<u8>>>);
+
+  impl PlatformKeyStore for MemoryKeyStore {
+    fn store(&self, key: Vec<u8>) -> u64 {
+      let mut keys = self.0.lock().unwrap();
+      keys.push(key);
+      keys.len() as u64 - 1
+    }
+
+    fn get(&self, handle: u64) -> Option<Vec<u8>> {
+      self.0.lock().unwrap().get(handle as usize).cloned()
+    }
+  }
+
+  /// Secure hardware in software, handing out handles from 0 like the
+  /// keystore.
+  #[derive(Default)]
+  struct FakeSecureEnclave(Mutex<Vec<p256::ecdsa::SigningKey>>);
+
+  impl HardwareKeyStore for FakeSecureEnclave {
+    fn generate_p256(&self) -> Option<HardwareKey> {
+      let key = p256::ecdsa::SigningKey::random(&mut OsRng);
+      let public_key = key.verifying_key().to_encoded_point(false);
+      let mut keys = self.0.lock().unwrap();
+      keys.push(key);
+      Some(HardwareKey {
+        handle: keys.len() as u64 - 1,
+        public_key: public_key.as_bytes().to_vec(),
+      })
+    }
+
+    fn sign_digest(&self, handle: u64, digest: Vec<u8>) -> Option<Vec<u8>> {
+      use p256::ecdsa::signature::hazmat::PrehashSigner;
+
+      let keys = self.0.lock().unwrap();
+      let key = keys.get(handle as usize)?;
+      let signature: p256::ecdsa::Signature = key.sign_prehash(&digest).ok()?;
+      Some(signature.to_der().as_bytes().to_vec())
+    }
+  }
+
+  #[test]
+  fn test_hardware_keys() {
+    let keystore = Arc::new(MemoryKeyStore::default());
+    let hardware = Arc::new(FakeSecureEnclave::default());
+    let webcrypto = WebCrypto::with_hardware_keys(keystore, hardware.clone());
+
+    let key_pair = webcrypto
+      .generate_key_ec(
+        "ECDSA".to_string(),
+        "P-256".to_string(),
+        false,
+        vec![KeyUsage::Sign, KeyUsage::Verify],
+      )
+      .unwrap();
+    // The hardware handle 0 does not collide with the keystore one.
+    assert_eq!(key_pair.private_key.handle(), HARDWARE_HANDLE);
+    assert_eq!(key_pair.public_key.handle(), 0);
+    assert_eq!(hardware.0.lock().unwrap().len(), 1);
+
+    let signature = webcrypto
+      .sign(
+        "ECDSA".to_string(),
+        None,
+        key_pair.private_key.clone(),
+        b"data".to_vec(),
+      )
+      .unwrap();
+    assert_eq!(signature.len(), 64);
+    assert!(webcrypto
+      .verify(
+        "ECDSA".to_string(),
+        None,
+        key_pair.public_key,
+        signature,
+        b"data".to_vec(),
+      )
+      .unwrap());
+
+    // Extractable keys and other curves stay out of the hardware.
+    let key_pair = webcrypto
+      .generate_key_ec(
+        "ECDSA".to_string(),
+        "P-384".to_string(),
+        false,
+        vec![KeyUsage::Sign],
+      )
+      .unwrap();
+    assert_eq!(key_pair.private_key.handle() & HARDWARE_HANDLE, 0);
+    webcrypto
+      .generate_key_ec(
+        "ECDSA".to_string(),
+        "P-256".to_string(),
+        true,
+        vec![KeyUsage::Sign],
+      )
+      .unwrap();
+    assert_eq!(hardware.0.lock().unwrap().len(), 1);
+  }
+
+  #[test]
+  fn test_platform_keystore() {
+    let keystore = Arc::new(MemoryKeyStore::default());
+    let webcrypto = WebCrypto::new(keystore.clone());
+
+    let key = webcrypto
+      .generate_key_aes(
+        "AES-GCM".to_string(),
+        256,
+        false,
+        vec![KeyUsage::Encrypt, KeyUsage::Decrypt],
+      )
+      .unwrap();
+    assert_eq!(keystore.get(key.handle()).unwrap().len(), 32);
+
+    let iv = vec![0; 12];
+    let ciphertext = webcrypto
+      .encrypt(
+        "AES-GCM".to_string(),
+        iv.clone(),
+        key.clone(),
+        b"data".to_vec(),
+      )
+      .unwrap();
+    let plaintext = webcrypto
+      .decrypt("AES-GCM".to_string(), iv, key, ciphertext)
+      .unwrap();
+    assert_eq!(plaintext, b"data");
+  }
+
+  /// A keystore handing out handles in the hardware key space.
+  struct OutOfRangeKeyStore;
+
+  impl PlatformKeyStore for OutOfRangeKeyStore {
+    fn store(&self, _key: Vec<u8>) -> u64 {
+      HARDWARE_HANDLE
+    }
+
+    fn get(&self, _handle: u64) -> Option<Vec<u8>> {
+      None
+    }
+  }
+
+  #[test]
+  fn test_platform_keystore_handle_range() {
+    let webcrypto = WebCrypto::new(Arc::new(OutOfRangeKeyStore));
+    assert!(webcrypto
+      .generate_key_aes(
+        "AES-GCM".to_string(),
+        256,
+        false,
+        vec![KeyUsage::Encrypt]
+      )
+      .is_err());
+  }
+}
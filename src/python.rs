@@ -0,0 +1,515 @@
+//! Python bindings, enabled with the `python` feature.
+//!
+//! Build the extension module with [maturin](https://github.com/PyO3/maturin):
+//!
+//! ```text
+//! maturin develop --features python
+//! ```
+//!
+//! Algorithms are passed as dictionaries (or plain strings) shaped like
+//! their WebCrypto counterparts and all data is exchanged as `bytes`:
+//!
+//! ```text
+//! from webcrypto import Context
+//!
+//! ctx = Context()  # or Context(vault="keys.vault")
+//! pair = ctx.generate_key(
+//!   {"name": "RSA-PSS", "modulusLength": 2048,
+//!    "publicExponent": b"\x01\x00\x01", "hash": "SHA-256"},
+//!   True, ["sign", "verify"])
+//! sig = ctx.sign({"name": "RSA-PSS", "saltLength": 32},
+//!   pair["privateKey"], b"data")
+//! ```
+use std::path::PathBuf;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3::types::PyDict;
+use pyo3::types::PyString;
+
+use crate::storage::Backend;
+use crate::subtle;
+use crate::Context;
+use crate::WebCryptoError;
+
+fn operation_error(error: WebCryptoError) -> PyErr {
+  PyValueError::new_err(error.to_string())
+}
+
+fn algorithm_name(name: &str) -> PyResult<&'static str> {
+  subtle::algorithm_name(name).ok_or_else(|| {
+    PyValueError::new_err(format!("Unrecognized algorithm name: {}", name))
+  })
+}
+
+/// Read an algorithm argument, which is either a name or a dictionary
+/// with a `name` member.
+fn algorithm_dict(algorithm: &PyAny) -> PyResult<(&'static str, &PyDict)> {
+  if let Ok(name) = algorithm.downcast::<PyString>() {
+    return Ok((algorithm_name(name.to_str()?)?, PyDict::new(algorithm.py())));
+  }
+
+  let dict = algorithm.downcast::<PyDict>()?;
+  let name = member(dict, "name")?.extract::<&str>()?;
+  Ok((algorithm_name(name)?, dict))
+}
+
+fn member<'a>(dict: &'a PyDict, key: &str) -> PyResult<&'a PyAny> {
+  dict.get_item(key).ok_or_else(|| {
+    PyValueError::new_err(format!("Missing algorithm member: {}", key))
+  })
+}
+
+fn hash_member(dict: &PyDict) -> PyResult<subtle::HashAlgorithmIdentifer> {
+  let (name, _) = algorithm_dict(member(dict, "hash")?)?;
+  Ok(subtle::HashAlgorithmIdentifer { name })
+}
+
+fn named_curve(dict: &PyDict) -> PyResult<subtle::NamedCurve> {
+  let named_curve: &str = member(dict, "namedCurve")?.extract()?;
+  Ok(match named_curve {
+    "P-256" => subtle::NamedCurve::P256,
+    "P-384" => subtle::NamedCurve::P384,
+    "P-521" => subtle::NamedCurve::P521,
+    #[cfg(feature = "k256")]
+    "K-256" => subtle::NamedCurve::K256,
+    _ => {
+      return Err(PyValueError::new_err(format!(
+        "Unrecognized named curve: {}",
+        named_curve
+      )))
+    }
+  })
+}
+
+fn key_usage(usage: &str) -> PyResult<subtle::KeyUsage> {
+  Ok(match usage {
+    "encrypt" => subtle::KeyUsage::Encrypt,
+    "decrypt" => subtle::KeyUsage::Decrypt,
+    "sign" => subtle::KeyUsage::Sign,
+    "verify" => subtle::KeyUsage::Verify,
+    "wrapKey" => subtle::KeyUsage::WrapKey,
+    "unwrapKey" => subtle::KeyUsage::UnwrapKey,
+    "deriveKey" => subtle::KeyUsage::DeriveKey,
+    "deriveBits" => subtle::KeyUsage::DeriveBits,
+    "encapsulateBits" => subtle::KeyUsage::EncapsulateBits,
+    "decapsulateBits" => subtle::KeyUsage::DecapsulateBits,
+    _ => {
+      return Err(PyValueError::new_err(format!(
+        "Unrecognized key usage: {}",
+        usage
+      )))
+    }
+  })
+}
+
+fn key_usages(usages: Vec<&str>) -> PyResult<Vec<subtle::KeyUsage>> {
+  usages.into_iter().map(key_usage).collect()
+}
+
+fn key_gen_params(algorithm: &PyAny) -> PyResult<subtle::KeyGenParams> {
+  let (name, dict) = algorithm_dict(algorithm)?;
+  Ok(match name {
+    "RSASSA-PKCS1-v1_5" | "RSA-PSS" | "RSA-OAEP" => {
+      let public_exponent = subtle::PublicExponent::from_be_bytes(
+        member(dict, "publicExponent")?.extract::<&[u8]>()?,
+      )
+      .map_err(|_| PyValueError::new_err("Unsupported publicExponent"))?;
+
+      subtle::RsaHashedKeyGenParams {
+        name,
+        hash: hash_member(dict)?,
+        modulus_length: member(dict, "modulusLength")?.extract()?,
+        public_exponent,
+      }
+      .into()
+    }
+    "ECDSA" | "ECDH" => subtle::EcKeyGenParams {
+      name,
+      named_curve: named_curve(dict)?,
+    }
+    .into(),
+    "AES-CTR" | "AES-CBC" | "AES-GCM" | "AES-KW" | "AES-KWP" | "AES-CMAC" => {
+      subtle::AesKeyGenParams {
+        name,
+        length: member(dict, "length")?.extract()?,
+      }
+      .into()
+    }
+    "HMAC" => subtle::HmacKeyGenParams {
+      name,
+      hash: hash_member(dict)?,
+      length: dict.get_item("length").map(|l| l.extract()).transpose()?,
+    }
+    .into(),
+    "ChaCha20-Poly1305" | "XChaCha20-Poly1305" | "BLAKE3" => {
+      subtle::KeyGenParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+        name,
+      })
+    }
+    _ => {
+      return Err(PyValueError::new_err(format!(
+        "Unsupported algorithm: {}",
+        name
+      )))
+    }
+  })
+}
+
+fn sign_params(algorithm: &PyAny) -> PyResult<subtle::SignParams> {
+  let (name, dict) = algorithm_dict(algorithm)?;
+  Ok(match name {
+    "RSA-PSS" => subtle::SignParams::RsaPssParams(subtle::RsaPssParams {
+      name,
+      salt_length: dict
+        .get_item("saltLength")
+        .map(|salt_length| salt_length.extract())
+        .transpose()?,
+    }),
+    "ECDSA" => subtle::SignParams::EcdsaParams(subtle::EcdsaParams {
+      name,
+      hash: hash_member(dict)?,
+      deterministic: dict
+        .get_item("deterministic")
+        .map(|deterministic| deterministic.extract())
+        .transpose()?
+        .unwrap_or(false),
+      low_s: dict
+        .get_item("lowS")
+        .map(|low_s| low_s.extract())
+        .transpose()?
+        .unwrap_or(false),
+    }),
+    _ => subtle::SignParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+      name,
+    }),
+  })
+}
+
+fn encrypt_params(algorithm: &PyAny) -> PyResult<subtle::EncryptParams> {
+  let (name, dict) = algorithm_dict(algorithm)?;
+  Ok(match name {
+    "AES-GCM" => subtle::EncryptParams::AesGcmParams(subtle::AesGcmParams {
+      name,
+      iv: member(dict, "iv")?.extract()?,
+      additional_data: match dict.get_item("additionalData") {
+        Some(additional_data) => additional_data.extract()?,
+        None => vec![],
+      },
+      tag_length: dict
+        .get_item("tagLength")
+        .map(|tag_length| tag_length.extract())
+        .transpose()?,
+      key_commitment: match dict.get_item("keyCommitment") {
+        Some(key_commitment) => key_commitment.extract()?,
+        None => false,
+      },
+    }),
+    "RSA-OAEP" => subtle::EncryptParams::RsaOaepParams(subtle::RsaOaepParams {
+      name,
+      label: match dict.get_item("label") {
+        Some(label) => label.extract()?,
+        None => vec![],
+      },
+    }),
+    "ChaCha20-Poly1305" => subtle::EncryptParams::ChaCha20Poly1305Params(
+      subtle::ChaCha20Poly1305Params {
+        name,
+        iv: member(dict, "iv")?.extract()?,
+      },
+    ),
+    "XChaCha20-Poly1305" => subtle::EncryptParams::XChaCha20Poly1305Params(
+      subtle::XChaCha20Poly1305Params {
+        name,
+        iv: member(dict, "iv")?.extract()?,
+      },
+    ),
+    _ => {
+      subtle::EncryptParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+        name,
+      })
+    }
+  })
+}
+
+fn import_params(algorithm: &PyAny) -> PyResult<subtle::ImportParams> {
+  let (name, dict) = algorithm_dict(algorithm)?;
+  Ok(match name {
+    "HMAC" => {
+      subtle::ImportParams::HmacImportParams(subtle::HmacImportParams {
+        name,
+        hash: hash_member(dict)?,
+        length: dict.get_item("length").map(|l| l.extract()).transpose()?,
+      })
+    }
+    "RSASSA-PKCS1-v1_5" | "RSA-PSS" | "RSA-OAEP" => {
+      subtle::ImportParams::RsaHashedImportParams(
+        subtle::RsaHashedImportParams {
+          name,
+          hash: hash_member(dict)?,
+        },
+      )
+    }
+    "ECDSA" | "ECDH" => {
+      subtle::ImportParams::EcKeyImportParams(subtle::EcKeyImportParams {
+        name,
+        named_curve: named_curve(dict)?,
+      })
+    }
+    _ => subtle::ImportParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+      name,
+    }),
+  })
+}
+
+/// A `CryptoKey` handed out to Python.
+#[pyclass(name = "CryptoKey")]
+pub struct PyCryptoKey(subtle::CryptoKey<usize>);
+
+#[pymethods]
+impl PyCryptoKey {
+  #[getter]
+  fn extractable(&self) -> bool {
+    self.0.extractable
+  }
+
+  #[getter]
+  fn algorithm(&self) -> &'static str {
+    self.0.algorithm.name()
+  }
+
+  #[getter]
+  fn r#type(&self) -> &'static str {
+    match self.0.type_ {
+      subtle::KeyType::Public => "public",
+      subtle::KeyType::Private => "private",
+      subtle::KeyType::Secret => "secret",
+    }
+  }
+
+  #[getter]
+  fn usages(&self) -> Vec<&'static str> {
+    self
+      .0
+      .usages
+      .iter()
+      .map(|usage| match usage {
+        subtle::KeyUsage::Encrypt => "encrypt",
+        subtle::KeyUsage::Decrypt => "decrypt",
+        subtle::KeyUsage::Sign => "sign",
+        subtle::KeyUsage::Verify => "verify",
+        subtle::KeyUsage::WrapKey => "wrapKey",
+        subtle::KeyUsage::UnwrapKey => "unwrapKey",
+        subtle::KeyUsage::DeriveKey => "deriveKey",
+        subtle::KeyUsage::DeriveBits => "deriveBits",
+        subtle::KeyUsage::EncapsulateBits => "encapsulateBits",
+        subtle::KeyUsage::DecapsulateBits => "decapsulateBits",
+      })
+      .collect()
+  }
+}
+
+/// A WebCrypto context backed by the operating system RNG. Keys are kept
+/// in the `FileVault` at `vault`, or in memory without one.
+#[pyclass(name = "Context")]
+pub struct PyContext(Context<rand::rngs::OsRng, Backend>);
+
+#[pymethods]
+impl PyContext {
+  #[new]
+  #[pyo3(signature = (vault = None))]
+  fn new(vault: Option<PathBuf>) -> PyResult<Self> {
+    Ok(PyContext(Context::new(
+      rand::rngs::OsRng,
+      Backend::open(vault)?,
+    )))
+  }
+
+  fn get_random_values<'py>(
+    &mut self,
+    py: Python<'py>,
+    length: usize,
+  ) -> PyResult<&'py PyBytes> {
+    let mut bytes = vec![0u8; length];
+    self
+      .0
+      .get_random_values(&mut bytes)
+      .map_err(operation_error)?;
+    Ok(PyBytes::new(py, &bytes))
+  }
+
+  fn random_uuid(&mut self) -> String {
+    self.0.random_uuid()
+  }
+
+  fn generate_key(
+    &mut self,
+    py: Python<'_>,
+    algorithm: &PyAny,
+    extractable: bool,
+    usages: Vec<&str>,
+  ) -> PyResult<PyObject> {
+    let key = self
+      .0
+      .subtle
+      .generate_key(
+        key_gen_params(algorithm)?,
+        extractable,
+        key_usages(usages)?,
+      )
+      .map_err(operation_error)?;
+
+    Ok(match key {
+      subtle::CryptoKeyOrPair::CryptoKey(key) => {
+        Py::new(py, PyCryptoKey(key))?.into_py(py)
+      }
+      subtle::CryptoKeyOrPair::CryptoKeyPair(pair) => {
+        let dict = PyDict::new(py);
+        dict
+          .set_item("publicKey", Py::new(py, PyCryptoKey(pair.public_key))?)?;
+        dict.set_item(
+          "privateKey",
+          Py::new(py, PyCryptoKey(pair.private_key))?,
+        )?;
+        dict.into_py(py)
+      }
+    })
+  }
+
+  fn sign<'py>(
+    &mut self,
+    py: Python<'py>,
+    algorithm: &PyAny,
+    key: PyRef<PyCryptoKey>,
+    data: &[u8],
+  ) -> PyResult<&'py PyBytes> {
+    let signature = self
+      .0
+      .subtle
+      .sign(sign_params(algorithm)?, &key.0, data)
+      .map_err(operation_error)?;
+    Ok(PyBytes::new(py, &signature))
+  }
+
+  fn verify(
+    &mut self,
+    algorithm: &PyAny,
+    key: PyRef<PyCryptoKey>,
+    signature: &[u8],
+    data: &[u8],
+  ) -> PyResult<bool> {
+    self
+      .0
+      .subtle
+      .verify(sign_params(algorithm)?, &key.0, signature, data)
+      .map_err(operation_error)
+  }
+
+  fn import_key(
+    &mut self,
+    format: &str,
+    key_data: &[u8],
+    algorithm: &PyAny,
+    extractable: bool,
+    usages: Vec<&str>,
+  ) -> PyResult<PyCryptoKey> {
+    let key = self
+      .0
+      .subtle
+      .import_key(
+        format,
+        key_data,
+        import_params(algorithm)?,
+        extractable,
+        key_usages(usages)?,
+      )
+      .map_err(operation_error)?;
+    Ok(PyCryptoKey(key))
+  }
+
+  fn encrypt<'py>(
+    &mut self,
+    py: Python<'py>,
+    algorithm: &PyAny,
+    key: PyRef<PyCryptoKey>,
+    data: &[u8],
+  ) -> PyResult<&'py PyBytes> {
+    let ciphertext = self
+      .0
+      .subtle
+      .encrypt(encrypt_params(algorithm)?, &key.0, data)
+      .map_err(operation_error)?;
+    Ok(PyBytes::new(py, &ciphertext))
+  }
+
+  fn decrypt<'py>(
+    &mut self,
+    py: Python<'py>,
+    algorithm: &PyAny,
+    key: PyRef<PyCryptoKey>,
+    data: &[u8],
+  ) -> PyResult<&'py PyBytes> {
+    let plaintext = self
+      .0
+      .subtle
+      .decrypt(encrypt_params(algorithm)?, &key.0, data)
+      .map_err(operation_error)?;
+    Ok(PyBytes::new(py, &plaintext))
+  }
+
+  fn wrap_key<'py>(
+    &mut self,
+    py: Python<'py>,
+    format: &str,
+    key: PyRef<PyCryptoKey>,
+    wrapping_key: PyRef<PyCryptoKey>,
+    wrap_algorithm: &PyAny,
+  ) -> PyResult<&'py PyBytes> {
+    let wrapped = self
+      .0
+      .subtle
+      .wrap_key(
+        format,
+        &key.0,
+        &wrapping_key.0,
+        encrypt_params(wrap_algorithm)?,
+      )
+      .map_err(operation_error)?;
+    Ok(PyBytes::new(py, &wrapped))
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn unwrap_key(
+    &mut self,
+    format: &str,
+    wrapped_key: &[u8],
+    unwrapping_key: PyRef<PyCryptoKey>,
+    unwrap_algorithm: &PyAny,
+    unwrapped_key_algorithm: &PyAny,
+    extractable: bool,
+    usages: Vec<&str>,
+  ) -> PyResult<PyCryptoKey> {
+    let key = self
+      .0
+      .subtle
+      .unwrap_key(
+        format,
+        wrapped_key,
+        &unwrapping_key.0,
+        encrypt_params(unwrap_algorithm)?,
+        import_params(unwrapped_key_algorithm)?,
+        extractable,
+        key_usages(usages)?,
+      )
+      .map_err(operation_error)?;
+    Ok(PyCryptoKey(key))
+  }
+}
+
+#[pymodule]
+fn webcrypto(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+  m.add_class::<PyContext>()?;
+  m.add_class::<PyCryptoKey>()?;
+  Ok(())
+}
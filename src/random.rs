@@ -0,0 +1,41 @@
+use rand::RngCore;
+
+mod sealed {
+  pub trait Sealed {}
+}
+
+/// The integer types `Context::get_random_values` fills, those of the
+/// typed arrays `crypto.getRandomValues` accepts: `u8` and `i8` to
+/// `u64` and `i64`. Floats are not random values.
+pub trait RandomValue: sealed::Sealed + Sized {
+  #[doc(hidden)]
+  fn fill<R: RngCore>(values: &mut [Self], rng: &mut R);
+}
+
+impl sealed::Sealed for u8 {}
+
+impl RandomValue for u8 {
+  fn fill<R: RngCore>(values: &mut [Self], rng: &mut R) {
+    rng.fill_bytes(values);
+  }
+}
+
+macro_rules! impl_random_value {
+  ($($type:ty),*) => {
+    $(
+      impl sealed::Sealed for $type {}
+
+      impl RandomValue for $type {
+        fn fill<R: RngCore>(values: &mut [Self], rng: &mut R) {
+          for value in values {
+            let mut bytes = [0; std::mem::size_of::<$type>()];
+            rng.fill_bytes(&mut bytes);
+            *value = <$type>::from_ne_bytes(bytes);
+          }
+        }
+      }
+    )*
+  };
+}
+
+impl_random_value!(i8, u16, i16, u32, i32, u64, i64);
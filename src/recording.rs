@@ -0,0 +1,240 @@
+//! Transcripts of subtle operations for golden tests, enabled with the
+//! `recording` feature.
+//!
+//! A [`Recorder`] attached with [`SubtleCrypto::set_recorder`] logs the
+//! inputs and outputs of every operation, and a [`RecordingRng`] logs the
+//! random bytes the context draws. Running the same flow again with a
+//! [`ReplayRng`] draws the recorded random bytes instead, so it has to
+//! reproduce the transcript exactly.
+//!
+//! ```
+//! use webcrypto::recording::Recorder;
+//! use webcrypto::recording::RecordingRng;
+//! use webcrypto::recording::ReplayRng;
+//! use webcrypto::recording::Transcript;
+//! use webcrypto::storage::InMemoryVault;
+//! use webcrypto::subtle;
+//! use webcrypto::Context;
+//!
+//! fn flow<R: rand::RngCore + rand::CryptoRng>(
+//!   rng: R,
+//!   recorder: &'static Recorder,
+//! ) -> Transcript {
+//!   let mut ctx = Context::new(rng, InMemoryVault::new());
+//!   ctx.subtle.set_recorder(Some(recorder));
+//!
+//!   let params = subtle::ChaCha20Poly1305Params {
+//!     name: "ChaCha20-Poly1305",
+//!     iv: vec![0; 12],
+//!   };
+//!   let key = match ctx.subtle.generate_key(
+//!     subtle::KeyGenParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+//!       name: "ChaCha20-Poly1305",
+//!     }),
+//!     false,
+//!     vec![subtle::KeyUsage::Encrypt],
+//!   ) {
+//!     Ok(subtle::CryptoKeyOrPair::CryptoKey(key)) => key,
+//!     _ => unreachable!(),
+//!   };
+//!   let algorithm = subtle::EncryptParams::ChaCha20Poly1305Params(params);
+//!   ctx.subtle.encrypt(algorithm, &key, b"golden").unwrap();
+//!
+//!   recorder.transcript()
+//! }
+//!
+//! let recorder = Recorder::leak();
+//! let golden = flow(RecordingRng::new(rand::rngs::OsRng, recorder), recorder);
+//! let json = golden.to_json();
+//!
+//! let golden = Transcript::from_json(&json).unwrap();
+//! let recorder = Recorder::leak();
+//! let rng = ReplayRng::new(&golden, recorder).unwrap();
+//! assert_eq!(flow(rng, recorder), golden);
+//! ```
+//!
+//! [`SubtleCrypto::set_recorder`]: crate::subtle::SubtleCrypto::set_recorder
+use std::sync::Mutex;
+
+use rand::CryptoRng;
+use rand::RngCore;
+
+use crate::WebCryptoError;
+use serde::Deserialize;
+use serde::Serialize;
+
+fn encode(bytes: &[u8]) -> String {
+  base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// One operation of a transcript. Binary values are base64url encoded
+/// without padding.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Record {
+  /// The WebCrypto name of the operation, eg. "importKey".
+  pub operation: String,
+  /// The byte and string arguments, eg. the format and key data of
+  /// "importKey". Algorithm parameters are not recorded.
+  pub inputs: Vec<String>,
+  /// The output, empty for operations returning keys, or `None` when the
+  /// operation failed.
+  pub output: Option<String>,
+}
+
+/// The operations of a context and the random bytes it drew, in order.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Transcript {
+  /// Each draw from the random number generator, base64url encoded.
+  pub random: Vec<String>,
+  pub records: Vec<Record>,
+}
+
+impl Transcript {
+  pub fn to_json(&self) -> String {
+    serde_json::to_string_pretty(self).unwrap()
+  }
+
+  pub fn from_json(json: &str) -> Result<Self, WebCryptoError> {
+    serde_json::from_str(json).map_err(|_| WebCryptoError::OperationError)
+  }
+}
+
+/// Collects a `Transcript`.
+#[derive(Debug, Default)]
+pub struct Recorder(Mutex<Transcript>);
+
+impl Recorder {
+  /// Create a recorder living until the process exits. Random number
+  /// generators have to be `'static` and `Copy` to sign, so they can
+  /// only refer to a leaked recorder.
+  pub fn leak() -> &'static Recorder {
+    Box::leak(Box::default())
+  }
+
+  /// A copy of everything recorded so far.
+  pub fn transcript(&self) -> Transcript {
+    self.0.lock().unwrap().clone()
+  }
+
+  pub(crate) fn record(
+    &self,
+    operation: &str,
+    inputs: &[&[u8]],
+    output: Option<&[u8]>,
+  ) {
+    self.0.lock().unwrap().records.push(Record {
+      operation: operation.to_string(),
+      inputs: inputs.iter().map(|input| encode(input)).collect(),
+      output: output.map(encode),
+    });
+  }
+
+  fn random(&self, bytes: &[u8]) {
+    self.0.lock().unwrap().random.push(encode(bytes));
+  }
+}
+
+/// Records the bytes drawn from `rng`.
+#[derive(Clone, Copy)]
+pub struct RecordingRng<R> {
+  rng: R,
+  recorder: &'static Recorder,
+}
+
+impl<R: RngCore> RecordingRng<R> {
+  pub fn new(rng: R, recorder: &'static Recorder) -> Self {
+    RecordingRng { rng, recorder }
+  }
+}
+
+impl<R: RngCore> RngCore for RecordingRng<R> {
+  fn next_u32(&mut self) -> u32 {
+    let mut bytes = [0; 4];
+    self.fill_bytes(&mut bytes);
+    u32::from_le_bytes(bytes)
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    let mut bytes = [0; 8];
+    self.fill_bytes(&mut bytes);
+    u64::from_le_bytes(bytes)
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    self.rng.fill_bytes(dest);
+    self.recorder.random(dest);
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+    self.rng.try_fill_bytes(dest)?;
+    self.recorder.random(dest);
+    Ok(())
+  }
+}
+
+impl<R: CryptoRng> CryptoRng for RecordingRng<R> {}
+
+/// Replays the random bytes of a transcript, recording them again. It is
+/// predictable by design: only use it in tests.
+#[derive(Clone, Copy)]
+pub struct ReplayRng {
+  random: &'static Mutex<(Vec<u8>, usize)>,
+  recorder: &'static Recorder,
+}
+
+impl ReplayRng {
+  /// Replay the random bytes of `transcript`. Like `Recorder::leak`, the
+  /// bytes live until the process exits.
+  pub fn new(
+    transcript: &Transcript,
+    recorder: &'static Recorder,
+  ) -> Result<Self, WebCryptoError> {
+    let mut random = Vec::new();
+    for draw in &transcript.random {
+      random.extend(
+        base64::decode_config(draw, base64::URL_SAFE_NO_PAD)
+          .map_err(|_| WebCryptoError::OperationError)?,
+      );
+    }
+
+    Ok(ReplayRng {
+      random: Box::leak(Box::new(Mutex::new((random, 0)))),
+      recorder,
+    })
+  }
+}
+
+impl RngCore for ReplayRng {
+  fn next_u32(&mut self) -> u32 {
+    let mut bytes = [0; 4];
+    self.fill_bytes(&mut bytes);
+    u32::from_le_bytes(bytes)
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    let mut bytes = [0; 8];
+    self.fill_bytes(&mut bytes);
+    u64::from_le_bytes(bytes)
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    self
+      .try_fill_bytes(dest)
+      .expect("the transcript has no more random bytes")
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+    let mut state = self.random.lock().unwrap();
+    let (random, position) = &mut *state;
+    let bytes = random
+      .get(*position..*position + dest.len())
+      .ok_or_else(|| rand::Error::new("transcript exhausted"))?;
+    dest.copy_from_slice(bytes);
+    *position += dest.len();
+
+    self.recorder.random(dest);
+    Ok(())
+  }
+}
+
+impl CryptoRng for ReplayRng {}
@@ -0,0 +1,499 @@
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::storage::InMemoryVault;
+use crate::subtle;
+use crate::subtle::CryptoKey;
+use crate::subtle::CryptoKeyOrPair;
+use crate::subtle::CryptoKeyPair;
+use crate::subtle::KeyUsage;
+use crate::subtle::SubtleCrypto;
+use crate::WebCryptoError;
+
+type Subtle = SubtleCrypto<OsRng, InMemoryVault>;
+
+/// The outcome of the self-test of one algorithm.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelfTestResult {
+  /// The algorithm name, or "RNG" for the random number generator.
+  pub algorithm: &'static str,
+  pub passed: bool,
+}
+
+/// The outcome of `Context::self_test`, one result per algorithm.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SelfTestReport {
+  pub results: Vec<SelfTestResult>,
+}
+
+impl SelfTestReport {
+  /// Whether every self-test passed.
+  pub fn passed(&self) -> bool {
+    self.results.iter().all(|result| result.passed)
+  }
+
+  /// The algorithms whose self-test failed.
+  pub fn failures(&self) -> Vec<&'static str> {
+    self
+      .results
+      .iter()
+      .filter(|result| !result.passed)
+      .map(|result| result.algorithm)
+      .collect()
+  }
+}
+
+const DIGESTS: &[(&str, &str)] = &[
+  ("SHA-1", "a9993e364706816aba3e25717850c26c9cd0d89d"),
+  (
+    "SHA-256",
+    "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+  ),
+  (
+    "SHA-384",
+    "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded163\
+     1a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+  ),
+  (
+    "SHA-512",
+    "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+     2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+  ),
+  (
+    "BLAKE3",
+    "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+  ),
+];
+
+fn hex(hex: &str) -> Vec<u8> {
+  (0..hex.len())
+    .step_by(2)
+    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+    .collect()
+}
+
+/// Run the self-tests on a scratch context, so keys never reach the
+/// storage of the caller. `rng` is the generator of the caller.
+pub(crate) fn run(rng: &mut impl RngCore) -> SelfTestReport {
+  let mut subtle = SubtleCrypto::new(OsRng, InMemoryVault::new());
+  let mut results = Vec::new();
+  let mut check = |algorithm, result: Result<bool, WebCryptoError>| {
+    results.push(SelfTestResult {
+      algorithm,
+      passed: result == Ok(true),
+    });
+  };
+
+  check("RNG", Ok(rng_test(rng)));
+  for &(name, expected) in DIGESTS {
+    let algorithm = subtle::AlgorithmIdentifer { name };
+    check(
+      name,
+      subtle.digest(algorithm, b"abc").map(|d| d == hex(expected)),
+    );
+  }
+  check("AES-GCM", aes_gcm(&mut subtle));
+  check("AES-KW", aes_kw(&mut subtle));
+  check("ChaCha20-Poly1305", chacha20_poly1305(&mut subtle));
+  check("XChaCha20-Poly1305", xchacha20_poly1305(&mut subtle));
+  check(
+    "RSASSA-PKCS1-v1_5",
+    rsa_sign(&mut subtle, "RSASSA-PKCS1-v1_5"),
+  );
+  check("RSA-PSS", rsa_sign(&mut subtle, "RSA-PSS"));
+  check("RSA-OAEP", rsa_oaep(&mut subtle));
+  check("RSA-KEM", rsa_kem(&mut subtle));
+  check("ECDSA", ecdsa(&mut subtle));
+  check("ECDH", ecdh(&mut subtle));
+  check("DH", dh(&mut subtle));
+  check("Argon2id", argon2id(&mut subtle));
+  check("scrypt", scrypt(&mut subtle));
+  #[cfg(feature = "ml-kem")]
+  check("X-Wing", xwing(&mut subtle));
+
+  SelfTestReport { results }
+}
+
+/// Two draws from a working generator never repeat, nor are they zero.
+fn rng_test(rng: &mut impl RngCore) -> bool {
+  let mut a = [0; 32];
+  let mut b = [0; 32];
+  rng.fill_bytes(&mut a);
+  rng.fill_bytes(&mut b);
+  a != b && a != [0; 32]
+}
+
+fn import(
+  subtle: &mut Subtle,
+  format: &str,
+  key_data: &[u8],
+  algorithm: subtle::ImportParams,
+  usages: Vec<KeyUsage>,
+) -> Result<CryptoKey<usize>, WebCryptoError> {
+  subtle.import_key(format, key_data, algorithm, false, usages)
+}
+
+fn identifier(name: &'static str) -> subtle::ImportParams {
+  subtle::ImportParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer { name })
+}
+
+fn key_pair(
+  subtle: &mut Subtle,
+  algorithm: subtle::KeyGenParams,
+  usages: Vec<KeyUsage>,
+) -> Result<CryptoKeyPair<usize>, WebCryptoError> {
+  match subtle.generate_key(algorithm, false, usages)? {
+    CryptoKeyOrPair::CryptoKeyPair(key_pair) => Ok(key_pair),
+    CryptoKeyOrPair::CryptoKey(_) => Err(WebCryptoError::OperationError),
+  }
+}
+
+/// The all-zero key, IV and block of the GCM specification, test case 2.
+fn aes_gcm(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let usages = vec![KeyUsage::Encrypt, KeyUsage::Decrypt];
+  let key = import(subtle, "raw", &[0; 16], identifier("AES-GCM"), usages)?;
+  let params = subtle::EncryptParams::AesGcmParams(subtle::AesGcmParams {
+    name: "AES-GCM",
+    iv: vec![0; 12],
+    additional_data: vec![],
+    tag_length: None,
+    key_commitment: false,
+  });
+
+  let ciphertext = subtle.encrypt(params.clone(), &key, &[0; 16])?;
+  Ok(
+    ciphertext
+      == hex(
+        "0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf",
+      )
+      && subtle.decrypt(params, &key, &ciphertext)? == [0; 16],
+  )
+}
+
+/// RFC 3394, section 4.1.
+fn aes_kw(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let kek_data: Vec<u8> = (0..16).collect();
+  let kek = import(
+    subtle,
+    "raw",
+    &kek_data,
+    identifier("AES-KW"),
+    vec![KeyUsage::WrapKey],
+  )?;
+  let key = subtle.import_key(
+    "raw",
+    &hex("00112233445566778899aabbccddeeff"),
+    identifier("AES-GCM"),
+    true,
+    vec![KeyUsage::Encrypt],
+  )?;
+  let params =
+    subtle::EncryptParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+      name: "AES-KW",
+    });
+
+  let wrapped = subtle.wrap_key("raw", &key, &kek, params)?;
+  Ok(wrapped == hex("1fa68b0a8112b447aef34bd8fb5a7b829d3e862371d2cfe5"))
+}
+
+/// The RFC 8439 key and nonce.
+fn chacha20_poly1305(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let key_data: Vec<u8> = (0x80..0xa0).collect();
+  let usages = vec![KeyUsage::Encrypt, KeyUsage::Decrypt];
+  let key = import(
+    subtle,
+    "raw",
+    &key_data,
+    identifier("ChaCha20-Poly1305"),
+    usages,
+  )?;
+  let params = subtle::EncryptParams::ChaCha20Poly1305Params(
+    subtle::ChaCha20Poly1305Params {
+      name: "ChaCha20-Poly1305",
+      iv: hex("070000004041424344454647"),
+    },
+  );
+
+  let ciphertext = subtle.encrypt(params.clone(), &key, b"Hello, world!")?;
+  Ok(
+    ciphertext
+      == hex("d71e85316ed160cd7a90e39f17a35e9cc5b7bb81882a8bff5d6a2d738d")
+      && subtle.decrypt(params, &key, &ciphertext)? == b"Hello, world!",
+  )
+}
+
+/// A round trip, and a tampered ciphertext is rejected.
+fn xchacha20_poly1305(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let usages = vec![KeyUsage::Encrypt, KeyUsage::Decrypt];
+  let key = import(
+    subtle,
+    "raw",
+    &[0x42; 32],
+    identifier("XChaCha20-Poly1305"),
+    usages,
+  )?;
+  let params = subtle::EncryptParams::XChaCha20Poly1305Params(
+    subtle::XChaCha20Poly1305Params {
+      name: "XChaCha20-Poly1305",
+      iv: vec![0x24; 24],
+    },
+  );
+
+  let mut ciphertext = subtle.encrypt(params.clone(), &key, b"abc")?;
+  let round_trip = subtle.decrypt(params.clone(), &key, &ciphertext)?;
+  ciphertext[0] ^= 1;
+  Ok(round_trip == b"abc" && subtle.decrypt(params, &key, &ciphertext).is_err())
+}
+
+fn rsa_key_pair(
+  subtle: &mut Subtle,
+  name: &'static str,
+  private_usages: Vec<KeyUsage>,
+  public_usages: Vec<KeyUsage>,
+) -> Result<CryptoKeyPair<usize>, WebCryptoError> {
+  let algorithm = subtle::ImportParams::RsaHashedImportParams(
+    subtle::RsaHashedImportParams {
+      name,
+      hash: subtle::HashAlgorithmIdentifer { name: "SHA-256" },
+    },
+  );
+  Ok(CryptoKeyPair {
+    private_key: import(
+      subtle,
+      "pkcs8",
+      include_bytes!("../testdata/rsa1024.pk8"),
+      algorithm,
+      private_usages,
+    )?,
+    public_key: import(
+      subtle,
+      "spki",
+      include_bytes!("../testdata/rsa1024.spki"),
+      algorithm,
+      public_usages,
+    )?,
+  })
+}
+
+/// Sign, then check the signature verifies and a modified one does not.
+fn rsa_sign(
+  subtle: &mut Subtle,
+  name: &'static str,
+) -> Result<bool, WebCryptoError> {
+  let key =
+    rsa_key_pair(subtle, name, vec![KeyUsage::Sign], vec![KeyUsage::Verify])?;
+  let params = match name {
+    "RSA-PSS" => subtle::SignParams::RsaPssParams(subtle::RsaPssParams {
+      name,
+      salt_length: None,
+    }),
+    _ => subtle::SignParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+      name,
+    }),
+  };
+
+  let mut signature = subtle.sign(params, &key.private_key, b"abc")?;
+  let valid = subtle.verify(params, &key.public_key, &signature, b"abc")?;
+  signature[0] ^= 1;
+  Ok(valid && !subtle.verify(params, &key.public_key, &signature, b"abc")?)
+}
+
+fn rsa_oaep(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let key = rsa_key_pair(
+    subtle,
+    "RSA-OAEP",
+    vec![KeyUsage::Decrypt],
+    vec![KeyUsage::Encrypt],
+  )?;
+  let params = subtle::EncryptParams::RsaOaepParams(subtle::RsaOaepParams {
+    name: "RSA-OAEP",
+    label: vec![],
+  });
+
+  let ciphertext = subtle.encrypt(params.clone(), &key.public_key, b"abc")?;
+  Ok(subtle.decrypt(params, &key.private_key, &ciphertext)? == b"abc")
+}
+
+fn rsa_kem(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let key = rsa_key_pair(
+    subtle,
+    "RSA-KEM",
+    vec![KeyUsage::DecapsulateBits],
+    vec![KeyUsage::EncapsulateBits],
+  )?;
+  let params = subtle::EncapsulateParams::RsaKemParams(subtle::RsaKemParams {
+    name: "RSA-KEM",
+    length: 32,
+  });
+
+  let encapsulated = subtle.encapsulate_bits(params, &key.public_key)?;
+  let shared_key = subtle.decapsulate_bits(
+    params,
+    &key.private_key,
+    &encapsulated.ciphertext,
+  )?;
+  Ok(shared_key == encapsulated.shared_key)
+}
+
+/// RFC 6979, appendix A.2.5: P-256 with SHA-256.
+fn ecdsa(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let key = import(
+    subtle,
+    "pkcs8",
+    include_bytes!("../testdata/p256_rfc6979.pk8"),
+    subtle::ImportParams::EcKeyImportParams(subtle::EcKeyImportParams {
+      name: "ECDSA",
+      named_curve: subtle::NamedCurve::P256,
+    }),
+    vec![KeyUsage::Sign],
+  )?;
+  let params = subtle::SignParams::EcdsaParams(subtle::EcdsaParams {
+    name: "ECDSA",
+    hash: subtle::HashAlgorithmIdentifer { name: "SHA-256" },
+    deterministic: true,
+    low_s: false,
+  });
+
+  let signature = subtle.sign(params, &key, b"sample")?;
+  Ok(
+    signature
+      == hex(
+        "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716\
+         f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8",
+      ),
+  )
+}
+
+/// Both parties derive the same secret.
+fn ecdh(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let generate = |subtle: &mut Subtle| {
+    key_pair(
+      subtle,
+      subtle::EcKeyGenParams {
+        name: "ECDH",
+        named_curve: subtle::NamedCurve::P256,
+      }
+      .into(),
+      vec![KeyUsage::DeriveBits],
+    )
+  };
+  let alice = generate(subtle)?;
+  let bob = generate(subtle)?;
+  let params = |public| {
+    subtle::DeriveParams::EcdhKeyDeriveParams(subtle::EcdhKeyDeriveParams {
+      name: "ECDH",
+      public,
+    })
+  };
+
+  Ok(
+    subtle.derive_bits(params(&bob.public_key), &alice.private_key, None)?
+      == subtle.derive_bits(
+        params(&alice.public_key),
+        &bob.private_key,
+        None,
+      )?,
+  )
+}
+
+/// The 2048-bit MODP group, against a secret computed by OpenSSL.
+fn dh(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let algorithm =
+    subtle::ImportParams::DhKeyImportParams(subtle::DhKeyImportParams {
+      name: "DH",
+      group: subtle::DhGroup::Modp2048,
+    });
+  let private_key = import(
+    subtle,
+    "raw-private",
+    &[0x5a, 0x1f].repeat(16),
+    algorithm,
+    vec![KeyUsage::DeriveBits],
+  )?;
+  let public = import(
+    subtle,
+    "raw",
+    include_bytes!("../testdata/dh2048_b.pub"),
+    algorithm,
+    vec![],
+  )?;
+  let params =
+    subtle::DeriveParams::DhKeyDeriveParams(subtle::DhKeyDeriveParams {
+      name: "DH",
+      public: &public,
+    });
+
+  let secret = subtle.derive_bits(params, &private_key, None)?;
+  Ok(secret == include_bytes!("../testdata/dh2048.secret"))
+}
+
+/// Checked against OpenSSL.
+fn argon2id(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let password = import(
+    subtle,
+    "raw",
+    b"correct horse battery staple",
+    identifier("Argon2id"),
+    vec![KeyUsage::DeriveBits],
+  )?;
+  let params = subtle::DeriveParams::Argon2Params(subtle::Argon2Params {
+    name: "Argon2id",
+    memory: 64,
+    iterations: 3,
+    parallelism: 2,
+    salt: (0..16).collect(),
+  });
+
+  let bits = subtle.derive_bits(params, &password, Some(256))?;
+  Ok(
+    bits
+      == hex(
+        "c760c305ae1d5b24527edbc8db77158f2b632197d8bad49cec5ab071809f3def",
+      ),
+  )
+}
+
+/// RFC 7914, section 12: the empty password and salt with N = 16.
+fn scrypt(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let password = import(
+    subtle,
+    "raw",
+    b"",
+    identifier("scrypt"),
+    vec![KeyUsage::DeriveBits],
+  )?;
+  let params = subtle::DeriveParams::ScryptParams(subtle::ScryptParams {
+    name: "scrypt",
+    n: 16,
+    r: 1,
+    p: 1,
+    salt: vec![],
+  });
+
+  let bits = subtle.derive_bits(params, &password, Some(256))?;
+  Ok(
+    bits
+      == hex(
+        "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442",
+      ),
+  )
+}
+
+#[cfg(feature = "ml-kem")]
+fn xwing(subtle: &mut Subtle) -> Result<bool, WebCryptoError> {
+  let algorithm = subtle::AlgorithmIdentifer { name: "X-Wing" };
+  let key = key_pair(
+    subtle,
+    subtle::KeyGenParams::AlgorithmIdentifer(algorithm),
+    vec![KeyUsage::EncapsulateBits, KeyUsage::DecapsulateBits],
+  )?;
+  let params = subtle::EncapsulateParams::AlgorithmIdentifer(algorithm);
+
+  let encapsulated = subtle.encapsulate_bits(params, &key.public_key)?;
+  let shared_key = subtle.decapsulate_bits(
+    params,
+    &key.private_key,
+    &encapsulated.ciphertext,
+  )?;
+  Ok(shared_key == encapsulated.shared_key)
+}
@@ -1,3 +1,31 @@
+mod file;
+#[cfg(feature = "tpm")]
+mod tpm;
+#[cfg(feature = "vault-transit")]
+mod vault_transit;
+
+use std::io;
+
+pub use file::FileVault;
+pub use file::IntegrityReport;
+pub use file::FORMAT_VERSION;
+#[cfg(feature = "tpm")]
+pub use tpm::Tpm;
+#[cfg(feature = "tpm")]
+pub use tpm::TpmDevice;
+#[cfg(feature = "tpm")]
+pub use tpm::TpmTransport;
+#[cfg(feature = "vault-transit")]
+pub use vault_transit::VaultTransit;
+#[cfg(feature = "vault-transit")]
+pub use vault_transit::VaultTransport;
+
+use crate::subtle::Algorithm;
+use crate::subtle::EncryptParams;
+use crate::subtle::KeyGenParams;
+use crate::subtle::SignParams;
+use crate::WebCryptoError;
+
 /// An opaque wrapper to protect direct access
 /// to the underlying key material.
 #[derive(PartialEq)]
@@ -5,7 +33,7 @@ pub struct KeyMaterial(pub(crate) Vec<u8>);
 
 impl<const N: usize> PartialEq<[u8; N]> for KeyMaterial {
   fn eq(&self, other: &[u8; N]) -> bool {
-    &self.0 == other
+    self.0 == other
   }
 }
 
@@ -15,6 +43,36 @@ impl PartialEq<Vec<u8>> for KeyMaterial {
   }
 }
 
+/// A key generated inside a storage, see `KeyStorage::generate`.
+pub struct GeneratedKey<H> {
+  /// The handle of the secret or private key.
+  pub handle: H,
+  /// The DER encoded SubjectPublicKeyInfo of the public key, for key
+  /// pairs.
+  pub public_key: Option<Vec<u8>>,
+}
+
+/// An operation carried out by a storage with a key it holds, see
+/// `KeyStorage::perform`.
+#[non_exhaustive]
+pub enum KeyOperation<'a> {
+  /// Sign the `hash` `digest` of the data. ECDSA signatures are `r || s`,
+  /// like `SubtleCrypto::sign` returns.
+  Sign {
+    params: &'a SignParams,
+    hash: &'static str,
+    digest: &'a [u8],
+  },
+  Encrypt {
+    params: &'a EncryptParams,
+    data: &'a [u8],
+  },
+  Decrypt {
+    params: &'a EncryptParams,
+    data: &'a [u8],
+  },
+}
+
 /// A `KeyStorage` implementation is responsible for providing a way to
 /// store and retrieve actual key material in a storage.
 ///
@@ -46,8 +104,226 @@ pub trait KeyStorage {
   /// Returns a handle that can be used to retrieve the key later.
   fn store(&mut self, key: KeyMaterial) -> Self::Handle;
 
+  /// Store the given key, failing when the storage cannot keep it, eg.
+  /// on a write error. Contexts store keys with this method.
+  ///
+  /// The default calls `store`, for storages which cannot fail.
+  fn try_store(&mut self, key: KeyMaterial) -> io::Result<Self::Handle> {
+    Ok(self.store(key))
+  }
+
   /// Retrieve the key with the given handle.
   fn get(&self, handle: Self::Handle) -> Option<&KeyMaterial>;
+
+  /// Zero and remove the key with the given handle, so `get` returns
+  /// `None` for it from now on. Returns whether the key was deleted.
+  ///
+  /// Storages that cannot delete keys keep the default, which deletes
+  /// nothing; consume-once keys (see `CryptoKey::mark_consume_once`) then
+  /// fail every operation instead of being used more than once.
+  fn delete(&mut self, _handle: Self::Handle) -> bool {
+    false
+  }
+
+  /// The length in bytes of the key material this storage holds in
+  /// memory, including keys stored before it was handed to a context, eg.
+  /// loaded from a file. `None`, the default, when the storage cannot
+  /// tell: contexts then count the key material they stored.
+  fn resident_bytes(&self) -> Option<usize> {
+    None
+  }
+
+  /// Generate a key of `algorithm` inside the storage, for storages
+  /// holding keys they never release, eg. in hardware. `None`, the
+  /// default, generates the key in memory and stores it.
+  ///
+  /// Contexts only ask for non-extractable keys, once their parameters
+  /// passed the checks of in-memory generation, and `delete` the key
+  /// again when its public key is refused. Storages refuse the algorithms
+  /// they cannot hold with `NotSupportedError`, or return `None` to keep
+  /// those keys in memory.
+  fn generate(
+    &mut self,
+    _algorithm: &KeyGenParams,
+  ) -> Option<Result<GeneratedKey<Self::Handle>, WebCryptoError>> {
+    None
+  }
+
+  /// Carry out `operation` with the key of `handle`, of `algorithm`,
+  /// inside the storage. `None`, the default, for keys whose material
+  /// `get` returns: contexts then carry out the operation themselves.
+  fn perform(
+    &mut self,
+    _handle: Self::Handle,
+    _algorithm: &Algorithm,
+    _operation: KeyOperation<'_>,
+  ) -> Option<Result<Vec<u8>, WebCryptoError>> {
+    None
+  }
+}
+
+/// A borrowed storage stores in the storage it borrows, see
+/// `SubtleCrypto::with_rng`.
+impl<S: KeyStorage + ?Sized> KeyStorage for &mut S {
+  type Handle = S::Handle;
+
+  fn store(&mut self, key: KeyMaterial) -> Self::Handle {
+    (**self).store(key)
+  }
+
+  fn try_store(&mut self, key: KeyMaterial) -> io::Result<Self::Handle> {
+    (**self).try_store(key)
+  }
+
+  fn get(&self, handle: Self::Handle) -> Option<&KeyMaterial> {
+    (**self).get(handle)
+  }
+
+  fn delete(&mut self, handle: Self::Handle) -> bool {
+    (**self).delete(handle)
+  }
+
+  fn resident_bytes(&self) -> Option<usize> {
+    (**self).resident_bytes()
+  }
+
+  fn generate(
+    &mut self,
+    algorithm: &KeyGenParams,
+  ) -> Option<Result<GeneratedKey<Self::Handle>, WebCryptoError>> {
+    (**self).generate(algorithm)
+  }
+
+  fn perform(
+    &mut self,
+    handle: Self::Handle,
+    algorithm: &Algorithm,
+    operation: KeyOperation<'_>,
+  ) -> Option<Result<Vec<u8>, WebCryptoError>> {
+    (**self).perform(handle, algorithm, operation)
+  }
+}
+
+/// Converts the handles of a `KeyStorage` to and from strings, so keys
+/// can be described to another process using the same storage backend,
+/// see `SubtleCrypto::serialize_key`.
+pub trait HandleCodec<H> {
+  /// Encode `handle`.
+  fn encode(&self, handle: H) -> String;
+
+  /// Decode a handle, or `None` when `encoded` is not one this codec
+  /// produced.
+  fn decode(&self, encoded: &str) -> Option<H>;
+}
+
+/// A `HandleCodec` for index handles, like those of `FileVault`, written
+/// in decimal.
+pub struct IndexCodec;
+
+impl HandleCodec<usize> for IndexCodec {
+  fn encode(&self, handle: usize) -> String {
+    handle.to_string()
+  }
+
+  fn decode(&self, encoded: &str) -> Option<usize> {
+    // Only the canonical form, so each handle has one encoding.
+    let handle: usize = encoded.parse().ok()?;
+    (handle.to_string() == encoded).then_some(handle)
+  }
+}
+
+/// A `KeyStorage` keeping key material in memory for the lifetime
+/// of the process. Handles are indices into the vault, and are not reused
+/// after a key is deleted.
+#[derive(Default)]
+pub struct InMemoryVault(Vec<Option<KeyMaterial>>);
+
+impl InMemoryVault {
+  pub fn new() -> Self {
+    InMemoryVault(Vec::new())
+  }
+}
+
+impl KeyStorage for InMemoryVault {
+  type Handle = usize;
+
+  fn store(&mut self, key: KeyMaterial) -> usize {
+    self.0.push(Some(key));
+    self.0.len() - 1
+  }
+
+  fn get(&self, handle: usize) -> Option<&KeyMaterial> {
+    self.0.get(handle)?.as_ref()
+  }
+
+  fn delete(&mut self, handle: usize) -> bool {
+    match self.0.get_mut(handle).and_then(Option::take) {
+      Some(mut key) => {
+        key.0.fill(0);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn resident_bytes(&self) -> Option<usize> {
+    Some(self.0.iter().flatten().map(|key| key.0.len()).sum())
+  }
+}
+
+/// A storage chosen at run time, eg. by the C and Python bindings.
+pub enum Backend {
+  Memory(InMemoryVault),
+  File(FileVault),
+}
+
+impl Backend {
+  /// A `FileVault` at `path`, or an `InMemoryVault` without one.
+  pub fn open<P: AsRef<std::path::Path>>(path: Option<P>) -> io::Result<Self> {
+    Ok(match path {
+      Some(path) => Backend::File(FileVault::open(path)?),
+      None => Backend::Memory(InMemoryVault::new()),
+    })
+  }
+}
+
+impl KeyStorage for Backend {
+  type Handle = usize;
+
+  fn store(&mut self, key: KeyMaterial) -> usize {
+    match self {
+      Backend::Memory(vault) => vault.store(key),
+      Backend::File(vault) => vault.store(key),
+    }
+  }
+
+  fn try_store(&mut self, key: KeyMaterial) -> io::Result<usize> {
+    match self {
+      Backend::Memory(vault) => vault.try_store(key),
+      Backend::File(vault) => vault.try_store(key),
+    }
+  }
+
+  fn get(&self, handle: usize) -> Option<&KeyMaterial> {
+    match self {
+      Backend::Memory(vault) => vault.get(handle),
+      Backend::File(vault) => vault.get(handle),
+    }
+  }
+
+  fn delete(&mut self, handle: usize) -> bool {
+    match self {
+      Backend::Memory(vault) => vault.delete(handle),
+      Backend::File(vault) => vault.delete(handle),
+    }
+  }
+
+  fn resident_bytes(&self) -> Option<usize> {
+    match self {
+      Backend::Memory(vault) => vault.resident_bytes(),
+      Backend::File(vault) => vault.resident_bytes(),
+    }
+  }
 }
 
 #[cfg(test)]
@@ -0,0 +1,860 @@
+//! A `KeyStorage` persisting key material to a single file.
+//!
+//! # Format
+//!
+//! All integers are little endian.
+//!
+//! ```text
+//! header: magic "WCVAULT\0" | version: u16 | flags: u16
+//! record: magic "WCR\0" | kind: u8 | handle: u64 | length: u32
+//!         | header_crc32: u32 | payload | crc32: u32
+//! ```
+//!
+//! The header CRC-32 covers the magic, kind, handle and length of its
+//! record, so a damaged length is caught before it is used. The trailing
+//! CRC-32 covers everything before it in the record.
+//!
+//! # Corruption
+//!
+//! A record failing its checksum is quarantined: it stays in the file,
+//! but its key material is unavailable. Reading resumes at the next
+//! record whose header checksum holds, and as every record carries its
+//! handle, later handles don't move. Bytes after the last complete
+//! record, as left by a crash during a write, are left in place until
+//! [`FileVault::quarantine_tail`] moves them to `<path>.quarantine`. Use
+//! [`FileVault::verify_integrity`] to scan the file on demand.
+//!
+//! # Compatibility
+//!
+//! - Records are only ever appended, and a handle is the index of its
+//!   record, so handles stay valid across restarts and upgrades.
+//! - Files with an older `version` are migrated to the current format
+//!   when opened, preserving the order of records. Files with a newer
+//!   `version` are refused rather than rewritten.
+//! - Records of an unknown `kind` are kept as is, so files written by a
+//!   newer crate with the same `version` can be opened and appended to.
+//!
+//! Version 1 records (`kind: u8 | length: u32 | payload | crc32: u32`)
+//! have no header checksum: a record failing its checksum there extends
+//! to the next offset where a record's checksum holds.
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::KeyMaterial;
+use super::KeyStorage;
+
+const MAGIC: &[u8; 8] = b"WCVAULT\0";
+const HEADER_LEN: usize = 12;
+
+/// The version written by this crate.
+pub const FORMAT_VERSION: u16 = 2;
+
+/// Marks the start of a record, so reading can resume after damage.
+const RECORD_MAGIC: &[u8; 4] = b"WCR\0";
+/// The magic, kind, handle, length and header checksum of a record.
+const RECORD_HEADER_LEN: usize = 21;
+/// The record header and the trailing checksum.
+const RECORD_OVERHEAD: usize = RECORD_HEADER_LEN + 4;
+
+/// The record kind holding the bytes of a quarantined record, written
+/// when migrating a file so its handle is kept.
+const RECORD_QUARANTINED: u8 = 0;
+/// The record kind holding key material.
+const RECORD_KEY: u8 = 1;
+/// The record kind of deleted key material, overwritten with zeros.
+const RECORD_DELETED: u8 = 2;
+
+fn invalid_data(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn header(version: u16) -> [u8; HEADER_LEN] {
+  let mut header = [0; HEADER_LEN];
+  header[..8].copy_from_slice(MAGIC);
+  header[8..10].copy_from_slice(&version.to_le_bytes());
+  header
+}
+
+fn encode_record(kind: u8, handle: usize, payload: &[u8]) -> Vec<u8> {
+  let mut record = Vec::with_capacity(payload.len() + RECORD_OVERHEAD);
+  record.extend_from_slice(RECORD_MAGIC);
+  record.push(kind);
+  record.extend_from_slice(&(handle as u64).to_le_bytes());
+  record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+  let checksum = crc32fast::hash(&record);
+  record.extend_from_slice(&checksum.to_le_bytes());
+  record.extend_from_slice(payload);
+  let checksum = crc32fast::hash(&record);
+  record.extend_from_slice(&checksum.to_le_bytes());
+  record
+}
+
+enum Record {
+  Valid(u8, KeyMaterial),
+  /// A record failing its checksum, kept verbatim, or a record lost to
+  /// damage, kept empty.
+  Corrupt(Vec<u8>),
+}
+
+impl Record {
+  fn encode(&self, handle: usize) -> Vec<u8> {
+    match self {
+      Record::Valid(kind, key) => encode_record(*kind, handle, &key.0),
+      Record::Corrupt(bytes) => {
+        encode_record(RECORD_QUARANTINED, handle, bytes)
+      }
+    }
+  }
+}
+
+/// Records parsed from a file, followed by any bytes which don't form
+/// a complete record.
+struct Records {
+  records: Vec<Record>,
+  /// The offset of each record after the file header, if known.
+  offsets: Vec<Option<usize>>,
+  /// Number of bytes between records that are not part of any.
+  skipped: usize,
+  tail: Vec<u8>,
+}
+
+/// The kind, handle and length of a record header whose checksum holds.
+fn decode_record_header(bytes: &[u8]) -> Option<(u8, usize, usize)> {
+  let header = bytes.get(..RECORD_HEADER_LEN)?;
+  let checksum = u32::from_le_bytes(header[17..].try_into().unwrap());
+  if &header[..4] != RECORD_MAGIC || crc32fast::hash(&header[..17]) != checksum
+  {
+    return None;
+  }
+
+  let handle = u64::from_le_bytes(header[5..13].try_into().unwrap());
+  let length = u32::from_le_bytes(header[13..17].try_into().unwrap());
+  Some((header[4], usize::try_from(handle).ok()?, length as usize))
+}
+
+/// Parse the records of a version 2 file.
+fn decode_records_v2(bytes: &[u8]) -> Records {
+  let mut records = Vec::new();
+  let mut offsets = Vec::new();
+  let mut skipped = 0;
+  let mut offset = 0;
+  // The end of the last record read.
+  let mut end = 0;
+
+  while offset + RECORD_OVERHEAD <= bytes.len() {
+    let start = offset;
+    let damaged = offset.saturating_sub(end);
+    let (kind, handle, length) = match decode_record_header(&bytes[offset..]) {
+      // Each record lost to damage took up at least a record's overhead.
+      Some((kind, handle, length))
+        if length <= bytes.len() - offset - RECORD_OVERHEAD
+          && handle <= records.len() + damaged / RECORD_OVERHEAD =>
+      {
+        (kind, handle, length)
+      }
+      _ => {
+        offset += 1;
+        continue;
+      }
+    };
+
+    let payload =
+      offset + RECORD_HEADER_LEN..offset + RECORD_HEADER_LEN + length;
+    let record_end = payload.end + 4;
+    let checksum =
+      u32::from_le_bytes(bytes[payload.end..record_end].try_into().unwrap());
+    let record = if crc32fast::hash(&bytes[offset..payload.end]) == checksum {
+      let payload = bytes[payload].to_vec();
+      offset = record_end;
+      match kind {
+        RECORD_QUARANTINED => Record::Corrupt(payload),
+        kind => Record::Valid(kind, KeyMaterial(payload)),
+      }
+    } else {
+      let record = Record::Corrupt(bytes[offset..record_end].to_vec());
+      // The length held, but the payload may be a torn write followed by
+      // later records: look for them inside it.
+      offset = payload.start;
+      record
+    };
+    skipped += damaged;
+    end = end.max(record_end);
+
+    while records.len() < handle {
+      records.push(Record::Corrupt(Vec::new()));
+      offsets.push(None);
+    }
+    if handle == records.len() {
+      records.push(record);
+      offsets.push(Some(start));
+    } else if matches!(records[handle], Record::Corrupt(_))
+      && matches!(record, Record::Valid(..))
+    {
+      records[handle] = record;
+      offsets[handle] = Some(start);
+    }
+  }
+
+  Records {
+    records,
+    offsets,
+    skipped,
+    tail: bytes[end..].to_vec(),
+  }
+}
+
+/// The version 1 record at the start of `bytes`, if its checksum holds,
+/// and its length.
+fn decode_record_v1(bytes: &[u8]) -> Option<(Record, usize)> {
+  let length = u32::from_le_bytes(bytes.get(1..5)?.try_into().unwrap());
+  let record_end = (length as usize).checked_add(9)?;
+  let (record, checksum) = bytes.get(..record_end)?.split_at(record_end - 4);
+  if crc32fast::hash(record) != u32::from_le_bytes(checksum.try_into().unwrap())
+  {
+    return None;
+  }
+
+  Some((
+    Record::Valid(record[0], KeyMaterial(record[5..].to_vec())),
+    record_end,
+  ))
+}
+
+/// Parse the records of a version 1 file.
+fn decode_records_v1(bytes: &[u8]) -> Records {
+  let mut records = Vec::new();
+  let mut offset = 0;
+
+  while offset < bytes.len() {
+    if let Some((record, length)) = decode_record_v1(&bytes[offset..]) {
+      records.push(record);
+      offset += length;
+      continue;
+    }
+
+    // Lengths are not checked on their own: the damaged record extends to
+    // the next record whose checksum holds.
+    match (offset + 1..bytes.len())
+      .find(|next| decode_record_v1(&bytes[*next..]).is_some())
+    {
+      Some(next) => {
+        records.push(Record::Corrupt(bytes[offset..next].to_vec()));
+        offset = next;
+      }
+      None => break,
+    }
+  }
+
+  Records {
+    offsets: vec![None; records.len()],
+    records,
+    skipped: 0,
+    tail: bytes[offset..].to_vec(),
+  }
+}
+
+/// Parse the records following a header of `version`.
+fn decode_records(version: u16, bytes: &[u8]) -> io::Result<Records> {
+  match version {
+    1 => Ok(decode_records_v1(bytes)),
+    2 => Ok(decode_records_v2(bytes)),
+    // Older formats get a reader here when the version is bumped.
+    _ => Err(invalid_data("unsupported vault version")),
+  }
+}
+
+/// Read and check the header of a vault file, returning its version.
+fn decode_header(bytes: &[u8]) -> io::Result<u16> {
+  if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+    return Err(invalid_data("not a vault file"));
+  }
+  let version = u16::from_le_bytes([bytes[8], bytes[9]]);
+  if version > FORMAT_VERSION {
+    return Err(invalid_data("vault written by a newer version"));
+  }
+
+  Ok(version)
+}
+
+/// The outcome of [`FileVault::verify_integrity`].
+#[derive(Debug, Default, PartialEq)]
+pub struct IntegrityReport {
+  /// Number of records in the file, including corrupt ones.
+  pub records: usize,
+  /// Handles of records failing their checksum.
+  pub corrupt: Vec<usize>,
+  /// Number of bytes between records that are not part of any.
+  pub skipped_bytes: usize,
+  /// Number of trailing bytes not forming a complete record.
+  pub trailing_bytes: usize,
+}
+
+impl IntegrityReport {
+  pub fn is_ok(&self) -> bool {
+    self.corrupt.is_empty()
+      && self.skipped_bytes == 0
+      && self.trailing_bytes == 0
+  }
+}
+
+/// A `KeyStorage` persisting key material to a file.
+///
+/// Key material is written when stored: contexts fail to store a key
+/// which cannot be written. With `KeyStorage::store`, the record is kept
+/// in memory instead and [`FileVault::flush`] reports the error. Deleted
+/// keys are overwritten with zeros in the file.
+pub struct FileVault {
+  path: PathBuf,
+  records: Vec<Record>,
+  /// The offset of each record in the file, once written.
+  offsets: Vec<Option<u64>>,
+  /// Number of records written to the file.
+  persisted: usize,
+  /// Number of bytes after the last complete record when the file was
+  /// opened, while no record has been appended after them.
+  trailing: usize,
+}
+
+impl FileVault {
+  /// Open the vault at `path`, creating it if it does not exist.
+  ///
+  /// The file is never truncated: bytes after the last complete record
+  /// stay in place, see [`FileVault::quarantine_tail`].
+  pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    let path = path.as_ref().to_path_buf();
+
+    let mut bytes = Vec::new();
+    match File::open(&path) {
+      Ok(mut file) => {
+        file.read_to_end(&mut bytes)?;
+      }
+      Err(err) if err.kind() == io::ErrorKind::NotFound => {
+        write_atomic(&path, &header(FORMAT_VERSION))?;
+        return Ok(FileVault {
+          path,
+          records: Vec::new(),
+          offsets: Vec::new(),
+          persisted: 0,
+          trailing: 0,
+        });
+      }
+      Err(err) => return Err(err),
+    }
+
+    let version = decode_header(&bytes)?;
+    let Records {
+      records,
+      offsets,
+      tail,
+      ..
+    } = decode_records(version, &bytes[HEADER_LEN..])?;
+    let mut vault = FileVault {
+      path,
+      offsets: offsets
+        .into_iter()
+        .map(|offset| offset.map(|offset| (HEADER_LEN + offset) as u64))
+        .collect(),
+      persisted: records.len(),
+      records,
+      trailing: tail.len(),
+    };
+
+    if version < FORMAT_VERSION {
+      vault.migrate(&tail)?;
+    }
+
+    Ok(vault)
+  }
+
+  /// The path of the vault file.
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Handles of records quarantined because they failed their checksum.
+  pub fn quarantined(&self) -> Vec<usize> {
+    self
+      .records
+      .iter()
+      .enumerate()
+      .filter(|(_, record)| matches!(record, Record::Corrupt(_)))
+      .map(|(handle, _)| handle)
+      .collect()
+  }
+
+  /// Re-read the vault file and check every record against its
+  /// checksum.
+  pub fn verify_integrity(&self) -> io::Result<IntegrityReport> {
+    let bytes = fs::read(&self.path)?;
+    let version = decode_header(&bytes)?;
+    let Records {
+      records,
+      skipped,
+      tail,
+      ..
+    } = decode_records(version, &bytes[HEADER_LEN..])?;
+
+    Ok(IntegrityReport {
+      records: records.len(),
+      corrupt: records
+        .iter()
+        .enumerate()
+        .filter(|(_, record)| matches!(record, Record::Corrupt(_)))
+        .map(|(handle, _)| handle)
+        .collect(),
+      skipped_bytes: skipped,
+      trailing_bytes: tail.len(),
+    })
+  }
+
+  /// Move the bytes after the last complete record, as left by a crash
+  /// during a write, to `<path>.quarantine` and truncate the file before
+  /// them. Returns the number of bytes moved.
+  ///
+  /// Once records are appended after them, the bytes no longer trail and
+  /// are skipped when reading instead: nothing is moved.
+  pub fn quarantine_tail(&mut self) -> io::Result<usize> {
+    if self.trailing == 0 {
+      return Ok(0);
+    }
+
+    let mut file =
+      OpenOptions::new().read(true).write(true).open(&self.path)?;
+    let offset = file.seek(SeekFrom::End(-(self.trailing as i64)))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    let mut path = self.path.as_os_str().to_owned();
+    path.push(".quarantine");
+    let mut quarantine =
+      OpenOptions::new().create(true).append(true).open(path)?;
+    quarantine.write_all(&tail)?;
+    quarantine.sync_all()?;
+
+    file.set_len(offset)?;
+    file.sync_all()?;
+    self.trailing = 0;
+    Ok(tail.len())
+  }
+
+  /// Write records which previously failed to persist.
+  pub fn flush(&mut self) -> io::Result<()> {
+    if self.persisted == self.records.len() {
+      return Ok(());
+    }
+
+    let mut file = OpenOptions::new().write(true).open(&self.path)?;
+    let len = file.seek(SeekFrom::End(0))?;
+
+    let mut bytes = Vec::new();
+    let mut offsets = Vec::new();
+    for (handle, record) in self.records.iter().enumerate().skip(self.persisted)
+    {
+      offsets.push(len + bytes.len() as u64);
+      bytes.extend(record.encode(handle));
+    }
+
+    // Don't leave a torn record behind.
+    if let Err(err) = file.write_all(&bytes).and_then(|_| file.sync_data()) {
+      file.set_len(len)?;
+      return Err(err);
+    }
+
+    for (handle, offset) in (self.persisted..).zip(offsets) {
+      self.offsets[handle] = Some(offset);
+    }
+    self.persisted = self.records.len();
+    self.trailing = 0;
+    Ok(())
+  }
+
+  /// Rewrite the file in the current format, keeping `tail` after the
+  /// records.
+  fn migrate(&mut self, tail: &[u8]) -> io::Result<()> {
+    let mut bytes = header(FORMAT_VERSION).to_vec();
+    let mut offsets = Vec::new();
+    for (handle, record) in self.records.iter().enumerate() {
+      offsets.push(Some(bytes.len() as u64));
+      bytes.extend(record.encode(handle));
+    }
+    bytes.extend_from_slice(tail);
+
+    write_atomic(&self.path, &bytes)?;
+    self.offsets = offsets;
+    Ok(())
+  }
+}
+
+/// Write `bytes` to a temporary file next to `path` and move it in
+/// place, so a crash never leaves a partially written vault.
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+  let mut tmp = path.as_os_str().to_owned();
+  tmp.push(".tmp");
+
+  let mut file = File::create(&tmp)?;
+  file.write_all(bytes)?;
+  file.sync_all()?;
+  fs::rename(&tmp, path)
+}
+
+impl KeyStorage for FileVault {
+  type Handle = usize;
+
+  /// Store `key`, keeping it in memory when it cannot be written: the
+  /// write is retried by the next `store` or `flush`. Contexts use
+  /// `try_store` instead.
+  fn store(&mut self, key: KeyMaterial) -> usize {
+    self.records.push(Record::Valid(RECORD_KEY, key));
+    self.offsets.push(None);
+    let _ = self.flush();
+    self.records.len() - 1
+  }
+
+  /// Store `key`, failing unless it is written to the file.
+  fn try_store(&mut self, key: KeyMaterial) -> io::Result<usize> {
+    self.records.push(Record::Valid(RECORD_KEY, key));
+    self.offsets.push(None);
+    if let Err(err) = self.flush() {
+      self.offsets.pop();
+      if let Some(Record::Valid(_, mut key)) = self.records.pop() {
+        key.0.fill(0);
+      }
+      return Err(err);
+    }
+
+    Ok(self.records.len() - 1)
+  }
+
+  fn get(&self, handle: usize) -> Option<&KeyMaterial> {
+    match self.records.get(handle) {
+      Some(Record::Valid(RECORD_KEY, key)) => Some(key),
+      _ => None,
+    }
+  }
+
+  /// Overwrite the key with zeros, in the file too. The record keeps its
+  /// length and place, so no other record moves.
+  fn delete(&mut self, handle: usize) -> bool {
+    let length = match self.records.get(handle) {
+      Some(Record::Valid(RECORD_KEY, key)) => key.0.len(),
+      _ => return false,
+    };
+    let deleted = Record::Valid(RECORD_DELETED, KeyMaterial(vec![0; length]));
+
+    if let Some(offset) = self.offsets[handle] {
+      let written =
+        OpenOptions::new()
+          .write(true)
+          .open(&self.path)
+          .and_then(|mut file| {
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&deleted.encode(handle))?;
+            file.sync_data()
+          });
+      if written.is_err() {
+        return false;
+      }
+    }
+
+    if let Record::Valid(_, mut key) =
+      std::mem::replace(&mut self.records[handle], deleted)
+    {
+      key.0.fill(0);
+    }
+    true
+  }
+
+  fn resident_bytes(&self) -> Option<usize> {
+    Some(
+      self
+        .records
+        .iter()
+        .map(|record| match record {
+          Record::Valid(RECORD_KEY, key) => key.0.len(),
+          _ => 0,
+        })
+        .sum(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn vault_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+      "webcrypto-{}-{}.vault",
+      name,
+      std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    path
+  }
+
+  #[test]
+  fn test_reopen() {
+    let path = vault_path("reopen");
+
+    let mut vault = FileVault::open(&path).unwrap();
+    let first = vault.store(KeyMaterial(vec![1; 16]));
+    let second = vault.store(KeyMaterial(vec![2; 32]));
+    drop(vault);
+
+    let vault = FileVault::open(&path).unwrap();
+    assert!(vault.get(first).unwrap() == &vec![1; 16]);
+    assert!(vault.get(second).unwrap() == &vec![2; 32]);
+
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_unknown_records() {
+    let path = vault_path("unknown");
+
+    // A record kind from a future release sits between two keys.
+    let mut bytes = header(FORMAT_VERSION).to_vec();
+    bytes.extend(encode_record(RECORD_KEY, 0, &[1; 16]));
+    bytes.extend(encode_record(0xff, 1, b"future"));
+    fs::write(&path, bytes).unwrap();
+
+    let mut vault = FileVault::open(&path).unwrap();
+    assert!(vault.get(1).is_none());
+    let handle = vault.store(KeyMaterial(vec![2; 16]));
+    assert_eq!(handle, 2);
+    drop(vault);
+
+    let vault = FileVault::open(&path).unwrap();
+    assert!(vault.get(0).unwrap() == &vec![1; 16]);
+    assert!(vault.get(2).unwrap() == &vec![2; 16]);
+
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_invalid_files() {
+    let path = vault_path("invalid");
+
+    fs::write(&path, header(FORMAT_VERSION + 1)).unwrap();
+    assert!(FileVault::open(&path).is_err());
+
+    fs::write(&path, b"not a vault").unwrap();
+    assert!(FileVault::open(&path).is_err());
+
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_quarantine() {
+    let path = vault_path("quarantine");
+
+    let mut vault = FileVault::open(&path).unwrap();
+    vault.store(KeyMaterial(vec![1; 16]));
+    vault.store(KeyMaterial(vec![2; 16]));
+    assert!(vault.verify_integrity().unwrap().is_ok());
+    drop(vault);
+
+    // Flip a bit in the first key and leave a torn record at the end.
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[HEADER_LEN + RECORD_HEADER_LEN] ^= 1;
+    bytes.extend(&encode_record(RECORD_KEY, 2, &[3; 16])[..30]);
+    fs::write(&path, &bytes).unwrap();
+
+    let mut vault = FileVault::open(&path).unwrap();
+    assert_eq!(vault.quarantined(), vec![0]);
+    assert!(vault.get(0).is_none());
+    assert!(vault.get(1).unwrap() == &vec![2; 16]);
+    assert_eq!(
+      vault.verify_integrity().unwrap(),
+      IntegrityReport {
+        records: 2,
+        corrupt: vec![0],
+        skipped_bytes: 0,
+        trailing_bytes: 30,
+      }
+    );
+    // Opening leaves the file as it is.
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+
+    // New records keep their place after the quarantined one, and are
+    // found past the torn record.
+    assert_eq!(vault.store(KeyMaterial(vec![4; 16])), 2);
+    drop(vault);
+    let mut vault = FileVault::open(&path).unwrap();
+    assert!(vault.get(2).unwrap() == &vec![4; 16]);
+    assert_eq!(vault.quarantine_tail().unwrap(), 0);
+
+    // Trailing bytes are only moved on request.
+    fs::OpenOptions::new()
+      .append(true)
+      .open(&path)
+      .unwrap()
+      .write_all(&encode_record(RECORD_KEY, 3, &[5; 16])[..10])
+      .unwrap();
+    let mut vault = FileVault::open(&path).unwrap();
+    assert_eq!(vault.quarantine_tail().unwrap(), 10);
+    assert_eq!(vault.verify_integrity().unwrap().trailing_bytes, 0);
+
+    let mut quarantine = path.as_os_str().to_owned();
+    quarantine.push(".quarantine");
+    assert_eq!(fs::read(&quarantine).unwrap().len(), 10);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&quarantine).unwrap();
+  }
+
+  #[test]
+  fn test_damaged_length() {
+    let path = vault_path("damaged");
+
+    let mut vault = FileVault::open(&path).unwrap();
+    for key in 1..=3 {
+      vault.store(KeyMaterial(vec![key; 16]));
+    }
+    drop(vault);
+
+    // A damaged length must not take the records after it along.
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[HEADER_LEN + RECORD_OVERHEAD + 16 + 13] ^= 0x40;
+    fs::write(&path, bytes).unwrap();
+
+    let vault = FileVault::open(&path).unwrap();
+    assert_eq!(vault.quarantined(), vec![1]);
+    assert!(vault.get(0).unwrap() == &vec![1; 16]);
+    assert!(vault.get(2).unwrap() == &vec![3; 16]);
+    assert_eq!(
+      vault.verify_integrity().unwrap(),
+      IntegrityReport {
+        records: 3,
+        corrupt: vec![1],
+        skipped_bytes: RECORD_OVERHEAD + 16,
+        trailing_bytes: 0,
+      }
+    );
+
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_migrate_v1() {
+    let path = vault_path("v1");
+
+    let record_v1 = |payload: &[u8]| {
+      let mut record = vec![RECORD_KEY];
+      record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+      record.extend_from_slice(payload);
+      let checksum = crc32fast::hash(&record);
+      record.extend_from_slice(&checksum.to_le_bytes());
+      record
+    };
+
+    // The length of the second of three records is damaged.
+    let mut bytes = header(1).to_vec();
+    bytes.extend(record_v1(&[1; 16]));
+    let damaged = bytes.len();
+    bytes.extend(record_v1(&[2; 16]));
+    bytes.extend(record_v1(&[3; 16]));
+    bytes[damaged + 2] ^= 1;
+    fs::write(&path, bytes).unwrap();
+
+    let mut vault = FileVault::open(&path).unwrap();
+    assert_eq!(vault.quarantined(), vec![1]);
+    assert!(vault.get(0).unwrap() == &vec![1; 16]);
+    assert!(vault.get(2).unwrap() == &vec![3; 16]);
+    assert_eq!(vault.store(KeyMaterial(vec![4; 16])), 3);
+    drop(vault);
+
+    let vault = FileVault::open(&path).unwrap();
+    assert_eq!(
+      &fs::read(&path).unwrap()[8..10],
+      &FORMAT_VERSION.to_le_bytes()
+    );
+    assert_eq!(vault.quarantined(), vec![1]);
+    assert!(vault.get(2).unwrap() == &vec![3; 16]);
+    assert!(vault.get(3).unwrap() == &vec![4; 16]);
+
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_delete() {
+    let path = vault_path("delete");
+
+    let mut vault = FileVault::open(&path).unwrap();
+    let first = vault.store(KeyMaterial(vec![1; 16]));
+    let second = vault.store(KeyMaterial(vec![2; 16]));
+    assert!(vault.delete(first));
+    assert!(!vault.delete(first));
+    assert!(vault.get(first).is_none());
+    assert_eq!(vault.resident_bytes(), Some(16));
+    drop(vault);
+
+    // The key is zeroed in the file, and later records keep their place.
+    let bytes = fs::read(&path).unwrap();
+    assert!(!bytes.windows(16).any(|window| window == [1; 16]));
+    let vault = FileVault::open(&path).unwrap();
+    assert!(vault.get(first).is_none());
+    assert!(vault.get(second).unwrap() == &vec![2; 16]);
+    assert!(vault.verify_integrity().unwrap().is_ok());
+
+    // Consume-once keys are deleted once used.
+    let mut ctx = crate::Context::new(rand::rngs::OsRng, vault);
+    let blake3 = crate::subtle::AlgorithmIdentifer { name: "BLAKE3" };
+    let mut key = ctx
+      .subtle
+      .import_key(
+        "raw",
+        &[7; 32],
+        crate::subtle::ImportParams::AlgorithmIdentifer(blake3),
+        false,
+        vec![crate::subtle::KeyUsage::Sign],
+      )
+      .unwrap();
+    key.mark_consume_once();
+    let sign = |ctx: &mut crate::Context<_, FileVault>| {
+      ctx.subtle.sign(
+        crate::subtle::SignParams::AlgorithmIdentifer(blake3),
+        &key,
+        b"data",
+      )
+    };
+    assert!(sign(&mut ctx).is_ok());
+    assert!(sign(&mut ctx).is_err());
+
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_store_error() {
+    let path = vault_path("store-error");
+
+    let mut vault = FileVault::open(&path).unwrap();
+    vault.store(KeyMaterial(vec![1; 16]));
+    fs::remove_file(&path).unwrap();
+
+    // Storing fails unless the key reaches the file.
+    assert!(vault.try_store(KeyMaterial(vec![2; 16])).is_err());
+    assert!(vault.get(1).is_none());
+
+    let mut ctx = crate::Context::new(rand::rngs::OsRng, vault);
+    let key = ctx.subtle.import_key(
+      "raw",
+      &[0; 16],
+      crate::subtle::ImportParams::AlgorithmIdentifer(
+        crate::subtle::AlgorithmIdentifer { name: "AES-GCM" },
+      ),
+      false,
+      vec![crate::subtle::KeyUsage::Encrypt],
+    );
+    assert!(matches!(key, Err(crate::WebCryptoError::OperationError)));
+    assert_eq!(ctx.subtle.key_material_bytes(), 16);
+  }
+}
@@ -0,0 +1,1237 @@
+//! A `KeyStorage` keeping private keys in a TPM 2.0, which signs and
+//! decrypts with them itself.
+//!
+//! Non-extractable ECDSA keys on P-256 and P-384, and RSASSA-PKCS1-v1_5
+//! and RSA-OAEP keys of 2048 or 3072 bits, are created in the TPM under
+//! the storage hierarchy. The TPM wraps them with a primary key derived
+//! from its storage seed, so the wrapped keys held here only load into
+//! the same TPM. Other key pairs are refused; secret keys and anything
+//! imported or extractable is kept in memory, like `InMemoryVault` does.
+//!
+//! RSA-PSS keys are refused too: a TPM picks the salt length of its PSS
+//! signatures itself, the hash length or the largest the key allows
+//! depending on its version, while WebCrypto callers choose it.
+//!
+//! [`Tpm::set_pcr_policy`] binds the keys created afterwards to the
+//! current values of SHA-256 PCRs: they only sign and decrypt while those
+//! PCRs hold the same values, eg. until the machine boots other software.
+//!
+//! Commands are marshaled here and carried by a [`TpmTransport`], eg. a
+//! [`TpmDevice`] on `/dev/tpmrm0`. The owner hierarchy and the keys use
+//! empty passwords.
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+use p256::pkcs8::EncodePublicKey;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use rsa::pkcs8::ToPublicKey;
+use rsa::BigUint;
+use rsa::RsaPublicKey;
+
+use super::GeneratedKey;
+use super::KeyMaterial;
+use super::KeyOperation;
+use super::KeyStorage;
+use crate::subtle::permitted_usages;
+use crate::subtle::Algorithm;
+use crate::subtle::AlgorithmIdentifer;
+use crate::subtle::AlgorithmName;
+use crate::subtle::EncryptParams;
+use crate::subtle::KeyGenParams;
+use crate::subtle::KeyType;
+use crate::subtle::NamedCurve;
+use crate::subtle::PublicExponent;
+use crate::subtle::SignParams;
+use crate::WebCryptoError;
+
+const TPM_ST_NO_SESSIONS: u16 = 0x8001;
+const TPM_ST_SESSIONS: u16 = 0x8002;
+const TPM_ST_HASHCHECK: u16 = 0x8024;
+
+const TPM_RH_OWNER: u32 = 0x4000_0001;
+const TPM_RH_NULL: u32 = 0x4000_0007;
+const TPM_RS_PW: u32 = 0x4000_0009;
+
+const TPM_CC_CREATE_PRIMARY: u32 = 0x0131;
+const TPM_CC_CREATE: u32 = 0x0153;
+const TPM_CC_LOAD: u32 = 0x0157;
+const TPM_CC_RSA_DECRYPT: u32 = 0x0159;
+const TPM_CC_SIGN: u32 = 0x015d;
+const TPM_CC_FLUSH_CONTEXT: u32 = 0x0165;
+const TPM_CC_START_AUTH_SESSION: u32 = 0x0176;
+const TPM_CC_POLICY_PCR: u32 = 0x017f;
+const TPM_CC_POLICY_GET_DIGEST: u32 = 0x0189;
+
+const TPM_ALG_RSA: u16 = 0x0001;
+const TPM_ALG_SHA1: u16 = 0x0004;
+const TPM_ALG_AES: u16 = 0x0006;
+const TPM_ALG_SHA256: u16 = 0x000b;
+const TPM_ALG_SHA384: u16 = 0x000c;
+const TPM_ALG_SHA512: u16 = 0x000d;
+const TPM_ALG_NULL: u16 = 0x0010;
+const TPM_ALG_RSASSA: u16 = 0x0014;
+const TPM_ALG_OAEP: u16 = 0x0017;
+const TPM_ALG_ECDSA: u16 = 0x0018;
+const TPM_ALG_ECC: u16 = 0x0023;
+const TPM_ALG_CFB: u16 = 0x0043;
+
+const TPM_ECC_NIST_P256: u16 = 0x0003;
+const TPM_ECC_NIST_P384: u16 = 0x0004;
+
+const TPM_SE_POLICY: u8 = 0x01;
+const TPM_SE_TRIAL: u8 = 0x03;
+
+// TPMA_OBJECT
+const FIXED_TPM: u32 = 1 << 1;
+const FIXED_PARENT: u32 = 1 << 4;
+const SENSITIVE_DATA_ORIGIN: u32 = 1 << 5;
+const USER_WITH_AUTH: u32 = 1 << 6;
+const NO_DA: u32 = 1 << 10;
+const RESTRICTED: u32 = 1 << 16;
+const DECRYPT: u32 = 1 << 17;
+const SIGN: u32 = 1 << 18;
+
+/// A TPM2B_SENSITIVE_CREATE with an empty password and no data.
+const SENSITIVE_CREATE: [u8; 4] = [0; 4];
+
+/// Carries commands to a TPM and its responses back.
+pub trait TpmTransport {
+  /// Send the marshaled `command`, returning the marshaled response.
+  fn transact(&mut self, command: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// A TPM character device, eg. the resource manager at `/dev/tpmrm0`,
+/// which flushes what a process left loaded once it closes the device.
+pub struct TpmDevice(File);
+
+impl TpmDevice {
+  pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    Ok(TpmDevice(file))
+  }
+}
+
+impl TpmTransport for TpmDevice {
+  fn transact(&mut self, command: &[u8]) -> io::Result<Vec<u8>> {
+    self.0.write_all(command)?;
+    // Devices return a whole response from a single read.
+    let mut response = vec![0; 4096];
+    let length = self.0.read(&mut response)?;
+    response.truncate(length);
+    Ok(response)
+  }
+}
+
+/// A command, marshaled big-endian.
+struct Command(Vec<u8>);
+
+impl Command {
+  fn new(code: u32, sessions: bool) -> Self {
+    let mut command = Command(Vec::new());
+    command
+      .u16(if sessions {
+        TPM_ST_SESSIONS
+      } else {
+        TPM_ST_NO_SESSIONS
+      })
+      .u32(0)
+      .u32(code);
+    command
+  }
+
+  fn u8(&mut self, value: u8) -> &mut Self {
+    self.0.push(value);
+    self
+  }
+
+  fn u16(&mut self, value: u16) -> &mut Self {
+    self.0.extend_from_slice(&value.to_be_bytes());
+    self
+  }
+
+  fn u32(&mut self, value: u32) -> &mut Self {
+    self.0.extend_from_slice(&value.to_be_bytes());
+    self
+  }
+
+  fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+    self.0.extend_from_slice(bytes);
+    self
+  }
+
+  /// A TPM2B: the length of `bytes`, then `bytes`.
+  fn sized(&mut self, bytes: &[u8]) -> &mut Self {
+    self.u16(bytes.len() as u16).bytes(bytes)
+  }
+
+  /// The authorization area of the policy `session`, or of an empty
+  /// password without one.
+  fn authorization(&mut self, session: Option<u32>) -> &mut Self {
+    let nonce = match session {
+      Some(_) => nonce(),
+      None => Vec::new(),
+    };
+    let mut area = Command(Vec::new());
+    // Not continued: the TPM flushes a policy session once it is used.
+    area
+      .u32(session.unwrap_or(TPM_RS_PW))
+      .sized(&nonce)
+      .u8(0)
+      .sized(&[]);
+    self.u32(area.0.len() as u32).bytes(&area.0)
+  }
+
+  fn finish(mut self) -> Vec<u8> {
+    let size = self.0.len() as u32;
+    self.0[2..6].copy_from_slice(&size.to_be_bytes());
+    self.0
+  }
+}
+
+/// A response, or a structure of one, being unmarshaled.
+struct Response {
+  data: Vec<u8>,
+  position: usize,
+}
+
+impl Response {
+  fn new(data: Vec<u8>) -> Self {
+    Response { data, position: 0 }
+  }
+
+  fn bytes(&mut self, length: usize) -> Result<&[u8], WebCryptoError> {
+    let bytes = self
+      .data
+      .get(self.position..self.position + length)
+      .ok_or(WebCryptoError::OperationError)?;
+    self.position += length;
+    Ok(bytes)
+  }
+
+  fn u16(&mut self) -> Result<u16, WebCryptoError> {
+    Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+  }
+
+  fn u32(&mut self) -> Result<u32, WebCryptoError> {
+    Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+  }
+
+  /// The contents of a TPM2B.
+  fn sized(&mut self) -> Result<Vec<u8>, WebCryptoError> {
+    let length = self.u16()? as usize;
+    Ok(self.bytes(length)?.to_vec())
+  }
+}
+
+/// A random nonce as long as a SHA-256 digest.
+fn nonce() -> Vec<u8> {
+  let mut nonce = vec![0; 32];
+  OsRng.fill_bytes(&mut nonce);
+  nonce
+}
+
+fn hash_algorithm(name: &str) -> Result<u16, WebCryptoError> {
+  match name {
+    "SHA-1" => Ok(TPM_ALG_SHA1),
+    "SHA-256" => Ok(TPM_ALG_SHA256),
+    "SHA-384" => Ok(TPM_ALG_SHA384),
+    "SHA-512" => Ok(TPM_ALG_SHA512),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The TPM curve of `named_curve` and the length of its coordinates.
+fn curve(named_curve: NamedCurve) -> Result<(u16, usize), WebCryptoError> {
+  match named_curve {
+    NamedCurve::P256 => Ok((TPM_ECC_NIST_P256, 32)),
+    NamedCurve::P384 => Ok((TPM_ECC_NIST_P384, 48)),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// `bytes`, a big-endian integer, left-padded to `length` bytes.
+fn pad(bytes: &[u8], length: usize) -> Result<Vec<u8>, WebCryptoError> {
+  if bytes.len() > length {
+    return Err(WebCryptoError::OperationError);
+  }
+  let mut padded = vec![0; length - bytes.len()];
+  padded.extend_from_slice(bytes);
+  Ok(padded)
+}
+
+/// The kinds of objects this storage creates.
+#[derive(Clone, Copy)]
+enum Template {
+  /// The primary key wrapping the others.
+  Storage,
+  Ecc {
+    curve: u16,
+  },
+  Rsa {
+    bits: u16,
+    attributes: u32,
+  },
+}
+
+impl Template {
+  /// The TPMT_PUBLIC template, with `policy` as its authorization policy
+  /// unless it is empty.
+  fn marshal(self, policy: &[u8]) -> Vec<u8> {
+    let mut attributes =
+      FIXED_TPM | FIXED_PARENT | SENSITIVE_DATA_ORIGIN | NO_DA;
+    if policy.is_empty() {
+      attributes |= USER_WITH_AUTH;
+    }
+
+    let mut public = Command(Vec::new());
+    match self {
+      Template::Storage => {
+        public
+          .u16(TPM_ALG_ECC)
+          .u16(TPM_ALG_SHA256)
+          .u32(attributes | RESTRICTED | DECRYPT)
+          .sized(policy)
+          .u16(TPM_ALG_AES)
+          .u16(128)
+          .u16(TPM_ALG_CFB)
+          .u16(TPM_ALG_NULL)
+          .u16(TPM_ECC_NIST_P256)
+          .u16(TPM_ALG_NULL)
+          .sized(&[])
+          .sized(&[]);
+      }
+      // Schemes are left open and given with each operation.
+      Template::Ecc { curve } => {
+        public
+          .u16(TPM_ALG_ECC)
+          .u16(TPM_ALG_SHA256)
+          .u32(attributes | SIGN)
+          .sized(policy)
+          .u16(TPM_ALG_NULL)
+          .u16(TPM_ALG_NULL)
+          .u16(curve)
+          .u16(TPM_ALG_NULL)
+          .sized(&[])
+          .sized(&[]);
+      }
+      Template::Rsa {
+        bits,
+        attributes: usage,
+      } => {
+        // An exponent of 0 is 65537.
+        public
+          .u16(TPM_ALG_RSA)
+          .u16(TPM_ALG_SHA256)
+          .u32(attributes | usage)
+          .sized(policy)
+          .u16(TPM_ALG_NULL)
+          .u16(TPM_ALG_NULL)
+          .u16(bits)
+          .u32(0)
+          .sized(&[]);
+      }
+    }
+    public.0
+  }
+}
+
+/// The SubjectPublicKeyInfo of the TPMT_PUBLIC `public`.
+fn public_key(public: &[u8]) -> Result<Vec<u8>, WebCryptoError> {
+  let mut public = Response::new(public.to_vec());
+  let type_ = public.u16()?;
+  // The name algorithm, attributes and authorization policy.
+  public.u16()?;
+  public.u32()?;
+  public.sized()?;
+  if public.u16()? != TPM_ALG_NULL {
+    // The key bits and mode of the symmetric algorithm.
+    public.u32()?;
+  }
+  if public.u16()? != TPM_ALG_NULL {
+    // The hash of the scheme.
+    public.u16()?;
+  }
+
+  let der = match type_ {
+    TPM_ALG_ECC => {
+      let curve = public.u16()?;
+      if public.u16()? != TPM_ALG_NULL {
+        public.u16()?;
+      }
+      let length = match curve {
+        TPM_ECC_NIST_P256 => 32,
+        TPM_ECC_NIST_P384 => 48,
+        _ => return Err(WebCryptoError::NotSupportedError),
+      };
+      let mut point = vec![0x04];
+      point.extend(pad(&public.sized()?, length)?);
+      point.extend(pad(&public.sized()?, length)?);
+
+      let der = if curve == TPM_ECC_NIST_P256 {
+        p256::PublicKey::from_sec1_bytes(&point)
+          .map_err(|_| WebCryptoError::OperationError)?
+          .to_public_key_der()
+      } else {
+        p384::PublicKey::from_sec1_bytes(&point)
+          .map_err(|_| WebCryptoError::OperationError)?
+          .to_public_key_der()
+      };
+      der
+        .map_err(|_| WebCryptoError::OperationError)?
+        .as_bytes()
+        .to_vec()
+    }
+    TPM_ALG_RSA => {
+      public.u16()?;
+      let exponent = match public.u32()? {
+        0 => 65537,
+        exponent => exponent,
+      };
+      let modulus = public.sized()?;
+      let public_key = RsaPublicKey::new(
+        BigUint::from_bytes_be(&modulus),
+        BigUint::from(exponent),
+      )
+      .map_err(|_| WebCryptoError::OperationError)?;
+      public_key
+        .to_public_key_der()
+        .map_err(|_| WebCryptoError::OperationError)?
+        .as_ref()
+        .to_vec()
+    }
+    _ => return Err(WebCryptoError::OperationError),
+  };
+  Ok(der)
+}
+
+enum Entry {
+  Material(KeyMaterial),
+  /// A key held by the TPM: its TPM2B_PRIVATE and TPM2B_PUBLIC contents,
+  /// and the PCRs its policy binds it to.
+  Object {
+    private: Vec<u8>,
+    public: Vec<u8>,
+    pcrs: [u8; 3],
+  },
+}
+
+/// A `KeyStorage` creating private keys in a TPM 2.0, see the module
+/// documentation. Handles are indices, not reused after a key is
+/// deleted.
+pub struct Tpm<T: TpmTransport> {
+  transport: T,
+  keys: Vec<Option<Entry>>,
+  /// The handle of the primary key, once loaded.
+  primary: Option<u32>,
+  /// The PCRs keys are bound to, a bitmap of PCRs 0 to 23.
+  pcrs: [u8; 3],
+}
+
+impl<T: TpmTransport> Tpm<T> {
+  pub fn new(transport: T) -> Self {
+    Tpm {
+      transport,
+      keys: Vec::new(),
+      primary: None,
+      pcrs: [0; 3],
+    }
+  }
+
+  /// Bind the keys created from now on to the current values of the
+  /// SHA-256 PCRs `pcrs`, from 0 to 23, or to none when empty, the
+  /// default.
+  pub fn set_pcr_policy(&mut self, pcrs: &[u32]) -> Result<(), WebCryptoError> {
+    let mut selection = [0; 3];
+    for &pcr in pcrs {
+      if pcr > 23 {
+        return Err(WebCryptoError::NotSupportedError);
+      }
+      selection[pcr as usize / 8] |= 1 << (pcr % 8);
+    }
+    self.pcrs = selection;
+    Ok(())
+  }
+
+  /// Send `command`, returning the response after its header.
+  fn transact(&mut self, command: Command) -> Result<Response, WebCryptoError> {
+    let response = self
+      .transport
+      .transact(&command.finish())
+      .map_err(|_| WebCryptoError::OperationError)?;
+
+    let mut response = Response::new(response);
+    response.u16()?;
+    let size = response.u32()?;
+    let code = response.u32()?;
+    if size as usize != response.data.len() || code != 0 {
+      return Err(WebCryptoError::OperationError);
+    }
+    Ok(response)
+  }
+
+  fn flush(&mut self, handle: u32) {
+    let mut command = Command::new(TPM_CC_FLUSH_CONTEXT, false);
+    command.u32(handle);
+    // Nothing more to do if it fails, the resource manager flushes it
+    // eventually.
+    let _ = self.transact(command);
+  }
+
+  /// The handle of the primary key, created from the storage seed the
+  /// first time.
+  fn primary(&mut self) -> Result<u32, WebCryptoError> {
+    if let Some(handle) = self.primary {
+      return Ok(handle);
+    }
+
+    let mut command = Command::new(TPM_CC_CREATE_PRIMARY, true);
+    command
+      .u32(TPM_RH_OWNER)
+      .authorization(None)
+      .sized(&SENSITIVE_CREATE)
+      .sized(&Template::Storage.marshal(&[]))
+      .sized(&[])
+      .u32(0);
+    let handle = self.transact(command)?.u32()?;
+    self.primary = Some(handle);
+    Ok(handle)
+  }
+
+  /// Start a policy session satisfied by the current values of `pcrs`,
+  /// or a trial session to compute the digest of the policy.
+  fn policy_session(
+    &mut self,
+    pcrs: [u8; 3],
+    trial: bool,
+  ) -> Result<u32, WebCryptoError> {
+    let mut command = Command::new(TPM_CC_START_AUTH_SESSION, false);
+    command
+      .u32(TPM_RH_NULL)
+      .u32(TPM_RH_NULL)
+      .sized(&nonce())
+      .sized(&[])
+      .u8(if trial { TPM_SE_TRIAL } else { TPM_SE_POLICY })
+      .u16(TPM_ALG_NULL)
+      .u16(TPM_ALG_SHA256);
+    let session = self.transact(command)?.u32()?;
+
+    let mut command = Command::new(TPM_CC_POLICY_PCR, false);
+    command
+      .u32(session)
+      .sized(&[])
+      .u32(1)
+      .u16(TPM_ALG_SHA256)
+      .u8(3)
+      .bytes(&pcrs);
+    if let Err(error) = self.transact(command) {
+      self.flush(session);
+      return Err(error);
+    }
+    Ok(session)
+  }
+
+  /// The digest of the policy satisfied by the current values of `pcrs`.
+  fn policy_digest(
+    &mut self,
+    pcrs: [u8; 3],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let session = self.policy_session(pcrs, true)?;
+    let mut command = Command::new(TPM_CC_POLICY_GET_DIGEST, false);
+    command.u32(session);
+    let digest = self
+      .transact(command)
+      .and_then(|mut response| response.sized());
+    self.flush(session);
+    digest
+  }
+
+  /// Create a key from `template`, bound to `pcrs` unless none are set,
+  /// returning its wrapped private and its public area.
+  fn create(
+    &mut self,
+    template: Template,
+    pcrs: [u8; 3],
+  ) -> Result<(Vec<u8>, Vec<u8>), WebCryptoError> {
+    let policy = match pcrs {
+      [0, 0, 0] => Vec::new(),
+      _ => self.policy_digest(pcrs)?,
+    };
+    let parent = self.primary()?;
+
+    let mut command = Command::new(TPM_CC_CREATE, true);
+    command
+      .u32(parent)
+      .authorization(None)
+      .sized(&SENSITIVE_CREATE)
+      .sized(&template.marshal(&policy))
+      .sized(&[])
+      .u32(0);
+    let mut response = self.transact(command)?;
+    // The size of the parameters.
+    response.u32()?;
+    let private = response.sized()?;
+    let public = response.sized()?;
+    Ok((private, public))
+  }
+
+  /// Load the key of `handle` and run `operation` with the handle it
+  /// loaded at and its policy session, if any. `None` for keys not held
+  /// by the TPM.
+  fn with_object(
+    &mut self,
+    handle: usize,
+    operation: impl FnOnce(
+      &mut Self,
+      u32,
+      Option<u32>,
+    ) -> Result<Vec<u8>, WebCryptoError>,
+  ) -> Option<Result<Vec<u8>, WebCryptoError>> {
+    let (private, public, pcrs) = match self.keys.get(handle) {
+      Some(Some(Entry::Object {
+        private,
+        public,
+        pcrs,
+      })) => (private.clone(), public.clone(), *pcrs),
+      _ => return None,
+    };
+
+    Some(self.primary().and_then(|parent| {
+      let mut command = Command::new(TPM_CC_LOAD, true);
+      command
+        .u32(parent)
+        .authorization(None)
+        .sized(&private)
+        .sized(&public);
+      let object = self.transact(command)?.u32()?;
+
+      let session = match pcrs {
+        [0, 0, 0] => Ok(None),
+        _ => self.policy_session(pcrs, false).map(Some),
+      };
+      let result = session.and_then(|session| {
+        let result = operation(self, object, session);
+        // Only a successful command flushes the session.
+        if let (Err(_), Some(session)) = (&result, session) {
+          self.flush(session);
+        }
+        result
+      });
+      self.flush(object);
+      result
+    }))
+  }
+
+  /// Sign `digest` with the loaded key `object`, returning an ECDSA
+  /// signature as `r || s` of `length` bytes each.
+  fn sign(
+    &mut self,
+    object: u32,
+    session: Option<u32>,
+    scheme: u16,
+    hash: u16,
+    digest: &[u8],
+    length: usize,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let mut command = Command::new(TPM_CC_SIGN, true);
+    command
+      .u32(object)
+      .authorization(session)
+      .sized(digest)
+      .u16(scheme)
+      .u16(hash)
+      // A null ticket, as the key is not restricted.
+      .u16(TPM_ST_HASHCHECK)
+      .u32(TPM_RH_NULL)
+      .sized(&[]);
+    let mut response = self.transact(command)?;
+    response.u32()?;
+    if response.u16()? != scheme {
+      return Err(WebCryptoError::OperationError);
+    }
+    response.u16()?;
+
+    match scheme {
+      TPM_ALG_ECDSA => {
+        let mut signature = pad(&response.sized()?, length)?;
+        signature.extend(pad(&response.sized()?, length)?);
+        Ok(signature)
+      }
+      _ => response.sized(),
+    }
+  }
+
+  /// Decrypt the RSA-OAEP `ciphertext` with the loaded key `object`.
+  fn decrypt(
+    &mut self,
+    object: u32,
+    session: Option<u32>,
+    hash: u16,
+    label: &[u8],
+    ciphertext: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let mut command = Command::new(TPM_CC_RSA_DECRYPT, true);
+    command
+      .u32(object)
+      .authorization(session)
+      .sized(ciphertext)
+      .u16(TPM_ALG_OAEP)
+      .u16(hash)
+      .sized(label);
+    let mut response = self.transact(command)?;
+    response.u32()?;
+    response.sized()
+  }
+}
+
+impl<T: TpmTransport> Drop for Tpm<T> {
+  fn drop(&mut self) {
+    if let Some(primary) = self.primary.take() {
+      self.flush(primary);
+    }
+  }
+}
+
+impl<T: TpmTransport> KeyStorage for Tpm<T> {
+  type Handle = usize;
+
+  fn store(&mut self, key: KeyMaterial) -> usize {
+    self.keys.push(Some(Entry::Material(key)));
+    self.keys.len() - 1
+  }
+
+  fn get(&self, handle: usize) -> Option<&KeyMaterial> {
+    match self.keys.get(handle)? {
+      Some(Entry::Material(key)) => Some(key),
+      _ => None,
+    }
+  }
+
+  fn delete(&mut self, handle: usize) -> bool {
+    match self.keys.get_mut(handle).and_then(Option::take) {
+      Some(Entry::Material(mut key)) => {
+        key.0.fill(0);
+        true
+      }
+      // Forgetting the wrapped key is enough: the TPM keeps no copy.
+      Some(Entry::Object { .. }) => true,
+      None => false,
+    }
+  }
+
+  fn resident_bytes(&self) -> Option<usize> {
+    let bytes = self.keys.iter().flatten().map(|entry| match entry {
+      Entry::Material(key) => key.0.len(),
+      Entry::Object { .. } => 0,
+    });
+    Some(bytes.sum())
+  }
+
+  fn generate(
+    &mut self,
+    algorithm: &KeyGenParams,
+  ) -> Option<Result<GeneratedKey<usize>, WebCryptoError>> {
+    let template = match algorithm {
+      KeyGenParams::EcKeyGenParams(params) if params.name == "ECDSA" => {
+        curve(params.named_curve).map(|(curve, _)| Template::Ecc { curve })
+      }
+      KeyGenParams::RsaHashedKeyGenParams(params)
+        if matches!(params.modulus_length, 2048 | 3072)
+          && params.public_exponent == PublicExponent::F4 =>
+      {
+        let bits = params.modulus_length as u16;
+        match params.name {
+          "RSASSA-PKCS1-v1_5" => Ok(Template::Rsa {
+            bits,
+            attributes: SIGN,
+          }),
+          "RSA-OAEP" => Ok(Template::Rsa {
+            bits,
+            attributes: DECRYPT,
+          }),
+          // RSA-PSS included, see the module documentation.
+          _ => Err(WebCryptoError::NotSupportedError),
+        }
+      }
+      // Secret keys are kept in memory.
+      _ => match algorithm.name().parse::<AlgorithmName>() {
+        Ok(name) if permitted_usages(name, KeyType::Private).is_empty() => {
+          return None;
+        }
+        _ => Err(WebCryptoError::NotSupportedError),
+      },
+    };
+
+    let pcrs = self.pcrs;
+    Some(template.and_then(|template| {
+      let (private, public) = self.create(template, pcrs)?;
+      let public_key = public_key(&public)?;
+      self.keys.push(Some(Entry::Object {
+        private,
+        public,
+        pcrs,
+      }));
+      Ok(GeneratedKey {
+        handle: self.keys.len() - 1,
+        public_key: Some(public_key),
+      })
+    }))
+  }
+
+  fn perform(
+    &mut self,
+    handle: usize,
+    algorithm: &Algorithm,
+    operation: KeyOperation<'_>,
+  ) -> Option<Result<Vec<u8>, WebCryptoError>> {
+    let algorithm = *algorithm;
+    self.with_object(handle, |tpm, object, session| match operation {
+      KeyOperation::Sign {
+        params,
+        hash,
+        digest,
+      } => {
+        let hash = hash_algorithm(hash)?;
+        match (params, algorithm) {
+          (SignParams::EcdsaParams(params), Algorithm::EcKeyAlgorithm(alg))
+            if !params.deterministic =>
+          {
+            let (_, length) = curve(alg.named_curve)?;
+            tpm.sign(object, session, TPM_ALG_ECDSA, hash, digest, length)
+          }
+          (
+            SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+              name: "RSASSA-PKCS1-v1_5",
+            }),
+            _,
+          ) => tpm.sign(object, session, TPM_ALG_RSASSA, hash, digest, 0),
+          _ => Err(WebCryptoError::NotSupportedError),
+        }
+      }
+      KeyOperation::Decrypt {
+        params: EncryptParams::RsaOaepParams(params),
+        data,
+      } => {
+        let hash = match algorithm {
+          Algorithm::RsaHashedKeyAlgorithm(alg) => {
+            hash_algorithm(alg.hash.name)?
+          }
+          _ => return Err(WebCryptoError::InvalidAccessError),
+        };
+        // The TPM hashes labels with their terminating zero, so only
+        // those with one match the WebCrypto label.
+        if params.label.last().is_some_and(|&last| last != 0) {
+          return Err(WebCryptoError::NotSupportedError);
+        }
+        tpm.decrypt(object, session, hash, &params.label, data)
+      }
+      _ => Err(WebCryptoError::NotSupportedError),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::cell::RefCell;
+  use std::collections::HashMap;
+  use std::rc::Rc;
+
+  use p256::ecdsa::signature::hazmat::PrehashSigner;
+  use sha2::Digest;
+  use sha2::Sha256;
+
+  use super::*;
+  use crate::subtle;
+  use crate::Context;
+
+  const TPM_RC_POLICY_FAIL: u32 = 0x099d;
+  const TPM_RC_AUTH_UNAVAILABLE: u32 = 0x012f;
+
+  struct Object {
+    key: p256::ecdsa::SigningKey,
+    attributes: u32,
+    policy: Vec<u8>,
+  }
+
+  /// A software TPM, enough of one for P-256 keys and PCR policies.
+  #[derive(Default)]
+  struct State {
+    pcrs: [[u8; 32]; 24],
+    objects: HashMap<u32, Object>,
+    sessions: HashMap<u32, Vec<u8>>,
+    next_handle: u32,
+  }
+
+  #[derive(Clone, Default)]
+  struct FakeTpm(Rc<RefCell<State>>);
+
+  /// The TPMT_PUBLIC template `template` of a P-256 key, filled in with
+  /// the public key of `key`.
+  fn fill_public(template: &[u8], key: &p256::ecdsa::SigningKey) -> Vec<u8> {
+    let mut template = Response::new(template.to_vec());
+    let type_ = template.u16().unwrap();
+    let name_algorithm = template.u16().unwrap();
+    let attributes = template.u32().unwrap();
+    let policy = template.sized().unwrap();
+    assert_eq!(type_, TPM_ALG_ECC);
+
+    let point = key.verifying_key().to_encoded_point(false);
+    let mut public = Command(Vec::new());
+    public
+      .u16(type_)
+      .u16(name_algorithm)
+      .u32(attributes)
+      .sized(&policy)
+      .u16(TPM_ALG_NULL)
+      .u16(TPM_ALG_NULL)
+      .u16(TPM_ECC_NIST_P256)
+      .u16(TPM_ALG_NULL)
+      .sized(point.x().unwrap())
+      .sized(point.y().unwrap());
+    public.0
+  }
+
+  impl State {
+    fn handle(&mut self, base: u32) -> u32 {
+      self.next_handle += 1;
+      base + self.next_handle
+    }
+
+    /// Check the authorization area of a command on `object`.
+    fn authorize(
+      &mut self,
+      command: &mut Response,
+      object: u32,
+    ) -> Result<(), u32> {
+      let size = command.u32().unwrap() as usize;
+      let session = command.u32().unwrap();
+      command.bytes(size - 4).unwrap();
+      let object = &self.objects[&object];
+      match session {
+        TPM_RS_PW if object.attributes & USER_WITH_AUTH != 0 => Ok(()),
+        TPM_RS_PW => Err(TPM_RC_AUTH_UNAVAILABLE),
+        session if self.sessions.remove(&session).unwrap() == object.policy => {
+          Ok(())
+        }
+        _ => Err(TPM_RC_POLICY_FAIL),
+      }
+    }
+
+    fn execute(&mut self, command: &mut Response) -> Result<Command, u32> {
+      let tag = command.u16().unwrap();
+      command.u32().unwrap();
+      let code = command.u32().unwrap();
+      let mut response = Command::new(0, tag == TPM_ST_SESSIONS);
+
+      match code {
+        TPM_CC_CREATE_PRIMARY => {
+          response.u32(0x8000_0000).u32(0);
+        }
+        TPM_CC_CREATE => {
+          command.u32().unwrap();
+          let size = command.u32().unwrap() as usize;
+          command.bytes(size).unwrap();
+          command.sized().unwrap();
+          let template = command.sized().unwrap();
+          let key = p256::ecdsa::SigningKey::random(&mut OsRng);
+          response
+            .u32(0)
+            .sized(&key.to_bytes())
+            .sized(&fill_public(&template, &key));
+        }
+        TPM_CC_LOAD => {
+          command.u32().unwrap();
+          let size = command.u32().unwrap() as usize;
+          command.bytes(size).unwrap();
+          let private = command.sized().unwrap();
+          let mut public = Response::new(command.sized().unwrap());
+          public.u32().unwrap();
+          let attributes = public.u32().unwrap();
+          let policy = public.sized().unwrap();
+          let object = Object {
+            key: p256::ecdsa::SigningKey::from_slice(&private).unwrap(),
+            attributes,
+            policy,
+          };
+          let handle = self.handle(0x8000_0000);
+          self.objects.insert(handle, object);
+          response.u32(handle).u32(2).sized(&[]);
+        }
+        TPM_CC_START_AUTH_SESSION => {
+          let handle = self.handle(0x0300_0000);
+          self.sessions.insert(handle, vec![0; 32]);
+          response.u32(handle).sized(&[0; 32]);
+        }
+        TPM_CC_POLICY_PCR => {
+          let session = command.u32().unwrap();
+          command.sized().unwrap();
+          command.bytes(7).unwrap();
+          let selection = command.bytes(3).unwrap().to_vec();
+          let digest = self.sessions.get_mut(&session).unwrap();
+          let mut hasher = Sha256::new();
+          hasher.update(&*digest);
+          hasher.update(&selection);
+          for pcr in 0..24 {
+            if selection[pcr / 8] & (1 << (pcr % 8)) != 0 {
+              hasher.update(self.pcrs[pcr]);
+            }
+          }
+          *digest = hasher.finalize().to_vec();
+        }
+        TPM_CC_POLICY_GET_DIGEST => {
+          let session = command.u32().unwrap();
+          response.sized(&self.sessions[&session]);
+        }
+        TPM_CC_SIGN => {
+          let object = command.u32().unwrap();
+          self.authorize(command, object)?;
+          let digest = command.sized().unwrap();
+          assert_eq!(command.u16().unwrap(), TPM_ALG_ECDSA);
+          let hash = command.u16().unwrap();
+          let signature: p256::ecdsa::Signature =
+            self.objects[&object].key.sign_prehash(&digest).unwrap();
+          response
+            .u32(0)
+            .u16(TPM_ALG_ECDSA)
+            .u16(hash)
+            .sized(&signature.r().to_bytes())
+            .sized(&signature.s().to_bytes());
+        }
+        TPM_CC_FLUSH_CONTEXT => {
+          let handle = command.u32().unwrap();
+          self.objects.remove(&handle);
+          self.sessions.remove(&handle);
+        }
+        _ => unreachable!(),
+      }
+      Ok(response)
+    }
+  }
+
+  impl TpmTransport for FakeTpm {
+    fn transact(&mut self, command: &[u8]) -> io::Result<Vec<u8>> {
+      let mut command = Response::new(command.to_vec());
+      let response = match self.0.borrow_mut().execute(&mut command) {
+        Ok(response) => response,
+        Err(code) => Command::new(code, false),
+      };
+      Ok(response.finish())
+    }
+  }
+
+  fn generate_ecdsa<T: TpmTransport>(
+    ctx: &mut Context<OsRng, Tpm<T>>,
+    extractable: bool,
+  ) -> subtle::CryptoKeyPair<usize> {
+    match ctx
+      .subtle
+      .generate_key(
+        subtle::EcKeyGenParams {
+          name: "ECDSA",
+          named_curve: subtle::NamedCurve::P256,
+        }
+        .into(),
+        extractable,
+        vec![subtle::KeyUsage::Sign, subtle::KeyUsage::Verify],
+      )
+      .unwrap()
+    {
+      subtle::CryptoKeyOrPair::CryptoKeyPair(key_pair) => key_pair,
+      _ => unreachable!(),
+    }
+  }
+
+  fn ecdsa() -> subtle::SignParams {
+    subtle::SignParams::EcdsaParams(subtle::EcdsaParams {
+      name: "ECDSA",
+      hash: subtle::HashAlgorithmIdentifer { name: "SHA-256" },
+      deterministic: false,
+      low_s: false,
+    })
+  }
+
+  #[test]
+  fn test_sign() {
+    let fake = FakeTpm::default();
+    let mut ctx = Context::new(OsRng, Tpm::new(fake.clone()));
+
+    let key_pair = generate_ecdsa(&mut ctx, false);
+    let signature = ctx
+      .subtle
+      .sign(ecdsa(), &key_pair.private_key, b"message")
+      .unwrap();
+    assert!(ctx
+      .subtle
+      .verify(ecdsa(), &key_pair.public_key, &signature, b"message")
+      .unwrap());
+    assert!(ctx
+      .subtle
+      .export_key("pkcs8", &key_pair.private_key)
+      .is_err());
+
+    // Loaded objects are flushed once used.
+    assert!(fake.0.borrow().objects.is_empty());
+
+    // Extractable keys stay in memory.
+    let key_pair = generate_ecdsa(&mut ctx, true);
+    assert!(ctx
+      .subtle
+      .export_key("pkcs8", &key_pair.private_key)
+      .is_ok());
+
+    // So do secret keys.
+    let key = ctx.subtle.generate_key(
+      subtle::AesKeyGenParams {
+        name: "AES-GCM",
+        length: 256,
+      }
+      .into(),
+      false,
+      vec![subtle::KeyUsage::Encrypt],
+    );
+    assert!(key.is_ok());
+
+    // Ed25519 keys do not fit in a TPM.
+    let key = ctx.subtle.generate_key(
+      subtle::KeyGenParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+        name: "Ed25519",
+      }),
+      false,
+      vec![subtle::KeyUsage::Sign],
+    );
+    assert!(matches!(key, Err(WebCryptoError::NotSupportedError)));
+  }
+
+  #[test]
+  fn test_pcr_policy() {
+    let fake = FakeTpm::default();
+    let mut tpm = Tpm::new(fake.clone());
+    tpm.set_pcr_policy(&[0, 7]).unwrap();
+    assert!(tpm.set_pcr_policy(&[24]).is_err());
+    let mut ctx = Context::new(OsRng, tpm);
+
+    let key_pair = generate_ecdsa(&mut ctx, false);
+    let signature = ctx
+      .subtle
+      .sign(ecdsa(), &key_pair.private_key, b"message")
+      .unwrap();
+    assert!(ctx
+      .subtle
+      .verify(ecdsa(), &key_pair.public_key, &signature, b"message")
+      .unwrap());
+
+    // Other software booted.
+    fake.0.borrow_mut().pcrs[7] = [1; 32];
+    let signature = ctx.subtle.sign(ecdsa(), &key_pair.private_key, b"message");
+    assert!(matches!(signature, Err(WebCryptoError::OperationError)));
+    assert!(fake.0.borrow().sessions.is_empty());
+  }
+
+  /// The server socket of swtpm, which carries bare TPM commands.
+  struct Swtpm(std::net::TcpStream);
+
+  impl TpmTransport for Swtpm {
+    fn transact(&mut self, command: &[u8]) -> io::Result<Vec<u8>> {
+      self.0.write_all(command)?;
+      let mut response = vec![0; 10];
+      self.0.read_exact(&mut response)?;
+      let length = u32::from_be_bytes(response[2..6].try_into().unwrap());
+      response.resize(length as usize, 0);
+      self.0.read_exact(&mut response[10..])?;
+      Ok(response)
+    }
+  }
+
+  fn generate_rsa<T: TpmTransport>(
+    ctx: &mut Context<OsRng, Tpm<T>>,
+    name: &'static str,
+    usages: Vec<subtle::KeyUsage>,
+  ) -> Result<subtle::CryptoKeyPair<usize>, WebCryptoError> {
+    let params = subtle::RsaHashedKeyGenParams {
+      name,
+      modulus_length: 2048,
+      public_exponent: PublicExponent::F4,
+      hash: subtle::HashAlgorithmIdentifer { name: "SHA-256" },
+    };
+    match ctx.subtle.generate_key(params.into(), false, usages)? {
+      subtle::CryptoKeyOrPair::CryptoKeyPair(key_pair) => Ok(key_pair),
+      _ => unreachable!(),
+    }
+  }
+
+  /// Runs against a real TPM implementation when `WEBCRYPTO_SWTPM` holds
+  /// the address of a swtpm server socket, eg. started with
+  /// `swtpm socket --tpm2 --server type=tcp,port=2321
+  /// --ctrl type=tcp,port=2322 --flags not-need-init,startup-clear
+  /// --tpmstate dir=/tmp/swtpm`.
+  #[test]
+  fn test_swtpm() {
+    let address = match std::env::var("WEBCRYPTO_SWTPM") {
+      Ok(address) => address,
+      Err(_) => return,
+    };
+    let stream = std::net::TcpStream::connect(address).unwrap();
+    let mut tpm = Tpm::new(Swtpm(stream));
+    tpm.set_pcr_policy(&[0, 7]).unwrap();
+    let mut ctx = Context::new(OsRng, tpm);
+
+    let key_pair = generate_ecdsa(&mut ctx, false);
+    let signature = ctx
+      .subtle
+      .sign(ecdsa(), &key_pair.private_key, b"message")
+      .unwrap();
+    assert!(ctx
+      .subtle
+      .verify(ecdsa(), &key_pair.public_key, &signature, b"message")
+      .unwrap());
+
+    let pkcs1 =
+      subtle::SignParams::AlgorithmIdentifer(subtle::AlgorithmIdentifer {
+        name: "RSASSA-PKCS1-v1_5",
+      });
+    let key_pair = generate_rsa(
+      &mut ctx,
+      "RSASSA-PKCS1-v1_5",
+      vec![subtle::KeyUsage::Sign, subtle::KeyUsage::Verify],
+    )
+    .unwrap();
+    let signature = ctx
+      .subtle
+      .sign(pkcs1, &key_pair.private_key, b"message")
+      .unwrap();
+    assert!(ctx
+      .subtle
+      .verify(pkcs1, &key_pair.public_key, &signature, b"message")
+      .unwrap());
+
+    let oaep = subtle::EncryptParams::RsaOaepParams(subtle::RsaOaepParams {
+      name: "RSA-OAEP",
+      label: vec![],
+    });
+    let key_pair = generate_rsa(
+      &mut ctx,
+      "RSA-OAEP",
+      vec![subtle::KeyUsage::Encrypt, subtle::KeyUsage::Decrypt],
+    )
+    .unwrap();
+    let ciphertext = ctx
+      .subtle
+      .encrypt(oaep.clone(), &key_pair.public_key, b"message")
+      .unwrap();
+    assert_eq!(
+      ctx
+        .subtle
+        .decrypt(oaep, &key_pair.private_key, &ciphertext)
+        .unwrap(),
+      b"message"
+    );
+
+    let key_pair =
+      generate_rsa(&mut ctx, "RSA-PSS", vec![subtle::KeyUsage::Sign]);
+    assert!(matches!(key_pair, Err(WebCryptoError::NotSupportedError)));
+  }
+}
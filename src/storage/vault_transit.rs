@@ -0,0 +1,746 @@
+//! A `KeyStorage` proxying to the transit secrets engine of HashiCorp
+//! Vault, which creates keys and signs, encrypts and decrypts with them
+//! without ever releasing them.
+//!
+//! Non-extractable ECDSA keys on P-256, P-384 and P-521, RSASSA-PKCS1-v1_5,
+//! RSA-PSS and RSA-OAEP keys of 2048, 3072 or 4096 bits, and AES-GCM and
+//! ChaCha20-Poly1305 keys are created in Vault. Other key pairs are
+//! refused; other secret keys and anything imported or extractable is
+//! kept in memory, like `InMemoryVault` does.
+//!
+//! Vault draws its own nonces, so AES-GCM and ChaCha20-Poly1305 keys held
+//! by Vault take an empty `iv`: any other fails with `NotSupportedError`
+//! rather than being ignored, and so do `encrypt_with_random_iv`, the
+//! nonce sequences and in-place encryption. Ciphertexts are Vault's
+//! `vault:v1:` strings rather than WebCrypto ones. RSA-OAEP decryption
+//! only takes SHA-256 keys and empty labels.
+//!
+//! Requests go through a [`VaultTransport`] implemented by the
+//! application on top of its HTTP client, which also authenticates them.
+use std::io;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde_json::json;
+use serde_json::Value;
+
+use super::GeneratedKey;
+use super::KeyMaterial;
+use super::KeyOperation;
+use super::KeyStorage;
+use crate::subtle::permitted_usages;
+use crate::subtle::Algorithm;
+use crate::subtle::AlgorithmIdentifer;
+use crate::subtle::AlgorithmName;
+use crate::subtle::EncryptParams;
+use crate::subtle::KeyGenParams;
+use crate::subtle::KeyType;
+use crate::subtle::NamedCurve;
+use crate::subtle::PublicExponent;
+use crate::subtle::SignParams;
+use crate::WebCryptoError;
+
+/// Carries requests to a Vault server and its responses back.
+pub trait VaultTransport {
+  /// Send a `method` request for `path`, eg. `/v1/transit/keys/name`, with
+  /// the JSON `body`, if any, authenticated eg. with an `X-Vault-Token`
+  /// header. Returns the response body, empty for `204 No Content`, or an
+  /// error when the request failed.
+  fn request(
+    &mut self,
+    method: &str,
+    path: &str,
+    body: Option<&str>,
+  ) -> io::Result<String>;
+}
+
+enum Entry {
+  Material(KeyMaterial),
+  /// A key held by Vault under `name`.
+  Remote(String),
+}
+
+/// A `KeyStorage` creating keys in Vault's transit engine, see the
+/// module documentation. Handles are indices, not reused after a key is
+/// deleted.
+pub struct VaultTransit<T: VaultTransport> {
+  transport: T,
+  /// The mount path of the engine.
+  mount: String,
+  keys: Vec<Option<Entry>>,
+}
+
+fn hash_name(hash: &str) -> Result<&'static str, WebCryptoError> {
+  match hash {
+    "SHA-1" => Ok("sha1"),
+    "SHA-256" => Ok("sha2-256"),
+    "SHA-384" => Ok("sha2-384"),
+    "SHA-512" => Ok("sha2-512"),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The DER contents of a PEM document.
+fn pem_contents(pem: &str) -> Result<Vec<u8>, WebCryptoError> {
+  let contents: String = pem
+    .lines()
+    .filter(|line| !line.starts_with("-----"))
+    .collect();
+  base64::decode(contents.trim()).map_err(|_| WebCryptoError::OperationError)
+}
+
+/// The base64 contents of a `vault:v1:` string.
+fn vault_contents(value: &Value) -> Result<&str, WebCryptoError> {
+  let value = value.as_str().ok_or(WebCryptoError::OperationError)?;
+  value
+    .strip_prefix("vault:v")
+    .and_then(|value| value.split_once(':'))
+    .map(|(_, contents)| contents)
+    .ok_or(WebCryptoError::OperationError)
+}
+
+impl<T: VaultTransport> VaultTransit<T> {
+  pub fn new(transport: T) -> Self {
+    VaultTransit {
+      transport,
+      mount: "transit".to_string(),
+      keys: Vec::new(),
+    }
+  }
+
+  /// Use the engine mounted at `mount` rather than at "transit".
+  pub fn set_mount(&mut self, mount: &str) {
+    self.mount = mount.trim_matches('/').to_string();
+  }
+
+  /// The name in Vault of the key of `handle`, for keys Vault holds.
+  pub fn key_name(&self, handle: usize) -> Option<&str> {
+    match self.keys.get(handle)? {
+      Some(Entry::Remote(name)) => Some(name),
+      _ => None,
+    }
+  }
+
+  /// Send a request for `path`, below the mount, returning the `data` of
+  /// the response.
+  fn request(
+    &mut self,
+    method: &str,
+    path: &str,
+    body: Option<Value>,
+  ) -> Result<Value, WebCryptoError> {
+    let path = format!("/v1/{}/{}", self.mount, path);
+    let body = body.map(|body| body.to_string());
+    let response = self
+      .transport
+      .request(method, &path, body.as_deref())
+      .map_err(|_| WebCryptoError::OperationError)?;
+    if response.is_empty() {
+      return Ok(Value::Null);
+    }
+
+    let mut response: Value = serde_json::from_str(&response)
+      .map_err(|_| WebCryptoError::OperationError)?;
+    if response.get("errors").is_some() {
+      return Err(WebCryptoError::OperationError);
+    }
+    Ok(response["data"].take())
+  }
+
+  /// Create a key of the Vault `type_` under a fresh name, returning the
+  /// name and, for key pairs, the DER encoded SubjectPublicKeyInfo.
+  fn create(
+    &mut self,
+    type_: &str,
+    pair: bool,
+  ) -> Result<(String, Option<Vec<u8>>), WebCryptoError> {
+    let mut id = [0; 16];
+    OsRng.fill_bytes(&mut id);
+    let id: String = id.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let name = format!("webcrypto-{}", id);
+
+    let path = format!("keys/{}", name);
+    self.request("POST", &path, Some(json!({ "type": type_ })))?;
+    if !pair {
+      return Ok((name, None));
+    }
+
+    let public_key = self.request("GET", &path, None).and_then(|key| {
+      let pem = key["keys"]["1"]["public_key"]
+        .as_str()
+        .ok_or(WebCryptoError::OperationError)?;
+      pem_contents(pem)
+    });
+    match public_key {
+      Ok(public_key) => Ok((name, Some(public_key))),
+      Err(err) => {
+        // Nothing holds the key: do not leave it in Vault.
+        let _ = self.delete_remote(&name);
+        Err(err)
+      }
+    }
+  }
+
+  /// Delete the key `name` from Vault.
+  fn delete_remote(&mut self, name: &str) -> Result<(), WebCryptoError> {
+    // Vault refuses to delete keys until told to allow it.
+    let config = format!("keys/{}/config", name);
+    let allowed = json!({ "deletion_allowed": true });
+    self.request("POST", &config, Some(allowed))?;
+    self.request("DELETE", &format!("keys/{}", name), None)?;
+    Ok(())
+  }
+
+  fn sign(
+    &mut self,
+    name: &str,
+    params: &SignParams,
+    hash: &str,
+    digest: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let mut body = json!({
+      "input": base64::encode(digest),
+      "prehashed": true,
+    });
+    match params {
+      // JWS signatures are `r || s`.
+      SignParams::EcdsaParams(params) if !params.deterministic => {
+        body["marshaling_algorithm"] = json!("jws");
+      }
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "RSASSA-PKCS1-v1_5",
+      }) => {
+        body["signature_algorithm"] = json!("pkcs1v15");
+      }
+      SignParams::RsaPssParams(params) => {
+        body["signature_algorithm"] = json!("pss");
+        body["salt_length"] = match params.salt_length {
+          Some(salt_length) => json!(salt_length),
+          None => json!("hash"),
+        };
+      }
+      _ => return Err(WebCryptoError::NotSupportedError),
+    }
+
+    let path = format!("sign/{}/{}", name, hash_name(hash)?);
+    let response = self.request("POST", &path, Some(body))?;
+    let signature = vault_contents(&response["signature"])?;
+    let signature = match params {
+      SignParams::EcdsaParams(_) => {
+        base64::decode_config(signature, base64::URL_SAFE_NO_PAD)
+      }
+      _ => base64::decode(signature),
+    };
+    signature.map_err(|_| WebCryptoError::OperationError)
+  }
+
+  fn encrypt(
+    &mut self,
+    name: &str,
+    associated_data: &[u8],
+    plaintext: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let mut body = json!({ "plaintext": base64::encode(plaintext) });
+    if !associated_data.is_empty() {
+      body["associated_data"] = json!(base64::encode(associated_data));
+    }
+    let path = format!("encrypt/{}", name);
+    let response = self.request("POST", &path, Some(body))?;
+    response["ciphertext"]
+      .as_str()
+      .map(|ciphertext| ciphertext.as_bytes().to_vec())
+      .ok_or(WebCryptoError::OperationError)
+  }
+
+  fn decrypt(
+    &mut self,
+    name: &str,
+    associated_data: &[u8],
+    ciphertext: &str,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let mut body = json!({ "ciphertext": ciphertext });
+    if !associated_data.is_empty() {
+      body["associated_data"] = json!(base64::encode(associated_data));
+    }
+    let path = format!("decrypt/{}", name);
+    let response = self.request("POST", &path, Some(body))?;
+    let plaintext = response["plaintext"]
+      .as_str()
+      .ok_or(WebCryptoError::OperationError)?;
+    base64::decode(plaintext).map_err(|_| WebCryptoError::OperationError)
+  }
+}
+
+impl<T: VaultTransport> KeyStorage for VaultTransit<T> {
+  type Handle = usize;
+
+  fn store(&mut self, key: KeyMaterial) -> usize {
+    self.keys.push(Some(Entry::Material(key)));
+    self.keys.len() - 1
+  }
+
+  fn get(&self, handle: usize) -> Option<&KeyMaterial> {
+    match self.keys.get(handle)? {
+      Some(Entry::Material(key)) => Some(key),
+      _ => None,
+    }
+  }
+
+  fn delete(&mut self, handle: usize) -> bool {
+    match self.keys.get_mut(handle) {
+      Some(Some(Entry::Material(key))) => key.0.fill(0),
+      Some(Some(Entry::Remote(name))) => {
+        let name = name.clone();
+        if self.delete_remote(&name).is_err() {
+          return false;
+        }
+      }
+      _ => return false,
+    }
+    self.keys[handle] = None;
+    true
+  }
+
+  fn resident_bytes(&self) -> Option<usize> {
+    let bytes = self.keys.iter().flatten().map(|entry| match entry {
+      Entry::Material(key) => key.0.len(),
+      Entry::Remote(_) => 0,
+    });
+    Some(bytes.sum())
+  }
+
+  fn generate(
+    &mut self,
+    algorithm: &KeyGenParams,
+  ) -> Option<Result<GeneratedKey<usize>, WebCryptoError>> {
+    let type_ = match algorithm {
+      KeyGenParams::EcKeyGenParams(params) if params.name == "ECDSA" => {
+        match params.named_curve {
+          NamedCurve::P256 => Ok(("ecdsa-p256", true)),
+          NamedCurve::P384 => Ok(("ecdsa-p384", true)),
+          NamedCurve::P521 => Ok(("ecdsa-p521", true)),
+          _ => Err(WebCryptoError::NotSupportedError),
+        }
+      }
+      KeyGenParams::RsaHashedKeyGenParams(params)
+        if params.public_exponent == PublicExponent::F4 =>
+      {
+        match params.modulus_length {
+          2048 => Ok(("rsa-2048", true)),
+          3072 => Ok(("rsa-3072", true)),
+          4096 => Ok(("rsa-4096", true)),
+          _ => Err(WebCryptoError::NotSupportedError),
+        }
+      }
+      KeyGenParams::AesKeyGenParams(params) if params.name == "AES-GCM" => {
+        match params.length {
+          128 => Ok(("aes128-gcm96", false)),
+          256 => Ok(("aes256-gcm96", false)),
+          _ => Err(WebCryptoError::NotSupportedError),
+        }
+      }
+      KeyGenParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "ChaCha20-Poly1305",
+      }) => Ok(("chacha20-poly1305", false)),
+      // Other secret keys are kept in memory.
+      _ => match algorithm.name().parse::<AlgorithmName>() {
+        Ok(name) if permitted_usages(name, KeyType::Private).is_empty() => {
+          return None;
+        }
+        _ => Err(WebCryptoError::NotSupportedError),
+      },
+    };
+
+    Some(type_.and_then(|(type_, pair)| {
+      let (name, public_key) = self.create(type_, pair)?;
+      self.keys.push(Some(Entry::Remote(name)));
+      Ok(GeneratedKey {
+        handle: self.keys.len() - 1,
+        public_key,
+      })
+    }))
+  }
+
+  fn perform(
+    &mut self,
+    handle: usize,
+    algorithm: &Algorithm,
+    operation: KeyOperation<'_>,
+  ) -> Option<Result<Vec<u8>, WebCryptoError>> {
+    let name = self.key_name(handle)?.to_string();
+
+    Some(match operation {
+      KeyOperation::Sign {
+        params,
+        hash,
+        digest,
+      } => self.sign(&name, params, hash, digest),
+      // Vault draws the nonce: an `iv` given here would not be used.
+      KeyOperation::Encrypt { params, data } => match params {
+        EncryptParams::AesGcmParams(params)
+          if params.iv.is_empty()
+            && matches!(params.tag_length, None | Some(128))
+            && !params.key_commitment =>
+        {
+          self.encrypt(&name, &params.additional_data, data)
+        }
+        EncryptParams::ChaCha20Poly1305Params(params)
+          if params.iv.is_empty() =>
+        {
+          self.encrypt(&name, &[], data)
+        }
+        _ => Err(WebCryptoError::NotSupportedError),
+      },
+      KeyOperation::Decrypt { params, data } => {
+        let ciphertext =
+          std::str::from_utf8(data).map_err(|_| WebCryptoError::OperationError);
+        match params {
+          EncryptParams::AesGcmParams(params)
+            if params.iv.is_empty()
+              && matches!(params.tag_length, None | Some(128))
+              && !params.key_commitment =>
+          {
+            ciphertext.and_then(|ciphertext| {
+              self.decrypt(&name, &params.additional_data, ciphertext)
+            })
+          }
+          EncryptParams::ChaCha20Poly1305Params(params)
+            if params.iv.is_empty() =>
+          {
+            ciphertext
+              .and_then(|ciphertext| self.decrypt(&name, &[], ciphertext))
+          }
+          // Vault pads with SHA-256 and no label.
+          EncryptParams::RsaOaepParams(params)
+            if params.label.is_empty()
+              && matches!(
+                algorithm,
+                Algorithm::RsaHashedKeyAlgorithm(alg)
+                  if alg.hash.name == "SHA-256"
+              ) =>
+          {
+            let ciphertext = format!("vault:v1:{}", base64::encode(data));
+            self.decrypt(&name, &[], &ciphertext)
+          }
+          _ => Err(WebCryptoError::NotSupportedError),
+        }
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::cell::RefCell;
+  use std::collections::HashMap;
+  use std::rc::Rc;
+
+  use p256::ecdsa::signature::hazmat::PrehashSigner;
+  use p256::pkcs8::EncodePublicKey;
+
+  use super::*;
+  use crate::subtle;
+  use crate::Context;
+
+  enum FakeKey {
+    Ecdsa(p256::ecdsa::SigningKey),
+    /// The plaintexts and associated data encrypted so far.
+    Aes(Vec<(Vec<u8>, Vec<u8>)>),
+  }
+
+  /// A transit engine in software, enough of one for P-256 and AES keys.
+  #[derive(Default)]
+  struct State {
+    keys: HashMap<String, FakeKey>,
+    deletable: Vec<String>,
+    /// Fail to read keys back, eg. after losing the connection.
+    unreadable: bool,
+  }
+
+  #[derive(Clone, Default)]
+  struct FakeVault(Rc<RefCell<State>>);
+
+  fn bad_request() -> io::Error {
+    io::Error::other("400 Bad Request")
+  }
+
+  impl State {
+    fn request(
+      &mut self,
+      method: &str,
+      path: &str,
+      body: Value,
+    ) -> io::Result<Value> {
+      let path: Vec<&str> = path
+        .strip_prefix("/v1/transit/")
+        .unwrap()
+        .split('/')
+        .collect();
+      let decode =
+        |member: &str| base64::decode(body[member].as_str().unwrap_or(""));
+
+      let response = match (method, &path[..]) {
+        ("POST", ["keys", name]) => {
+          let key = match body["type"].as_str().unwrap() {
+            "ecdsa-p256" => {
+              FakeKey::Ecdsa(p256::ecdsa::SigningKey::random(&mut OsRng))
+            }
+            "aes256-gcm96" => FakeKey::Aes(Vec::new()),
+            _ => return Err(bad_request()),
+          };
+          self.keys.insert(name.to_string(), key);
+          Value::Null
+        }
+        ("GET", ["keys", _]) if self.unreadable => return Err(bad_request()),
+        ("GET", ["keys", name]) => match &self.keys[*name] {
+          FakeKey::Ecdsa(key) => {
+            let spki = key.verifying_key().to_public_key_der().unwrap();
+            let pem = format!(
+              "-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n",
+              base64::encode(spki.as_bytes())
+            );
+            json!({ "data": { "keys": { "1": { "public_key": pem } } } })
+          }
+          FakeKey::Aes(_) => json!({ "data": { "keys": { "1": 0 } } }),
+        },
+        ("POST", ["sign", name, "sha2-256"]) => {
+          let key = match &self.keys[*name] {
+            FakeKey::Ecdsa(key) => key,
+            FakeKey::Aes(_) => return Err(bad_request()),
+          };
+          assert_eq!(body["prehashed"], json!(true));
+          assert_eq!(body["marshaling_algorithm"], json!("jws"));
+          let signature: p256::ecdsa::Signature =
+            key.sign_prehash(&decode("input").unwrap()).unwrap();
+          let signature = base64::encode_config(
+            signature.to_bytes(),
+            base64::URL_SAFE_NO_PAD,
+          );
+          json!({ "data": { "signature": format!("vault:v1:{}", signature) } })
+        }
+        ("POST", ["encrypt", name]) => {
+          let ciphertexts = match self.keys.get_mut(*name) {
+            Some(FakeKey::Aes(ciphertexts)) => ciphertexts,
+            _ => return Err(bad_request()),
+          };
+          ciphertexts.push((
+            decode("plaintext").unwrap(),
+            decode("associated_data").unwrap(),
+          ));
+          let ciphertext = format!("vault:v1:{}", ciphertexts.len() - 1);
+          json!({ "data": { "ciphertext": ciphertext } })
+        }
+        ("POST", ["decrypt", name]) => {
+          let ciphertexts = match self.keys.get(*name) {
+            Some(FakeKey::Aes(ciphertexts)) => ciphertexts,
+            _ => return Err(bad_request()),
+          };
+          let index: usize =
+            body["ciphertext"].as_str().unwrap()[9..].parse().unwrap();
+          let (plaintext, associated_data) = &ciphertexts[index];
+          if decode("associated_data").unwrap() != *associated_data {
+            return Err(bad_request());
+          }
+          json!({ "data": { "plaintext": base64::encode(plaintext) } })
+        }
+        ("POST", ["keys", name, "config"]) => {
+          if body["deletion_allowed"] == json!(true) {
+            self.deletable.push(name.to_string());
+          }
+          Value::Null
+        }
+        ("DELETE", ["keys", name]) => {
+          if !self.deletable.iter().any(|deletable| deletable == name) {
+            return Ok(json!({ "errors": ["deletion is not allowed"] }));
+          }
+          self.keys.remove(*name);
+          Value::Null
+        }
+        _ => return Err(bad_request()),
+      };
+      Ok(response)
+    }
+  }
+
+  impl VaultTransport for FakeVault {
+    fn request(
+      &mut self,
+      method: &str,
+      path: &str,
+      body: Option<&str>,
+    ) -> io::Result<String> {
+      let body = body
+        .map(|body| serde_json::from_str(body).unwrap())
+        .unwrap_or(Value::Null);
+      let response = self.0.borrow_mut().request(method, path, body)?;
+      Ok(match response {
+        Value::Null => String::new(),
+        response => response.to_string(),
+      })
+    }
+  }
+
+  fn ecdsa() -> subtle::SignParams {
+    subtle::SignParams::EcdsaParams(subtle::EcdsaParams {
+      name: "ECDSA",
+      hash: subtle::HashAlgorithmIdentifer { name: "SHA-256" },
+      deterministic: false,
+      low_s: false,
+    })
+  }
+
+  /// AES-GCM with the empty `iv` of keys held by Vault.
+  fn aes_gcm(additional_data: &[u8]) -> subtle::EncryptParams {
+    subtle::EncryptParams::AesGcmParams(subtle::AesGcmParams {
+      name: "AES-GCM",
+      iv: vec![],
+      additional_data: additional_data.to_vec(),
+      tag_length: None,
+      key_commitment: false,
+    })
+  }
+
+  #[test]
+  fn test_sign() {
+    let vault = FakeVault::default();
+    let mut ctx = Context::new(OsRng, VaultTransit::new(vault.clone()));
+
+    let key_pair = match ctx
+      .subtle
+      .generate_key(
+        subtle::EcKeyGenParams {
+          name: "ECDSA",
+          named_curve: subtle::NamedCurve::P256,
+        }
+        .into(),
+        false,
+        vec![subtle::KeyUsage::Sign, subtle::KeyUsage::Verify],
+      )
+      .unwrap()
+    {
+      subtle::CryptoKeyOrPair::CryptoKeyPair(key_pair) => key_pair,
+      _ => unreachable!(),
+    };
+    assert_eq!(vault.0.borrow().keys.len(), 1);
+
+    let signature = ctx
+      .subtle
+      .sign(ecdsa(), &key_pair.private_key, b"message")
+      .unwrap();
+    assert!(ctx
+      .subtle
+      .verify(ecdsa(), &key_pair.public_key, &signature, b"message")
+      .unwrap());
+
+    // Deleting a key deletes it from Vault.
+    assert!(ctx.subtle.delete_key(key_pair.private_key.handle()));
+    assert!(vault.0.borrow().keys.is_empty());
+    assert!(ctx
+      .subtle
+      .sign(ecdsa(), &key_pair.private_key, b"message")
+      .is_err());
+
+    // A key whose public key cannot be read is deleted from Vault.
+    vault.0.borrow_mut().unreadable = true;
+    let key_pair = ctx.subtle.generate_key(
+      subtle::EcKeyGenParams {
+        name: "ECDSA",
+        named_curve: subtle::NamedCurve::P256,
+      }
+      .into(),
+      false,
+      vec![subtle::KeyUsage::Sign],
+    );
+    assert!(key_pair.is_err());
+    assert!(vault.0.borrow().keys.is_empty());
+  }
+
+  #[test]
+  fn test_encrypt() {
+    let vault = FakeVault::default();
+    let mut ctx = Context::new(OsRng, VaultTransit::new(vault.clone()));
+
+    let key = match ctx
+      .subtle
+      .generate_key(
+        subtle::AesKeyGenParams {
+          name: "AES-GCM",
+          length: 256,
+        }
+        .into(),
+        false,
+        vec![subtle::KeyUsage::Encrypt, subtle::KeyUsage::Decrypt],
+      )
+      .unwrap()
+    {
+      subtle::CryptoKeyOrPair::CryptoKey(key) => key,
+      _ => unreachable!(),
+    };
+
+    let ciphertext = ctx
+      .subtle
+      .encrypt(aes_gcm(b"header"), &key, b"data")
+      .unwrap();
+    assert!(ciphertext.starts_with(b"vault:v1:"));
+    let plaintext = ctx
+      .subtle
+      .decrypt(aes_gcm(b"header"), &key, &ciphertext)
+      .unwrap();
+    assert_eq!(plaintext, b"data");
+    assert!(ctx
+      .subtle
+      .decrypt(aes_gcm(b"other"), &key, &ciphertext)
+      .is_err());
+
+    // Vault cannot use a nonce of the caller's.
+    let mut explicit = aes_gcm(b"header");
+    if let subtle::EncryptParams::AesGcmParams(params) = &mut explicit {
+      params.iv = vec![0; 12];
+    }
+    assert_eq!(
+      ctx.subtle.encrypt(explicit.clone(), &key, b"data").err(),
+      Some(WebCryptoError::NotSupportedError)
+    );
+    assert_eq!(
+      ctx
+        .subtle
+        .decrypt(explicit.clone(), &key, &ciphertext)
+        .err(),
+      Some(WebCryptoError::NotSupportedError)
+    );
+    assert_eq!(
+      ctx
+        .subtle
+        .encrypt_with_random_iv(explicit, &key, b"data")
+        .err(),
+      Some(WebCryptoError::NotSupportedError)
+    );
+
+    // Extractable keys and other secret keys stay in memory.
+    ctx
+      .subtle
+      .generate_key(
+        subtle::AesKeyGenParams {
+          name: "AES-GCM",
+          length: 256,
+        }
+        .into(),
+        true,
+        vec![subtle::KeyUsage::Encrypt],
+      )
+      .unwrap();
+    ctx
+      .subtle
+      .generate_key(
+        subtle::AesKeyGenParams {
+          name: "AES-CBC",
+          length: 256,
+        }
+        .into(),
+        false,
+        vec![subtle::KeyUsage::Encrypt],
+      )
+      .unwrap();
+    assert_eq!(vault.0.borrow().keys.len(), 1);
+  }
+}
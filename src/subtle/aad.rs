@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+
+/// Builds the additional authenticated data of an AEAD cipher from named
+/// fields, eg. the key id, a format version and the tenant the data
+/// belongs to.
+///
+/// The encoding does not depend on the order the fields are set in, so
+/// services agreeing on the fields produce the same bytes. Fields are
+/// sorted by name and each name and value is prefixed with its length as
+/// a big-endian `u32`. Setting a field twice keeps the last value.
+///
+/// ```
+/// use webcrypto::subtle::AadBuilder;
+///
+/// let aad = AadBuilder::new()
+///   .key_id(b"2024-01")
+///   .version(2)
+///   .tenant("acme")
+///   .build();
+/// // The same fields in another order.
+/// let other = AadBuilder::new()
+///   .tenant("acme")
+///   .version(2)
+///   .key_id(b"2024-01")
+///   .build();
+/// assert_eq!(aad, other);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AadBuilder {
+  fields: BTreeMap<String, Vec<u8>>,
+}
+
+impl AadBuilder {
+  pub fn new() -> Self {
+    AadBuilder::default()
+  }
+
+  /// Bind `value` under `name`.
+  pub fn field(mut self, name: &str, value: &[u8]) -> Self {
+    self.fields.insert(name.to_string(), value.to_vec());
+    self
+  }
+
+  /// Bind the id of the encryption key, as the "kid" field.
+  pub fn key_id(self, key_id: &[u8]) -> Self {
+    self.field("kid", key_id)
+  }
+
+  /// Bind a format or schema version, as the big-endian "version" field.
+  pub fn version(self, version: u32) -> Self {
+    self.field("version", &version.to_be_bytes())
+  }
+
+  /// Bind the tenant owning the data, as the "tenant" field.
+  pub fn tenant(self, tenant: &str) -> Self {
+    self.field("tenant", tenant.as_bytes())
+  }
+
+  pub fn build(&self) -> Vec<u8> {
+    let mut aad = Vec::new();
+    for (name, value) in &self.fields {
+      for bytes in [name.as_bytes(), value] {
+        aad.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        aad.extend_from_slice(bytes);
+      }
+    }
+    aad
+  }
+}
@@ -0,0 +1,57 @@
+use crate::WebCryptoError;
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::BlockDecryptMut;
+use aes::cipher::BlockEncryptMut;
+use aes::cipher::KeyIvInit;
+use aes::Aes128;
+use aes::Aes192;
+use aes::Aes256;
+
+type Aes128CbcEnc = cbc::Encryptor<Aes128>;
+type Aes192CbcEnc = cbc::Encryptor<Aes192>;
+type Aes256CbcEnc = cbc::Encryptor<Aes256>;
+type Aes128CbcDec = cbc::Decryptor<Aes128>;
+type Aes192CbcDec = cbc::Decryptor<Aes192>;
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+
+/// Encrypt `data` under the AES key `key` with PKCS#7 padding.
+pub(crate) fn encrypt(
+  key: &[u8],
+  iv: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let ciphertext = match key.len() {
+    16 => Aes128CbcEnc::new_from_slices(key, iv)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .encrypt_padded_vec_mut::<Pkcs7>(data),
+    24 => Aes192CbcEnc::new_from_slices(key, iv)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .encrypt_padded_vec_mut::<Pkcs7>(data),
+    32 => Aes256CbcEnc::new_from_slices(key, iv)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .encrypt_padded_vec_mut::<Pkcs7>(data),
+    _ => return Err(WebCryptoError::OperationError),
+  };
+  Ok(ciphertext)
+}
+
+/// Decrypt `data` and remove its PKCS#7 padding.
+pub(crate) fn decrypt(
+  key: &[u8],
+  iv: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  match key.len() {
+    16 => Aes128CbcDec::new_from_slices(key, iv)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .decrypt_padded_vec_mut::<Pkcs7>(data),
+    24 => Aes192CbcDec::new_from_slices(key, iv)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .decrypt_padded_vec_mut::<Pkcs7>(data),
+    32 => Aes256CbcDec::new_from_slices(key, iv)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .decrypt_padded_vec_mut::<Pkcs7>(data),
+    _ => return Err(WebCryptoError::OperationError),
+  }
+  .map_err(|_| WebCryptoError::OperationError)
+}
@@ -0,0 +1,78 @@
+use aes::cipher::consts::U16;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::BlockEncrypt;
+use aes::cipher::KeyInit;
+use aes::Aes128;
+use aes::Aes192;
+use aes::Aes256;
+
+use crate::util::constant_time_eq;
+use crate::WebCryptoError;
+
+type Block = GenericArray<u8, U16>;
+
+/// Multiply by x in GF(2^128), deriving the CMAC subkeys.
+fn dbl(block: &Block) -> Block {
+  let value = u128::from_be_bytes((*block).into());
+  let reduction = if value >> 127 == 1 { 0x87 } else { 0 };
+  ((value << 1) ^ reduction).to_be_bytes().into()
+}
+
+fn xor(block: &mut Block, other: &[u8]) {
+  block.iter_mut().zip(other).for_each(|(a, b)| *a ^= b);
+}
+
+fn cmac<C: BlockEncrypt<BlockSize = U16> + KeyInit>(
+  key: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let cipher =
+    C::new_from_slice(key).map_err(|_| WebCryptoError::OperationError)?;
+
+  let mut l = Block::default();
+  cipher.encrypt_block(&mut l);
+  let k1 = dbl(&l);
+  let k2 = dbl(&k1);
+
+  // The last block is complete, or padded with 10*.
+  let split = data.len().saturating_sub(1) / 16 * 16;
+  let (blocks, last) = data.split_at(split);
+
+  let mut x = Block::default();
+  for block in blocks.chunks(16) {
+    xor(&mut x, block);
+    cipher.encrypt_block(&mut x);
+  }
+
+  xor(&mut x, last);
+  if last.len() == 16 {
+    xor(&mut x, &k1);
+  } else {
+    x[last.len()] ^= 0x80;
+    xor(&mut x, &k2);
+  }
+  cipher.encrypt_block(&mut x);
+
+  Ok(x.to_vec())
+}
+
+/// The 128-bit AES-CMAC (NIST SP 800-38B, RFC 4493) of `data` under the
+/// AES key `key`.
+pub(crate) fn sign(key: &[u8], data: &[u8]) -> Result<Vec<u8>, WebCryptoError> {
+  match key.len() {
+    16 => cmac::<Aes128>(key, data),
+    24 => cmac::<Aes192>(key, data),
+    32 => cmac::<Aes256>(key, data),
+    _ => Err(WebCryptoError::OperationError),
+  }
+}
+
+/// Check the tag `signature` of `data` in constant time.
+pub(crate) fn verify(
+  key: &[u8],
+  signature: &[u8],
+  data: &[u8],
+) -> Result<bool, WebCryptoError> {
+  let expected = sign(key, data)?;
+  Ok(constant_time_eq(&expected, signature))
+}
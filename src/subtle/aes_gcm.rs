@@ -0,0 +1,233 @@
+use aes::Aes192;
+use aes_gcm::aead::consts::U12;
+use aes_gcm::aead::consts::U16;
+use aes_gcm::aead::AeadInPlace;
+use aes_gcm::aead::KeyInit;
+use aes_gcm::Aes128Gcm;
+use aes_gcm::Aes256Gcm;
+use aes_gcm::AesGcm;
+use aes_gcm::Nonce;
+use aes_gcm::Tag;
+
+use crate::util::constant_time_eq;
+use crate::WebCryptoError;
+
+type Aes192Gcm = AesGcm<Aes192, U12>;
+
+/// The longest plaintext of a single invocation, 2^39 - 256 bits
+/// (NIST SP 800-38D).
+const MAX_PLAINTEXT_LENGTH: u64 = (1 << 36) - 32;
+
+/// The tag lengths in bits accepted by WebCrypto.
+const TAG_LENGTHS: &[usize] = &[32, 64, 96, 104, 112, 120, 128];
+
+/// The BLAKE3 key derivation context of key-committing AES-GCM.
+const COMMITMENT_CONTEXT: &str = "webcrypto 2024 AES-GCM key commitment";
+
+const COMMITMENT_LENGTH: usize = 32;
+
+/// The tag length in bytes, 128 bits unless given.
+fn tag_bytes(tag_length: Option<usize>) -> Result<usize, WebCryptoError> {
+  match tag_length {
+    None => Ok(16),
+    Some(tag_length) if TAG_LENGTHS.contains(&tag_length) => Ok(tag_length / 8),
+    Some(_) => Err(WebCryptoError::OperationError),
+  }
+}
+
+fn seal<C: KeyInit + AeadInPlace<NonceSize = U12, TagSize = U16>>(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  data: &mut [u8],
+) -> Result<Tag, WebCryptoError> {
+  C::new_from_slice(key)
+    .map_err(|_| WebCryptoError::OperationError)?
+    .encrypt_in_place_detached(Nonce::from_slice(iv), additional_data, data)
+    .map_err(|_| WebCryptoError::OperationError)
+}
+
+fn open<C: KeyInit + AeadInPlace<NonceSize = U12, TagSize = U16>>(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  data: &mut [u8],
+  tag: &[u8],
+) -> Result<(), WebCryptoError> {
+  C::new_from_slice(key)
+    .map_err(|_| WebCryptoError::OperationError)?
+    .decrypt_in_place_detached(
+      Nonce::from_slice(iv),
+      additional_data,
+      data,
+      Tag::from_slice(tag),
+    )
+    .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// Encrypt `data` in place, returning the full 128-bit tag.
+fn seal_in_place(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  data: &mut [u8],
+) -> Result<Tag, WebCryptoError> {
+  match key.len() {
+    16 => seal::<Aes128Gcm>(key, iv, additional_data, data),
+    24 => seal::<Aes192Gcm>(key, iv, additional_data, data),
+    32 => seal::<Aes256Gcm>(key, iv, additional_data, data),
+    _ => Err(WebCryptoError::OperationError),
+  }
+}
+
+/// Encrypt `buffer` in place under the AES key `key`, appending the
+/// authentication tag, truncated to `tag_length` bits.
+pub(crate) fn encrypt_in_place(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  tag_length: Option<usize>,
+  buffer: &mut Vec<u8>,
+) -> Result<(), WebCryptoError> {
+  let tag_length = tag_bytes(tag_length)?;
+  // Only 96-bit IVs are supported for now.
+  if iv.len() != 12 || buffer.len() as u64 > MAX_PLAINTEXT_LENGTH {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  let tag = seal_in_place(key, iv, additional_data, buffer)?;
+  buffer.extend_from_slice(&tag[..tag_length]);
+  Ok(())
+}
+
+/// Encrypt `data` under the AES key `key`, returning the ciphertext
+/// with the authentication tag, truncated to `tag_length` bits, appended.
+pub(crate) fn encrypt(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  tag_length: Option<usize>,
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let mut ciphertext = data.to_vec();
+  encrypt_in_place(key, iv, additional_data, tag_length, &mut ciphertext)?;
+  Ok(ciphertext)
+}
+
+/// Derive a commitment to `key` and `iv`, and the AES-GCM key that
+/// encrypts under them. BLAKE3 is collision resistant, so no other key
+/// has the same commitment.
+fn commit(key: &[u8], iv: &[u8]) -> (blake3::Hash, Vec<u8>) {
+  let mut hasher = blake3::Hasher::new_derive_key(COMMITMENT_CONTEXT);
+  hasher.update(key);
+  hasher.update(iv);
+
+  let mut output = vec![0; COMMITMENT_LENGTH + key.len()];
+  hasher.finalize_xof().fill(&mut output);
+  let subkey = output.split_off(COMMITMENT_LENGTH);
+  let commitment: [u8; COMMITMENT_LENGTH] = output.try_into().unwrap();
+
+  (commitment.into(), subkey)
+}
+
+/// Key-committing AES-GCM: encrypt `buffer` in place under a key derived
+/// from `key` and `iv`, prefixing the ciphertext with a commitment to
+/// both. Unlike plain GCM, a ciphertext cannot be crafted to decrypt
+/// under several keys.
+pub(crate) fn encrypt_committing_in_place(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  tag_length: Option<usize>,
+  buffer: &mut Vec<u8>,
+) -> Result<(), WebCryptoError> {
+  let (commitment, subkey) = commit(key, iv);
+
+  encrypt_in_place(&subkey, iv, additional_data, tag_length, buffer)?;
+  buffer.splice(..0, commitment.as_bytes().iter().copied());
+  Ok(())
+}
+
+/// Check the commitment of `buffer`, then decrypt the rest of it in
+/// place.
+pub(crate) fn decrypt_committing_in_place(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  tag_length: Option<usize>,
+  buffer: &mut Vec<u8>,
+) -> Result<(), WebCryptoError> {
+  if buffer.len() < COMMITMENT_LENGTH {
+    return Err(WebCryptoError::OperationError);
+  }
+  let expected: [u8; COMMITMENT_LENGTH] =
+    buffer[..COMMITMENT_LENGTH].try_into().unwrap();
+
+  let (commitment, subkey) = commit(key, iv);
+  // Constant-time comparison.
+  if commitment != blake3::Hash::from_bytes(expected) {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  buffer.drain(..COMMITMENT_LENGTH);
+  decrypt_in_place(&subkey, iv, additional_data, tag_length, buffer)
+}
+
+/// Decrypt and authenticate `buffer` (ciphertext followed by the tag) in
+/// place, leaving the plaintext.
+pub(crate) fn decrypt_in_place(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  tag_length: Option<usize>,
+  buffer: &mut Vec<u8>,
+) -> Result<(), WebCryptoError> {
+  let tag_length = tag_bytes(tag_length)?;
+  if iv.len() != 12
+    || buffer.len() < tag_length
+    || (buffer.len() - tag_length) as u64 > MAX_PLAINTEXT_LENGTH
+  {
+    return Err(WebCryptoError::OperationError);
+  }
+  let split = buffer.len() - tag_length;
+  let mut tag = [0; 16];
+  tag[..tag_length].copy_from_slice(&buffer[split..]);
+  let tag = &tag[..tag_length];
+  buffer.truncate(split);
+
+  if tag_length == 16 {
+    return match key.len() {
+      16 => open::<Aes128Gcm>(key, iv, additional_data, buffer, tag),
+      24 => open::<Aes192Gcm>(key, iv, additional_data, buffer, tag),
+      32 => open::<Aes256Gcm>(key, iv, additional_data, buffer, tag),
+      _ => Err(WebCryptoError::OperationError),
+    };
+  }
+
+  // A shortened tag is the prefix of the full one. The keystream is its
+  // own inverse, so encrypting the ciphertext recovers the plaintext, and
+  // encrypting that again yields the full tag of the ciphertext.
+  seal_in_place(key, iv, additional_data, buffer)?;
+  let mut check = buffer.clone();
+  let expected = seal_in_place(key, iv, additional_data, &mut check)?;
+  if !constant_time_eq(&expected[..tag_length], tag) {
+    // Do not release unauthenticated plaintext.
+    buffer.fill(0);
+    return Err(WebCryptoError::OperationError);
+  }
+
+  Ok(())
+}
+
+/// Decrypt and authenticate `data` (ciphertext followed by the tag).
+pub(crate) fn decrypt(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  tag_length: Option<usize>,
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let mut plaintext = data.to_vec();
+  decrypt_in_place(key, iv, additional_data, tag_length, &mut plaintext)?;
+  Ok(plaintext)
+}
@@ -0,0 +1,103 @@
+use crate::WebCryptoError;
+use aes_kw::KekAes128;
+use aes_kw::KekAes192;
+use aes_kw::KekAes256;
+
+/// Wrap `data` under the key encryption key `kek` (RFC 3394).
+///
+/// The size of `kek` selects between AES-128, AES-192 and AES-256.
+pub(crate) fn wrap(kek: &[u8], data: &[u8]) -> Result<Vec<u8>, WebCryptoError> {
+  // RFC 3394 operates on 64-bit blocks and needs at least two of them.
+  if !data.len().is_multiple_of(8) || data.len() < 16 {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  match kek.len() {
+    16 => KekAes128::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .wrap_vec(data),
+    24 => KekAes192::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .wrap_vec(data),
+    32 => KekAes256::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .wrap_vec(data),
+    _ => return Err(WebCryptoError::OperationError),
+  }
+  .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// Unwrap `data` previously wrapped under `kek` (RFC 3394).
+///
+/// Fails if the integrity check of the unwrapped key does not pass.
+pub(crate) fn unwrap(
+  kek: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  if !data.len().is_multiple_of(8) || data.len() < 24 {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  match kek.len() {
+    16 => KekAes128::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .unwrap_vec(data),
+    24 => KekAes192::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .unwrap_vec(data),
+    32 => KekAes256::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .unwrap_vec(data),
+    _ => return Err(WebCryptoError::OperationError),
+  }
+  .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// Wrap `data` of any length under `kek` with padding (RFC 5649).
+pub(crate) fn wrap_with_padding(
+  kek: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  if data.is_empty() || data.len() > u32::MAX as usize {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  match kek.len() {
+    16 => KekAes128::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .wrap_with_padding_vec(data),
+    24 => KekAes192::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .wrap_with_padding_vec(data),
+    32 => KekAes256::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .wrap_with_padding_vec(data),
+    _ => return Err(WebCryptoError::OperationError),
+  }
+  .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// Unwrap `data` previously wrapped under `kek` with padding (RFC 5649),
+/// removing the padding.
+pub(crate) fn unwrap_with_padding(
+  kek: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  if !data.len().is_multiple_of(8) || data.len() < 16 {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  match kek.len() {
+    16 => KekAes128::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .unwrap_with_padding_vec(data),
+    24 => KekAes192::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .unwrap_with_padding_vec(data),
+    32 => KekAes256::try_from(kek)
+      .map_err(|_| WebCryptoError::OperationError)?
+      .unwrap_with_padding_vec(data),
+    _ => return Err(WebCryptoError::OperationError),
+  }
+  .map_err(|_| WebCryptoError::OperationError)
+}
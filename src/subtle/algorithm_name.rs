@@ -0,0 +1,102 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::WebCryptoError;
+
+macro_rules! algorithm_names {
+  ($($variant:ident => $name:literal,)*) => {
+    /// A registered algorithm name, for building parameters from names
+    /// known only at runtime, eg. read from a config file:
+    ///
+    /// ```
+    /// use webcrypto::subtle::AlgorithmName;
+    /// use webcrypto::subtle::AlgorithmIdentifer;
+    ///
+    /// let name: AlgorithmName = "aes-gcm".parse().unwrap();
+    /// assert_eq!(name, AlgorithmName::AesGcm);
+    /// assert_eq!(name.to_string(), "AES-GCM");
+    ///
+    /// let algorithm = AlgorithmIdentifer::from(name);
+    /// assert_eq!(algorithm.name, "AES-GCM");
+    /// ```
+    #[non_exhaustive]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub enum AlgorithmName {
+      $($variant,)*
+    }
+
+    impl AlgorithmName {
+      /// Every registered name.
+      pub const ALL: &'static [AlgorithmName] = &[$(AlgorithmName::$variant,)*];
+
+      /// The registered form of the name, eg. "RSA-PSS", as the `name`
+      /// of the parameter structs.
+      pub const fn as_str(&self) -> &'static str {
+        match self {
+          $(AlgorithmName::$variant => $name,)*
+        }
+      }
+    }
+  };
+}
+
+algorithm_names! {
+  RsassaPkcs1V1_5 => "RSASSA-PKCS1-v1_5",
+  RsaPss => "RSA-PSS",
+  RsaOaep => "RSA-OAEP",
+  RsaKem => "RSA-KEM",
+  XWing => "X-Wing",
+  Ecdsa => "ECDSA",
+  Ecdh => "ECDH",
+  Ed25519 => "Ed25519",
+  Ed448 => "Ed448",
+  X448 => "X448",
+  Dh => "DH",
+  AesCtr => "AES-CTR",
+  AesCbc => "AES-CBC",
+  AesGcm => "AES-GCM",
+  AesKw => "AES-KW",
+  AesKwp => "AES-KWP",
+  AesCmac => "AES-CMAC",
+  AesFf1 => "AES-FF1",
+  ChaCha20Poly1305 => "ChaCha20-Poly1305",
+  XChaCha20Poly1305 => "XChaCha20-Poly1305",
+  Hmac => "HMAC",
+  Sha1 => "SHA-1",
+  Sha256 => "SHA-256",
+  Sha384 => "SHA-384",
+  Sha512 => "SHA-512",
+  Blake3 => "BLAKE3",
+  Hkdf => "HKDF",
+  Pbkdf2 => "PBKDF2",
+  ConcatKdf => "ConcatKDF",
+  X963Kdf => "X963KDF",
+  Argon2id => "Argon2id",
+  Scrypt => "scrypt",
+}
+
+/// Names match case-insensitively, like in the Web Crypto API. Unknown
+/// names fail with `NotSupportedError`.
+impl FromStr for AlgorithmName {
+  type Err = WebCryptoError;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    AlgorithmName::ALL
+      .iter()
+      .find(|n| n.as_str().eq_ignore_ascii_case(name))
+      .copied()
+      .ok_or(WebCryptoError::NotSupportedError)
+  }
+}
+
+impl fmt::Display for AlgorithmName {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl From<AlgorithmName> for &'static str {
+  fn from(name: AlgorithmName) -> Self {
+    name.as_str()
+  }
+}
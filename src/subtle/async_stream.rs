@@ -0,0 +1,452 @@
+// Async adapters over the chunks of `encrypt_stream` and
+// `decrypt_stream`, eg. for proxies decrypting large bodies. Readers only
+// read ciphertext once the plaintext before it was read, and writers only
+// take plaintext once the ciphertext before it was written, so either
+// holds a chunk and a bit in memory however slow the other side is.
+
+use std::io;
+use std::pin::Pin;
+use std::task::ready;
+use std::task::Context;
+use std::task::Poll;
+
+use rand::CryptoRng;
+use rand::RngCore;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+
+use super::stream;
+use super::CryptoKey;
+use super::EncryptParams;
+use super::StreamNonces;
+use super::SubtleCrypto;
+use crate::storage::KeyStorage;
+use crate::WebCryptoError;
+
+/// `error` for the reader or writer of a stream.
+fn io_error(error: WebCryptoError) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// The `WebCryptoError` of an `io::Error` raised by a stream,
+/// `OperationError` for errors of the underlying reader or writer.
+pub(crate) fn crypto_error(error: io::Error) -> WebCryptoError {
+  error
+    .get_ref()
+    .and_then(|error| error.downcast_ref::<WebCryptoError>())
+    .copied()
+    .unwrap_or(WebCryptoError::OperationError)
+}
+
+/// The decryption of a stream read in chunks. Keeps no reference to the
+/// context, so one context can decrypt and encrypt at once.
+pub(crate) struct Decryption<'a, H> {
+  algorithm: EncryptParams,
+  key: &'a CryptoKey<H>,
+  /// The length of the nonce prefix starting the stream.
+  prefix_length: usize,
+  /// The length of an encrypted chunk.
+  chunk_length: usize,
+  /// Set once the prefix was read.
+  nonces: Option<StreamNonces>,
+  /// Ciphertext read and not decrypted yet, up to a chunk and the start
+  /// of the next: only a full chunk followed by more is not the last.
+  input: Vec<u8>,
+  filled: usize,
+  eof: bool,
+  /// Set once a chunk fails, ending the stream.
+  error: Option<WebCryptoError>,
+}
+
+impl<'a, H> Decryption<'a, H> {
+  pub(crate) fn new(
+    algorithm: EncryptParams,
+    key: &'a CryptoKey<H>,
+    chunk_length: usize,
+  ) -> Result<Self, WebCryptoError> {
+    if key.consume_once {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    // NotSupportedError when the algorithm takes no IV.
+    let (_, length) = algorithm
+      .clone()
+      .iv_mut()
+      .ok_or(WebCryptoError::NotSupportedError)?;
+    if chunk_length == 0 {
+      return Err(WebCryptoError::OperationError);
+    }
+    let prefix_length = length - 5;
+    let chunk_length = chunk_length + stream::overhead(&algorithm)?;
+
+    Ok(Decryption {
+      algorithm,
+      key,
+      prefix_length,
+      chunk_length,
+      nonces: None,
+      input: vec![0; prefix_length.max(2 * chunk_length)],
+      filled: 0,
+      eof: false,
+      error: None,
+    })
+  }
+
+  /// Drop the first `length` bytes of the input.
+  fn consume(&mut self, length: usize) {
+    self.input.copy_within(length..self.filled, 0);
+    self.filled -= length;
+  }
+
+  /// The plaintext of the next chunk, `None` once the last chunk was
+  /// decrypted.
+  pub(crate) fn poll_chunk<R, S, Rd>(
+    &mut self,
+    cx: &mut Context<'_>,
+    subtle: &mut SubtleCrypto<R, S>,
+    mut reader: Pin<&mut Rd>,
+  ) -> Poll<io::Result<Option<Vec<u8>>>>
+  where
+    R: RngCore + CryptoRng,
+    S: KeyStorage<Handle = H>,
+    Rd: AsyncRead,
+  {
+    loop {
+      if let Some(error) = self.error {
+        return Poll::Ready(Err(io_error(error)));
+      }
+
+      if self.nonces.is_none() {
+        if self.filled >= self.prefix_length {
+          let nonces = StreamNonces::new(&self.input[..self.prefix_length]);
+          self.nonces = Some(nonces);
+          self.consume(self.prefix_length);
+          continue;
+        }
+        if self.eof {
+          self.error = Some(WebCryptoError::OperationError);
+          continue;
+        }
+      } else if self.filled > self.chunk_length || self.eof {
+        let nonces = self.nonces.as_mut().unwrap();
+        if nonces.is_finished() {
+          return Poll::Ready(Ok(None));
+        }
+
+        // Only a full chunk can be followed by another.
+        let last = self.filled <= self.chunk_length;
+        let length = self.filled.min(self.chunk_length);
+        let result = subtle.decrypt_chunk(
+          self.algorithm.clone(),
+          self.key,
+          nonces,
+          &self.input[..length],
+          last,
+        );
+        self.consume(length);
+        match result {
+          Ok(plaintext) => return Poll::Ready(Ok(Some(plaintext))),
+          Err(error) => {
+            self.error = Some(error);
+            continue;
+          }
+        }
+      }
+
+      let mut buffer = ReadBuf::new(&mut self.input[self.filled..]);
+      ready!(reader.as_mut().poll_read(cx, &mut buffer))?;
+      match buffer.filled().len() {
+        0 => self.eof = true,
+        read => self.filled += read,
+      }
+    }
+  }
+}
+
+/// The encryption of a stream written in chunks. Keeps no reference to
+/// the context, so one context can decrypt and encrypt at once.
+pub(crate) struct Encryption<'a, H> {
+  algorithm: EncryptParams,
+  key: &'a CryptoKey<H>,
+  chunk_length: usize,
+  nonces: StreamNonces,
+  /// Plaintext taken and not encrypted yet. A full chunk is only
+  /// encrypted once more plaintext follows or the stream is shut down,
+  /// as the last chunk is marked.
+  input: Vec<u8>,
+  /// Ciphertext not written yet, the nonce prefix at first.
+  output: Vec<u8>,
+  written: usize,
+  /// Set once a chunk fails, ending the stream.
+  error: Option<WebCryptoError>,
+}
+
+impl<'a, H> Encryption<'a, H> {
+  pub(crate) fn new(
+    algorithm: EncryptParams,
+    key: &'a CryptoKey<H>,
+    chunk_length: usize,
+    nonces: StreamNonces,
+  ) -> Result<Self, WebCryptoError> {
+    if key.consume_once {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    if chunk_length == 0 {
+      return Err(WebCryptoError::OperationError);
+    }
+
+    Ok(Encryption {
+      algorithm,
+      key,
+      chunk_length,
+      output: nonces.prefix().to_vec(),
+      nonces,
+      input: Vec::with_capacity(chunk_length),
+      written: 0,
+      error: None,
+    })
+  }
+
+  /// Encrypt the input into the output, which has to be written already.
+  fn encrypt<R, S>(
+    &mut self,
+    subtle: &mut SubtleCrypto<R, S>,
+    last: bool,
+  ) -> io::Result<()>
+  where
+    R: RngCore + CryptoRng,
+    S: KeyStorage<Handle = H>,
+  {
+    if let Some(error) = self.error {
+      return Err(io_error(error));
+    }
+
+    let result = subtle.encrypt_chunk(
+      self.algorithm.clone(),
+      self.key,
+      &mut self.nonces,
+      &self.input,
+      last,
+    );
+    match result {
+      Ok(ciphertext) => {
+        self.input.clear();
+        self.output = ciphertext;
+        self.written = 0;
+        Ok(())
+      }
+      Err(error) => {
+        self.error = Some(error);
+        Err(io_error(error))
+      }
+    }
+  }
+
+  /// Write the output to `writer`.
+  fn poll_output<W: AsyncWrite>(
+    &mut self,
+    cx: &mut Context<'_>,
+    mut writer: Pin<&mut W>,
+  ) -> Poll<io::Result<()>> {
+    while self.written < self.output.len() {
+      let written =
+        ready!(writer.as_mut().poll_write(cx, &self.output[self.written..]))?;
+      if written == 0 {
+        return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+      }
+      self.written += written;
+    }
+    Poll::Ready(Ok(()))
+  }
+
+  /// Take some of `data`, once the ciphertext before it was written.
+  pub(crate) fn poll_write<R, S, W>(
+    &mut self,
+    cx: &mut Context<'_>,
+    subtle: &mut SubtleCrypto<R, S>,
+    mut writer: Pin<&mut W>,
+    data: &[u8],
+  ) -> Poll<io::Result<usize>>
+  where
+    R: RngCore + CryptoRng,
+    S: KeyStorage<Handle = H>,
+    W: AsyncWrite,
+  {
+    if let Some(error) = self.error {
+      return Poll::Ready(Err(io_error(error)));
+    }
+    ready!(self.poll_output(cx, writer.as_mut()))?;
+    if data.is_empty() {
+      return Poll::Ready(Ok(0));
+    }
+    if self.input.len() == self.chunk_length {
+      self.encrypt(subtle, false)?;
+    }
+
+    let taken = data.len().min(self.chunk_length - self.input.len());
+    self.input.extend_from_slice(&data[..taken]);
+    Poll::Ready(Ok(taken))
+  }
+
+  /// Write the output and flush `writer`. The chunk being taken is not
+  /// written until it is full and followed by more, or the stream is
+  /// shut down.
+  pub(crate) fn poll_flush<W: AsyncWrite>(
+    &mut self,
+    cx: &mut Context<'_>,
+    mut writer: Pin<&mut W>,
+  ) -> Poll<io::Result<()>> {
+    ready!(self.poll_output(cx, writer.as_mut()))?;
+    writer.poll_flush(cx)
+  }
+
+  /// Encrypt and write the last chunk, then shut `writer` down.
+  pub(crate) fn poll_shutdown<R, S, W>(
+    &mut self,
+    cx: &mut Context<'_>,
+    subtle: &mut SubtleCrypto<R, S>,
+    mut writer: Pin<&mut W>,
+  ) -> Poll<io::Result<()>>
+  where
+    R: RngCore + CryptoRng,
+    S: KeyStorage<Handle = H>,
+    W: AsyncWrite,
+  {
+    ready!(self.poll_output(cx, writer.as_mut()))?;
+    if !self.nonces.is_finished() {
+      self.encrypt(subtle, true)?;
+      ready!(self.poll_output(cx, writer.as_mut()))?;
+    }
+    writer.poll_shutdown(cx)
+  }
+}
+
+/// Reads the plaintext of a stream encrypted by `encrypt_stream` or an
+/// `EncryptWriter`. Created by `SubtleCrypto::decrypt_reader`.
+///
+/// Chunks can be read as soon as they are authenticated, so on error
+/// everything read so far has to be discarded.
+pub struct DecryptReader<'a, R: RngCore + CryptoRng, S: KeyStorage, Rd> {
+  subtle: &'a mut SubtleCrypto<R, S>,
+  decryption: Decryption<'a, S::Handle>,
+  reader: Rd,
+  /// The plaintext of the last chunk, read up to `position`.
+  plaintext: Vec<u8>,
+  position: usize,
+}
+
+impl<'a, R: RngCore + CryptoRng, S: KeyStorage, Rd>
+  DecryptReader<'a, R, S, Rd>
+{
+  pub(crate) fn new(
+    subtle: &'a mut SubtleCrypto<R, S>,
+    decryption: Decryption<'a, S::Handle>,
+    reader: Rd,
+  ) -> Self {
+    DecryptReader {
+      subtle,
+      decryption,
+      reader,
+      plaintext: Vec::new(),
+      position: 0,
+    }
+  }
+
+  pub fn into_inner(self) -> Rd {
+    self.reader
+  }
+}
+
+impl<R, S, Rd> AsyncRead for DecryptReader<'_, R, S, Rd>
+where
+  R: RngCore + CryptoRng,
+  S: KeyStorage,
+  Rd: AsyncRead + Unpin,
+{
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buffer: &mut ReadBuf<'_>,
+  ) -> Poll<io::Result<()>> {
+    let this = self.get_mut();
+    while this.position == this.plaintext.len() {
+      let reader = Pin::new(&mut this.reader);
+      match ready!(this.decryption.poll_chunk(cx, this.subtle, reader))? {
+        Some(plaintext) => {
+          this.plaintext = plaintext;
+          this.position = 0;
+        }
+        None => return Poll::Ready(Ok(())),
+      }
+    }
+
+    let length = buffer.remaining().min(this.plaintext.len() - this.position);
+    buffer.put_slice(&this.plaintext[this.position..][..length]);
+    this.position += length;
+    Poll::Ready(Ok(()))
+  }
+}
+
+/// Encrypts the plaintext written to it into a stream `decrypt_stream`
+/// and `DecryptReader` can read. Created by
+/// `SubtleCrypto::encrypt_writer`.
+///
+/// The stream is only complete once shut down, which writes the last
+/// chunk.
+pub struct EncryptWriter<'a, R: RngCore + CryptoRng, S: KeyStorage, W> {
+  subtle: &'a mut SubtleCrypto<R, S>,
+  encryption: Encryption<'a, S::Handle>,
+  writer: W,
+}
+
+impl<'a, R: RngCore + CryptoRng, S: KeyStorage, W> EncryptWriter<'a, R, S, W> {
+  pub(crate) fn new(
+    subtle: &'a mut SubtleCrypto<R, S>,
+    encryption: Encryption<'a, S::Handle>,
+    writer: W,
+  ) -> Self {
+    EncryptWriter {
+      subtle,
+      encryption,
+      writer,
+    }
+  }
+
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+}
+
+impl<R, S, W> AsyncWrite for EncryptWriter<'_, R, S, W>
+where
+  R: RngCore + CryptoRng,
+  S: KeyStorage,
+  W: AsyncWrite + Unpin,
+{
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    data: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    let writer = Pin::new(&mut this.writer);
+    this.encryption.poll_write(cx, this.subtle, writer, data)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    let this = self.get_mut();
+    this.encryption.poll_flush(cx, Pin::new(&mut this.writer))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    let this = self.get_mut();
+    let writer = Pin::new(&mut this.writer);
+    this.encryption.poll_shutdown(cx, this.subtle, writer)
+  }
+}
@@ -0,0 +1,65 @@
+// Blind indexes: truncated MACs of normalized values, stored next to an
+// encrypted column so it can be searched for equality without
+// decrypting it.
+
+use crate::WebCryptoError;
+
+/// The MAC input prefix of blind indexes, separating them from other MACs
+/// under the same key.
+const CONTEXT: &[u8] = b"webcrypto 2024 blind index\0";
+
+/// How values are normalized before indexing, so that values meant to
+/// match get the same index.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Normalization {
+  /// Values match exactly.
+  Exact,
+  /// Leading and trailing whitespace is ignored.
+  Trim,
+  /// Whitespace around values and between words, and letter case, are
+  /// ignored. Unicode normalization forms are not applied.
+  CaseInsensitive,
+}
+
+#[derive(Copy, Clone)]
+pub struct BlindIndexParams<'a> {
+  /// The name of the indexed column. Equal values in different columns
+  /// get unrelated indexes.
+  pub column: &'a str,
+  /// The length in bits of the index, a multiple of 8 from 8 to 256.
+  pub length: usize,
+  pub normalization: Normalization,
+}
+
+fn normalize(value: &str, normalization: Normalization) -> String {
+  match normalization {
+    Normalization::Exact => value.to_string(),
+    Normalization::Trim => value.trim().to_string(),
+    Normalization::CaseInsensitive => value
+      .split_whitespace()
+      .map(str::to_lowercase)
+      .collect::<Vec<_>>()
+      .join(" "),
+  }
+}
+
+/// The data MACed for the index of `value`, and the index length in
+/// bytes.
+pub(crate) fn mac_input(
+  params: &BlindIndexParams,
+  value: &str,
+) -> Result<(Vec<u8>, usize), WebCryptoError> {
+  if params.length == 0
+    || params.length > 256
+    || !params.length.is_multiple_of(8)
+  {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  let column = params.column.as_bytes();
+  let mut input = CONTEXT.to_vec();
+  input.extend_from_slice(&(column.len() as u32).to_be_bytes());
+  input.extend_from_slice(column);
+  input.extend_from_slice(normalize(value, params.normalization).as_bytes());
+  Ok((input, params.length / 8))
+}
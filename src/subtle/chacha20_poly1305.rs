@@ -0,0 +1,82 @@
+use crate::WebCryptoError;
+use chacha20poly1305::aead::AeadInPlace;
+use chacha20poly1305::aead::KeyInit;
+use chacha20poly1305::ChaCha20Poly1305;
+use chacha20poly1305::Nonce;
+use chacha20poly1305::XChaCha20Poly1305;
+use chacha20poly1305::XNonce;
+
+/// Encrypt `buffer` in place under the 256-bit key `key`, appending the
+/// 128-bit authentication tag over the ciphertext and `additional_data`.
+pub(crate) fn encrypt_in_place(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  buffer: &mut Vec<u8>,
+) -> Result<(), WebCryptoError> {
+  if iv.len() != 12 {
+    return Err(WebCryptoError::OperationError);
+  }
+  let nonce = Nonce::from_slice(iv);
+
+  ChaCha20Poly1305::new_from_slice(key)
+    .map_err(|_| WebCryptoError::OperationError)?
+    .encrypt_in_place(nonce, additional_data, buffer)
+    .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// Decrypt and authenticate `buffer` (ciphertext followed by the tag) in
+/// place, leaving the plaintext.
+pub(crate) fn decrypt_in_place(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  buffer: &mut Vec<u8>,
+) -> Result<(), WebCryptoError> {
+  if iv.len() != 12 {
+    return Err(WebCryptoError::OperationError);
+  }
+  let nonce = Nonce::from_slice(iv);
+
+  ChaCha20Poly1305::new_from_slice(key)
+    .map_err(|_| WebCryptoError::OperationError)?
+    .decrypt_in_place(nonce, additional_data, buffer)
+    .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// Encrypt `buffer` in place with XChaCha20-Poly1305, which takes a
+/// 192-bit nonce.
+pub(crate) fn xencrypt_in_place(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  buffer: &mut Vec<u8>,
+) -> Result<(), WebCryptoError> {
+  if iv.len() != 24 {
+    return Err(WebCryptoError::OperationError);
+  }
+  let nonce = XNonce::from_slice(iv);
+
+  XChaCha20Poly1305::new_from_slice(key)
+    .map_err(|_| WebCryptoError::OperationError)?
+    .encrypt_in_place(nonce, additional_data, buffer)
+    .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// Decrypt and authenticate `buffer` in place with XChaCha20-Poly1305.
+pub(crate) fn xdecrypt_in_place(
+  key: &[u8],
+  iv: &[u8],
+  additional_data: &[u8],
+  buffer: &mut Vec<u8>,
+) -> Result<(), WebCryptoError> {
+  if iv.len() != 24 {
+    return Err(WebCryptoError::OperationError);
+  }
+  let nonce = XNonce::from_slice(iv);
+
+  XChaCha20Poly1305::new_from_slice(key)
+    .map_err(|_| WebCryptoError::OperationError)?
+    .decrypt_in_place(nonce, additional_data, buffer)
+    .map_err(|_| WebCryptoError::OperationError)
+}
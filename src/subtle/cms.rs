@@ -0,0 +1,491 @@
+use cms::cert::CertificateChoices;
+use cms::cert::IssuerAndSerialNumber;
+use cms::content_info::CmsVersion;
+use cms::content_info::ContentInfo;
+use cms::signed_data::CertificateSet;
+use cms::signed_data::EncapsulatedContentInfo;
+use cms::signed_data::SignedData;
+use cms::signed_data::SignerIdentifier;
+use cms::signed_data::SignerInfo;
+use cms::signed_data::SignerInfos;
+
+mod enveloped;
+
+pub(crate) use enveloped::decrypt as decrypt_enveloped;
+pub(crate) use enveloped::encrypt as encrypt_enveloped;
+pub(crate) use enveloped::x963_kdf;
+pub(crate) use enveloped::RecipientKey;
+
+use der::asn1::ObjectIdentifier;
+use der::asn1::OctetString;
+use der::asn1::SetOfVec;
+use der::Any;
+use der::Decode;
+use der::Encode;
+
+use rsa::pkcs8::FromPublicKey;
+use rsa::PublicKey;
+use rsa::RsaPublicKey;
+
+use x509_cert::attr::Attribute;
+use x509_cert::ext::pkix::SubjectKeyIdentifier;
+use x509_cert::spki::AlgorithmIdentifierOwned;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+
+use super::digest;
+use super::ec;
+use super::Certificate;
+use super::KeyType;
+use super::NamedCurve;
+use crate::WebCryptoError;
+
+const ID_DATA: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+const ID_SIGNED_DATA: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+const ID_CONTENT_TYPE: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.3");
+const ID_MESSAGE_DIGEST: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+const ID_SUBJECT_KEY_IDENTIFIER: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("2.5.29.14");
+
+const RSA_ENCRYPTION: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const ID_EC_PUBLIC_KEY: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+
+const HASHES: [(&str, &str, &str, &str); 4] = [
+  (
+    "SHA-1",
+    "1.3.14.3.2.26",
+    "1.2.840.113549.1.1.5",
+    "1.2.840.10045.4.1",
+  ),
+  (
+    "SHA-256",
+    "2.16.840.1.101.3.4.2.1",
+    "1.2.840.113549.1.1.11",
+    "1.2.840.10045.4.3.2",
+  ),
+  (
+    "SHA-384",
+    "2.16.840.1.101.3.4.2.2",
+    "1.2.840.113549.1.1.12",
+    "1.2.840.10045.4.3.3",
+  ),
+  (
+    "SHA-512",
+    "2.16.840.1.101.3.4.2.3",
+    "1.2.840.113549.1.1.13",
+    "1.2.840.10045.4.3.4",
+  ),
+];
+
+/// The signature scheme of a CMS signer.
+#[derive(Copy, Clone)]
+pub(crate) enum Scheme {
+  RsassaPkcs1v15,
+  Ecdsa(NamedCurve),
+}
+
+fn oid(oid: &str) -> ObjectIdentifier {
+  ObjectIdentifier::new_unwrap(oid)
+}
+
+fn algorithm_identifier(oid: ObjectIdentifier) -> AlgorithmIdentifierOwned {
+  AlgorithmIdentifierOwned {
+    oid,
+    parameters: None,
+  }
+}
+
+fn hash_oids(hash: &str) -> Result<(&str, &str, &str), WebCryptoError> {
+  match HASHES.iter().find(|(name, ..)| *name == hash) {
+    Some((_, digest, rsa, ecdsa)) => Ok((digest, rsa, ecdsa)),
+    None => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+fn hash_name(
+  digest_algorithm: &ObjectIdentifier,
+) -> Result<&'static str, WebCryptoError> {
+  match HASHES
+    .iter()
+    .find(|(_, digest, ..)| oid(digest) == *digest_algorithm)
+  {
+    Some((name, ..)) => Ok(name),
+    None => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The hash of the RSASSA-PKCS1-v1_5 or ECDSA signature algorithm
+/// `signature_algorithm`.
+pub(crate) fn signature_hash(
+  signature_algorithm: &ObjectIdentifier,
+) -> Result<&'static str, WebCryptoError> {
+  match HASHES.iter().find(|(_, _, rsa, ecdsa)| {
+    oid(rsa) == *signature_algorithm || oid(ecdsa) == *signature_algorithm
+  }) {
+    Some((name, ..)) => Ok(name),
+    None => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+fn named_curve(parameters: &Option<Any>) -> Result<NamedCurve, WebCryptoError> {
+  let curve = parameters
+    .as_ref()
+    .ok_or(WebCryptoError::OperationError)?
+    .decode_as::<ObjectIdentifier>()
+    .map_err(|_| WebCryptoError::OperationError)?;
+  match curve.to_string().as_str() {
+    "1.2.840.10045.3.1.7" => Ok(NamedCurve::P256),
+    "1.3.132.0.34" => Ok(NamedCurve::P384),
+    "1.3.132.0.35" => Ok(NamedCurve::P521),
+    #[cfg(feature = "k256")]
+    "1.3.132.0.10" => Ok(NamedCurve::K256),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+fn attribute(
+  oid: ObjectIdentifier,
+  value: Any,
+) -> Result<Attribute, WebCryptoError> {
+  Ok(Attribute {
+    oid,
+    values: SetOfVec::try_from(vec![value])
+      .map_err(|_| WebCryptoError::OperationError)?,
+  })
+}
+
+fn attribute_value(
+  attributes: &SetOfVec<Attribute>,
+  oid: ObjectIdentifier,
+) -> Result<&Any, WebCryptoError> {
+  let attribute = attributes
+    .iter()
+    .find(|attribute| attribute.oid == oid)
+    .ok_or(WebCryptoError::OperationError)?;
+  match attribute.values.as_slice() {
+    [value] => Ok(value),
+    _ => Err(WebCryptoError::DataError),
+  }
+}
+
+pub(crate) fn parse_certificate(
+  certificate: &Certificate,
+) -> Result<x509_cert::Certificate, WebCryptoError> {
+  x509_cert::Certificate::from_der(&certificate.der)
+    .map_err(|_| WebCryptoError::DataError)
+}
+
+fn issuer_and_serial_number(
+  certificate: &x509_cert::Certificate,
+) -> IssuerAndSerialNumber {
+  IssuerAndSerialNumber {
+    issuer: certificate.tbs_certificate.issuer.clone(),
+    serial_number: certificate.tbs_certificate.serial_number.clone(),
+  }
+}
+
+/// The signed attributes covering `content`. Their DER encoding is what
+/// the signer signs.
+pub(crate) fn signed_attributes(
+  hash: &str,
+  content: &[u8],
+) -> Result<SetOfVec<Attribute>, WebCryptoError> {
+  let message_digest = OctetString::new(digest(hash, content)?)
+    .map_err(|_| WebCryptoError::OperationError)?;
+  SetOfVec::try_from(vec![
+    attribute(
+      ID_CONTENT_TYPE,
+      Any::encode_from(&ID_DATA).map_err(|_| WebCryptoError::OperationError)?,
+    )?,
+    attribute(
+      ID_MESSAGE_DIGEST,
+      Any::encode_from(&message_digest)
+        .map_err(|_| WebCryptoError::OperationError)?,
+    )?,
+  ])
+  .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// Encode a SignedData `ContentInfo` with one signer, the subject of the
+/// first of `certificates`.
+pub(crate) fn encode(
+  scheme: Scheme,
+  hash: &str,
+  certificates: &[Certificate],
+  content: Option<&[u8]>,
+  signed_attributes: SetOfVec<Attribute>,
+  signature: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let certificates = certificates
+    .iter()
+    .map(parse_certificate)
+    .collect::<Result<Vec<_>, _>>()?;
+  let signer = match certificates.first() {
+    Some(signer) => signer,
+    None => {
+      return Err(WebCryptoError::DataError);
+    }
+  };
+
+  let (digest_oid, rsa_oid, ecdsa_oid) = hash_oids(hash)?;
+  let signature = match scheme {
+    Scheme::RsassaPkcs1v15 => signature.to_vec(),
+    Scheme::Ecdsa(named_curve) => ec::signature_to_der(named_curve, signature)?,
+  };
+  let signature_algorithm = match scheme {
+    Scheme::RsassaPkcs1v15 => AlgorithmIdentifierOwned {
+      oid: oid(rsa_oid),
+      parameters: Some(Any::null()),
+    },
+    Scheme::Ecdsa(_) => algorithm_identifier(oid(ecdsa_oid)),
+  };
+
+  let signer_info = SignerInfo {
+    version: CmsVersion::V1,
+    sid: SignerIdentifier::IssuerAndSerialNumber(issuer_and_serial_number(
+      signer,
+    )),
+    digest_alg: algorithm_identifier(oid(digest_oid)),
+    signed_attrs: Some(signed_attributes),
+    signature_algorithm,
+    signature: OctetString::new(signature)
+      .map_err(|_| WebCryptoError::OperationError)?,
+    unsigned_attrs: None,
+  };
+
+  let econtent = match content {
+    Some(content) => Some(
+      Any::encode_from(
+        &OctetString::new(content)
+          .map_err(|_| WebCryptoError::OperationError)?,
+      )
+      .map_err(|_| WebCryptoError::OperationError)?,
+    ),
+    None => None,
+  };
+
+  let certificates = certificates
+    .into_iter()
+    .map(CertificateChoices::Certificate)
+    .collect::<Vec<_>>();
+
+  let signed_data = SignedData {
+    version: CmsVersion::V1,
+    digest_algorithms: SetOfVec::try_from(vec![algorithm_identifier(oid(
+      digest_oid,
+    ))])
+    .map_err(|_| WebCryptoError::OperationError)?,
+    encap_content_info: EncapsulatedContentInfo {
+      econtent_type: ID_DATA,
+      econtent,
+    },
+    certificates: Some(CertificateSet(
+      SetOfVec::try_from(certificates)
+        .map_err(|_| WebCryptoError::OperationError)?,
+    )),
+    crls: None,
+    signer_infos: SignerInfos(
+      SetOfVec::try_from(vec![signer_info])
+        .map_err(|_| WebCryptoError::OperationError)?,
+    ),
+  };
+
+  let content_info = ContentInfo {
+    content_type: ID_SIGNED_DATA,
+    content: Any::encode_from(&signed_data)
+      .map_err(|_| WebCryptoError::OperationError)?,
+  };
+  content_info
+    .to_der()
+    .map_err(|_| WebCryptoError::OperationError)
+}
+
+fn find_certificate<'a>(
+  certificates: &'a [x509_cert::Certificate],
+  sid: &SignerIdentifier,
+) -> Option<&'a x509_cert::Certificate> {
+  certificates.iter().find(|certificate| {
+    let tbs = &certificate.tbs_certificate;
+    match sid {
+      SignerIdentifier::IssuerAndSerialNumber(sid) => {
+        tbs.issuer == sid.issuer && tbs.serial_number == sid.serial_number
+      }
+      SignerIdentifier::SubjectKeyIdentifier(sid) => tbs
+        .extensions
+        .iter()
+        .flatten()
+        .filter(|extension| extension.extn_id == ID_SUBJECT_KEY_IDENTIFIER)
+        .any(|extension| {
+          SubjectKeyIdentifier::from_der(extension.extn_value.as_bytes())
+            .map(|ski| ski == *sid)
+            .unwrap_or(false)
+        }),
+    }
+  })
+}
+
+/// Verify `signature` over `data` with the subject public key of
+/// `certificate`.
+pub(crate) fn verify_signature(
+  certificate: &x509_cert::Certificate,
+  hash: &'static str,
+  data: &[u8],
+  signature: &[u8],
+) -> Result<bool, WebCryptoError> {
+  verify_spki_signature(
+    &certificate.tbs_certificate.subject_public_key_info,
+    hash,
+    data,
+    signature,
+  )
+}
+
+/// Verify an RSASSA-PKCS1-v1_5 or DER encoded ECDSA `signature` over
+/// `data` with the public key `spki`.
+pub(crate) fn verify_spki_signature(
+  spki: &SubjectPublicKeyInfoOwned,
+  hash: &'static str,
+  data: &[u8],
+  signature: &[u8],
+) -> Result<bool, WebCryptoError> {
+  let hashed = digest(hash, data)?;
+
+  if spki.algorithm.oid == RSA_ENCRYPTION {
+    let spki = spki.to_der().map_err(|_| WebCryptoError::OperationError)?;
+    let public_key = RsaPublicKey::from_public_key_der(&spki)
+      .map_err(|_| WebCryptoError::OperationError)?;
+    let hash = match hash {
+      "SHA-1" => rsa::hash::Hash::SHA1,
+      "SHA-256" => rsa::hash::Hash::SHA2_256,
+      "SHA-384" => rsa::hash::Hash::SHA2_384,
+      "SHA-512" => rsa::hash::Hash::SHA2_512,
+      _ => {
+        return Err(WebCryptoError::NotSupportedError);
+      }
+    };
+    let padding =
+      rsa::padding::PaddingScheme::PKCS1v15Sign { hash: Some(hash) };
+
+    Ok(public_key.verify(padding, &hashed, signature).is_ok())
+  } else if spki.algorithm.oid == ID_EC_PUBLIC_KEY {
+    let named_curve = named_curve(&spki.algorithm.parameters)?;
+    let public_key = spki.subject_public_key.raw_bytes();
+    let signature = match ec::signature_from_der(named_curve, signature) {
+      Ok(signature) => signature,
+      Err(_) => return Ok(false),
+    };
+
+    ec::verify(
+      named_curve,
+      &KeyType::Public,
+      public_key,
+      &hashed,
+      &signature,
+    )
+  } else {
+    Err(WebCryptoError::NotSupportedError)
+  }
+}
+
+/// Verify every signer of a SignedData `ContentInfo`.
+///
+/// Returns the signers' certificates, or `None` if a signature or
+/// message digest does not match. `content` is required for detached
+/// signatures and ignored otherwise.
+pub(crate) fn verify(
+  data: &[u8],
+  content: Option<&[u8]>,
+) -> Result<Option<Vec<Certificate>>, WebCryptoError> {
+  let content_info =
+    ContentInfo::from_der(data).map_err(|_| WebCryptoError::DataError)?;
+  if content_info.content_type != ID_SIGNED_DATA {
+    return Err(WebCryptoError::DataError);
+  }
+  let signed_data: SignedData = content_info
+    .content
+    .decode_as()
+    .map_err(|_| WebCryptoError::OperationError)?;
+
+  let econtent = match &signed_data.encap_content_info.econtent {
+    Some(econtent) => Some(
+      econtent
+        .decode_as::<OctetString>()
+        .map_err(|_| WebCryptoError::OperationError)?
+        .into_bytes(),
+    ),
+    None => None,
+  };
+  let content = match (&econtent, content) {
+    (Some(econtent), _) => econtent.as_slice(),
+    (None, Some(content)) => content,
+    (None, None) => {
+      return Err(WebCryptoError::DataError);
+    }
+  };
+
+  let certificates = signed_data
+    .certificates
+    .iter()
+    .flat_map(|certificates| certificates.0.iter())
+    .filter_map(|certificate| match certificate {
+      CertificateChoices::Certificate(certificate) => Some(certificate.clone()),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+
+  let mut signers = vec![];
+  for signer_info in signed_data.signer_infos.0.iter() {
+    let certificate = match find_certificate(&certificates, &signer_info.sid) {
+      Some(certificate) => certificate,
+      None => {
+        return Err(WebCryptoError::DataError);
+      }
+    };
+    let hash = hash_name(&signer_info.digest_alg.oid)?;
+
+    let signed = match &signer_info.signed_attrs {
+      Some(attributes) => {
+        let content_type: ObjectIdentifier =
+          attribute_value(attributes, ID_CONTENT_TYPE)?
+            .decode_as()
+            .map_err(|_| WebCryptoError::OperationError)?;
+        let message_digest: OctetString =
+          attribute_value(attributes, ID_MESSAGE_DIGEST)?
+            .decode_as()
+            .map_err(|_| WebCryptoError::OperationError)?;
+        if content_type != signed_data.encap_content_info.econtent_type
+          || message_digest.as_bytes() != digest(hash, content)?
+        {
+          return Ok(None);
+        }
+
+        attributes
+          .to_der()
+          .map_err(|_| WebCryptoError::OperationError)?
+      }
+      None => content.to_vec(),
+    };
+
+    if !verify_signature(
+      certificate,
+      hash,
+      &signed,
+      signer_info.signature.as_bytes(),
+    )? {
+      return Ok(None);
+    }
+
+    signers.push(Certificate::from_der(
+      &certificate
+        .to_der()
+        .map_err(|_| WebCryptoError::OperationError)?,
+    )?);
+  }
+
+  Ok(Some(signers))
+}
@@ -0,0 +1,497 @@
+use cms::content_info::CmsVersion;
+use cms::content_info::ContentInfo;
+use cms::enveloped_data::EncryptedContentInfo;
+use cms::enveloped_data::EnvelopedData;
+use cms::enveloped_data::KeyAgreeRecipientIdentifier;
+use cms::enveloped_data::KeyAgreeRecipientInfo;
+use cms::enveloped_data::KeyTransRecipientInfo;
+use cms::enveloped_data::OriginatorIdentifierOrKey;
+use cms::enveloped_data::OriginatorPublicKey;
+use cms::enveloped_data::RecipientEncryptedKey;
+use cms::enveloped_data::RecipientIdentifier;
+use cms::enveloped_data::RecipientInfo;
+use cms::enveloped_data::RecipientInfos;
+
+use der::asn1::BitString;
+use der::asn1::ObjectIdentifier;
+use der::asn1::OctetString;
+use der::asn1::SetOfVec;
+use der::Any;
+use der::Decode;
+use der::Encode;
+use der::Sequence;
+
+use rand::CryptoRng;
+use rand::RngCore;
+
+use rsa::pkcs1::FromRsaPrivateKey;
+use rsa::pkcs8::FromPublicKey;
+use rsa::PublicKey;
+use rsa::RsaPrivateKey;
+use rsa::RsaPublicKey;
+
+use x509_cert::spki::AlgorithmIdentifierOwned;
+
+use super::algorithm_identifier;
+use super::hash_name;
+use super::hash_oids;
+use super::issuer_and_serial_number;
+use super::named_curve;
+use super::oid;
+use super::parse_certificate;
+use super::ID_DATA;
+use super::ID_EC_PUBLIC_KEY;
+use super::RSA_ENCRYPTION;
+use crate::subtle::aes_cbc;
+use crate::subtle::aes_kw;
+use crate::subtle::digest;
+use crate::subtle::ec;
+use crate::subtle::hash_oaep_padding;
+use crate::subtle::Certificate;
+use crate::subtle::NamedCurve;
+use crate::WebCryptoError;
+
+const ID_ENVELOPED_DATA: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.3");
+const ID_RSAES_OAEP: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.7");
+const ID_MGF1: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.8");
+
+/// dhSinglePass-stdDH-sha256kdf-scheme, used for new EC recipients.
+const ECDH_SHA256_KDF: &str = "1.3.132.1.11.1";
+
+/// ECDH key agreement schemes and the hash of their X9.63 KDF.
+const ECDH_SCHEMES: [(&str, &str); 4] = [
+  ("1.3.133.16.840.63.0.2", "SHA-1"),
+  (ECDH_SHA256_KDF, "SHA-256"),
+  ("1.3.132.1.11.2", "SHA-384"),
+  ("1.3.132.1.11.3", "SHA-512"),
+];
+
+/// AES-CBC content encryption algorithms and their key length.
+const AES_CBC: [(&str, usize); 3] = [
+  ("2.16.840.1.101.3.4.1.2", 16),
+  ("2.16.840.1.101.3.4.1.22", 24),
+  ("2.16.840.1.101.3.4.1.42", 32),
+];
+
+/// AES key wrap algorithms and their key length.
+const AES_WRAP: [(&str, usize); 3] = [
+  ("2.16.840.1.101.3.4.1.5", 16),
+  ("2.16.840.1.101.3.4.1.25", 24),
+  ("2.16.840.1.101.3.4.1.45", 32),
+];
+
+/// RSAES-OAEP-params (RFC 4055). Absent members default to SHA-1.
+#[derive(Sequence)]
+struct RsaesOaepParams {
+  #[asn1(context_specific = "0", tag_mode = "EXPLICIT", optional = "true")]
+  hash_algorithm: Option<AlgorithmIdentifierOwned>,
+  #[asn1(context_specific = "1", tag_mode = "EXPLICIT", optional = "true")]
+  mask_gen_algorithm: Option<AlgorithmIdentifierOwned>,
+  #[asn1(context_specific = "2", tag_mode = "EXPLICIT", optional = "true")]
+  p_source_algorithm: Option<AlgorithmIdentifierOwned>,
+}
+
+/// ECC-CMS-SharedInfo (RFC 5753), the X9.63 KDF shared info.
+#[derive(Sequence)]
+struct EccCmsSharedInfo {
+  key_info: AlgorithmIdentifierOwned,
+  #[asn1(context_specific = "0", tag_mode = "EXPLICIT", optional = "true")]
+  entity_u_info: Option<OctetString>,
+  #[asn1(context_specific = "2", tag_mode = "EXPLICIT")]
+  supp_pub_info: OctetString,
+}
+
+/// The private key of a recipient.
+pub(crate) enum RecipientKey<'a> {
+  /// A PKCS#1 private key.
+  RsaOaep(&'a [u8]),
+  /// A private scalar on the curve.
+  Ecdh(NamedCurve, &'a [u8]),
+}
+
+fn lookup<T: Copy>(
+  table: &[(&str, T)],
+  id: &ObjectIdentifier,
+) -> Result<T, WebCryptoError> {
+  match table.iter().find(|(entry, _)| oid(entry) == *id) {
+    Some((_, value)) => Ok(*value),
+    None => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+fn hash_identifier(
+  hash: &str,
+) -> Result<AlgorithmIdentifierOwned, WebCryptoError> {
+  let (digest_oid, ..) = hash_oids(hash)?;
+  Ok(AlgorithmIdentifierOwned {
+    oid: oid(digest_oid),
+    parameters: Some(Any::null()),
+  })
+}
+
+fn oaep_parameters(hash: &str) -> Result<Any, WebCryptoError> {
+  let hash_algorithm = hash_identifier(hash)?;
+  let mask_gen_algorithm = AlgorithmIdentifierOwned {
+    oid: ID_MGF1,
+    parameters: Some(
+      Any::encode_from(&hash_algorithm)
+        .map_err(|_| WebCryptoError::OperationError)?,
+    ),
+  };
+  let params = RsaesOaepParams {
+    hash_algorithm: Some(hash_algorithm),
+    mask_gen_algorithm: Some(mask_gen_algorithm),
+    p_source_algorithm: None,
+  };
+  Any::encode_from(&params).map_err(|_| WebCryptoError::OperationError)
+}
+
+/// The OAEP hash of RSAES-OAEP-params. MGF1 has to use the same hash and
+/// labels are not supported.
+fn oaep_hash(parameters: &Option<Any>) -> Result<&'static str, WebCryptoError> {
+  let params: RsaesOaepParams = match parameters {
+    Some(parameters) => parameters
+      .decode_as()
+      .map_err(|_| WebCryptoError::OperationError)?,
+    None => {
+      return Err(WebCryptoError::DataError);
+    }
+  };
+
+  let hash = match &params.hash_algorithm {
+    Some(hash_algorithm) => hash_name(&hash_algorithm.oid)?,
+    None => "SHA-1",
+  };
+  let mgf_hash = match &params.mask_gen_algorithm {
+    Some(mgf) if mgf.oid == ID_MGF1 => {
+      let mgf_hash: AlgorithmIdentifierOwned = mgf
+        .parameters
+        .as_ref()
+        .ok_or(WebCryptoError::OperationError)?
+        .decode_as()
+        .map_err(|_| WebCryptoError::OperationError)?;
+      hash_name(&mgf_hash.oid)?
+    }
+    Some(_) => {
+      return Err(WebCryptoError::NotSupportedError);
+    }
+    None => "SHA-1",
+  };
+  if mgf_hash != hash || params.p_source_algorithm.is_some() {
+    return Err(WebCryptoError::NotSupportedError);
+  }
+
+  Ok(hash)
+}
+
+/// The ANSI X9.63 KDF, also known as KDF2.
+pub(crate) fn x963_kdf(
+  hash: &str,
+  secret: &[u8],
+  shared_info: &[u8],
+  length: usize,
+) -> Result<Vec<u8>, WebCryptoError> {
+  let mut key = Vec::with_capacity(length);
+  let mut counter: u32 = 1;
+  while key.len() < length {
+    let mut input = secret.to_vec();
+    input.extend_from_slice(&counter.to_be_bytes());
+    input.extend_from_slice(shared_info);
+    key.extend(digest(hash, &input)?);
+    counter += 1;
+  }
+  key.truncate(length);
+  Ok(key)
+}
+
+/// Derive the key encryption key of an ECDH recipient from the shared
+/// secret.
+fn key_encryption_key(
+  hash: &str,
+  wrap_algorithm: &AlgorithmIdentifierOwned,
+  ukm: Option<&OctetString>,
+  secret: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let length = lookup(&AES_WRAP, &wrap_algorithm.oid)?;
+  let shared_info = EccCmsSharedInfo {
+    key_info: wrap_algorithm.clone(),
+    entity_u_info: ukm.cloned(),
+    supp_pub_info: OctetString::new(((length * 8) as u32).to_be_bytes())
+      .map_err(|_| WebCryptoError::OperationError)?,
+  };
+  let shared_info = shared_info
+    .to_der()
+    .map_err(|_| WebCryptoError::OperationError)?;
+
+  x963_kdf(hash, secret, &shared_info, length)
+}
+
+fn recipient_info<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  recipient: &Certificate,
+  content_key: &[u8],
+) -> Result<RecipientInfo, WebCryptoError> {
+  let certificate = parse_certificate(recipient)?;
+  let spki = &certificate.tbs_certificate.subject_public_key_info;
+
+  if spki.algorithm.oid == RSA_ENCRYPTION {
+    let public_key = RsaPublicKey::from_public_key_der(
+      &spki.to_der().map_err(|_| WebCryptoError::OperationError)?,
+    )
+    .map_err(|_| WebCryptoError::OperationError)?;
+    let encrypted_key = public_key
+      .encrypt(rng, hash_oaep_padding("SHA-256")?, content_key)
+      .map_err(|_| WebCryptoError::OperationError)?;
+
+    Ok(RecipientInfo::Ktri(KeyTransRecipientInfo {
+      version: CmsVersion::V0,
+      rid: RecipientIdentifier::IssuerAndSerialNumber(
+        issuer_and_serial_number(&certificate),
+      ),
+      key_enc_alg: AlgorithmIdentifierOwned {
+        oid: ID_RSAES_OAEP,
+        parameters: Some(oaep_parameters("SHA-256")?),
+      },
+      enc_key: OctetString::new(encrypted_key)
+        .map_err(|_| WebCryptoError::OperationError)?,
+    }))
+  } else if spki.algorithm.oid == ID_EC_PUBLIC_KEY {
+    let named_curve = named_curve(&spki.algorithm.parameters)?;
+    let (private_key, public_key) = ec::generate(named_curve, rng);
+    let secret = ec::derive(
+      named_curve,
+      &private_key,
+      spki.subject_public_key.raw_bytes(),
+    )?;
+
+    let wrap_algorithm = algorithm_identifier(oid(AES_WRAP[2].0));
+    let kek = key_encryption_key("SHA-256", &wrap_algorithm, None, &secret)?;
+    let encrypted_key = aes_kw::wrap(&kek, content_key)?;
+
+    Ok(RecipientInfo::Kari(KeyAgreeRecipientInfo {
+      version: CmsVersion::V3,
+      originator: OriginatorIdentifierOrKey::OriginatorKey(
+        OriginatorPublicKey {
+          algorithm: algorithm_identifier(ID_EC_PUBLIC_KEY),
+          public_key: BitString::from_bytes(&public_key)
+            .map_err(|_| WebCryptoError::OperationError)?,
+        },
+      ),
+      ukm: None,
+      key_enc_alg: AlgorithmIdentifierOwned {
+        oid: oid(ECDH_SHA256_KDF),
+        parameters: Some(
+          Any::encode_from(&wrap_algorithm)
+            .map_err(|_| WebCryptoError::OperationError)?,
+        ),
+      },
+      recipient_enc_keys: vec![RecipientEncryptedKey {
+        rid: KeyAgreeRecipientIdentifier::IssuerAndSerialNumber(
+          issuer_and_serial_number(&certificate),
+        ),
+        enc_key: OctetString::new(encrypted_key)
+          .map_err(|_| WebCryptoError::OperationError)?,
+      }],
+    }))
+  } else {
+    Err(WebCryptoError::NotSupportedError)
+  }
+}
+
+/// Encrypt `content` with AES-256-CBC under a random key and encode it as
+/// an EnvelopedData `ContentInfo` for `recipients`.
+pub(crate) fn encrypt<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  recipients: &[Certificate],
+  content: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  if recipients.is_empty() {
+    return Err(WebCryptoError::DataError);
+  }
+
+  let (cbc_oid, key_length) = AES_CBC[2];
+  let mut content_key = vec![0; key_length];
+  rng.fill_bytes(&mut content_key);
+  let mut iv = vec![0; 16];
+  rng.fill_bytes(&mut iv);
+
+  let recipient_infos = recipients
+    .iter()
+    .map(|recipient| recipient_info(rng, recipient, &content_key))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  // RFC 5652 6.1: v2 as soon as a recipient is not a v0 one.
+  let version = if recipient_infos
+    .iter()
+    .all(|recipient_info| matches!(recipient_info, RecipientInfo::Ktri(_)))
+  {
+    CmsVersion::V0
+  } else {
+    CmsVersion::V2
+  };
+
+  let encrypted_content = aes_cbc::encrypt(&content_key, &iv, content)?;
+  let enveloped_data = EnvelopedData {
+    version,
+    originator_info: None,
+    recip_infos: RecipientInfos(
+      SetOfVec::try_from(recipient_infos)
+        .map_err(|_| WebCryptoError::OperationError)?,
+    ),
+    encrypted_content: EncryptedContentInfo {
+      content_type: ID_DATA,
+      content_enc_alg: AlgorithmIdentifierOwned {
+        oid: oid(cbc_oid),
+        parameters: Some(
+          Any::encode_from(
+            &OctetString::new(iv)
+              .map_err(|_| WebCryptoError::OperationError)?,
+          )
+          .map_err(|_| WebCryptoError::OperationError)?,
+        ),
+      },
+      encrypted_content: Some(
+        OctetString::new(encrypted_content)
+          .map_err(|_| WebCryptoError::OperationError)?,
+      ),
+    },
+    unprotected_attrs: None,
+  };
+
+  let content_info = ContentInfo {
+    content_type: ID_ENVELOPED_DATA,
+    content: Any::encode_from(&enveloped_data)
+      .map_err(|_| WebCryptoError::OperationError)?,
+  };
+  content_info
+    .to_der()
+    .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// Recover the content encryption key from the recipient info addressed
+/// to `certificate`, if `recipient_info` is one.
+fn content_key<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  key: &RecipientKey,
+  certificate: &x509_cert::Certificate,
+  recipient_info: &RecipientInfo,
+) -> Result<Option<Vec<u8>>, WebCryptoError> {
+  let id = issuer_and_serial_number(certificate);
+
+  match (recipient_info, key) {
+    (RecipientInfo::Ktri(ktri), RecipientKey::RsaOaep(private_key)) => {
+      match &ktri.rid {
+        RecipientIdentifier::IssuerAndSerialNumber(rid) if *rid == id => {}
+        _ => return Ok(None),
+      }
+      if ktri.key_enc_alg.oid != ID_RSAES_OAEP {
+        return Err(WebCryptoError::NotSupportedError);
+      }
+
+      let private_key = RsaPrivateKey::from_pkcs1_der(private_key)
+        .map_err(|_| WebCryptoError::OperationError)?;
+      let padding =
+        hash_oaep_padding(oaep_hash(&ktri.key_enc_alg.parameters)?)?;
+      let content_key = private_key
+        .decrypt_blinded(rng, padding, ktri.enc_key.as_bytes())
+        .map_err(|_| WebCryptoError::OperationError)?;
+      Ok(Some(content_key))
+    }
+    (
+      RecipientInfo::Kari(kari),
+      RecipientKey::Ecdh(named_curve, private_key),
+    ) => {
+      let encrypted_key = match kari.recipient_enc_keys.iter().find(|key| {
+        matches!(
+          &key.rid,
+          KeyAgreeRecipientIdentifier::IssuerAndSerialNumber(rid) if *rid == id
+        )
+      }) {
+        Some(encrypted_key) => encrypted_key,
+        None => return Ok(None),
+      };
+      let originator = match &kari.originator {
+        OriginatorIdentifierOrKey::OriginatorKey(originator) => {
+          originator.public_key.raw_bytes()
+        }
+        _ => {
+          return Err(WebCryptoError::NotSupportedError);
+        }
+      };
+
+      let hash = lookup(&ECDH_SCHEMES, &kari.key_enc_alg.oid)?;
+      let wrap_algorithm: AlgorithmIdentifierOwned = kari
+        .key_enc_alg
+        .parameters
+        .as_ref()
+        .ok_or(WebCryptoError::OperationError)?
+        .decode_as()
+        .map_err(|_| WebCryptoError::OperationError)?;
+
+      let secret = ec::derive(*named_curve, private_key, originator)?;
+      let kek =
+        key_encryption_key(hash, &wrap_algorithm, kari.ukm.as_ref(), &secret)?;
+      aes_kw::unwrap(&kek, encrypted_key.enc_key.as_bytes()).map(Some)
+    }
+    _ => Ok(None),
+  }
+}
+
+/// Decrypt an EnvelopedData `ContentInfo` as the recipient identified by
+/// `certificate`.
+pub(crate) fn decrypt<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  key: RecipientKey,
+  certificate: &Certificate,
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let certificate = parse_certificate(certificate)?;
+
+  let content_info =
+    ContentInfo::from_der(data).map_err(|_| WebCryptoError::DataError)?;
+  if content_info.content_type != ID_ENVELOPED_DATA {
+    return Err(WebCryptoError::DataError);
+  }
+  let enveloped_data: EnvelopedData = content_info
+    .content
+    .decode_as()
+    .map_err(|_| WebCryptoError::OperationError)?;
+
+  let mut content_key = None;
+  for recipient_info in enveloped_data.recip_infos.0.iter() {
+    content_key = self::content_key(rng, &key, &certificate, recipient_info)?;
+    if content_key.is_some() {
+      break;
+    }
+  }
+  let content_key = match content_key {
+    Some(content_key) => content_key,
+    None => {
+      // DataError: not addressed to `certificate`.
+      return Err(WebCryptoError::DataError);
+    }
+  };
+
+  let encrypted_content = &enveloped_data.encrypted_content;
+  let key_length = lookup(&AES_CBC, &encrypted_content.content_enc_alg.oid)?;
+  if content_key.len() != key_length {
+    return Err(WebCryptoError::DataError);
+  }
+  let iv: OctetString = encrypted_content
+    .content_enc_alg
+    .parameters
+    .as_ref()
+    .ok_or(WebCryptoError::OperationError)?
+    .decode_as()
+    .map_err(|_| WebCryptoError::OperationError)?;
+  // Detached content is not supported.
+  let ciphertext = encrypted_content
+    .encrypted_content
+    .as_ref()
+    .ok_or(WebCryptoError::OperationError)?;
+
+  aes_cbc::decrypt(&content_key, iv.as_bytes(), ciphertext.as_bytes())
+}
@@ -0,0 +1,336 @@
+// Key descriptors: the attributes of a `CryptoKey` and its encoded
+// handle as JSON, without the key material, for sending keys between
+// processes sharing a storage backend.
+//
+// Field names follow WebCrypto dictionaries. `version` is bumped for
+// changes older readers would misinterpret; newer descriptors are
+// refused.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::algorithm_name;
+use super::jwk;
+use super::AesKeyAlgorithm;
+use super::Algorithm;
+use super::CryptoKey;
+use super::DhGroup;
+use super::DhKeyAlgorithm;
+use super::EcKeyAlgorithm;
+use super::HashAlgorithmIdentifer;
+use super::HmacKeyAlgorithm;
+use super::KeyAlgorithm;
+use super::KeyType;
+use super::KeyUsage;
+use super::NamedCurve;
+use super::PublicExponent;
+use super::RsaHashedKeyAlgorithm;
+use super::RsaKeyAlgorithm;
+use crate::WebCryptoError;
+
+/// The version of descriptors written by this crate. Version 1 gave the
+/// length of AES keys in bytes, version 2 in bits. Version 2 gave the
+/// length of imported HMAC keys in bytes, version 3 in bits.
+pub const DESCRIPTOR_VERSION: u32 = 3;
+
+const USAGES: [KeyUsage; 10] = [
+  KeyUsage::Encrypt,
+  KeyUsage::Decrypt,
+  KeyUsage::Sign,
+  KeyUsage::Verify,
+  KeyUsage::WrapKey,
+  KeyUsage::UnwrapKey,
+  KeyUsage::DeriveKey,
+  KeyUsage::DeriveBits,
+  KeyUsage::EncapsulateBits,
+  KeyUsage::DecapsulateBits,
+];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AlgorithmDescriptor {
+  name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  hash: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  modulus_length: Option<usize>,
+  /// Base64url encoded, without padding.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  public_exponent: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  named_curve: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  group: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  length: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+  version: u32,
+  handle: String,
+  #[serde(rename = "type")]
+  type_: String,
+  extractable: bool,
+  usages: Vec<String>,
+  algorithm: AlgorithmDescriptor,
+  /// Bytes encrypted by AES-GCM, so the rekey threshold carries over.
+  #[serde(default)]
+  encrypted_bytes: u64,
+  /// Set for consume-once keys only, so other descriptors are unchanged.
+  /// Readers older than the field ignore it.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  consume_once: bool,
+}
+
+fn curve_name(curve: NamedCurve) -> &'static str {
+  match curve {
+    NamedCurve::P256 => "P-256",
+    NamedCurve::P384 => "P-384",
+    NamedCurve::P521 => "P-521",
+    #[cfg(feature = "k256")]
+    NamedCurve::K256 => "secp256k1",
+  }
+}
+
+fn named_curve(name: &str) -> Result<NamedCurve, WebCryptoError> {
+  match name {
+    "P-256" => Ok(NamedCurve::P256),
+    "P-384" => Ok(NamedCurve::P384),
+    "P-521" => Ok(NamedCurve::P521),
+    #[cfg(feature = "k256")]
+    "secp256k1" => Ok(NamedCurve::K256),
+    _ => Err(WebCryptoError::DataError),
+  }
+}
+
+fn group_name(group: DhGroup) -> &'static str {
+  match group {
+    DhGroup::Modp1536 => "modp1536",
+    DhGroup::Modp2048 => "modp2048",
+    DhGroup::Modp3072 => "modp3072",
+    DhGroup::Modp4096 => "modp4096",
+    DhGroup::Modp6144 => "modp6144",
+    DhGroup::Modp8192 => "modp8192",
+  }
+}
+
+fn dh_group(name: &str) -> Result<DhGroup, WebCryptoError> {
+  match name {
+    "modp1536" => Ok(DhGroup::Modp1536),
+    "modp2048" => Ok(DhGroup::Modp2048),
+    "modp3072" => Ok(DhGroup::Modp3072),
+    "modp4096" => Ok(DhGroup::Modp4096),
+    "modp6144" => Ok(DhGroup::Modp6144),
+    "modp8192" => Ok(DhGroup::Modp8192),
+    _ => Err(WebCryptoError::DataError),
+  }
+}
+
+fn type_name(type_: &KeyType) -> &'static str {
+  match type_ {
+    KeyType::Public => "public",
+    KeyType::Private => "private",
+    KeyType::Secret => "secret",
+  }
+}
+
+fn key_type(name: &str) -> Result<KeyType, WebCryptoError> {
+  match name {
+    "public" => Ok(KeyType::Public),
+    "private" => Ok(KeyType::Private),
+    "secret" => Ok(KeyType::Secret),
+    _ => Err(WebCryptoError::DataError),
+  }
+}
+
+fn key_usage(name: &str) -> Result<KeyUsage, WebCryptoError> {
+  USAGES
+    .iter()
+    .find(|usage| jwk::key_op(usage) == name)
+    .cloned()
+    .ok_or(WebCryptoError::DataError)
+}
+
+fn registered(name: &str) -> Result<&'static str, WebCryptoError> {
+  algorithm_name(name).ok_or(WebCryptoError::DataError)
+}
+
+fn public_exponent(encoded: &str) -> Result<PublicExponent, WebCryptoError> {
+  PublicExponent::from_be_bytes(
+    &base64::decode_config(encoded, base64::URL_SAFE_NO_PAD)
+      .map_err(|_| WebCryptoError::DataError)?,
+  )
+}
+
+impl AlgorithmDescriptor {
+  fn new(algorithm: &Algorithm) -> Self {
+    let mut descriptor = AlgorithmDescriptor {
+      name: algorithm.name().to_string(),
+      hash: None,
+      modulus_length: None,
+      public_exponent: None,
+      named_curve: None,
+      group: None,
+      length: None,
+    };
+    let encode =
+      |bytes: &[u8]| base64::encode_config(bytes, base64::URL_SAFE_NO_PAD);
+
+    match algorithm {
+      Algorithm::RsaKeyAlgorithm(alg) => {
+        descriptor.modulus_length = Some(alg.modulus_length);
+        descriptor.public_exponent =
+          Some(encode(&alg.public_exponent.to_be_bytes()));
+      }
+      Algorithm::RsaHashedKeyAlgorithm(alg) => {
+        descriptor.hash = Some(alg.hash.name.to_string());
+        descriptor.modulus_length = Some(alg.modulus_length);
+        descriptor.public_exponent =
+          Some(encode(&alg.public_exponent.to_be_bytes()));
+      }
+      Algorithm::EcKeyAlgorithm(alg) => {
+        descriptor.named_curve = Some(curve_name(alg.named_curve).to_string());
+      }
+      Algorithm::DhKeyAlgorithm(alg) => {
+        descriptor.group = Some(group_name(alg.group).to_string());
+      }
+      Algorithm::AesKeyAlgorithm(alg) => {
+        descriptor.length = Some(alg.length);
+      }
+      Algorithm::HmacKeyAlgorithm(alg) => {
+        descriptor.hash = Some(alg.hash.name.to_string());
+        descriptor.length = Some(alg.length);
+      }
+      Algorithm::KeyAlgorithm(_) => {}
+    }
+
+    descriptor
+  }
+
+  /// The algorithm described, picked by the members present.
+  fn algorithm(&self) -> Result<Algorithm, WebCryptoError> {
+    let name = registered(&self.name)?;
+    let hash = match &self.hash {
+      Some(hash) => Some(HashAlgorithmIdentifer {
+        name: registered(hash)?,
+      }),
+      None => None,
+    };
+
+    let algorithm = match (
+      hash,
+      self.modulus_length,
+      &self.public_exponent,
+      &self.named_curve,
+      &self.group,
+      self.length,
+    ) {
+      (None, Some(modulus_length), Some(exponent), None, None, None) => {
+        Algorithm::RsaKeyAlgorithm(RsaKeyAlgorithm {
+          name,
+          modulus_length,
+          public_exponent: public_exponent(exponent)?,
+        })
+      }
+      (Some(hash), Some(modulus_length), Some(exponent), None, None, None) => {
+        Algorithm::RsaHashedKeyAlgorithm(RsaHashedKeyAlgorithm {
+          name,
+          hash,
+          modulus_length,
+          public_exponent: public_exponent(exponent)?,
+        })
+      }
+      (None, None, None, Some(curve), None, None) => {
+        Algorithm::EcKeyAlgorithm(EcKeyAlgorithm {
+          name,
+          named_curve: named_curve(curve)?,
+        })
+      }
+      (None, None, None, None, Some(group), None) => {
+        Algorithm::DhKeyAlgorithm(DhKeyAlgorithm {
+          name,
+          group: dh_group(group)?,
+        })
+      }
+      (None, None, None, None, None, Some(length)) => {
+        Algorithm::AesKeyAlgorithm(AesKeyAlgorithm { name, length })
+      }
+      (Some(hash), None, None, None, None, Some(length)) => {
+        Algorithm::HmacKeyAlgorithm(HmacKeyAlgorithm { name, hash, length })
+      }
+      (None, None, None, None, None, None) => {
+        Algorithm::KeyAlgorithm(KeyAlgorithm { name })
+      }
+      _ => return Err(WebCryptoError::DataError),
+    };
+
+    Ok(algorithm)
+  }
+}
+
+/// The descriptor of `key`, whose handle is encoded as `handle`.
+pub(crate) fn serialize<H>(key: &CryptoKey<H>, handle: String) -> String {
+  let descriptor = Descriptor {
+    version: DESCRIPTOR_VERSION,
+    handle,
+    type_: type_name(&key.type_).to_string(),
+    extractable: key.extractable,
+    usages: key
+      .usages
+      .iter()
+      .map(|usage| jwk::key_op(usage).to_string())
+      .collect(),
+    algorithm: AlgorithmDescriptor::new(&key.algorithm),
+    encrypted_bytes: key.encrypted.load(Ordering::Relaxed),
+    consume_once: key.consume_once,
+  };
+  serde_json::to_string(&descriptor).unwrap()
+}
+
+/// The key of `descriptor`. `lookup` decodes its handle, returning the
+/// handle and the length in bytes of the key material stored for it.
+pub(crate) fn deserialize<H>(
+  descriptor: &str,
+  lookup: impl FnOnce(&str) -> Result<(H, usize), WebCryptoError>,
+) -> Result<CryptoKey<H>, WebCryptoError> {
+  let descriptor: Descriptor =
+    serde_json::from_str(descriptor).map_err(|_| WebCryptoError::DataError)?;
+  if descriptor.version > DESCRIPTOR_VERSION {
+    return Err(WebCryptoError::NotSupportedError);
+  }
+
+  let type_ = key_type(&descriptor.type_)?;
+  let usages = descriptor
+    .usages
+    .iter()
+    .map(|usage| key_usage(usage))
+    .collect::<Result<Vec<_>, WebCryptoError>>()?;
+  let mut algorithm = descriptor.algorithm.algorithm()?;
+  let (handle, key_length) = lookup(&descriptor.handle)?;
+  match &mut algorithm {
+    Algorithm::AesKeyAlgorithm(alg) if descriptor.version < 2 => {
+      alg.length *= 8;
+    }
+    // Imported keys gave bytes and generated ones whole bytes in bits,
+    // so either way the length is that of the key material.
+    Algorithm::HmacKeyAlgorithm(alg) if descriptor.version < 3 => {
+      alg.length = key_length * 8;
+    }
+    _ => {}
+  }
+
+  Ok(CryptoKey {
+    extractable: descriptor.extractable,
+    usages,
+    type_,
+    algorithm,
+    handle,
+    encrypted: AtomicU64::new(descriptor.encrypted_bytes),
+    consume_once: descriptor.consume_once,
+  })
+}
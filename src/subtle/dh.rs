@@ -0,0 +1,234 @@
+use crypto_bigint::modular::runtime_mod::DynResidue;
+use crypto_bigint::modular::runtime_mod::DynResidueParams;
+use crypto_bigint::Limb;
+use crypto_bigint::Uint;
+use crypto_bigint::U1536;
+use crypto_bigint::U2048;
+use crypto_bigint::U3072;
+use crypto_bigint::U4096;
+use crypto_bigint::U6144;
+use crypto_bigint::U8192;
+use rsa::BigUint;
+
+use super::DhGroup;
+use crate::WebCryptoError;
+
+// The primes of RFC 3526. All groups use the generator 2.
+
+/// The 1536-bit MODP group (group 5).
+const MODP_1536: &str = concat!(
+  "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74",
+  "020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437",
+  "4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+  "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF05",
+  "98DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB",
+  "9ED529077096966D670C354E4ABC9804F1746C08CA237327FFFFFFFFFFFFFFFF",
+);
+
+/// The 2048-bit MODP group (group 14).
+const MODP_2048: &str = concat!(
+  "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74",
+  "020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437",
+  "4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+  "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF05",
+  "98DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB",
+  "9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+  "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF695581718",
+  "3995497CEA956AE515D2261898FA051015728E5A8AACAA68FFFFFFFFFFFFFFFF",
+);
+
+/// The 3072-bit MODP group (group 15).
+const MODP_3072: &str = concat!(
+  "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74",
+  "020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437",
+  "4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+  "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF05",
+  "98DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB",
+  "9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+  "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF695581718",
+  "3995497CEA956AE515D2261898FA051015728E5A8AAAC42DAD33170D04507A33",
+  "A85521ABDF1CBA64ECFB850458DBEF0A8AEA71575D060C7DB3970F85A6E1E4C7",
+  "ABF5AE8CDB0933D71E8C94E04A25619DCEE3D2261AD2EE6BF12FFA06D98A0864",
+  "D87602733EC86A64521F2B18177B200CBBE117577A615D6C770988C0BAD946E2",
+  "08E24FA074E5AB3143DB5BFCE0FD108E4B82D120A93AD2CAFFFFFFFFFFFFFFFF",
+);
+
+/// The 4096-bit MODP group (group 16).
+const MODP_4096: &str = concat!(
+  "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74",
+  "020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437",
+  "4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+  "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF05",
+  "98DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB",
+  "9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+  "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF695581718",
+  "3995497CEA956AE515D2261898FA051015728E5A8AAAC42DAD33170D04507A33",
+  "A85521ABDF1CBA64ECFB850458DBEF0A8AEA71575D060C7DB3970F85A6E1E4C7",
+  "ABF5AE8CDB0933D71E8C94E04A25619DCEE3D2261AD2EE6BF12FFA06D98A0864",
+  "D87602733EC86A64521F2B18177B200CBBE117577A615D6C770988C0BAD946E2",
+  "08E24FA074E5AB3143DB5BFCE0FD108E4B82D120A92108011A723C12A787E6D7",
+  "88719A10BDBA5B2699C327186AF4E23C1A946834B6150BDA2583E9CA2AD44CE8",
+  "DBBBC2DB04DE8EF92E8EFC141FBECAA6287C59474E6BC05D99B2964FA090C3A2",
+  "233BA186515BE7ED1F612970CEE2D7AFB81BDD762170481CD0069127D5B05AA9",
+  "93B4EA988D8FDDC186FFB7DC90A6C08F4DF435C934063199FFFFFFFFFFFFFFFF",
+);
+
+/// The 6144-bit MODP group (group 17).
+const MODP_6144: &str = concat!(
+  "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74",
+  "020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437",
+  "4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+  "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF05",
+  "98DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB",
+  "9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+  "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF695581718",
+  "3995497CEA956AE515D2261898FA051015728E5A8AAAC42DAD33170D04507A33",
+  "A85521ABDF1CBA64ECFB850458DBEF0A8AEA71575D060C7DB3970F85A6E1E4C7",
+  "ABF5AE8CDB0933D71E8C94E04A25619DCEE3D2261AD2EE6BF12FFA06D98A0864",
+  "D87602733EC86A64521F2B18177B200CBBE117577A615D6C770988C0BAD946E2",
+  "08E24FA074E5AB3143DB5BFCE0FD108E4B82D120A92108011A723C12A787E6D7",
+  "88719A10BDBA5B2699C327186AF4E23C1A946834B6150BDA2583E9CA2AD44CE8",
+  "DBBBC2DB04DE8EF92E8EFC141FBECAA6287C59474E6BC05D99B2964FA090C3A2",
+  "233BA186515BE7ED1F612970CEE2D7AFB81BDD762170481CD0069127D5B05AA9",
+  "93B4EA988D8FDDC186FFB7DC90A6C08F4DF435C93402849236C3FAB4D27C7026",
+  "C1D4DCB2602646DEC9751E763DBA37BDF8FF9406AD9E530EE5DB382F413001AE",
+  "B06A53ED9027D831179727B0865A8918DA3EDBEBCF9B14ED44CE6CBACED4BB1B",
+  "DB7F1447E6CC254B332051512BD7AF426FB8F401378CD2BF5983CA01C64B92EC",
+  "F032EA15D1721D03F482D7CE6E74FEF6D55E702F46980C82B5A84031900B1C9E",
+  "59E7C97FBEC7E8F323A97A7E36CC88BE0F1D45B7FF585AC54BD407B22B4154AA",
+  "CC8F6D7EBF48E1D814CC5ED20F8037E0A79715EEF29BE32806A1D58BB7C5DA76",
+  "F550AA3D8A1FBFF0EB19CCB1A313D55CDA56C9EC2EF29632387FE8D76E3C0468",
+  "043E8F663F4860EE12BF2D5B0B7474D6E694F91E6DCC4024FFFFFFFFFFFFFFFF",
+);
+
+/// The 8192-bit MODP group (group 18).
+const MODP_8192: &str = concat!(
+  "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74",
+  "020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437",
+  "4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+  "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF05",
+  "98DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB",
+  "9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+  "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF695581718",
+  "3995497CEA956AE515D2261898FA051015728E5A8AAAC42DAD33170D04507A33",
+  "A85521ABDF1CBA64ECFB850458DBEF0A8AEA71575D060C7DB3970F85A6E1E4C7",
+  "ABF5AE8CDB0933D71E8C94E04A25619DCEE3D2261AD2EE6BF12FFA06D98A0864",
+  "D87602733EC86A64521F2B18177B200CBBE117577A615D6C770988C0BAD946E2",
+  "08E24FA074E5AB3143DB5BFCE0FD108E4B82D120A92108011A723C12A787E6D7",
+  "88719A10BDBA5B2699C327186AF4E23C1A946834B6150BDA2583E9CA2AD44CE8",
+  "DBBBC2DB04DE8EF92E8EFC141FBECAA6287C59474E6BC05D99B2964FA090C3A2",
+  "233BA186515BE7ED1F612970CEE2D7AFB81BDD762170481CD0069127D5B05AA9",
+  "93B4EA988D8FDDC186FFB7DC90A6C08F4DF435C93402849236C3FAB4D27C7026",
+  "C1D4DCB2602646DEC9751E763DBA37BDF8FF9406AD9E530EE5DB382F413001AE",
+  "B06A53ED9027D831179727B0865A8918DA3EDBEBCF9B14ED44CE6CBACED4BB1B",
+  "DB7F1447E6CC254B332051512BD7AF426FB8F401378CD2BF5983CA01C64B92EC",
+  "F032EA15D1721D03F482D7CE6E74FEF6D55E702F46980C82B5A84031900B1C9E",
+  "59E7C97FBEC7E8F323A97A7E36CC88BE0F1D45B7FF585AC54BD407B22B4154AA",
+  "CC8F6D7EBF48E1D814CC5ED20F8037E0A79715EEF29BE32806A1D58BB7C5DA76",
+  "F550AA3D8A1FBFF0EB19CCB1A313D55CDA56C9EC2EF29632387FE8D76E3C0468",
+  "043E8F663F4860EE12BF2D5B0B7474D6E694F91E6DBE115974A3926F12FEE5E4",
+  "38777CB6A932DF8CD8BEC4D073B931BA3BC832B68D9DD300741FA7BF8AFC47ED",
+  "2576F6936BA424663AAB639C5AE4F5683423B4742BF1C978238F16CBE39D652D",
+  "E3FDB8BEFC848AD922222E04A4037C0713EB57A81A23F0C73473FC646CEA306B",
+  "4BCBC8862F8385DDFA9D4B7FA2C087E879683303ED5BDD3A062B3CF5B3A278A6",
+  "6D2A13F83F44F82DDF310EE074AB6A364597E899A0255DC164F31CC50846851D",
+  "F9AB48195DED7EA1B1D510BD7EE74D73FAF36BC31ECFA268359046F4EB879F92",
+  "4009438B481C6CD7889A002ED5EE382BC9190DA6FC026E479558E4475677E9AA",
+  "9E3050E2765694DFC81F56E880B96E7160C980DD98EDD3DFFFFFFFFFFFFFFFFF",
+);
+
+fn prime(group: DhGroup) -> BigUint {
+  let hex = match group {
+    DhGroup::Modp1536 => MODP_1536,
+    DhGroup::Modp2048 => MODP_2048,
+    DhGroup::Modp3072 => MODP_3072,
+    DhGroup::Modp4096 => MODP_4096,
+    DhGroup::Modp6144 => MODP_6144,
+    DhGroup::Modp8192 => MODP_8192,
+  };
+  BigUint::parse_bytes(hex.as_bytes(), 16).unwrap()
+}
+
+/// Parse the public or private value `value` of `group`, which has to be
+/// in [2, p - 2].
+fn element(
+  group: DhGroup,
+  value: &[u8],
+) -> Result<(BigUint, BigUint), WebCryptoError> {
+  let p = prime(group);
+  let value = BigUint::from_bytes_be(value);
+  if value < BigUint::from(2u8) || value > &p - 2u8 {
+    return Err(WebCryptoError::DataError);
+  }
+  Ok((p, value))
+}
+
+/// Check that `value` is a valid public or private value of `group`.
+pub(crate) fn check(
+  group: DhGroup,
+  value: &[u8],
+) -> Result<(), WebCryptoError> {
+  element(group, value).map(|_| ())
+}
+
+/// Compute the shared secret `public ^ private mod p`, left-padded to the
+/// length of the prime.
+pub(crate) fn derive(
+  group: DhGroup,
+  private_key: &[u8],
+  public_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let (p, private_key) = element(group, private_key)?;
+  let (_, public_key) = element(group, public_key)?;
+
+  let pow = match group {
+    DhGroup::Modp1536 => pow_mod::<{ U1536::LIMBS }>,
+    DhGroup::Modp2048 => pow_mod::<{ U2048::LIMBS }>,
+    DhGroup::Modp3072 => pow_mod::<{ U3072::LIMBS }>,
+    DhGroup::Modp4096 => pow_mod::<{ U4096::LIMBS }>,
+    DhGroup::Modp6144 => pow_mod::<{ U6144::LIMBS }>,
+    DhGroup::Modp8192 => pow_mod::<{ U8192::LIMBS }>,
+  };
+  let secret = pow(
+    &public_key.to_bytes_be(),
+    &private_key.to_bytes_be(),
+    &p.to_bytes_be(),
+  );
+  // The limbs of every group are as long as its prime.
+  let secret = secret[secret.len() - p.bits().div_ceil(8)..].to_vec();
+
+  // A secret of 1 or p - 1 means the public value is in a small subgroup.
+  let value = BigUint::from_bytes_be(&secret);
+  if value == BigUint::from(1u8) || value == &p - 1u8 {
+    return Err(WebCryptoError::OperationError);
+  }
+  Ok(secret)
+}
+
+/// The big-endian `value` as a `Uint` of `LIMBS` words.
+fn uint<const LIMBS: usize>(value: &[u8]) -> Uint<LIMBS> {
+  let mut padded = vec![0; Limb::BYTES * LIMBS - value.len()];
+  padded.extend_from_slice(value);
+  Uint::from_be_slice(&padded)
+}
+
+/// `base ^ exponent mod modulus`, big-endian, in time independent of the
+/// values of `base` and `exponent`. The private exponent must not leak
+/// through the time `BigUint::modpow` takes.
+fn pow_mod<const LIMBS: usize>(
+  base: &[u8],
+  exponent: &[u8],
+  modulus: &[u8],
+) -> Vec<u8> {
+  let params = DynResidueParams::new(&uint::<LIMBS>(modulus));
+  let power = DynResidue::new(&uint::<LIMBS>(base), params)
+    .pow(&uint::<LIMBS>(exponent))
+    .retrieve();
+
+  power
+    .as_words()
+    .iter()
+    .rev()
+    .flat_map(|word| word.to_be_bytes())
+    .collect()
+}
@@ -0,0 +1,454 @@
+use p256::ecdsa::signature::hazmat::PrehashSigner;
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use p256::ecdsa::signature::hazmat::RandomizedPrehashSigner;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::pkcs8::DecodePrivateKey;
+use p256::pkcs8::DecodePublicKey;
+use p256::pkcs8::EncodePrivateKey;
+use p256::pkcs8::EncodePublicKey;
+use rand::CryptoRng;
+use rand::RngCore;
+
+use super::KeyType;
+use super::NamedCurve;
+use crate::WebCryptoError;
+
+/// Run `$body` with `$curve` bound to the crate implementing
+/// `$named_curve`.
+macro_rules! with_curve {
+  ($named_curve:expr, $curve:ident => $body:expr) => {
+    match $named_curve {
+      NamedCurve::P256 => {
+        use p256 as $curve;
+        $body
+      }
+      NamedCurve::P384 => {
+        use p384 as $curve;
+        $body
+      }
+      NamedCurve::P521 => {
+        use p521 as $curve;
+        $body
+      }
+      #[cfg(feature = "k256")]
+      NamedCurve::K256 => {
+        use k256 as $curve;
+        $body
+      }
+    }
+  };
+}
+
+/// Parse a PKCS#8 private key on `named_curve`, returning the private
+/// scalar. Fails if the key is on a different curve.
+pub(crate) fn import_pkcs8(
+  named_curve: NamedCurve,
+  der: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let secret_key = curve::SecretKey::from_pkcs8_der(der).map_err(|_| WebCryptoError::DataError)?;
+    Ok(secret_key.to_bytes().to_vec())
+  })
+}
+
+/// Parse a SEC1 ECPrivateKey on `named_curve`, returning the private
+/// scalar. Fails if the key names a different curve.
+pub(crate) fn import_sec1(
+  named_curve: NamedCurve,
+  der: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let secret_key = curve::SecretKey::from_sec1_der(der).map_err(|_| WebCryptoError::DataError)?;
+    Ok(secret_key.to_bytes().to_vec())
+  })
+}
+
+/// Parse a SubjectPublicKeyInfo on `named_curve`, returning the public
+/// key as an uncompressed SEC1 point. Fails if the key is on a different
+/// curve.
+pub(crate) fn import_spki(
+  named_curve: NamedCurve,
+  der: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let public_key =
+      curve::PublicKey::from_public_key_der(der).map_err(|_| WebCryptoError::DataError)?;
+    Ok(public_key.to_encoded_point(false).as_bytes().to_vec())
+  })
+}
+
+/// Check the big-endian private scalar `d` on `named_curve`, which must
+/// be as long as the curve order and in the range 1..n.
+pub(crate) fn import_scalar(
+  named_curve: NamedCurve,
+  d: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let secret_key = curve::SecretKey::from_slice(d).map_err(|_| WebCryptoError::DataError)?;
+    let scalar = secret_key.to_bytes().to_vec();
+    if scalar.len() != d.len() {
+      return Err(WebCryptoError::DataError);
+    }
+    Ok(scalar)
+  })
+}
+
+/// Parse a SEC1 point on `named_curve`, compressed or not, returning it
+/// uncompressed. Fails if the point is not on the curve.
+pub(crate) fn import_raw(
+  named_curve: NamedCurve,
+  point: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let public_key = curve::PublicKey::from_sec1_bytes(point).map_err(|_| WebCryptoError::DataError)?;
+    Ok(public_key.to_encoded_point(false).as_bytes().to_vec())
+  })
+}
+
+/// Compress the SEC1 point `public_key`.
+pub(crate) fn export_compressed(
+  named_curve: NamedCurve,
+  public_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let public_key =
+      curve::PublicKey::from_sec1_bytes(public_key).map_err(|_| WebCryptoError::OperationError)?;
+    Ok(public_key.to_encoded_point(true).as_bytes().to_vec())
+  })
+}
+
+/// Encode the private scalar `private_key` as PKCS#8.
+pub(crate) fn export_pkcs8(
+  named_curve: NamedCurve,
+  private_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let secret_key =
+      curve::SecretKey::from_slice(private_key).map_err(|_| WebCryptoError::OperationError)?;
+    let der = secret_key.to_pkcs8_der().map_err(|_| WebCryptoError::OperationError)?;
+    Ok(der.as_bytes().to_vec())
+  })
+}
+
+/// Encode the SEC1 point `public_key` as a SubjectPublicKeyInfo.
+pub(crate) fn export_spki(
+  named_curve: NamedCurve,
+  public_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let public_key =
+      curve::PublicKey::from_sec1_bytes(public_key).map_err(|_| WebCryptoError::OperationError)?;
+    let der = public_key.to_public_key_der().map_err(|_| WebCryptoError::OperationError)?;
+    Ok(der.as_bytes().to_vec())
+  })
+}
+
+/// Generate a key pair on `named_curve`, returning the private scalar
+/// and the public key as an uncompressed SEC1 point.
+pub(crate) fn generate<R: RngCore + CryptoRng>(
+  named_curve: NamedCurve,
+  rng: &mut R,
+) -> (Vec<u8>, Vec<u8>) {
+  with_curve!(named_curve, curve => {
+    let secret_key = curve::SecretKey::random(rng);
+    let public_key = secret_key.public_key().to_encoded_point(false);
+    (secret_key.to_bytes().to_vec(), public_key.as_bytes().to_vec())
+  })
+}
+
+/// The public key of the private scalar `private_key`, as an uncompressed
+/// SEC1 point.
+pub(crate) fn public_key(
+  named_curve: NamedCurve,
+  private_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let secret_key =
+      curve::SecretKey::from_slice(private_key).map_err(|_| WebCryptoError::OperationError)?;
+    let public_key = secret_key.public_key().to_encoded_point(false);
+    Ok(public_key.as_bytes().to_vec())
+  })
+}
+
+/// Compute the ECDH shared secret, the x-coordinate of the shared point,
+/// between the private scalar `private_key` and the SEC1 point
+/// `public_key`.
+pub(crate) fn derive(
+  named_curve: NamedCurve,
+  private_key: &[u8],
+  public_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let secret_key =
+      curve::SecretKey::from_slice(private_key).map_err(|_| WebCryptoError::OperationError)?;
+    let public_key =
+      curve::PublicKey::from_sec1_bytes(public_key).map_err(|_| WebCryptoError::OperationError)?;
+    let shared_secret = curve::ecdh::diffie_hellman(
+      secret_key.to_nonzero_scalar(),
+      public_key.as_affine(),
+    );
+    Ok(shared_secret.raw_secret_bytes().to_vec())
+  })
+}
+
+/// Sign the hashed message `digest` with the private scalar
+/// `private_key`, returning the signature as `r || s`.
+/// Left-pad `digest` to the field size of `$curve`. The ecdsa crate
+/// rejects digests shorter than half the field, like SHA-256 on P-521,
+/// although they convert to the same integer.
+macro_rules! prehash {
+  ($curve:ident, $digest:expr) => {{
+    let mut prehash = $curve::FieldBytes::default();
+    let offset = prehash.len().saturating_sub($digest.len());
+    if offset > 0 {
+      prehash[offset..].copy_from_slice($digest);
+      prehash.to_vec()
+    } else {
+      $digest.to_vec()
+    }
+  }};
+}
+
+pub(crate) fn sign<R: RngCore + CryptoRng>(
+  named_curve: NamedCurve,
+  private_key: &[u8],
+  digest: &[u8],
+  rng: &mut R,
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let signing_key =
+      curve::ecdsa::SigningKey::from_slice(private_key).map_err(|_| WebCryptoError::OperationError)?;
+    let signature: curve::ecdsa::Signature = signing_key
+      .sign_prehash_with_rng(rng, &prehash!(curve, digest))
+      .map_err(|_| WebCryptoError::OperationError)?;
+    Ok(signature.to_bytes().to_vec())
+  })
+}
+
+/// Sign the hashed message `digest` with a deterministic nonce (RFC
+/// 6979), so signing needs no randomness and the same message always has
+/// the same signature.
+pub(crate) fn sign_deterministic(
+  named_curve: NamedCurve,
+  private_key: &[u8],
+  digest: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let signing_key =
+      curve::ecdsa::SigningKey::from_slice(private_key).map_err(|_| WebCryptoError::OperationError)?;
+    let signature: curve::ecdsa::Signature = signing_key
+      .sign_prehash(&prehash!(curve, digest))
+      .map_err(|_| WebCryptoError::OperationError)?;
+    Ok(signature.to_bytes().to_vec())
+  })
+}
+
+/// Replace s of the `r || s` `signature` by n - s if it is in the upper
+/// half of the curve order, the low-S form.
+pub(crate) fn normalize_s(
+  named_curve: NamedCurve,
+  signature: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let signature =
+      curve::ecdsa::Signature::from_slice(signature).map_err(|_| WebCryptoError::OperationError)?;
+    let signature = signature.normalize_s().unwrap_or(signature);
+    Ok(signature.to_bytes().to_vec())
+  })
+}
+
+/// Whether s of the `r || s` `signature` is in the upper half of the
+/// curve order. Malformed signatures are not, and fail verification
+/// anyway.
+pub(crate) fn is_high_s(named_curve: NamedCurve, signature: &[u8]) -> bool {
+  with_curve!(named_curve, curve => {
+    curve::ecdsa::Signature::from_slice(signature)
+      .map(|signature| signature.normalize_s().is_some())
+      .unwrap_or(false)
+  })
+}
+
+/// Convert an `r || s` signature into the DER encoding used by X.509
+/// and CMS.
+pub(crate) fn signature_to_der(
+  named_curve: NamedCurve,
+  signature: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let signature =
+      curve::ecdsa::Signature::from_slice(signature).map_err(|_| WebCryptoError::OperationError)?;
+    Ok(signature.to_der().as_bytes().to_vec())
+  })
+}
+
+/// Convert a DER encoded signature into `r || s`.
+pub(crate) fn signature_from_der(
+  named_curve: NamedCurve,
+  signature: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let signature =
+      curve::ecdsa::Signature::from_der(signature).map_err(|_| WebCryptoError::OperationError)?;
+    Ok(signature.to_bytes().to_vec())
+  })
+}
+
+/// An ECDSA signature on a named curve, to convert between the `r || s`
+/// encoding of WebCrypto `sign` and `verify` and the DER encoding of
+/// OpenSSL and most other tooling.
+///
+/// ```
+/// use webcrypto::subtle::EcdsaSignature;
+/// use webcrypto::subtle::NamedCurve;
+///
+/// // r = 1, s = 2.
+/// let mut raw = [0; 64];
+/// raw[31] = 1;
+/// raw[63] = 2;
+/// let signature = EcdsaSignature::from_raw(NamedCurve::P256, &raw).unwrap();
+/// let der = signature.to_der();
+/// assert_eq!(der, [0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]);
+/// let parsed = EcdsaSignature::from_der(NamedCurve::P256, &der).unwrap();
+/// assert_eq!(parsed.to_raw(), raw);
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct EcdsaSignature {
+  named_curve: NamedCurve,
+  /// The `r || s` encoding.
+  raw: Vec<u8>,
+}
+
+impl EcdsaSignature {
+  /// Parse an `r || s` signature, both halves as long as the curve order.
+  pub fn from_raw(
+    named_curve: NamedCurve,
+    raw: &[u8],
+  ) -> Result<Self, WebCryptoError> {
+    // Checks the length and that r and s are in range.
+    signature_to_der(named_curve, raw)?;
+    Ok(EcdsaSignature {
+      named_curve,
+      raw: raw.to_vec(),
+    })
+  }
+
+  /// Parse a DER encoded `Ecdsa-Sig-Value`.
+  pub fn from_der(
+    named_curve: NamedCurve,
+    der: &[u8],
+  ) -> Result<Self, WebCryptoError> {
+    Ok(EcdsaSignature {
+      named_curve,
+      raw: signature_from_der(named_curve, der)?,
+    })
+  }
+
+  pub fn to_raw(&self) -> Vec<u8> {
+    self.raw.clone()
+  }
+
+  pub fn to_der(&self) -> Vec<u8> {
+    // `raw` was checked when parsed.
+    signature_to_der(self.named_curve, &self.raw).unwrap()
+  }
+}
+
+/// Verify the `r || s` `signature` over the hashed message `digest`.
+///
+/// `key` is a SEC1 encoded point for public keys and the private scalar
+/// for private keys.
+pub(crate) fn verify(
+  named_curve: NamedCurve,
+  type_: &KeyType,
+  key: &[u8],
+  digest: &[u8],
+  signature: &[u8],
+) -> Result<bool, WebCryptoError> {
+  with_curve!(named_curve, curve => {
+    let verifying_key = match type_ {
+      KeyType::Private => curve::ecdsa::VerifyingKey::from(
+        &curve::ecdsa::SigningKey::from_slice(key).map_err(|_| WebCryptoError::OperationError)?,
+      ),
+      _ => curve::ecdsa::VerifyingKey::from_sec1_bytes(key).map_err(|_| WebCryptoError::OperationError)?,
+    };
+    let signature = match curve::ecdsa::Signature::from_slice(signature) {
+      Ok(signature) => signature,
+      Err(_) => return Ok(false),
+    };
+    Ok(
+      verifying_key
+        .verify_prehash(&prehash!(curve, digest), &signature)
+        .is_ok(),
+    )
+  })
+}
+
+/// A parsed ECDSA public key, to verify many signatures without parsing
+/// the point each time.
+pub(crate) enum VerifyingKey {
+  P256(p256::ecdsa::VerifyingKey),
+  P384(p384::ecdsa::VerifyingKey),
+  P521(p521::ecdsa::VerifyingKey),
+  #[cfg(feature = "k256")]
+  K256(k256::ecdsa::VerifyingKey),
+}
+
+impl VerifyingKey {
+  /// Parse the SEC1 encoded point `public_key`.
+  pub(crate) fn from_sec1(
+    named_curve: NamedCurve,
+    public_key: &[u8],
+  ) -> Result<Self, WebCryptoError> {
+    Ok(match named_curve {
+      NamedCurve::P256 => VerifyingKey::P256(
+        p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key)
+          .map_err(|_| WebCryptoError::OperationError)?,
+      ),
+      NamedCurve::P384 => VerifyingKey::P384(
+        p384::ecdsa::VerifyingKey::from_sec1_bytes(public_key)
+          .map_err(|_| WebCryptoError::OperationError)?,
+      ),
+      NamedCurve::P521 => VerifyingKey::P521(
+        p521::ecdsa::VerifyingKey::from_sec1_bytes(public_key)
+          .map_err(|_| WebCryptoError::OperationError)?,
+      ),
+      #[cfg(feature = "k256")]
+      NamedCurve::K256 => VerifyingKey::K256(
+        k256::ecdsa::VerifyingKey::from_sec1_bytes(public_key)
+          .map_err(|_| WebCryptoError::OperationError)?,
+      ),
+    })
+  }
+
+  pub(crate) fn named_curve(&self) -> NamedCurve {
+    match self {
+      VerifyingKey::P256(_) => NamedCurve::P256,
+      VerifyingKey::P384(_) => NamedCurve::P384,
+      VerifyingKey::P521(_) => NamedCurve::P521,
+      #[cfg(feature = "k256")]
+      VerifyingKey::K256(_) => NamedCurve::K256,
+    }
+  }
+
+  /// Verify the `r || s` `signature` over the hashed message `digest`.
+  pub(crate) fn verify(&self, digest: &[u8], signature: &[u8]) -> bool {
+    macro_rules! verify {
+      ($curve:ident, $key:expr) => {
+        match $curve::ecdsa::Signature::from_slice(signature) {
+          Ok(signature) => $key
+            .verify_prehash(&prehash!($curve, digest), &signature)
+            .is_ok(),
+          Err(_) => false,
+        }
+      };
+    }
+
+    match self {
+      VerifyingKey::P256(key) => verify!(p256, key),
+      VerifyingKey::P384(key) => verify!(p384, key),
+      VerifyingKey::P521(key) => verify!(p521, key),
+      #[cfg(feature = "k256")]
+      VerifyingKey::K256(key) => verify!(k256, key),
+    }
+  }
+}
@@ -0,0 +1,226 @@
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::EdwardsPoint;
+use curve25519_dalek::Scalar;
+use ed25519_dalek::hazmat::ExpandedSecretKey;
+use ed25519_dalek::Digest;
+use ed25519_dalek::Signature;
+use ed25519_dalek::SigningKey;
+use ed25519_dalek::VerifyingKey;
+use rand::CryptoRng;
+use rand::RngCore;
+
+use crate::WebCryptoError;
+
+// Ed25519 and Ed25519ph (RFC 8032 section 5.1), on ed25519-dalek.
+//
+// Private keys are stored as the 32-byte seed and public keys as the
+// encoded point. ed25519-dalek only takes a context for Ed25519ph, so
+// Ed25519ctx, pure Ed25519 under a non-empty context, is computed here
+// on curve25519-dalek.
+
+pub(crate) const KEY_LENGTH: usize = 32;
+
+/// The running hash of Ed25519ph, PH(M) = SHA-512(M).
+pub(crate) type Prehash = ed25519_dalek::Sha512;
+
+fn signing_key(seed: &[u8]) -> Result<SigningKey, WebCryptoError> {
+  let seed = seed
+    .try_into()
+    .map_err(|_| WebCryptoError::OperationError)?;
+  Ok(SigningKey::from_bytes(seed))
+}
+
+/// Parse the public key `public_key`, `DataError` unless it encodes a
+/// point of the curve.
+pub(crate) fn verifying_key(
+  public_key: &[u8],
+) -> Result<VerifyingKey, WebCryptoError> {
+  let public_key = public_key
+    .try_into()
+    .map_err(|_| WebCryptoError::DataError)?;
+  VerifyingKey::from_bytes(public_key).map_err(|_| WebCryptoError::DataError)
+}
+
+/// The public key of the private key `seed`.
+pub(crate) fn public_key(seed: &[u8]) -> Result<Vec<u8>, WebCryptoError> {
+  let signing_key = signing_key(seed).map_err(|_| WebCryptoError::DataError)?;
+  Ok(signing_key.verifying_key().to_bytes().to_vec())
+}
+
+/// Check that `public_key` encodes a point of the curve.
+pub(crate) fn check_public_key(
+  public_key: &[u8],
+) -> Result<(), WebCryptoError> {
+  verifying_key(public_key).map(|_| ())
+}
+
+/// Generate a key pair, returning the private and the public key.
+pub(crate) fn generate<R: RngCore + CryptoRng>(
+  rng: &mut R,
+) -> (Vec<u8>, Vec<u8>) {
+  let mut seed = vec![0; KEY_LENGTH];
+  rng.fill_bytes(&mut seed);
+  let public_key = public_key(&seed).unwrap();
+  (seed, public_key)
+}
+
+/// Contexts are at most 255 bytes.
+fn check_context(context: &[u8]) -> Result<(), WebCryptoError> {
+  if context.len() > 255 {
+    return Err(WebCryptoError::OperationError);
+  }
+  Ok(())
+}
+
+/// dom2(0, context), the prefix of every Ed25519ctx hash.
+fn dom2(context: &[u8]) -> Prehash {
+  Prehash::new()
+    .chain_update(b"SigEd25519 no Ed25519 collisions")
+    .chain_update([0, context.len() as u8])
+    .chain_update(context)
+}
+
+/// The Ed25519ctx challenge SHA-512(dom2(0, context) || R || A || M).
+fn challenge(
+  context: &[u8],
+  r: &CompressedEdwardsY,
+  public_key: &[u8; KEY_LENGTH],
+  data: &[u8],
+) -> Scalar {
+  let hash = dom2(context)
+    .chain_update(r.as_bytes())
+    .chain_update(public_key)
+    .chain_update(data)
+    .finalize();
+  Scalar::from_bytes_mod_order_wide(&hash.into())
+}
+
+/// Sign `data` with Ed25519ctx under the non-empty `context`.
+fn sign_ctx(
+  seed: &[u8],
+  context: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let signing_key = signing_key(seed)?;
+  let expanded = ExpandedSecretKey::from(signing_key.as_bytes());
+  let hash = dom2(context)
+    .chain_update(expanded.hash_prefix)
+    .chain_update(data)
+    .finalize();
+  let r = Scalar::from_bytes_mod_order_wide(&hash.into());
+  let big_r = EdwardsPoint::mul_base(&r).compress();
+  let k = challenge(
+    context,
+    &big_r,
+    signing_key.verifying_key().as_bytes(),
+    data,
+  );
+  let s = r + k * expanded.scalar;
+
+  let mut signature = big_r.to_bytes().to_vec();
+  signature.extend_from_slice(s.as_bytes());
+  Ok(signature)
+}
+
+/// Verify an Ed25519ctx `signature` with the same strictness as
+/// `VerifyingKey::verify_strict`.
+fn verify_ctx(
+  public_key: &VerifyingKey,
+  context: &[u8],
+  signature: &[u8],
+  data: &[u8],
+) -> bool {
+  if signature.len() != 64 {
+    return false;
+  }
+  let big_r = CompressedEdwardsY::from_slice(&signature[..32]).unwrap();
+  let s: Option<Scalar> =
+    Scalar::from_canonical_bytes(signature[32..].try_into().unwrap()).into();
+  let (s, r_point) = match (s, big_r.decompress()) {
+    (Some(s), Some(r_point)) => (s, r_point),
+    _ => return false,
+  };
+  let a = public_key.to_edwards();
+  if r_point.is_small_order() || a.is_small_order() {
+    return false;
+  }
+  let k = challenge(context, &big_r, public_key.as_bytes(), data);
+  EdwardsPoint::vartime_double_scalar_mul_basepoint(&k, &-a, &s).compress()
+    == big_r
+}
+
+/// The Ed25519ph prehash of `data`.
+pub(crate) fn prehash(data: &[u8]) -> Prehash {
+  Prehash::new().chain_update(data)
+}
+
+/// Sign `data` with pure Ed25519, or with Ed25519ctx when `context` is
+/// not empty.
+pub(crate) fn sign(
+  seed: &[u8],
+  context: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  check_context(context)?;
+  if !context.is_empty() {
+    return sign_ctx(seed, context, data);
+  }
+  let signing_key = signing_key(seed)?;
+  Ok(
+    ed25519_dalek::Signer::sign(&signing_key, data)
+      .to_bytes()
+      .to_vec(),
+  )
+}
+
+/// Sign the Ed25519ph `prehash` under `context`.
+pub(crate) fn sign_prehashed(
+  seed: &[u8],
+  context: &[u8],
+  prehash: Prehash,
+) -> Result<Vec<u8>, WebCryptoError> {
+  check_context(context)?;
+  let signature = signing_key(seed)?
+    .sign_prehashed(prehash, Some(context))
+    .map_err(|_| WebCryptoError::OperationError)?;
+  Ok(signature.to_bytes().to_vec())
+}
+
+/// Verify `signature` over `data` with pure Ed25519, or with Ed25519ctx
+/// when `context` is not empty, rejecting non-canonical signatures and
+/// small order keys.
+pub(crate) fn verify(
+  public_key: &VerifyingKey,
+  context: &[u8],
+  signature: &[u8],
+  data: &[u8],
+) -> Result<bool, WebCryptoError> {
+  check_context(context)?;
+  if !context.is_empty() {
+    return Ok(verify_ctx(public_key, context, signature, data));
+  }
+  let signature = match Signature::from_slice(signature) {
+    Ok(signature) => signature,
+    Err(_) => return Ok(false),
+  };
+  Ok(public_key.verify_strict(data, &signature).is_ok())
+}
+
+/// Verify `signature` over the Ed25519ph `prehash` under `context`.
+pub(crate) fn verify_prehashed(
+  public_key: &VerifyingKey,
+  context: &[u8],
+  signature: &[u8],
+  prehash: Prehash,
+) -> Result<bool, WebCryptoError> {
+  check_context(context)?;
+  let signature = match Signature::from_slice(signature) {
+    Ok(signature) => signature,
+    Err(_) => return Ok(false),
+  };
+  Ok(
+    public_key
+      .verify_prehashed_strict(prehash, Some(context), &signature)
+      .is_ok(),
+  )
+}
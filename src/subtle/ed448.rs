@@ -0,0 +1,66 @@
+use openssl::pkey::Id;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use openssl::sign::Verifier;
+use rand::CryptoRng;
+use rand::RngCore;
+
+use crate::WebCryptoError;
+
+// Ed448 (RFC 8032 section 5.2), on OpenSSL.
+//
+// Private keys are stored as the 57-byte seed and public keys as the
+// encoded point. OpenSSL only decodes the point when verifying, so a
+// public key that is not on the curve imports but verifies nothing.
+
+pub(crate) const KEY_LENGTH: usize = 57;
+
+/// The public key of the private key `seed`.
+pub(crate) fn public_key(seed: &[u8]) -> Result<Vec<u8>, WebCryptoError> {
+  if seed.len() != KEY_LENGTH {
+    return Err(WebCryptoError::DataError);
+  }
+  PKey::private_key_from_raw_bytes(seed, Id::ED448)
+    .and_then(|key| key.raw_public_key())
+    .map_err(|_| WebCryptoError::DataError)
+}
+
+/// Generate a key pair, returning the private and the public key.
+pub(crate) fn generate<R: RngCore + CryptoRng>(
+  rng: &mut R,
+) -> (Vec<u8>, Vec<u8>) {
+  let mut seed = vec![0; KEY_LENGTH];
+  rng.fill_bytes(&mut seed);
+  let public_key = public_key(&seed).unwrap();
+  (seed, public_key)
+}
+
+/// Sign `data` with pure Ed448, under an empty context.
+pub(crate) fn sign(
+  seed: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  if seed.len() != KEY_LENGTH {
+    return Err(WebCryptoError::OperationError);
+  }
+  let key = PKey::private_key_from_raw_bytes(seed, Id::ED448)
+    .map_err(|_| WebCryptoError::OperationError)?;
+  Signer::new_without_digest(&key)
+    .and_then(|mut signer| signer.sign_oneshot_to_vec(data))
+    .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// Verify `signature` over `data` with pure Ed448.
+pub(crate) fn verify(
+  public_key: &[u8],
+  signature: &[u8],
+  data: &[u8],
+) -> Result<bool, WebCryptoError> {
+  let key = PKey::public_key_from_raw_bytes(public_key, Id::ED448)
+    .map_err(|_| WebCryptoError::OperationError)?;
+  let mut verifier = Verifier::new_without_digest(&key)
+    .map_err(|_| WebCryptoError::OperationError)?;
+  // OpenSSL fails, rather than returning false, on a malformed signature
+  // or key.
+  Ok(verifier.verify_oneshot(signature, data).unwrap_or(false))
+}
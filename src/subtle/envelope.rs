@@ -0,0 +1,268 @@
+// A self-describing ciphertext format for `SubtleCrypto::seal` and
+// `SubtleCrypto::open`. The header names the algorithm and key of the
+// ciphertext, so envelopes sealed today still open after the default
+// algorithm changes.
+//
+// Layout, all lengths big-endian:
+//
+//   magic "WCEV" | version (1) | algorithm id (1)
+//   | key id length (1) | key id | nonce length (1) | nonce
+//   | additional data length (4) | additional data | ciphertext
+//
+// The whole header is the additional data of the AEAD, so none of it
+// can be changed without failing to open.
+//
+// Streamed envelopes, version 2, add the plaintext length of their
+// chunks after the additional data, and the ciphertext is the chunks of
+// the STREAM construction (see `StreamNonces`), the nonce of the header
+// being the nonce prefix:
+//
+//   ... | additional data | chunk length (4) | chunk | ... | last chunk
+//
+// Every chunk but the last is a full chunk, and every chunk
+// authenticates the whole header.
+
+use std::io::Read;
+
+use super::stream;
+use super::CryptoKey;
+use super::StreamNonces;
+use crate::WebCryptoError;
+use sha2::Digest;
+use sha2::Sha256;
+
+const MAGIC: &[u8; 4] = b"WCEV";
+
+/// The version of envelopes sealed by this version of the crate.
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// The version of streamed envelopes, see `SubtleCrypto::seal_stream`.
+pub const STREAM_ENVELOPE_VERSION: u8 = 2;
+
+/// The bytes each chunk of a streamed envelope grows by: the tag of every
+/// envelope algorithm is 16 bytes.
+pub(crate) const CHUNK_OVERHEAD: usize = 16;
+
+/// The key ID prefix, separating key IDs from other hashes of keys.
+const KEY_ID_CONTEXT: &[u8] = b"webcrypto envelope key id\0";
+
+/// Algorithm IDs of the envelope header. IDs are never reused; new
+/// algorithms get new IDs.
+const ALGORITHMS: &[(u8, &str)] = &[
+  (1, "AES-GCM"),
+  (2, "ChaCha20-Poly1305"),
+  (3, "XChaCha20-Poly1305"),
+];
+
+/// The header of an envelope.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvelopeHeader {
+  pub version: u8,
+  /// The algorithm the ciphertext was encrypted with, eg. "AES-GCM".
+  pub algorithm: &'static str,
+  /// The ID of the key, see `SubtleCrypto::envelope_key_id`. It picks
+  /// the key to open the envelope with out of a key ring.
+  pub key_id: Vec<u8>,
+  pub nonce: Vec<u8>,
+  /// Data authenticated but not encrypted, carried in the clear.
+  pub additional_data: Vec<u8>,
+  /// The plaintext length of the chunks of a streamed envelope, `None`
+  /// for other envelopes.
+  pub chunk_length: Option<u32>,
+}
+
+/// The longest chunks of streamed envelopes, bounding the memory opening
+/// one takes.
+pub const MAX_CHUNK_LENGTH: u32 = 1 << 24;
+
+/// The state of sealing a streamed envelope.
+pub(crate) struct StreamSealer {
+  pub(crate) algorithm: &'static str,
+  pub(crate) header_bytes: Vec<u8>,
+  pub(crate) nonces: StreamNonces,
+  pub(crate) chunk_length: usize,
+  /// Plaintext not sealed yet: a full chunk is sealed once more follows.
+  pub(crate) pending: Vec<u8>,
+}
+
+/// How `Context::reencrypt` seals: the new key, whose algorithm picks the
+/// envelope algorithm like `SubtleCrypto::seal`, and the chunk length of
+/// the new streamed envelope.
+pub struct ReencryptParams<'a, H> {
+  pub key: &'a CryptoKey<H>,
+  pub chunk_length: u32,
+}
+
+impl EnvelopeHeader {
+  /// Read the header of `envelope`, without authenticating it.
+  pub fn from_envelope(envelope: &[u8]) -> Result<Self, WebCryptoError> {
+    decode(envelope).map(|(header, _)| header)
+  }
+}
+
+/// The algorithm `seal` uses with keys of algorithm `key_algorithm`.
+/// ChaCha20-Poly1305 keys seal with XChaCha20-Poly1305, whose nonces can
+/// safely be chosen at random.
+pub(crate) fn seal_algorithm(
+  key_algorithm: &str,
+) -> Result<&'static str, WebCryptoError> {
+  match key_algorithm {
+    "AES-GCM" => Ok("AES-GCM"),
+    "ChaCha20-Poly1305" => Ok("XChaCha20-Poly1305"),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The algorithm of the keys `algorithm` encrypts with.
+pub(crate) fn key_algorithm(algorithm: &str) -> &str {
+  match algorithm {
+    "XChaCha20-Poly1305" => "ChaCha20-Poly1305",
+    _ => algorithm,
+  }
+}
+
+pub(crate) fn nonce_length(algorithm: &str) -> usize {
+  match algorithm {
+    "XChaCha20-Poly1305" => 24,
+    _ => 12,
+  }
+}
+
+/// The 8-byte ID of the key with secret key material `key_material`.
+pub(crate) fn key_id(key_material: &[u8]) -> Vec<u8> {
+  let mut hasher = Sha256::new();
+  hasher.update(KEY_ID_CONTEXT);
+  hasher.update(key_material);
+  hasher.finalize()[..8].to_vec()
+}
+
+pub(crate) fn encode(
+  header: &EnvelopeHeader,
+) -> Result<Vec<u8>, WebCryptoError> {
+  let id = ALGORITHMS
+    .iter()
+    .find(|(_, name)| *name == header.algorithm)
+    .map(|(id, _)| *id)
+    .ok_or(WebCryptoError::NotSupportedError)?;
+  let streamed = match (header.version, header.chunk_length) {
+    (ENVELOPE_VERSION, None) => false,
+    (STREAM_ENVELOPE_VERSION, Some(1..=MAX_CHUNK_LENGTH)) => true,
+    _ => return Err(WebCryptoError::OperationError),
+  };
+  if header.key_id.len() > u8::MAX as usize
+    || header.nonce.len() > u8::MAX as usize
+    || header.additional_data.len() as u64 > u32::MAX as u64
+  {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  let mut bytes = MAGIC.to_vec();
+  bytes.push(header.version);
+  bytes.push(id);
+  bytes.push(header.key_id.len() as u8);
+  bytes.extend_from_slice(&header.key_id);
+  bytes.push(header.nonce.len() as u8);
+  bytes.extend_from_slice(&header.nonce);
+  bytes.extend_from_slice(&(header.additional_data.len() as u32).to_be_bytes());
+  bytes.extend_from_slice(&header.additional_data);
+  if streamed {
+    bytes.extend_from_slice(&header.chunk_length.unwrap_or(0).to_be_bytes());
+  }
+  Ok(bytes)
+}
+
+/// Split `input` into its first `length` bytes and the rest.
+fn take(input: &mut &[u8], length: usize) -> Result<Vec<u8>, WebCryptoError> {
+  if input.len() < length {
+    return Err(WebCryptoError::DataError);
+  }
+  let (taken, rest) = input.split_at(length);
+  *input = rest;
+  Ok(taken.to_vec())
+}
+
+/// The header of `envelope` and its length in bytes.
+pub(crate) fn decode(
+  envelope: &[u8],
+) -> Result<(EnvelopeHeader, usize), WebCryptoError> {
+  let mut input = envelope;
+  if take(&mut input, 4)? != MAGIC {
+    return Err(WebCryptoError::DataError);
+  }
+  let version = take(&mut input, 1)?[0];
+  if version != ENVELOPE_VERSION && version != STREAM_ENVELOPE_VERSION {
+    return Err(WebCryptoError::NotSupportedError);
+  }
+  let id = take(&mut input, 1)?[0];
+  let algorithm = ALGORITHMS
+    .iter()
+    .find(|(algorithm_id, _)| *algorithm_id == id)
+    .map(|(_, name)| *name)
+    .ok_or(WebCryptoError::NotSupportedError)?;
+
+  let length = take(&mut input, 1)?[0] as usize;
+  let key_id = take(&mut input, length)?;
+  let length = take(&mut input, 1)?[0] as usize;
+  let nonce = take(&mut input, length)?;
+  let length = take(&mut input, 4)?;
+  let length = u32::from_be_bytes(length.try_into().unwrap()) as usize;
+  let additional_data = take(&mut input, length)?;
+  let chunk_length = if version == STREAM_ENVELOPE_VERSION {
+    let length = take(&mut input, 4)?;
+    match u32::from_be_bytes(length.try_into().unwrap()) {
+      length @ 1..=MAX_CHUNK_LENGTH => Some(length),
+      _ => return Err(WebCryptoError::DataError),
+    }
+  } else {
+    None
+  };
+
+  let header = EnvelopeHeader {
+    version,
+    algorithm,
+    key_id,
+    nonce,
+    additional_data,
+    chunk_length,
+  };
+  Ok((header, envelope.len() - input.len()))
+}
+
+/// Read `length` bytes of `reader` onto `bytes`, returning them.
+fn read<'a, Rd: Read>(
+  reader: &mut Rd,
+  bytes: &'a mut Vec<u8>,
+  length: usize,
+) -> Result<&'a [u8], WebCryptoError> {
+  let start = bytes.len();
+  bytes.resize(start + length, 0);
+  if stream::read_full(reader, &mut bytes[start..])? != length {
+    return Err(WebCryptoError::DataError);
+  }
+  Ok(&bytes[start..])
+}
+
+/// Read the header of a streamed envelope from `reader`, returning it
+/// with its encoding.
+pub(crate) fn read_stream_header<Rd: Read>(
+  reader: &mut Rd,
+) -> Result<(EnvelopeHeader, Vec<u8>), WebCryptoError> {
+  // The header up to the key ID, then each length and what it covers.
+  let mut bytes = vec![];
+  let start = read(reader, &mut bytes, 7)?;
+  if start[..4] != MAGIC[..] {
+    return Err(WebCryptoError::DataError);
+  }
+  if start[4] != STREAM_ENVELOPE_VERSION {
+    return Err(WebCryptoError::NotSupportedError);
+  }
+  let length = start[6] as usize;
+  let length = read(reader, &mut bytes, length + 1)?[length] as usize;
+  let length = read(reader, &mut bytes, length + 4)?;
+  let length =
+    u32::from_be_bytes(length[length.len() - 4..].try_into().unwrap());
+  read(reader, &mut bytes, length as usize + 4)?;
+
+  let (header, _) = decode(&bytes)?;
+  Ok((header, bytes))
+}
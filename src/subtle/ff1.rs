@@ -0,0 +1,273 @@
+// FF1 format-preserving encryption (NIST SP 800-38G Rev. 1): a 10-round
+// Feistel network over numeral strings, so a 16-digit card number
+// encrypts to another 16-digit string.
+//
+// Numerals are the positions of the symbols of a caller alphabet of at
+// most 256 distinct bytes, so the radix is the alphabet length.
+
+use aes::cipher::consts::U16;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::BlockEncrypt;
+use aes::cipher::KeyInit;
+use aes::Aes128;
+use aes::Aes192;
+use aes::Aes256;
+
+use crate::WebCryptoError;
+use rsa::BigUint;
+
+type Block = GenericArray<u8, U16>;
+
+const ROUNDS: u8 = 10;
+
+/// The smallest domain, radix^minlen, SP 800-38G Rev. 1 allows.
+const MIN_DOMAIN_SIZE: u32 = 1_000_000;
+
+/// radix^length.
+fn pow(radix: u32, length: usize) -> BigUint {
+  let mut digits = vec![0; length + 1];
+  digits[0] = 1;
+  BigUint::from_radix_be(&digits, radix).unwrap()
+}
+
+/// The number in base `radix` of the numeral string `x`, most
+/// significant numeral first.
+fn num(x: &[u8], radix: u32) -> BigUint {
+  if x.is_empty() {
+    return BigUint::default();
+  }
+  BigUint::from_radix_be(x, radix).unwrap()
+}
+
+/// The `length` numerals of `x` in base `radix`.
+fn string(x: &BigUint, radix: u32, length: usize) -> Vec<u8> {
+  let digits = x.to_radix_be(radix);
+  let mut numerals = vec![0; length - digits.len()];
+  numerals.extend_from_slice(&digits);
+  numerals
+}
+
+/// `x` as `length` big-endian bytes.
+fn bytes(x: &BigUint, length: usize) -> Vec<u8> {
+  let bytes = x.to_bytes_be();
+  let mut padded = vec![0; length - bytes.len()];
+  padded.extend_from_slice(&bytes);
+  padded
+}
+
+/// Check the alphabet and the length of `data`, returning the radix and
+/// the numerals of `data`.
+fn numerals(
+  alphabet: &[u8],
+  data: &[u8],
+) -> Result<(u32, Vec<u8>), WebCryptoError> {
+  let radix = alphabet.len() as u32;
+  let unique = alphabet
+    .iter()
+    .enumerate()
+    .all(|(i, symbol)| !alphabet[..i].contains(symbol));
+  if !(2..=256).contains(&radix) || !unique {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  // minlen: at least 2 numerals and a million values. Radix 256 is the
+  // only one where 2 numerals are too few.
+  let min_length = (2..)
+    .find(|&length| pow(radix, length) >= BigUint::from(MIN_DOMAIN_SIZE))
+    .unwrap();
+  if data.len() < min_length || data.len() as u64 > u32::MAX as u64 {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  let numerals = data
+    .iter()
+    .map(|symbol| alphabet.iter().position(|a| a == symbol).map(|i| i as u8))
+    .collect::<Option<Vec<_>>>()
+    .ok_or(WebCryptoError::OperationError)?;
+  Ok((radix, numerals))
+}
+
+struct Feistel<C> {
+  cipher: C,
+  radix: u32,
+  /// The length of the numeral strings.
+  n: usize,
+  /// The length of the first half.
+  u: usize,
+  tweak: Vec<u8>,
+  /// The length in bytes of the halves as numbers, b.
+  b: usize,
+  /// The length in bytes of the round function outputs, d.
+  d: usize,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + KeyInit> Feistel<C> {
+  fn new(
+    key: &[u8],
+    radix: u32,
+    n: usize,
+    tweak: &[u8],
+  ) -> Result<Self, WebCryptoError> {
+    if tweak.len() as u64 > u32::MAX as u64 {
+      return Err(WebCryptoError::OperationError);
+    }
+    let cipher =
+      C::new_from_slice(key).map_err(|_| WebCryptoError::OperationError)?;
+
+    let u = n / 2;
+    let v = n - u;
+    // ceil(ceil(v * log2(radix)) / 8), the byte length of radix^v - 1.
+    let b = (pow(radix, v) - 1u32).bits().div_ceil(8);
+    let d = 4 * b.div_ceil(4) + 4;
+
+    Ok(Feistel {
+      cipher,
+      radix,
+      n,
+      u,
+      tweak: tweak.to_vec(),
+      b,
+      d,
+    })
+  }
+
+  /// AES CBC-MAC with a zero IV of `data`, a whole number of blocks.
+  fn prf(&self, data: &[u8]) -> Block {
+    let mut y = Block::default();
+    for block in data.chunks(16) {
+      y.iter_mut().zip(block).for_each(|(a, b)| *a ^= b);
+      self.cipher.encrypt_block(&mut y);
+    }
+    y
+  }
+
+  /// The round function of round `i` on the numeral string `x`.
+  fn round(&self, i: u8, x: &[u8]) -> BigUint {
+    let t = self.tweak.len();
+
+    let mut p = vec![1, 2, 1];
+    p.extend_from_slice(&self.radix.to_be_bytes()[1..]);
+    p.push(10);
+    p.push(self.u as u8);
+    p.extend_from_slice(&(self.n as u32).to_be_bytes());
+    p.extend_from_slice(&(t as u32).to_be_bytes());
+
+    let mut q = self.tweak.clone();
+    let padding = (16 - (t + self.b + 1) % 16) % 16;
+    q.resize(t + padding, 0);
+    q.push(i);
+    q.extend_from_slice(&bytes(&num(x, self.radix), self.b));
+
+    p.extend_from_slice(&q);
+    let r = self.prf(&p);
+
+    let mut s = r.to_vec();
+    for j in 1..self.d.div_ceil(16) as u128 {
+      let mut block =
+        Block::from((u128::from_be_bytes(r.into()) ^ j).to_be_bytes());
+      self.cipher.encrypt_block(&mut block);
+      s.extend_from_slice(&block);
+    }
+    s.truncate(self.d);
+
+    BigUint::from_bytes_be(&s)
+  }
+
+  /// The length of the half replaced in round `i`.
+  fn m(&self, i: u8) -> usize {
+    if i.is_multiple_of(2) {
+      self.u
+    } else {
+      self.n - self.u
+    }
+  }
+
+  fn encrypt(&self, x: &[u8]) -> Vec<u8> {
+    let (a, b) = x.split_at(self.u);
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+
+    for i in 0..ROUNDS {
+      let m = self.m(i);
+      let y = self.round(i, &b);
+      let c = (num(&a, self.radix) + y) % pow(self.radix, m);
+      a = std::mem::replace(&mut b, string(&c, self.radix, m));
+    }
+
+    a.extend_from_slice(&b);
+    a
+  }
+
+  fn decrypt(&self, x: &[u8]) -> Vec<u8> {
+    let (a, b) = x.split_at(self.u);
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+
+    for i in (0..ROUNDS).rev() {
+      let m = self.m(i);
+      let modulus = pow(self.radix, m);
+      let y = self.round(i, &a) % &modulus;
+      let c = (num(&b, self.radix) + &modulus - y) % &modulus;
+      b = std::mem::replace(&mut a, string(&c, self.radix, m));
+    }
+
+    a.extend_from_slice(&b);
+    a
+  }
+}
+
+fn feistel<C: BlockEncrypt<BlockSize = U16> + KeyInit>(
+  key: &[u8],
+  tweak: &[u8],
+  alphabet: &[u8],
+  data: &[u8],
+  decrypt: bool,
+) -> Result<Vec<u8>, WebCryptoError> {
+  let (radix, x) = numerals(alphabet, data)?;
+  let feistel = Feistel::<C>::new(key, radix, x.len(), tweak)?;
+
+  let y = if decrypt {
+    feistel.decrypt(&x)
+  } else {
+    feistel.encrypt(&x)
+  };
+  Ok(
+    y.iter()
+      .map(|&numeral| alphabet[numeral as usize])
+      .collect(),
+  )
+}
+
+fn ff1(
+  key: &[u8],
+  tweak: &[u8],
+  alphabet: &[u8],
+  data: &[u8],
+  decrypt: bool,
+) -> Result<Vec<u8>, WebCryptoError> {
+  match key.len() {
+    16 => feistel::<Aes128>(key, tweak, alphabet, data, decrypt),
+    24 => feistel::<Aes192>(key, tweak, alphabet, data, decrypt),
+    32 => feistel::<Aes256>(key, tweak, alphabet, data, decrypt),
+    _ => Err(WebCryptoError::OperationError),
+  }
+}
+
+/// Encrypt the string `data` of symbols of `alphabet` under the AES key
+/// `key` and `tweak`, returning a string of the same length over the
+/// same alphabet.
+pub(crate) fn encrypt(
+  key: &[u8],
+  tweak: &[u8],
+  alphabet: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  ff1(key, tweak, alphabet, data, false)
+}
+
+pub(crate) fn decrypt(
+  key: &[u8],
+  tweak: &[u8],
+  alphabet: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  ff1(key, tweak, alphabet, data, true)
+}
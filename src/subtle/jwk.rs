@@ -0,0 +1,454 @@
+use rsa::pkcs1::ToRsaPrivateKey;
+use rsa::pkcs1::ToRsaPublicKey;
+use rsa::BigUint;
+use rsa::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use rsa::RsaPublicKey;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::ec;
+use super::okp;
+use super::KeyType;
+use super::KeyUsage;
+use super::NamedCurve;
+use crate::WebCryptoError;
+
+/// A JSON Web Key (RFC 7517).
+///
+/// All binary members are base64url encoded without padding. This is
+/// the format accepted by `import_key` and produced by `export_key`
+/// for "jwk", serialized as JSON.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct Jwk {
+  pub kty: String,
+  #[serde(rename = "use", skip_serializing_if = "Option::is_none")]
+  pub use_: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub key_ops: Option<Vec<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub alg: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ext: Option<bool>,
+
+  // EC and OKP members.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub crv: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub x: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub y: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub d: Option<String>,
+
+  // RSA members. `d` is shared with EC and OKP.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub n: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub e: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub p: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub q: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub dp: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub dq: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub qi: Option<String>,
+
+  // oct members.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub k: Option<String>,
+}
+
+fn encode(bytes: &[u8]) -> String {
+  base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(value: &Option<String>) -> Result<Vec<u8>, WebCryptoError> {
+  match value {
+    Some(value) => base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+      .map_err(|_| WebCryptoError::DataError),
+    None => Err(WebCryptoError::DataError),
+  }
+}
+
+fn decode_uint(value: &Option<String>) -> Result<BigUint, WebCryptoError> {
+  Ok(BigUint::from_bytes_be(&decode(value)?))
+}
+
+pub(crate) fn key_op(usage: &KeyUsage) -> &'static str {
+  match usage {
+    KeyUsage::Encrypt => "encrypt",
+    KeyUsage::Decrypt => "decrypt",
+    KeyUsage::Sign => "sign",
+    KeyUsage::Verify => "verify",
+    KeyUsage::WrapKey => "wrapKey",
+    KeyUsage::UnwrapKey => "unwrapKey",
+    KeyUsage::DeriveKey => "deriveKey",
+    KeyUsage::DeriveBits => "deriveBits",
+    KeyUsage::EncapsulateBits => "encapsulateBits",
+    KeyUsage::DecapsulateBits => "decapsulateBits",
+  }
+}
+
+/// Every `key_ops` value, those of RFC 7517 and the KEM operations.
+const KEY_OPS: &[&str] = &[
+  "encrypt",
+  "decrypt",
+  "sign",
+  "verify",
+  "wrapKey",
+  "unwrapKey",
+  "deriveKey",
+  "deriveBits",
+  "encapsulateBits",
+  "decapsulateBits",
+];
+
+/// The JWK `use` of keys of `name`: "sig" for signature and MAC
+/// algorithms, "enc" for the others.
+pub(crate) fn key_use(name: &str) -> &'static str {
+  match name {
+    "RSASSA-PKCS1-v1_5" | "RSA-PSS" | "ECDSA" | "Ed25519" | "Ed448"
+    | "HMAC" | "AES-CMAC" | "BLAKE3" => "sig",
+    _ => "enc",
+  }
+}
+
+/// The JWK `alg` value for an AES key of `length` bits.
+pub(crate) fn aes_alg(name: &str, length: usize) -> Option<String> {
+  let mode = match name {
+    "AES-CTR" => "CTR",
+    "AES-CBC" => "CBC",
+    "AES-GCM" => "GCM",
+    "AES-KW" => "KW",
+    _ => return None,
+  };
+  Some(format!("A{}{}", length, mode))
+}
+
+/// The JWK `alg` value for an HMAC key using `hash`.
+pub(crate) fn hmac_alg(hash: &str) -> Option<&'static str> {
+  match hash {
+    "SHA-1" => Some("HS1"),
+    "SHA-256" => Some("HS256"),
+    "SHA-384" => Some("HS384"),
+    "SHA-512" => Some("HS512"),
+    _ => None,
+  }
+}
+
+/// The JWK `alg` value for the RSA algorithm `name` using `hash`.
+pub(crate) fn rsa_alg(name: &str, hash: &str) -> Option<&'static str> {
+  match (name, hash) {
+    ("RSASSA-PKCS1-v1_5", "SHA-1") => Some("RS1"),
+    ("RSASSA-PKCS1-v1_5", "SHA-256") => Some("RS256"),
+    ("RSASSA-PKCS1-v1_5", "SHA-384") => Some("RS384"),
+    ("RSASSA-PKCS1-v1_5", "SHA-512") => Some("RS512"),
+    ("RSA-PSS", "SHA-1") => Some("PS1"),
+    ("RSA-PSS", "SHA-256") => Some("PS256"),
+    ("RSA-PSS", "SHA-384") => Some("PS384"),
+    ("RSA-PSS", "SHA-512") => Some("PS512"),
+    ("RSA-OAEP", "SHA-1") => Some("RSA-OAEP"),
+    ("RSA-OAEP", "SHA-256") => Some("RSA-OAEP-256"),
+    ("RSA-OAEP", "SHA-384") => Some("RSA-OAEP-384"),
+    ("RSA-OAEP", "SHA-512") => Some("RSA-OAEP-512"),
+    _ => None,
+  }
+}
+
+/// The JWK `crv` value of `named_curve`.
+pub(crate) fn ec_crv(named_curve: NamedCurve) -> &'static str {
+  match named_curve {
+    NamedCurve::P256 => "P-256",
+    NamedCurve::P384 => "P-384",
+    NamedCurve::P521 => "P-521",
+    #[cfg(feature = "k256")]
+    NamedCurve::K256 => "secp256k1",
+  }
+}
+
+/// The JWK `alg` value that ECDSA keys imported on `named_curve` may
+/// have. ECDH keys have none.
+pub(crate) fn ec_alg(
+  name: &str,
+  named_curve: NamedCurve,
+) -> Option<&'static str> {
+  match (name, named_curve) {
+    ("ECDSA", NamedCurve::P256) => Some("ES256"),
+    ("ECDSA", NamedCurve::P384) => Some("ES384"),
+    ("ECDSA", NamedCurve::P521) => Some("ES512"),
+    #[cfg(feature = "k256")]
+    ("ECDSA", NamedCurve::K256) => Some("ES256K"),
+    _ => None,
+  }
+}
+
+/// The JWK `alg` value for the OKP algorithm `name`.
+pub(crate) fn okp_alg(name: &str) -> Option<&'static str> {
+  match name {
+    "Ed25519" | "Ed448" => Some("EdDSA"),
+    _ => None,
+  }
+}
+
+impl Jwk {
+  pub(crate) fn from_slice(key_data: &[u8]) -> Result<Self, WebCryptoError> {
+    serde_json::from_slice(key_data).map_err(|_| WebCryptoError::DataError)
+  }
+
+  pub(crate) fn to_vec(&self) -> Result<Vec<u8>, WebCryptoError> {
+    serde_json::to_vec(self).map_err(|_| WebCryptoError::OperationError)
+  }
+
+  /// Run the checks shared by all key types: `kty`, `use`, `key_ops`,
+  /// `ext` and `alg` against the requested import of a `name` key.
+  pub(crate) fn check(
+    &self,
+    kty: &str,
+    name: &str,
+    alg: Option<&str>,
+    extractable: bool,
+    usages: &[KeyUsage],
+  ) -> Result<(), WebCryptoError> {
+    if self.kty != kty {
+      return Err(WebCryptoError::DataError);
+    }
+
+    if !usages.is_empty() {
+      if let Some(use_) = &self.use_ {
+        if use_ != key_use(name) {
+          return Err(WebCryptoError::DataError);
+        }
+      }
+    }
+
+    if let Some(key_ops) = &self.key_ops {
+      // RFC 7517, section 4.3: known operations, each listed once.
+      let known = |op: &String| KEY_OPS.contains(&op.as_str());
+      let unique = key_ops
+        .iter()
+        .enumerate()
+        .all(|(i, op)| !key_ops[..i].contains(op));
+      if !key_ops.iter().all(known) || !unique {
+        return Err(WebCryptoError::DataError);
+      }
+
+      if usages
+        .iter()
+        .any(|usage| !key_ops.iter().any(|op| op == key_op(usage)))
+      {
+        return Err(WebCryptoError::DataError);
+      }
+    }
+
+    if self.ext == Some(false) && extractable {
+      return Err(WebCryptoError::DataError);
+    }
+
+    if let Some(jwk_alg) = &self.alg {
+      if Some(jwk_alg.as_str()) != alg {
+        return Err(WebCryptoError::DataError);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Decode the `k` member of an "oct" key.
+  pub(crate) fn secret(&self) -> Result<Vec<u8>, WebCryptoError> {
+    decode(&self.k)
+  }
+
+  /// Decode an RSA key into PKCS#1 key material. The key is private
+  /// when the `d` member is present.
+  pub(crate) fn rsa_key_material(
+    &self,
+  ) -> Result<(KeyType, Vec<u8>), WebCryptoError> {
+    let n = decode_uint(&self.n)?;
+    let e = decode_uint(&self.e)?;
+
+    if self.d.is_none() {
+      let public_key =
+        RsaPublicKey::new(n, e).map_err(|_| WebCryptoError::DataError)?;
+      let pkcs1 = public_key
+        .to_pkcs1_der()
+        .map_err(|_| WebCryptoError::DataError)?;
+      return Ok((KeyType::Public, pkcs1.as_ref().to_vec()));
+    }
+
+    // Multi-prime keys (`oth`) are not supported.
+    let primes = vec![decode_uint(&self.p)?, decode_uint(&self.q)?];
+    let private_key =
+      RsaPrivateKey::from_components(n, e, decode_uint(&self.d)?, primes);
+    private_key
+      .validate()
+      .map_err(|_| WebCryptoError::DataError)?;
+
+    let pkcs1 = private_key
+      .to_pkcs1_der()
+      .map_err(|_| WebCryptoError::DataError)?;
+    Ok((KeyType::Private, pkcs1.as_ref().to_vec()))
+  }
+
+  /// Decode an EC key on `named_curve` into its key material: the
+  /// uncompressed point of `x` and `y`, or the private scalar when the
+  /// `d` member is present, whose public key has to be that point.
+  pub(crate) fn ec_key_material(
+    &self,
+    named_curve: NamedCurve,
+  ) -> Result<(KeyType, Vec<u8>), WebCryptoError> {
+    if self.crv.as_deref() != Some(ec_crv(named_curve)) {
+      return Err(WebCryptoError::DataError);
+    }
+
+    let x = decode(&self.x)?;
+    let y = decode(&self.y)?;
+    if x.len() != y.len() {
+      return Err(WebCryptoError::DataError);
+    }
+    let mut point = vec![0x04];
+    point.extend(x);
+    point.extend(y);
+    // DataError when the point is not on the curve.
+    let point = ec::import_raw(named_curve, &point)?;
+    if self.d.is_none() {
+      return Ok((KeyType::Public, point));
+    }
+
+    let d = ec::import_scalar(named_curve, &decode(&self.d)?)?;
+    if ec::public_key(named_curve, &d)? != point {
+      return Err(WebCryptoError::DataError);
+    }
+    Ok((KeyType::Private, d))
+  }
+
+  /// Decode an OKP key of the curve `name` into its key material. The
+  /// key is private when the `d` member is present, and its public key
+  /// has to match `x`.
+  pub(crate) fn okp_key_material(
+    &self,
+    name: &str,
+  ) -> Result<(KeyType, Vec<u8>), WebCryptoError> {
+    if self.crv.as_deref() != Some(name) {
+      return Err(WebCryptoError::DataError);
+    }
+
+    let x = decode(&self.x)?;
+    if self.d.is_none() {
+      okp::check_public_key(name, &x)?;
+      return Ok((KeyType::Public, x));
+    }
+
+    let d = decode(&self.d)?;
+    if d.len() != okp::key_length(name)? || okp::public_key(name, &d)? != x {
+      return Err(WebCryptoError::DataError);
+    }
+    Ok((KeyType::Private, d))
+  }
+
+  pub(crate) fn oct(key_data: &[u8], alg: Option<String>) -> Self {
+    Jwk {
+      kty: "oct".to_string(),
+      k: Some(encode(key_data)),
+      alg,
+      ..Default::default()
+    }
+  }
+
+  pub(crate) fn rsa_public(
+    public_key: &RsaPublicKey,
+    alg: Option<&str>,
+  ) -> Self {
+    Jwk {
+      kty: "RSA".to_string(),
+      n: Some(encode(&public_key.n().to_bytes_be())),
+      e: Some(encode(&public_key.e().to_bytes_be())),
+      alg: alg.map(String::from),
+      ..Default::default()
+    }
+  }
+
+  pub(crate) fn rsa_private(
+    private_key: &RsaPrivateKey,
+    alg: Option<&str>,
+  ) -> Result<Self, WebCryptoError> {
+    let (p, q) = match private_key.primes() {
+      [p, q] => (p, q),
+      _ => {
+        return Err(WebCryptoError::NotSupportedError);
+      }
+    };
+    let d = private_key.d();
+    let one = BigUint::from(1u8);
+    let two = BigUint::from(2u8);
+
+    // p is prime, so q^(p - 2) is the inverse of q modulo p.
+    let dp = d % (p - &one);
+    let dq = d % (q - &one);
+    let qi = q.modpow(&(p - &two), p);
+
+    Ok(Jwk {
+      d: Some(encode(&d.to_bytes_be())),
+      p: Some(encode(&p.to_bytes_be())),
+      q: Some(encode(&q.to_bytes_be())),
+      dp: Some(encode(&dp.to_bytes_be())),
+      dq: Some(encode(&dq.to_bytes_be())),
+      qi: Some(encode(&qi.to_bytes_be())),
+      ..Jwk::rsa_public(&private_key.to_public_key(), alg)
+    })
+  }
+
+  /// The EC public key `public_key`, an uncompressed SEC1 point. Like
+  /// browsers, no `alg` is set: ECDSA keys are not tied to a hash.
+  pub(crate) fn ec_public(named_curve: NamedCurve, public_key: &[u8]) -> Self {
+    let (x, y) = public_key[1..].split_at((public_key.len() - 1) / 2);
+    Jwk {
+      kty: "EC".to_string(),
+      crv: Some(ec_crv(named_curve).to_string()),
+      x: Some(encode(x)),
+      y: Some(encode(y)),
+      ..Default::default()
+    }
+  }
+
+  pub(crate) fn ec_private(
+    named_curve: NamedCurve,
+    public_key: &[u8],
+    private_key: &[u8],
+  ) -> Self {
+    Jwk {
+      d: Some(encode(private_key)),
+      ..Jwk::ec_public(named_curve, public_key)
+    }
+  }
+
+  pub(crate) fn okp_public(crv: &str, public_key: &[u8]) -> Self {
+    Jwk {
+      kty: "OKP".to_string(),
+      crv: Some(crv.to_string()),
+      x: Some(encode(public_key)),
+      alg: okp_alg(crv).map(String::from),
+      ..Default::default()
+    }
+  }
+
+  pub(crate) fn okp_private(
+    crv: &str,
+    public_key: &[u8],
+    private_key: &[u8],
+  ) -> Self {
+    Jwk {
+      d: Some(encode(private_key)),
+      ..Jwk::okp_public(crv, public_key)
+    }
+  }
+
+  pub(crate) fn set_key_ops(&mut self, usages: &[KeyUsage], extractable: bool) {
+    self.key_ops = Some(usages.iter().map(|u| key_op(u).to_string()).collect());
+    self.ext = Some(extractable);
+  }
+}
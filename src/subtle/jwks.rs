@@ -0,0 +1,435 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use rand::CryptoRng;
+use rand::RngCore;
+
+use serde::Deserialize;
+
+use super::AlgorithmIdentifer;
+use super::CryptoKey;
+use super::EcKeyImportParams;
+use super::EcdsaParams;
+use super::HashAlgorithmIdentifer;
+use super::ImportParams;
+use super::Jwk;
+use super::KeyUsage;
+use super::NamedCurve;
+use super::RsaHashedImportParams;
+use super::RsaPssParams;
+use super::SignParams;
+use super::SubtleCrypto;
+use super::VerifierPool;
+use crate::storage::KeyStorage;
+use crate::WebCryptoError;
+
+/// Fetches the current JWKS, eg. over HTTP, or `None` when it is
+/// unavailable.
+pub type JwksRefresh = Box<dyn FnMut() -> Option<Vec<u8>> + Send>;
+
+#[derive(Deserialize)]
+struct JwkSet {
+  keys: Vec<JwkSetKey>,
+}
+
+#[derive(Deserialize)]
+struct JwkSetKey {
+  kid: Option<String>,
+  #[serde(flatten)]
+  jwk: Jwk,
+}
+
+#[derive(Deserialize)]
+struct Header {
+  alg: String,
+  kid: Option<String>,
+}
+
+struct CachedKey {
+  alg: &'static str,
+  params: SignParams,
+  /// The `alg` and public key members of the JWK the key was imported
+  /// from, to notice a set binding the `kid` to another key.
+  members: String,
+  pinned_at: SystemTime,
+  /// The parsed key, the only one of the pool.
+  pool: VerifierPool,
+}
+
+struct KeySet {
+  keys: HashMap<String, CachedKey>,
+  loaded_at: Option<SystemTime>,
+}
+
+/// The JWS algorithms (RFC 7518) supported, and their hash.
+fn jws_algorithm(alg: &str) -> Option<(&'static str, &'static str)> {
+  Some(match alg {
+    "RS256" => ("RS256", "SHA-256"),
+    "RS384" => ("RS384", "SHA-384"),
+    "RS512" => ("RS512", "SHA-512"),
+    "PS256" => ("PS256", "SHA-256"),
+    "PS384" => ("PS384", "SHA-384"),
+    "PS512" => ("PS512", "SHA-512"),
+    "ES256" => ("ES256", "SHA-256"),
+    "ES384" => ("ES384", "SHA-384"),
+    "ES512" => ("ES512", "SHA-512"),
+    "EdDSA" => ("EdDSA", ""),
+    _ => return None,
+  })
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, WebCryptoError> {
+  base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+    .map_err(|_| WebCryptoError::DataError)
+}
+
+/// The parameters verifying `alg` with a key of the algorithm `name`,
+/// which tells the curve of "EdDSA".
+fn sign_params(
+  alg: &str,
+  hash: &'static str,
+  name: &'static str,
+) -> SignParams {
+  let hash = HashAlgorithmIdentifer { name: hash };
+  match &alg[..2] {
+    "RS" => SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+      name: "RSASSA-PKCS1-v1_5",
+    }),
+    "PS" => SignParams::RsaPssParams(RsaPssParams {
+      name: "RSA-PSS",
+      salt_length: None,
+    }),
+    "ES" => SignParams::EcdsaParams(EcdsaParams {
+      name: "ECDSA",
+      hash,
+      deterministic: false,
+      low_s: false,
+    }),
+    _ => SignParams::AlgorithmIdentifer(AlgorithmIdentifer { name }),
+  }
+}
+
+/// The `alg` and the public key members of `jwk`.
+fn public_members(alg: &str, jwk: &Jwk) -> String {
+  let members = [&jwk.crv, &jwk.x, &jwk.y, &jwk.n, &jwk.e];
+  let mut out = format!("{}.{}", alg, jwk.kty);
+  for member in members {
+    out.push('.');
+    out.push_str(member.as_deref().unwrap_or(""));
+  }
+  out
+}
+
+/// Import the public key `jwk` for verifying signatures of `alg`.
+fn import<R: RngCore + CryptoRng, S: KeyStorage>(
+  subtle: &mut SubtleCrypto<R, S>,
+  alg: &str,
+  hash: &'static str,
+  jwk: &Jwk,
+) -> Result<CryptoKey<S::Handle>, WebCryptoError> {
+  let hash = HashAlgorithmIdentifer { name: hash };
+  let usages = vec![KeyUsage::Verify];
+
+  match &alg[..2] {
+    "RS" | "PS" => {
+      let name = if alg.starts_with("RS") {
+        "RSASSA-PKCS1-v1_5"
+      } else {
+        "RSA-PSS"
+      };
+      let key_data =
+        serde_json::to_vec(jwk).map_err(|_| WebCryptoError::DataError)?;
+      let params = RsaHashedImportParams { name, hash };
+      subtle.import_key(
+        "jwk",
+        &key_data,
+        ImportParams::RsaHashedImportParams(params),
+        true,
+        usages,
+      )
+    }
+    "ES" => {
+      let named_curve = match alg {
+        "ES256" => NamedCurve::P256,
+        "ES384" => NamedCurve::P384,
+        _ => NamedCurve::P521,
+      };
+      let key_data =
+        serde_json::to_vec(jwk).map_err(|_| WebCryptoError::DataError)?;
+      let params = EcKeyImportParams {
+        name: "ECDSA",
+        named_curve,
+      };
+      subtle.import_key(
+        "jwk",
+        &key_data,
+        ImportParams::EcKeyImportParams(params),
+        true,
+        usages,
+      )
+    }
+    _ => {
+      // "EdDSA" names the algorithm of every OKP curve, told apart by
+      // `crv`.
+      let name = match (jwk.kty.as_str(), jwk.crv.as_deref()) {
+        ("OKP", Some("Ed25519")) => "Ed25519",
+        #[cfg(feature = "ed448")]
+        ("OKP", Some("Ed448")) => "Ed448",
+        _ => return Err(WebCryptoError::NotSupportedError),
+      };
+      let key_data =
+        serde_json::to_vec(jwk).map_err(|_| WebCryptoError::DataError)?;
+      subtle.import_key(
+        "jwk",
+        &key_data,
+        ImportParams::AlgorithmIdentifer(AlgorithmIdentifer { name }),
+        true,
+        usages,
+      )
+    }
+  }
+}
+
+/// Verifies JWS compact tokens, like JWTs, against the keys of a JSON Web
+/// Key Set (RFC 7517), eg. in an API gateway.
+///
+/// Keys are imported once, parsed into a `VerifierPool` and cached by
+/// `kid`; their key material is then deleted from the storage. Verifying
+/// takes `&self`: share the verifier between threads, eg. in an `Arc`.
+///
+/// A `kid` is pinned to the key first seen for it for the pin duration:
+/// until then, a set binding the same `kid` to another key keeps the
+/// pinned one. Afterwards the new key replaces it. Keys missing from a
+/// later set are dropped.
+///
+/// Once the set is older than its TTL, `refresh` calls the refresh hook
+/// and loads the new set. The cached keys stay in use while refreshing
+/// fails.
+pub struct JwksVerifier {
+  keys: RwLock<KeySet>,
+  ttl: Duration,
+  pin_duration: Duration,
+  refresh: Mutex<Option<JwksRefresh>>,
+}
+
+impl JwksVerifier {
+  /// Create a verifier without keys, refreshing them every `ttl`. Keys
+  /// are pinned for `ttl` too, see `set_pin_duration`.
+  pub fn new(ttl: Duration) -> Self {
+    JwksVerifier {
+      keys: RwLock::new(KeySet {
+        keys: HashMap::new(),
+        loaded_at: None,
+      }),
+      ttl,
+      pin_duration: ttl,
+      refresh: Mutex::new(None),
+    }
+  }
+
+  /// Call `refresh` for the current set once the cached one is stale.
+  pub fn set_refresh(&mut self, refresh: JwksRefresh) {
+    self.refresh = Mutex::new(Some(refresh));
+  }
+
+  /// Keep the key first seen for a `kid` for `pin_duration`, before a set
+  /// can bind the `kid` to another key.
+  pub fn set_pin_duration(&mut self, pin_duration: Duration) {
+    self.pin_duration = pin_duration;
+  }
+
+  /// The `kid` of each cached key.
+  pub fn kids(&self) -> Vec<String> {
+    self.keys.read().unwrap().keys.keys().cloned().collect()
+  }
+
+  /// Load the JWKS `jwks`, fetched at `time`. Keys without a `kid` or an
+  /// `alg`, with an unsupported `alg` or curve, or failing to import are
+  /// skipped.
+  ///
+  /// Returns the number of keys imported, or an error leaving the cache
+  /// unchanged when `jwks` is not a key set.
+  pub fn load<R: 'static + RngCore + CryptoRng + Copy, S: KeyStorage>(
+    &self,
+    subtle: &mut SubtleCrypto<R, S>,
+    jwks: &[u8],
+    time: SystemTime,
+  ) -> Result<usize, WebCryptoError> {
+    let set: JwkSet =
+      serde_json::from_slice(jwks).map_err(|_| WebCryptoError::DataError)?;
+
+    // Parse the new keys before taking the lock, so verification goes on
+    // meanwhile.
+    let mut candidates = Vec::new();
+    for JwkSetKey { kid, jwk } in set.keys {
+      let (kid, (alg, hash)) =
+        match (kid, jwk.alg.as_deref().and_then(jws_algorithm)) {
+          (Some(kid), Some(alg)) => (kid, alg),
+          _ => continue,
+        };
+      let members = public_members(alg, &jwk);
+      let unchanged = matches!(
+        self.keys.read().unwrap().keys.get(&kid),
+        Some(cached) if cached.members == members
+      );
+      if unchanged || candidates.iter().any(|(seen, _)| *seen == kid) {
+        candidates.push((kid, None));
+        continue;
+      }
+
+      let key = match import(subtle, alg, hash, &jwk) {
+        Ok(key) => key,
+        Err(_) => continue,
+      };
+      let pool = subtle.verifier_pool(&[&key]);
+      // The pool holds the parsed key: the storage does not need it.
+      subtle.delete_key(key.handle);
+      if let Ok(pool) = pool {
+        let params = sign_params(alg, hash, key.algorithm.name());
+        candidates.push((
+          kid,
+          Some(CachedKey {
+            alg,
+            params,
+            members,
+            pinned_at: time,
+            pool,
+          }),
+        ));
+      }
+    }
+
+    let mut set = self.keys.write().unwrap();
+    let mut keys = HashMap::new();
+    let mut imported = 0;
+    for (kid, candidate) in candidates {
+      if keys.contains_key(&kid) {
+        continue;
+      }
+      let cached = set.keys.remove(&kid);
+      let key = match (cached, candidate) {
+        // Pinned: keep the key first seen for `kid` while the pin lasts,
+        // even if the set now binds it to another key.
+        (Some(cached), Some(_))
+          if time < cached.pinned_at + self.pin_duration =>
+        {
+          cached
+        }
+        (_, Some(candidate)) => {
+          imported += 1;
+          candidate
+        }
+        (Some(cached), None) => cached,
+        (None, None) => continue,
+      };
+      keys.insert(kid, key);
+    }
+
+    set.keys = keys;
+    set.loaded_at = Some(time);
+    Ok(imported)
+  }
+
+  /// Whether the cached set is older than the TTL at `time`.
+  pub fn is_stale(&self, time: SystemTime) -> bool {
+    match self.keys.read().unwrap().loaded_at {
+      Some(loaded_at) => loaded_at + self.ttl <= time,
+      None => true,
+    }
+  }
+
+  /// Load the set of the refresh hook when the cached one is stale at
+  /// `time`. Returns whether a new set was loaded.
+  pub fn refresh<R: 'static + RngCore + CryptoRng + Copy, S: KeyStorage>(
+    &self,
+    subtle: &mut SubtleCrypto<R, S>,
+    time: SystemTime,
+  ) -> bool {
+    if !self.is_stale(time) {
+      return false;
+    }
+    let jwks = match self.refresh.lock().unwrap().as_mut() {
+      Some(refresh) => refresh(),
+      None => None,
+    };
+    // A bad set keeps the cached keys.
+    matches!(jwks, Some(jwks) if self.load(subtle, &jwks, time).is_ok())
+  }
+
+  /// Verify the JWS compact serialization `token`, returning its
+  /// payload. The `alg` of the header must be the one of the key named
+  /// by its `kid`.
+  ///
+  /// Only the signature is checked: claims like "exp" and "aud" are for
+  /// the caller to validate.
+  pub fn verify(&self, token: &str) -> Result<Vec<u8>, WebCryptoError> {
+    let mut parts = token.split('.');
+    let (header, payload, signature) =
+      match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(header), Some(payload), Some(signature), None) => {
+          (header, payload, signature)
+        }
+        _ => {
+          return Err(WebCryptoError::DataError);
+        }
+      };
+
+    let parsed: Header = serde_json::from_slice(&decode(header)?)
+      .map_err(|_| WebCryptoError::DataError)?;
+    let set = self.keys.read().unwrap();
+    let cached = match parsed.kid.and_then(|kid| set.keys.get(&kid)) {
+      Some(cached) if cached.alg == parsed.alg => cached,
+      _ => {
+        return Err(WebCryptoError::InvalidAccessError);
+      }
+    };
+
+    let signing_input = &token[..header.len() + 1 + payload.len()];
+    let signature = decode(signature)?;
+    if !cached.pool.verify(
+      0,
+      cached.params,
+      &signature,
+      signing_input.as_bytes(),
+    )? {
+      return Err(WebCryptoError::OperationError);
+    }
+
+    decode(payload)
+  }
+
+  /// Verify each of `tokens`, like `verify`, spread over the available
+  /// cores.
+  pub fn verify_all(
+    &self,
+    tokens: &[&str],
+  ) -> Vec<Result<Vec<u8>, WebCryptoError>> {
+    let threads = std::thread::available_parallelism().map_or(1, usize::from);
+    let chunk_size = tokens.len().div_ceil(threads).max(1);
+
+    std::thread::scope(|scope| {
+      let chunks = tokens
+        .chunks(chunk_size)
+        .map(|chunk| {
+          scope.spawn(move || {
+            chunk
+              .iter()
+              .map(|token| self.verify(token))
+              .collect::<Vec<_>>()
+          })
+        })
+        .collect::<Vec<_>>();
+      chunks
+        .into_iter()
+        .flat_map(|chunk| {
+          chunk
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+        .collect()
+    })
+  }
+}
@@ -0,0 +1,190 @@
+use crate::WebCryptoError;
+use argon2::Argon2;
+use argon2::Params;
+use argon2::Version;
+use hkdf::Hkdf;
+use sha1::Sha1;
+use sha2::digest::generic_array::ArrayLength;
+use sha2::digest::BlockInput;
+use sha2::digest::FixedOutput;
+use sha2::digest::Reset;
+use sha2::digest::Update;
+use sha2::Digest;
+use sha2::Sha256;
+use sha2::Sha384;
+use sha2::Sha512;
+
+/// Argon2id (RFC 9106, version 0x13) of `password`, producing `length`
+/// bytes. `memory` is in KiB.
+pub(crate) fn argon2id(
+  password: &[u8],
+  salt: &[u8],
+  memory: u32,
+  iterations: u32,
+  parallelism: u32,
+  length: usize,
+) -> Result<Vec<u8>, WebCryptoError> {
+  // OperationError when a parameter is out of range.
+  let params = Params::new(memory, iterations, parallelism, Some(length))
+    .map_err(|_| WebCryptoError::OperationError)?;
+  let argon2 = Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params);
+
+  let mut output = vec![0; length];
+  argon2
+    .hash_password_into(password, salt, &mut output)
+    .map_err(|_| WebCryptoError::OperationError)?;
+
+  Ok(output)
+}
+
+/// scrypt (RFC 7914) of `password`, producing `length` bytes. `n` is the
+/// CPU/memory cost and must be a power of two.
+pub(crate) fn scrypt(
+  password: &[u8],
+  salt: &[u8],
+  n: u64,
+  r: u32,
+  p: u32,
+  length: usize,
+) -> Result<Vec<u8>, WebCryptoError> {
+  if n < 2 || !n.is_power_of_two() {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  // The output length of `Params` only applies to password hashes.
+  let params = scrypt::Params::new(
+    n.trailing_zeros() as u8,
+    r,
+    p,
+    scrypt::Params::RECOMMENDED_LEN,
+  )
+  .map_err(|_| WebCryptoError::OperationError)?;
+
+  let mut output = vec![0; length];
+  scrypt::scrypt(password, salt, &params, &mut output)
+    .map_err(|_| WebCryptoError::OperationError)?;
+
+  Ok(output)
+}
+
+fn hkdf_with<D>(
+  salt: Option<&[u8]>,
+  key: &[u8],
+  info: &[u8],
+  length: usize,
+) -> Result<Vec<u8>, WebCryptoError>
+where
+  D: Update + BlockInput + FixedOutput + Reset + Default + Clone,
+  D::BlockSize: ArrayLength<u8>,
+{
+  let hkdf = match salt {
+    Some(salt) => Hkdf::<D>::new(Some(salt), key),
+    // The key is already a pseudorandom key, at least as long as the
+    // digest.
+    None => {
+      Hkdf::<D>::from_prk(key).map_err(|_| WebCryptoError::OperationError)?
+    }
+  };
+
+  // OperationError when `length` exceeds 255 blocks.
+  let mut output = vec![0; length];
+  hkdf
+    .expand(info, &mut output)
+    .map_err(|_| WebCryptoError::OperationError)?;
+
+  Ok(output)
+}
+
+/// HKDF (RFC 5869) of `key` with `hash`, producing `length` bytes. When
+/// `salt` is `None` the extract step is skipped and `key` is the
+/// pseudorandom key.
+pub(crate) fn hkdf(
+  hash: &str,
+  salt: Option<&[u8]>,
+  key: &[u8],
+  info: &[u8],
+  length: usize,
+) -> Result<Vec<u8>, WebCryptoError> {
+  match hash {
+    "SHA-1" => hkdf_with::<Sha1>(salt, key, info, length),
+    "SHA-256" => hkdf_with::<Sha256>(salt, key, info, length),
+    "SHA-384" => hkdf_with::<Sha384>(salt, key, info, length),
+    "SHA-512" => hkdf_with::<Sha512>(salt, key, info, length),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The hash-and-counter KDF shared by NIST SP 800-56A and ANSI X9.63,
+/// which differ only in where the 32-bit counter goes.
+fn counter_kdf_with<D: Digest>(
+  secret: &[u8],
+  info: &[u8],
+  length: usize,
+  counter_first: bool,
+) -> Result<Vec<u8>, WebCryptoError> {
+  let reps = length.div_ceil(<D as Digest>::output_size());
+  if reps > u32::MAX as usize {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  let mut output = Vec::with_capacity(reps * <D as Digest>::output_size());
+  for counter in 1..=reps as u32 {
+    let mut hasher = D::new();
+    if counter_first {
+      Digest::update(&mut hasher, counter.to_be_bytes());
+      Digest::update(&mut hasher, secret);
+    } else {
+      Digest::update(&mut hasher, secret);
+      Digest::update(&mut hasher, counter.to_be_bytes());
+    }
+    Digest::update(&mut hasher, info);
+    output.extend_from_slice(&hasher.finalize());
+  }
+  output.truncate(length);
+
+  Ok(output)
+}
+
+fn counter_kdf(
+  hash: &str,
+  secret: &[u8],
+  info: &[u8],
+  length: usize,
+  counter_first: bool,
+) -> Result<Vec<u8>, WebCryptoError> {
+  match hash {
+    "SHA-1" => counter_kdf_with::<Sha1>(secret, info, length, counter_first),
+    "SHA-256" => {
+      counter_kdf_with::<Sha256>(secret, info, length, counter_first)
+    }
+    "SHA-384" => {
+      counter_kdf_with::<Sha384>(secret, info, length, counter_first)
+    }
+    "SHA-512" => {
+      counter_kdf_with::<Sha512>(secret, info, length, counter_first)
+    }
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The one-step KDF of NIST SP 800-56A (section 5.8.2.1) with `hash`, known
+/// as Concat KDF, of the shared secret `secret`, producing `length` bytes.
+pub(crate) fn concat_kdf(
+  hash: &str,
+  secret: &[u8],
+  other_info: &[u8],
+  length: usize,
+) -> Result<Vec<u8>, WebCryptoError> {
+  counter_kdf(hash, secret, other_info, length, true)
+}
+
+/// The KDF of ANSI X9.63 (SEC 1 section 3.6.1) with `hash`, of the shared
+/// secret `secret`, producing `length` bytes.
+pub(crate) fn x963_kdf(
+  hash: &str,
+  secret: &[u8],
+  shared_info: &[u8],
+  length: usize,
+) -> Result<Vec<u8>, WebCryptoError> {
+  counter_kdf(hash, secret, shared_info, length, false)
+}
@@ -0,0 +1,97 @@
+// Parsed RSA private keys, so signing and decrypting with a key do not
+// parse it and recompute its CRT values every time. Keys are found by
+// the SHA-256 digest of their PKCS #1 encoding.
+//
+// Parsed keys count as resident key material: the cache gives way to the
+// key material budget, dropping the least recently used keys first.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use rsa::pkcs1::FromRsaPrivateKey;
+use rsa::RsaPrivateKey;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::WebCryptoError;
+
+/// The most keys cached without a key material budget.
+const CAPACITY: usize = 64;
+
+struct CachedKey {
+  id: [u8; 32],
+  /// The length of the PKCS #1 encoding, the size counted for the key.
+  size: usize,
+  key: Arc<RsaPrivateKey>,
+}
+
+#[derive(Default)]
+pub(crate) struct KeyCache {
+  /// The least recently used key first.
+  keys: Mutex<Vec<CachedKey>>,
+}
+
+fn id(der: &[u8]) -> [u8; 32] {
+  Sha256::digest(der).into()
+}
+
+impl KeyCache {
+  /// The size of the cached keys.
+  pub(crate) fn bytes(&self) -> usize {
+    self.keys.lock().unwrap().iter().map(|key| key.size).sum()
+  }
+
+  /// The RSA private key of the PKCS #1 encoding `der`, caching it when
+  /// the cache fits in `room` bytes with it.
+  pub(crate) fn rsa_private_key(
+    &self,
+    der: &[u8],
+    room: usize,
+  ) -> Result<Arc<RsaPrivateKey>, WebCryptoError> {
+    let id = id(der);
+    let mut keys = self.keys.lock().unwrap();
+    if let Some(index) = keys.iter().position(|key| key.id == id) {
+      let cached = keys.remove(index);
+      let key = cached.key.clone();
+      keys.push(cached);
+      return Ok(key);
+    }
+
+    let key = Arc::new(
+      RsaPrivateKey::from_pkcs1_der(der)
+        .map_err(|_| WebCryptoError::OperationError)?,
+    );
+    if der.len() <= room {
+      keys.push(CachedKey {
+        id,
+        size: der.len(),
+        key: key.clone(),
+      });
+      evict(&mut keys, room);
+    }
+    Ok(key)
+  }
+
+  /// Drop the least recently used keys until the cache fits in `bytes`.
+  pub(crate) fn evict(&self, bytes: usize) {
+    evict(&mut self.keys.lock().unwrap(), bytes);
+  }
+
+  /// Drop the key of the PKCS #1 encoding `der`, eg. once it is deleted.
+  pub(crate) fn remove(&self, der: &[u8]) {
+    let id = id(der);
+    self.keys.lock().unwrap().retain(|key| key.id != id);
+  }
+}
+
+fn evict(keys: &mut Vec<CachedKey>, bytes: usize) {
+  let mut size: usize = keys.iter().map(|key| key.size).sum();
+  let mut evicted = 0;
+  while evicted < keys.len()
+    && (size > bytes || keys.len() - evicted > CAPACITY)
+  {
+    size -= keys[evicted].size;
+    evicted += 1;
+  }
+  keys.drain(..evicted);
+}
@@ -1,12 +1,103 @@
+mod aad;
+mod aes_cbc;
+mod aes_cmac;
+mod aes_gcm;
+mod aes_kw;
+mod algorithm_name;
+#[cfg(feature = "tokio")]
+mod async_stream;
+mod blind_index;
+mod chacha20_poly1305;
+mod cms;
+mod descriptor;
+mod dh;
+mod ec;
+mod ed25519;
+#[cfg(feature = "ed448")]
+mod ed448;
+mod envelope;
+mod ff1;
+mod jwk;
+mod jwks;
+mod kdf;
+mod key_cache;
+mod multipart;
+mod nonce;
+mod oaep;
+mod okp;
+mod pem;
+mod pkcs12;
+mod rsa_kem;
+mod screen;
+mod stream;
+mod transcript;
+mod usages;
+mod verifier_pool;
+#[cfg(feature = "x448")]
+mod x448;
+mod x509;
+#[cfg(feature = "ml-kem")]
+mod xwing;
+
+pub use aad::AadBuilder;
+pub use algorithm_name::AlgorithmName;
+#[cfg(feature = "tokio")]
+pub use async_stream::DecryptReader;
+#[cfg(feature = "tokio")]
+pub use async_stream::EncryptWriter;
+pub use blind_index::BlindIndexParams;
+pub use blind_index::Normalization;
+pub use descriptor::DESCRIPTOR_VERSION;
+pub use ec::EcdsaSignature;
+pub use envelope::EnvelopeHeader;
+pub use envelope::ReencryptParams;
+pub use envelope::ENVELOPE_VERSION;
+pub use envelope::MAX_CHUNK_LENGTH;
+pub use envelope::STREAM_ENVELOPE_VERSION;
+pub use jwk::Jwk;
+pub use jwks::JwksRefresh;
+pub use jwks::JwksVerifier;
+pub use multipart::SignContext;
+pub use multipart::VerifyContext;
+pub use nonce::NonceSequence;
+pub use pkcs12::Certificate;
+pub use pkcs12::Pkcs12;
+pub use pkcs12::Pkcs12Key;
+pub use screen::check_ec_private_key;
+pub use screen::RsaScreen;
+pub use screen::WeakEcKey;
+pub use screen::WeakRsaKey;
+pub use stream::StreamNonces;
+pub use transcript::TranscriptHash;
+pub use usages::permitted_usages;
+pub use usages::Operation;
+pub use verifier_pool::VerifierPool;
+pub use x509::ChainError;
+
+use std::io::Read;
+use std::io::Write;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::SystemTime;
+
 use rand::CryptoRng;
 use rand::RngCore;
 
 use rsa::padding::PaddingScheme;
 use rsa::pkcs1::FromRsaPrivateKey;
+use rsa::pkcs1::FromRsaPublicKey;
 use rsa::pkcs1::ToRsaPrivateKey;
+use rsa::pkcs1::ToRsaPublicKey;
+use rsa::pkcs8::FromPrivateKey;
+use rsa::pkcs8::FromPublicKey;
+use rsa::pkcs8::ToPrivateKey;
+use rsa::pkcs8::ToPublicKey;
 use rsa::BigUint;
 use rsa::PublicKey;
+use rsa::PublicKeyParts;
 use rsa::RsaPrivateKey;
+use rsa::RsaPublicKey;
 
 use sha1::Sha1;
 use sha2::Digest;
@@ -14,8 +105,21 @@ use sha2::Sha256;
 use sha2::Sha384;
 use sha2::Sha512;
 
+use hmac::Hmac;
+use hmac::Mac;
+use hmac::NewMac;
+
+use der::Decode;
+use der::Encode;
+
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+
+use crate::storage::GeneratedKey;
+use crate::storage::HandleCodec;
 use crate::storage::KeyMaterial;
+use crate::storage::KeyOperation;
 use crate::storage::KeyStorage;
+use crate::WebCryptoError;
 
 macro_rules! impl_algorithm {
   (struct $name:ident {
@@ -23,50 +127,196 @@ macro_rules! impl_algorithm {
   }) => {
     #[derive(Copy, Clone)]
     pub struct $name {
+      /// The registered name. Prefer `AlgorithmName::as_str` to a string
+      /// literal: the field becomes an `AlgorithmName` in a future
+      /// release.
       pub name: &'static str,
       $(pub $field_name: $field_type,)*
     }
+
+    impl $name {
+      /// The typed `name`, `NotSupportedError` if it is not registered.
+      pub fn algorithm_name(&self) -> Result<AlgorithmName, WebCryptoError> {
+        self.name.parse()
+      }
+    }
   };
   (struct $name:ident {
     $($field_name:ident: $field_type:ty,)*
   }, $counterpart: ident) => {
     #[derive(Copy, Clone)]
     pub struct $name {
+      /// The registered name. Prefer `AlgorithmName::as_str` to a string
+      /// literal: the field becomes an `AlgorithmName` in a future
+      /// release.
       pub name: &'static str,
       $(pub $field_name: $field_type,)*
     }
 
-    impl Into<KeyGenParams> for $name {
-      fn into(self) -> KeyGenParams {
-        KeyGenParams::$name(self)
+    impl $name {
+      /// The typed `name`, `NotSupportedError` if it is not registered.
+      pub fn algorithm_name(&self) -> Result<AlgorithmName, WebCryptoError> {
+        self.name.parse()
+      }
+    }
+
+    impl From<$name> for KeyGenParams {
+      fn from(params: $name) -> Self {
+        KeyGenParams::$name(params)
       }
     }
 
     #[derive(Copy, Clone)]
     pub struct $counterpart {
+      /// The registered name. Prefer `AlgorithmName::as_str` to a string
+      /// literal: the field becomes an `AlgorithmName` in a future
+      /// release.
       pub name: &'static str,
       $(pub $field_name: $field_type,)*
     }
 
-    impl Into<Algorithm> for $counterpart {
-      fn into(self) -> Algorithm {
-        Algorithm::$counterpart(self)
+    impl $counterpart {
+      /// The typed `name`, `NotSupportedError` if it is not registered.
+      pub fn algorithm_name(&self) -> Result<AlgorithmName, WebCryptoError> {
+        self.name.parse()
       }
     }
 
-    impl Into<Algorithm> for $name {
-      fn into(self) -> Algorithm {
+    impl From<$counterpart> for Algorithm {
+      fn from(algorithm: $counterpart) -> Self {
+        Algorithm::$counterpart(algorithm)
+      }
+    }
+
+    impl From<$name> for Algorithm {
+      fn from(params: $name) -> Self {
         Algorithm::$counterpart($counterpart {
-          name: self.name,
-          $($field_name: self.$field_name,)*
+          name: params.name,
+          $($field_name: params.$field_name,)*
         })
       }
     }
  };
 }
 
+/// The algorithms of the Web Crypto API, the only ones of strict mode.
+/// Ed25519, Ed448 and X448 are those of the Secure Curves draft that
+/// browsers ship.
+const SPEC_ALGORITHM_NAMES: &[&str] = &[
+  "RSASSA-PKCS1-v1_5",
+  "RSA-PSS",
+  "RSA-OAEP",
+  "ECDSA",
+  "ECDH",
+  "Ed25519",
+  "Ed448",
+  "X448",
+  "AES-CTR",
+  "AES-CBC",
+  "AES-GCM",
+  "AES-KW",
+  "HMAC",
+  "SHA-1",
+  "SHA-256",
+  "SHA-384",
+  "SHA-512",
+  "HKDF",
+  "PBKDF2",
+];
+
+/// The lengths in bits of AES keys.
+const AES_KEY_LENGTHS: [usize; 3] = [128, 192, 256];
+
+/// The key formats of the Web Crypto API.
+const SPEC_FORMATS: &[&str] = &["raw", "spki", "pkcs8", "jwk"];
+
+/// Map a dynamically provided algorithm name to its registered
+/// `&'static str` form, for bindings that receive names at runtime.
+/// Names match case-insensitively, like in the Web Crypto API.
+pub(crate) fn algorithm_name(name: &str) -> Option<&'static str> {
+  name.parse().ok().map(|name: AlgorithmName| name.as_str())
+}
+
+/// Replace `name` by its registered form, eg. "rsa-pss" by "RSA-PSS".
+/// Unknown names are kept, to fail as not supported.
+fn normalize_name(name: &mut &'static str) {
+  if let Some(registered) = algorithm_name(name) {
+    *name = registered;
+  }
+}
+
+/// Check the `usages` of a key generated for the algorithm `name`
+/// against `permitted_usages`: each must be one of the secret key or of
+/// either key of the pair, and the secret or private key needs at least
+/// one, or it would be unusable.
+fn check_generate_usages(
+  name: &str,
+  usages: &[KeyUsage],
+) -> Result<(), WebCryptoError> {
+  let name: AlgorithmName = name.parse()?;
+  let secret = permitted_usages(name, KeyType::Secret);
+  let private = permitted_usages(name, KeyType::Private);
+  let public = permitted_usages(name, KeyType::Public);
+  if secret.is_empty() && private.is_empty() {
+    return Err(WebCryptoError::NotSupportedError);
+  }
+
+  if usages.iter().any(|usage| {
+    !secret.contains(usage)
+      && !private.contains(usage)
+      && !public.contains(usage)
+  }) {
+    return Err(WebCryptoError::SyntaxError);
+  }
+  if !usages
+    .iter()
+    .any(|usage| secret.contains(usage) || private.contains(usage))
+  {
+    return Err(WebCryptoError::SyntaxError);
+  }
+  Ok(())
+}
+
+/// Check the `usages` of an imported key of the algorithm `name` and
+/// `type_` against `permitted_usages`, `SyntaxError` on any other.
+fn check_import_usages(
+  name: &str,
+  type_: KeyType,
+  usages: &[KeyUsage],
+) -> Result<(), WebCryptoError> {
+  let permitted = permitted_usages(name.parse()?, type_);
+  if usages.iter().any(|usage| !permitted.contains(usage)) {
+    return Err(WebCryptoError::SyntaxError);
+  }
+  Ok(())
+}
+
+/// Check that `key` may be used for `operation` with the algorithm
+/// `name`, before touching its material: it must be a key of `name`,
+/// have the usage the operation requires, and be of a type that may have
+/// that usage, eg. a private key to sign. `InvalidAccessError` otherwise.
+fn check_key_use<H>(
+  key: &CryptoKey<H>,
+  name: &str,
+  operation: Operation,
+) -> Result<(), WebCryptoError> {
+  let usage = operation.required_usage();
+  let permitted = key
+    .algorithm
+    .name()
+    .parse()
+    .map_or(&[][..], |algorithm| permitted_usages(algorithm, key.type_));
+  if key.algorithm.name() != name
+    || !key.usages.contains(&usage)
+    || !permitted.contains(&usage)
+  {
+    return Err(WebCryptoError::InvalidAccessError);
+  }
+  Ok(())
+}
+
 #[non_exhaustive]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum NamedCurve {
   /// NIST P-256 (secp256r1)
   P256,
@@ -74,6 +324,26 @@ pub enum NamedCurve {
   P384,
   /// NIST P-521 (secp512r1)
   P521,
+  /// secp256k1. Not a WebCrypto curve, browsers do not support it.
+  #[cfg(feature = "k256")]
+  K256,
+}
+
+/// The finite field Diffie-Hellman groups of RFC 3526.
+#[derive(Copy, Clone, PartialEq)]
+pub enum DhGroup {
+  /// 1536-bit MODP group (group 5)
+  Modp1536,
+  /// 2048-bit MODP group (group 14)
+  Modp2048,
+  /// 3072-bit MODP group (group 15)
+  Modp3072,
+  /// 4096-bit MODP group (group 16)
+  Modp4096,
+  /// 6144-bit MODP group (group 17)
+  Modp6144,
+  /// 8192-bit MODP group (group 18)
+  Modp8192,
 }
 
 impl_algorithm!(
@@ -84,16 +354,82 @@ impl_algorithm!(
   struct HashAlgorithmIdentifer {}
 );
 
+impl_algorithm!(
+  struct KeyAlgorithm {}
+);
+
+impl From<AlgorithmName> for AlgorithmIdentifer {
+  fn from(name: AlgorithmName) -> Self {
+    AlgorithmIdentifer {
+      name: name.as_str(),
+    }
+  }
+}
+
+impl From<AlgorithmName> for HashAlgorithmIdentifer {
+  fn from(name: AlgorithmName) -> Self {
+    HashAlgorithmIdentifer {
+      name: name.as_str(),
+    }
+  }
+}
+
+// `salt_length` defaults to the digest length.
 impl_algorithm!(
   struct RsaPssParams {
-    salt_length: usize,
+    salt_length: Option<usize>,
   }
 );
 
+/// An RSA public exponent, odd and at least 3. WebCrypto passes it as a
+/// big-endian byte string of any length.
+///
+/// The value is capped to 64 bits so that the key parameters holding it
+/// stay `Copy`. The rsa crate refuses exponents above 2^30 anyway.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PublicExponent(u64);
+
+impl PublicExponent {
+  /// 65537, the exponent of nearly all RSA keys.
+  pub const F4: PublicExponent = PublicExponent(65537);
+
+  /// Parse a big-endian exponent, ignoring leading zero bytes. Exponents
+  /// wider than 64 bits are an `OperationError`.
+  pub fn from_be_bytes(bytes: &[u8]) -> Result<Self, WebCryptoError> {
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    let bytes = &bytes[start..];
+    if bytes.len() > 8 {
+      return Err(WebCryptoError::OperationError);
+    }
+
+    let mut padded = [0; 8];
+    padded[8 - bytes.len()..].copy_from_slice(bytes);
+    Self::new(u64::from_be_bytes(padded))
+  }
+
+  pub fn new(exponent: u64) -> Result<Self, WebCryptoError> {
+    if exponent < 3 || exponent.is_multiple_of(2) {
+      return Err(WebCryptoError::OperationError);
+    }
+    Ok(PublicExponent(exponent))
+  }
+
+  /// The exponent as a big-endian byte string without leading zeros.
+  pub fn to_be_bytes(&self) -> Vec<u8> {
+    let bytes = self.0.to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap();
+    bytes[start..].to_vec()
+  }
+
+  fn to_biguint(self) -> BigUint {
+    BigUint::from(self.0)
+  }
+}
+
 impl_algorithm!(
   struct RsaKeyGenParams {
     modulus_length: usize,
-    public_exponent: [u8; 3],
+    public_exponent: PublicExponent,
   },
   RsaKeyAlgorithm
 );
@@ -102,7 +438,7 @@ impl_algorithm!(
   struct RsaHashedKeyGenParams {
     hash: HashAlgorithmIdentifer,
     modulus_length: usize,
-    public_exponent: [u8; 3],
+    public_exponent: PublicExponent,
   },
   RsaHashedKeyAlgorithm
 );
@@ -114,6 +450,7 @@ impl_algorithm!(
   EcKeyAlgorithm
 );
 
+// `length` is in bits: 128, 192 or 256.
 impl_algorithm!(
   struct AesKeyGenParams {
     length: usize,
@@ -121,506 +458,5391 @@ impl_algorithm!(
   AesKeyAlgorithm
 );
 
+// `length` is in bits and defaults to the block size of `hash`. Lengths
+// that are not a multiple of 8 round up to whole bytes, with the extra
+// low bits of the last byte zeroed.
 impl_algorithm!(
   struct HmacKeyGenParams {
     hash: HashAlgorithmIdentifer,
-    length: usize,
-  },
-  HmacKeyAlgorithm
+    length: Option<usize>,
+  }
 );
 
-#[derive(Copy, Clone)]
-pub enum KeyGenParams {
-  RsaKeyGenParams(RsaKeyGenParams),
-  RsaHashedKeyGenParams(RsaHashedKeyGenParams),
-  EcKeyGenParams(EcKeyGenParams),
-  AesKeyGenParams(AesKeyGenParams),
-  HmacKeyGenParams(HmacKeyGenParams),
+impl From<HmacKeyGenParams> for KeyGenParams {
+  fn from(params: HmacKeyGenParams) -> Self {
+    KeyGenParams::HmacKeyGenParams(params)
+  }
 }
 
-#[derive(Copy, Clone)]
-pub enum Algorithm {
-  RsaKeyAlgorithm(RsaKeyAlgorithm),
-  RsaHashedKeyAlgorithm(RsaHashedKeyAlgorithm),
-  EcKeyAlgorithm(EcKeyAlgorithm),
-  AesKeyAlgorithm(AesKeyAlgorithm),
-  HmacKeyAlgorithm(HmacKeyAlgorithm),
+impl From<HmacKeyGenParams> for Algorithm {
+  fn from(params: HmacKeyGenParams) -> Self {
+    Algorithm::HmacKeyAlgorithm(HmacKeyAlgorithm {
+      name: params.name,
+      hash: params.hash,
+      // Unsupported hashes are rejected by `generate_key`.
+      length: params
+        .length
+        .or_else(|| hmac_block_size(params.hash.name).ok())
+        .unwrap_or_default(),
+    })
+  }
+}
+
+impl_algorithm!(
+  struct HmacKeyAlgorithm {
+    hash: HashAlgorithmIdentifer,
+    length: usize,
+  }
+);
+
+impl_algorithm!(
+  struct EcKeyImportParams {
+    named_curve: NamedCurve,
+  }
+);
+
+// "DH" is an extension: classic Diffie-Hellman for legacy protocols.
+impl_algorithm!(
+  struct DhKeyImportParams {
+    group: DhGroup,
+  }
+);
+
+impl_algorithm!(
+  struct DhKeyAlgorithm {
+    group: DhGroup,
+  }
+);
+
+// `deterministic` derives the nonce from the key and the message (RFC
+// 6979) instead of the context RNG, an extension for environments with a
+// questionable RNG and for reproducible test signatures.
+//
+// `low_s`, another extension, signs with s in the lower half of the
+// curve order and rejects other signatures on verify, the canonical form
+// Bitcoin and Ethereum enforce.
+impl_algorithm!(
+  struct EcdsaParams {
+    hash: HashAlgorithmIdentifer,
+    deterministic: bool,
+    low_s: bool,
+  }
+);
+
+// "Ed25519" signing as Ed25519ph when `prehash` is set, signing the
+// SHA-512 digest of the data under `context`, at most 255 bytes (RFC
+// 8032, section 5.1), so data given in parts to `sign_context` is not
+// buffered. Without `prehash`, a non-empty `context` signs Ed25519ctx.
+// `SignParams::AlgorithmIdentifer` signs pure Ed25519.
+impl_algorithm!(
+  struct Ed25519Params {
+    context: &'static [u8],
+    prehash: bool,
+  }
+);
+
+// `length` is in bits and defaults to the length of the key data. It
+// has to fall within the last byte of the key data, whose bits past it
+// are zeroed.
+impl_algorithm!(
+  struct HmacImportParams {
+    hash: HashAlgorithmIdentifer,
+    length: Option<usize>,
+  }
+);
+
+impl_algorithm!(
+  struct RsaHashedImportParams {
+    hash: HashAlgorithmIdentifer,
+  }
+);
+
+#[derive(Clone)]
+pub struct RsaOaepParams {
+  pub name: &'static str,
+  /// Data bound to the ciphertext, which only decrypts with the same
+  /// label. Empty by default.
+  pub label: Vec<u8>,
 }
 
+// "RSA-KEM" is an extension (RFC 5990). KDF2 uses the hash of the key and
+// derives `length` bytes.
+impl_algorithm!(
+  struct RsaKemParams {
+    length: usize,
+  }
+);
+
 #[derive(Copy, Clone)]
-pub enum SignParams {
-  RsaPssParams(RsaPssParams),
+pub enum EncapsulateParams {
+  RsaKemParams(RsaKemParams),
+  /// Algorithms without parameters, like "X-Wing".
   AlgorithmIdentifer(AlgorithmIdentifer),
 }
 
-impl Into<Algorithm> for KeyGenParams {
-  fn into(self) -> Algorithm {
+impl EncapsulateParams {
+  /// Replace the algorithm name by its registered form.
+  fn normalize(&mut self) {
     match self {
-      KeyGenParams::RsaKeyGenParams(params) => params.into(),
-      KeyGenParams::RsaHashedKeyGenParams(params) => params.into(),
-      KeyGenParams::EcKeyGenParams(params) => params.into(),
-      KeyGenParams::AesKeyGenParams(params) => params.into(),
-      KeyGenParams::HmacKeyGenParams(params) => params.into(),
+      EncapsulateParams::RsaKemParams(params) => {
+        normalize_name(&mut params.name)
+      }
+      EncapsulateParams::AlgorithmIdentifer(params) => {
+        normalize_name(&mut params.name)
+      }
     }
   }
-}
 
-#[derive(PartialEq, Clone)]
-pub enum KeyUsage {
-  Encrypt,
-  Decrypt,
-  Sign,
-  Verify,
-  WrapKey,
-  UnwrapKey,
-  DeriveKey,
-  DeriveBits,
+  pub fn name(&self) -> &'static str {
+    match self {
+      EncapsulateParams::RsaKemParams(params) => params.name,
+      EncapsulateParams::AlgorithmIdentifer(params) => params.name,
+    }
+  }
 }
 
-#[derive(PartialEq)]
-pub enum KeyType {
-  Public,
-  Private,
-  Secret,
+/// A shared secret and the ciphertext that lets the holder of the private
+/// key recover it.
+pub struct EncapsulatedBits {
+  pub shared_key: Vec<u8>,
+  pub ciphertext: Vec<u8>,
 }
 
-pub struct CryptoKey<H> {
-  /// Determine whether the key can be exportable or not.
-  ///
-  /// A false value **does not** mean that the key material
-  /// is completely secure.
-  /// It is **your responsibility** to ensure that in your
-  /// key storage.
-  pub extractable: bool,
-  pub usages: Vec<KeyUsage>,
-  pub type_: KeyType,
-  pub algorithm: Algorithm,
-
-  handle: H,
+/// A ciphertext and the parameters it was encrypted with, defaults
+/// filled in, as returned by `SubtleCrypto::encrypt_detailed`. `params`
+/// decrypts `ciphertext` as is, so it can be stored along with it.
+#[derive(Clone)]
+pub struct EncryptOutput {
+  pub ciphertext: Vec<u8>,
+  pub params: EncryptParams,
 }
 
-pub struct CryptoKeyPair<H> {
-  pub private_key: CryptoKey<H>,
-  pub public_key: CryptoKey<H>,
+/// A signature and the parameters it was made with, defaults filled in,
+/// as returned by `SubtleCrypto::sign_detailed`. `params` verifies
+/// `signature` as is.
+#[derive(Clone)]
+pub struct SignOutput {
+  pub signature: Vec<u8>,
+  pub params: SignParams,
 }
 
-pub enum CryptoKeyOrPair<H> {
-  CryptoKey(CryptoKey<H>),
-  CryptoKeyPair(CryptoKeyPair<H>),
+#[derive(Clone)]
+pub struct AesGcmParams {
+  pub name: &'static str,
+  /// The initialization vector. It must never be reused with
+  /// the same key.
+  pub iv: Vec<u8>,
+  /// Data authenticated but not encrypted, eg. built with `AadBuilder`.
+  pub additional_data: Vec<u8>,
+  /// The tag length in bits, one of 32, 64, 96, 104, 112, 120 and 128.
+  /// Defaults to 128.
+  pub tag_length: Option<usize>,
+  /// Commit the ciphertext to the key, an extension. Plain GCM
+  /// ciphertexts can be crafted to decrypt under several keys, which
+  /// matters when recipients hold different keys. The ciphertext is
+  /// prefixed with a 32-byte commitment and is not compatible with
+  /// plain AES-GCM.
+  pub key_commitment: bool,
 }
 
-pub struct SubtleCrypto<R: RngCore + CryptoRng, S: KeyStorage> {
-  pub(crate) rng: R,
-  storage: S,
+/// FF1 format-preserving encryption (NIST SP 800-38G) with an
+/// "AES-FF1" key, an extension for tokenizing fields like card numbers
+/// while keeping their format. The data is a string of symbols of
+/// `alphabet`, and so is the ciphertext, with the same length.
+#[derive(Clone)]
+pub struct Ff1Params {
+  pub name: &'static str,
+  /// Public data varying the permutation, eg. the last digits of a card
+  /// number kept in the clear.
+  pub tweak: Vec<u8>,
+  /// The distinct symbols of the data, eg. b"0123456789". Its length is
+  /// the radix, from 2 to 256. Data must encode at least a million
+  /// values, eg. 6 decimal digits.
+  pub alphabet: Vec<u8>,
 }
 
-impl<R: RngCore + CryptoRng, S: KeyStorage> SubtleCrypto<R, S> {
-  pub fn new(rng: R, storage: S) -> Self {
-    SubtleCrypto { rng, storage }
-  }
+pub struct EcdhKeyDeriveParams<'a, H> {
+  pub name: &'static str,
+  /// The public key of the other party.
+  pub public: &'a CryptoKey<H>,
 }
 
-impl<R: RngCore + CryptoRng, S: KeyStorage> SubtleCrypto<R, S> {
-  pub fn generate_key(
-    &mut self,
-    algorithm: KeyGenParams,
-    extractable: bool,
-    usages: Vec<KeyUsage>,
-  ) -> Result<CryptoKeyOrPair<S::Handle>, ()> {
-    match algorithm {
-      KeyGenParams::RsaHashedKeyGenParams(ref rsa_alg) => {
-        match rsa_alg.name {
-          "RSASSA-PKCS1-v1_5" | "RSA-PSS" | "RSA-OAEP" => {
-            // 1.
-            if usages
-              .iter()
-              .find(|&usage| {
-                !(usage == &KeyUsage::Sign || usage == &KeyUsage::Verify)
-              })
-              .is_some()
-            {
-              // SyntaxError.
-              return Err(());
-            }
-
-            // 2.
-            let exp = BigUint::from_bytes_be(&rsa_alg.public_exponent);
-            let p_key = RsaPrivateKey::new_with_exp(
-              &mut self.rng,
-              rsa_alg.modulus_length,
-              &exp,
-            )
-            .map_err(|_| ())?;
-
-            let pkcs1 = p_key.to_pkcs1_der().map_err(|_| ())?;
+pub struct DhKeyDeriveParams<'a, H> {
+  pub name: &'static str,
+  /// The public key of the other party.
+  pub public: &'a CryptoKey<H>,
+}
 
-            let handle =
-              self.storage.store(KeyMaterial(pkcs1.as_ref().to_vec()));
+/// Parameters of the "Argon2id" extension (RFC 9106), a memory-hard
+/// password KDF. The base key is the password.
+#[derive(Clone)]
+pub struct Argon2Params {
+  pub name: &'static str,
+  /// Memory size in KiB.
+  pub memory: u32,
+  /// Number of passes over the memory.
+  pub iterations: u32,
+  /// Degree of parallelism, in lanes.
+  pub parallelism: u32,
+  pub salt: Vec<u8>,
+}
 
-            let key_pair = CryptoKeyPair {
-              private_key: CryptoKey {
-                extractable,
-                usages: usages.clone(),
-                handle,
-                type_: KeyType::Private,
-                algorithm: algorithm.into(),
-              },
-              public_key: CryptoKey {
-                extractable,
-                usages,
-                handle,
-                type_: KeyType::Public,
-                algorithm: algorithm.into(),
-              },
-            };
+/// Parameters of the "scrypt" extension (RFC 7914), for interoperating
+/// with existing password-based formats. The base key is the password.
+#[derive(Clone)]
+pub struct ScryptParams {
+  pub name: &'static str,
+  /// CPU/memory cost, a power of two.
+  pub n: u64,
+  /// Block size.
+  pub r: u32,
+  /// Parallelization.
+  pub p: u32,
+  pub salt: Vec<u8>,
+}
 
-            Ok(CryptoKeyOrPair::CryptoKeyPair(key_pair))
-          }
-          _ => todo!(),
-        }
-      }
-      KeyGenParams::AesKeyGenParams(ref aes_alg) => match aes_alg.name {
-        "AES-CTR" | "AES-CBC" | "AES-GCM" | "AES-KW" => {
-          let mut key_data = vec![0u8; aes_alg.length];
-          self.rng.fill_bytes(&mut key_data);
-          let handle = self.storage.store(KeyMaterial(key_data));
+/// Parameters of HKDF (RFC 5869). The base key is the input keying
+/// material.
+#[derive(Clone)]
+pub struct HkdfParams {
+  pub name: &'static str,
+  pub hash: HashAlgorithmIdentifer,
+  pub salt: Vec<u8>,
+  pub info: Vec<u8>,
+}
 
-          let key = CryptoKey {
-            extractable,
-            usages,
-            handle,
-            type_: KeyType::Secret,
-            algorithm: algorithm.into(),
-          };
+/// Parameters of HKDF-Expand alone, an extension for key schedules that
+/// expand one secret several times, like those of TLS 1.3 and Noise. The
+/// base key is an "HKDF" key holding the pseudorandom key, at least as
+/// long as the digest.
+#[derive(Clone)]
+pub struct HkdfExpandParams {
+  pub name: &'static str,
+  pub hash: HashAlgorithmIdentifer,
+  pub info: Vec<u8>,
+}
 
-          Ok(CryptoKeyOrPair::CryptoKey(key))
-        }
-        _ => todo!(),
-      },
-      KeyGenParams::HmacKeyGenParams(ref hmac_alg) => {
-        match hmac_alg.name {
-          "HMAC" => {
-            // TODO: length is optional, default to digest block length
-            let mut key_data = vec![0u8; hmac_alg.length];
-            self.rng.fill_bytes(&mut key_data);
+/// Parameters of the "ConcatKDF" extension, the one-step KDF of NIST SP
+/// 800-56A used by ECDH-ES in JWE (RFC 7518). The base key is the shared
+/// secret.
+#[derive(Clone)]
+pub struct ConcatKdfParams {
+  pub name: &'static str,
+  pub hash: HashAlgorithmIdentifer,
+  /// The fixed info: the algorithm identifier and the party
+  /// information, each encoded as the protocol requires.
+  pub other_info: Vec<u8>,
+}
 
-            let handle = self.storage.store(KeyMaterial(key_data));
+/// Parameters of the "X963KDF" extension, the KDF of ANSI X9.63 used by
+/// SEC 1 ECIES and other legacy EC encryption schemes. The base key is
+/// the shared secret.
+#[derive(Clone)]
+pub struct X963KdfParams {
+  pub name: &'static str,
+  pub hash: HashAlgorithmIdentifer,
+  pub shared_info: Vec<u8>,
+}
 
-            let key = CryptoKey {
-              extractable,
-              usages,
-              handle,
-              type_: KeyType::Secret,
-              algorithm: algorithm.into(),
-            };
+pub enum DeriveParams<'a, H> {
+  EcdhKeyDeriveParams(EcdhKeyDeriveParams<'a, H>),
+  DhKeyDeriveParams(DhKeyDeriveParams<'a, H>),
+  HkdfParams(HkdfParams),
+  HkdfExpandParams(HkdfExpandParams),
+  ConcatKdfParams(ConcatKdfParams),
+  X963KdfParams(X963KdfParams),
+  Argon2Params(Argon2Params),
+  ScryptParams(ScryptParams),
+}
 
-            Ok(CryptoKeyOrPair::CryptoKey(key))
-          }
-          _ => todo!(),
-        }
+impl<H> DeriveParams<'_, H> {
+  /// Replace the algorithm and hash names by their registered forms.
+  fn normalize(&mut self) {
+    match self {
+      DeriveParams::EcdhKeyDeriveParams(params) => {
+        normalize_name(&mut params.name)
+      }
+      DeriveParams::DhKeyDeriveParams(params) => {
+        normalize_name(&mut params.name)
       }
-      _ => todo!(),
+      DeriveParams::HkdfParams(params) => {
+        normalize_name(&mut params.name);
+        normalize_name(&mut params.hash.name);
+      }
+      DeriveParams::HkdfExpandParams(params) => {
+        normalize_name(&mut params.name);
+        normalize_name(&mut params.hash.name);
+      }
+      DeriveParams::ConcatKdfParams(params) => {
+        normalize_name(&mut params.name);
+        normalize_name(&mut params.hash.name);
+      }
+      DeriveParams::X963KdfParams(params) => {
+        normalize_name(&mut params.name);
+        normalize_name(&mut params.hash.name);
+      }
+      DeriveParams::Argon2Params(params) => normalize_name(&mut params.name),
+      DeriveParams::ScryptParams(params) => normalize_name(&mut params.name),
     }
   }
-}
 
-impl<R: 'static + RngCore + CryptoRng + Copy, S: KeyStorage>
-  SubtleCrypto<R, S>
-{
+  pub fn name(&self) -> &'static str {
+    match self {
+      DeriveParams::EcdhKeyDeriveParams(params) => params.name,
+      DeriveParams::DhKeyDeriveParams(params) => params.name,
+      DeriveParams::HkdfParams(params) => params.name,
+      DeriveParams::HkdfExpandParams(params) => params.name,
+      DeriveParams::ConcatKdfParams(params) => params.name,
+      DeriveParams::X963KdfParams(params) => params.name,
+      DeriveParams::Argon2Params(params) => params.name,
+      DeriveParams::ScryptParams(params) => params.name,
+    }
+  }
+
+  /// Whether the parameters go beyond the Web Crypto API, eg. HKDF
+  /// without the extract step.
+  fn is_extension(&self) -> bool {
+    match self {
+      DeriveParams::EcdhKeyDeriveParams(_) => false,
+      DeriveParams::HkdfParams(params) => {
+        !SPEC_ALGORITHM_NAMES.contains(&params.hash.name)
+      }
+      _ => true,
+    }
+  }
+}
+
+/// Parameters of the "ChaCha20-Poly1305" extension (RFC 8439).
+#[derive(Clone)]
+pub struct ChaCha20Poly1305Params {
+  pub name: &'static str,
+  /// The 96-bit nonce. It must never be reused with the same key.
+  pub iv: Vec<u8>,
+}
+
+/// Parameters of the "XChaCha20-Poly1305" extension. Its keys are
+/// "ChaCha20-Poly1305" sized, but the longer nonce can safely be chosen at
+/// random.
+#[derive(Clone)]
+pub struct XChaCha20Poly1305Params {
+  pub name: &'static str,
+  /// The 192-bit nonce.
+  pub iv: Vec<u8>,
+}
+
+#[derive(Copy, Clone)]
+pub enum KeyGenParams {
+  RsaKeyGenParams(RsaKeyGenParams),
+  RsaHashedKeyGenParams(RsaHashedKeyGenParams),
+  EcKeyGenParams(EcKeyGenParams),
+  AesKeyGenParams(AesKeyGenParams),
+  HmacKeyGenParams(HmacKeyGenParams),
+  /// Algorithms whose keys have no parameters, like
+  /// "ChaCha20-Poly1305".
+  AlgorithmIdentifer(AlgorithmIdentifer),
+}
+
+impl KeyGenParams {
+  /// Replace the algorithm and hash names by their registered forms.
+  fn normalize(&mut self) {
+    match self {
+      KeyGenParams::RsaKeyGenParams(params) => normalize_name(&mut params.name),
+      KeyGenParams::RsaHashedKeyGenParams(params) => {
+        normalize_name(&mut params.name);
+        normalize_name(&mut params.hash.name);
+      }
+      KeyGenParams::EcKeyGenParams(params) => normalize_name(&mut params.name),
+      KeyGenParams::AesKeyGenParams(params) => normalize_name(&mut params.name),
+      KeyGenParams::HmacKeyGenParams(params) => {
+        normalize_name(&mut params.name);
+        normalize_name(&mut params.hash.name);
+      }
+      KeyGenParams::AlgorithmIdentifer(params) => {
+        normalize_name(&mut params.name)
+      }
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      KeyGenParams::RsaKeyGenParams(params) => params.name,
+      KeyGenParams::RsaHashedKeyGenParams(params) => params.name,
+      KeyGenParams::EcKeyGenParams(params) => params.name,
+      KeyGenParams::AesKeyGenParams(params) => params.name,
+      KeyGenParams::HmacKeyGenParams(params) => params.name,
+      KeyGenParams::AlgorithmIdentifer(params) => params.name,
+    }
+  }
+
+  /// Whether the parameters go beyond the Web Crypto API, eg. a
+  /// secp256k1 key.
+  fn is_extension(&self) -> bool {
+    match self {
+      KeyGenParams::RsaKeyGenParams(_) => true,
+      KeyGenParams::RsaHashedKeyGenParams(params) => {
+        !SPEC_ALGORITHM_NAMES.contains(&params.hash.name)
+      }
+      KeyGenParams::EcKeyGenParams(params) => {
+        is_extension_curve(params.named_curve)
+      }
+      KeyGenParams::HmacKeyGenParams(params) => {
+        !SPEC_ALGORITHM_NAMES.contains(&params.hash.name)
+      }
+      _ => false,
+    }
+  }
+}
+
+#[derive(Copy, Clone)]
+pub enum Algorithm {
+  RsaKeyAlgorithm(RsaKeyAlgorithm),
+  RsaHashedKeyAlgorithm(RsaHashedKeyAlgorithm),
+  EcKeyAlgorithm(EcKeyAlgorithm),
+  DhKeyAlgorithm(DhKeyAlgorithm),
+  AesKeyAlgorithm(AesKeyAlgorithm),
+  HmacKeyAlgorithm(HmacKeyAlgorithm),
+  KeyAlgorithm(KeyAlgorithm),
+}
+
+impl Algorithm {
+  /// The registered name of the algorithm, eg. "AES-KW".
+  pub fn name(&self) -> &'static str {
+    match self {
+      Algorithm::RsaKeyAlgorithm(alg) => alg.name,
+      Algorithm::RsaHashedKeyAlgorithm(alg) => alg.name,
+      Algorithm::EcKeyAlgorithm(alg) => alg.name,
+      Algorithm::DhKeyAlgorithm(alg) => alg.name,
+      Algorithm::AesKeyAlgorithm(alg) => alg.name,
+      Algorithm::HmacKeyAlgorithm(alg) => alg.name,
+      Algorithm::KeyAlgorithm(alg) => alg.name,
+    }
+  }
+}
+
+#[derive(Copy, Clone)]
+pub enum SignParams {
+  RsaPssParams(RsaPssParams),
+  EcdsaParams(EcdsaParams),
+  Ed25519Params(Ed25519Params),
+  AlgorithmIdentifer(AlgorithmIdentifer),
+}
+
+impl SignParams {
+  /// Replace the algorithm and hash names by their registered forms.
+  fn normalize(&mut self) {
+    match self {
+      SignParams::RsaPssParams(params) => normalize_name(&mut params.name),
+      SignParams::EcdsaParams(params) => {
+        normalize_name(&mut params.name);
+        normalize_name(&mut params.hash.name);
+      }
+      SignParams::Ed25519Params(params) => normalize_name(&mut params.name),
+      SignParams::AlgorithmIdentifer(params) => {
+        normalize_name(&mut params.name)
+      }
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      SignParams::RsaPssParams(params) => params.name,
+      SignParams::EcdsaParams(params) => params.name,
+      SignParams::Ed25519Params(params) => params.name,
+      SignParams::AlgorithmIdentifer(params) => params.name,
+    }
+  }
+
+  /// Whether the parameters go beyond the Web Crypto API, eg.
+  /// deterministic ECDSA.
+  fn is_extension(&self) -> bool {
+    match self {
+      SignParams::RsaPssParams(params) => params.salt_length.is_none(),
+      SignParams::EcdsaParams(params) => {
+        params.deterministic
+          || params.low_s
+          || !SPEC_ALGORITHM_NAMES.contains(&params.hash.name)
+      }
+      SignParams::Ed25519Params(params) => {
+        params.prehash || !params.context.is_empty()
+      }
+      SignParams::AlgorithmIdentifer(_) => false,
+    }
+  }
+}
+
+#[derive(Clone)]
+pub enum EncryptParams {
+  AesGcmParams(AesGcmParams),
+  RsaOaepParams(RsaOaepParams),
+  ChaCha20Poly1305Params(ChaCha20Poly1305Params),
+  XChaCha20Poly1305Params(XChaCha20Poly1305Params),
+  Ff1Params(Ff1Params),
+  AlgorithmIdentifer(AlgorithmIdentifer),
+}
+
+impl EncryptParams {
+  /// Replace the algorithm name by its registered form.
+  fn normalize(&mut self) {
+    match self {
+      EncryptParams::AesGcmParams(params) => normalize_name(&mut params.name),
+      EncryptParams::RsaOaepParams(params) => normalize_name(&mut params.name),
+      EncryptParams::ChaCha20Poly1305Params(params) => {
+        normalize_name(&mut params.name)
+      }
+      EncryptParams::XChaCha20Poly1305Params(params) => {
+        normalize_name(&mut params.name)
+      }
+      EncryptParams::Ff1Params(params) => normalize_name(&mut params.name),
+      EncryptParams::AlgorithmIdentifer(params) => {
+        normalize_name(&mut params.name)
+      }
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      EncryptParams::AesGcmParams(params) => params.name,
+      EncryptParams::RsaOaepParams(params) => params.name,
+      EncryptParams::ChaCha20Poly1305Params(params) => params.name,
+      EncryptParams::XChaCha20Poly1305Params(params) => params.name,
+      EncryptParams::Ff1Params(params) => params.name,
+      EncryptParams::AlgorithmIdentifer(params) => params.name,
+    }
+  }
+
+  /// Whether the parameters go beyond the Web Crypto API, eg. a key
+  /// committing AES-GCM ciphertext.
+  fn is_extension(&self) -> bool {
+    match self {
+      EncryptParams::AesGcmParams(params) => params.key_commitment,
+      _ => false,
+    }
+  }
+
+  /// The IV of AEAD algorithms, and its required length in bytes.
+  fn iv_mut(&mut self) -> Option<(&mut Vec<u8>, usize)> {
+    match self {
+      EncryptParams::AesGcmParams(params) => Some((&mut params.iv, 12)),
+      EncryptParams::ChaCha20Poly1305Params(params) => {
+        Some((&mut params.iv, 12))
+      }
+      EncryptParams::XChaCha20Poly1305Params(params) => {
+        Some((&mut params.iv, 24))
+      }
+      _ => None,
+    }
+  }
+}
+
+#[derive(Copy, Clone)]
+pub enum ImportParams {
+  AlgorithmIdentifer(AlgorithmIdentifer),
+  HmacImportParams(HmacImportParams),
+  RsaHashedImportParams(RsaHashedImportParams),
+  EcKeyImportParams(EcKeyImportParams),
+  DhKeyImportParams(DhKeyImportParams),
+}
+
+impl ImportParams {
+  /// Replace the algorithm and hash names by their registered forms.
+  fn normalize(&mut self) {
+    match self {
+      ImportParams::AlgorithmIdentifer(params) => {
+        normalize_name(&mut params.name)
+      }
+      ImportParams::HmacImportParams(params) => {
+        normalize_name(&mut params.name);
+        normalize_name(&mut params.hash.name);
+      }
+      ImportParams::RsaHashedImportParams(params) => {
+        normalize_name(&mut params.name);
+        normalize_name(&mut params.hash.name);
+      }
+      ImportParams::EcKeyImportParams(params) => {
+        normalize_name(&mut params.name)
+      }
+      ImportParams::DhKeyImportParams(params) => {
+        normalize_name(&mut params.name)
+      }
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      ImportParams::AlgorithmIdentifer(params) => params.name,
+      ImportParams::HmacImportParams(params) => params.name,
+      ImportParams::RsaHashedImportParams(params) => params.name,
+      ImportParams::EcKeyImportParams(params) => params.name,
+      ImportParams::DhKeyImportParams(params) => params.name,
+    }
+  }
+
+  /// Whether the parameters go beyond the Web Crypto API, eg. a
+  /// secp256k1 key.
+  fn is_extension(&self) -> bool {
+    match self {
+      ImportParams::HmacImportParams(params) => {
+        !SPEC_ALGORITHM_NAMES.contains(&params.hash.name)
+      }
+      ImportParams::RsaHashedImportParams(params) => {
+        !SPEC_ALGORITHM_NAMES.contains(&params.hash.name)
+      }
+      ImportParams::EcKeyImportParams(params) => {
+        is_extension_curve(params.named_curve)
+      }
+      ImportParams::DhKeyImportParams(_) => true,
+      ImportParams::AlgorithmIdentifer(_) => false,
+    }
+  }
+}
+
+fn is_extension_curve(named_curve: NamedCurve) -> bool {
+  match named_curve {
+    NamedCurve::P256 | NamedCurve::P384 | NamedCurve::P521 => false,
+    #[cfg(feature = "k256")]
+    NamedCurve::K256 => true,
+  }
+}
+
+/// The range of RSA modulus lengths `generate_key` accepts, in bits.
+const MIN_RSA_MODULUS_LENGTH: usize = 256;
+const MAX_RSA_MODULUS_LENGTH: usize = 16384;
+
+/// Hash `data` with the digest algorithm `name`.
+fn digest(name: &str, data: &[u8]) -> Result<Vec<u8>, WebCryptoError> {
+  match name {
+    "SHA-1" => Ok(Sha1::digest(data).to_vec()),
+    "SHA-256" => Ok(Sha256::digest(data).to_vec()),
+    "SHA-384" => Ok(Sha384::digest(data).to_vec()),
+    "SHA-512" => Ok(Sha512::digest(data).to_vec()),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The HMAC of `data` under `key` with the digest algorithm `hash`.
+pub(crate) fn hmac(
+  hash: &str,
+  key: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  fn mac<M: Mac + NewMac>(
+    key: &[u8],
+    data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let mut mac =
+      M::new_from_slice(key).map_err(|_| WebCryptoError::OperationError)?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+  }
+
+  match hash {
+    "SHA-1" => mac::<Hmac<Sha1>>(key, data),
+    "SHA-256" => mac::<Hmac<Sha256>>(key, data),
+    "SHA-384" => mac::<Hmac<Sha384>>(key, data),
+    "SHA-512" => mac::<Hmac<Sha512>>(key, data),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The block size in bits of the hash function `name`, the default
+/// length of HMAC keys.
+fn hmac_block_size(name: &str) -> Result<usize, WebCryptoError> {
+  match name {
+    "SHA-1" | "SHA-256" => Ok(512),
+    "SHA-384" | "SHA-512" => Ok(1024),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The length in bits of HMAC key data imported with `length`,
+/// `DataError` unless it falls within the last byte of `key_data`.
+fn hmac_import_length(
+  key_data: &[u8],
+  length: Option<usize>,
+) -> Result<usize, WebCryptoError> {
+  let data_length = key_data.len() * 8;
+  match length {
+    None if data_length != 0 => Ok(data_length),
+    Some(length)
+      if length != 0 && length <= data_length && length + 8 > data_length =>
+    {
+      Ok(length)
+    }
+    _ => Err(WebCryptoError::DataError),
+  }
+}
+
+/// Zero the bits of `key_data` past its first `length`.
+fn truncate_bits(key_data: &mut [u8], length: usize) {
+  if !length.is_multiple_of(8) {
+    key_data[length / 8] &= 0xff << (8 - length % 8);
+  }
+}
+
+/// The RSA-PSS salt length, the digest length of `hash` unless given.
+/// The encoded message of a `modulus_bits` key has to fit the digest, the
+/// salt and two more bytes (RFC 8017, section 9.1.1).
+fn pss_salt_length(
+  hash: &str,
+  salt_length: Option<usize>,
+  modulus_bits: usize,
+) -> Result<usize, WebCryptoError> {
+  let digest_length = digest(hash, &[])?.len();
+  let salt_length = salt_length.unwrap_or(digest_length);
+  let em_length = (modulus_bits + 6) / 8;
+  if salt_length + digest_length + 2 > em_length {
+    // OperationError: the salt is too long for the modulus.
+    return Err(WebCryptoError::OperationError);
+  }
+
+  Ok(salt_length)
+}
+
+/// The digest length matching the security of `named_curve`, the
+/// shortest ECDSA digest that does not weaken its keys.
+fn curve_digest_length(named_curve: NamedCurve) -> usize {
+  match named_curve {
+    NamedCurve::P256 => 32,
+    NamedCurve::P384 => 48,
+    NamedCurve::P521 => 64,
+    #[cfg(feature = "k256")]
+    NamedCurve::K256 => 32,
+  }
+}
+
+/// Whether `algorithm` signs with weaker parameters than a key of
+/// algorithm `key_algorithm` supports: an ECDSA digest shorter than the
+/// curve calls for, or an RSA-PSS salt shorter than the digest.
+fn is_sign_downgrade(
+  algorithm: &SignParams,
+  key_algorithm: &Algorithm,
+) -> Result<bool, WebCryptoError> {
+  Ok(match (algorithm, key_algorithm) {
+    (SignParams::EcdsaParams(params), Algorithm::EcKeyAlgorithm(alg)) => {
+      digest(params.hash.name, &[])?.len()
+        < curve_digest_length(alg.named_curve)
+    }
+    (
+      SignParams::RsaPssParams(RsaPssParams {
+        salt_length: Some(salt_length),
+        ..
+      }),
+      Algorithm::RsaHashedKeyAlgorithm(alg),
+    ) => *salt_length < digest(alg.hash.name, &[])?.len(),
+    _ => false,
+  })
+}
+
+/// The hash `algorithm` signs the data with under a key of algorithm
+/// `key_algorithm`, for RSA and ECDSA, which sign a digest of the data.
+fn prehash(
+  algorithm: &SignParams,
+  key_algorithm: &Algorithm,
+) -> Result<&'static str, WebCryptoError> {
+  match (algorithm, key_algorithm) {
+    (
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "RSASSA-PKCS1-v1_5",
+      })
+      | SignParams::RsaPssParams(RsaPssParams {
+        name: "RSA-PSS", ..
+      }),
+      Algorithm::RsaHashedKeyAlgorithm(alg),
+    ) => Ok(alg.hash.name),
+    (SignParams::EcdsaParams(params), _) => Ok(params.hash.name),
+    _ => Err(WebCryptoError::InvalidAccessError),
+  }
+}
+
+/// RSASSA-PKCS1-v1_5 padding of a `hash` digest.
+fn pkcs1v15_padding(hash: &str) -> Result<PaddingScheme, WebCryptoError> {
+  let hash = match hash {
+    "SHA-1" => rsa::hash::Hash::SHA1,
+    "SHA-256" => rsa::hash::Hash::SHA2_256,
+    "SHA-384" => rsa::hash::Hash::SHA2_384,
+    "SHA-512" => rsa::hash::Hash::SHA2_512,
+    _ => {
+      return Err(WebCryptoError::SyntaxError);
+    }
+  };
+  Ok(PaddingScheme::PKCS1v15Sign { hash: Some(hash) })
+}
+
+/// RSA-PSS padding of a `hash` digest, with MGF1 using `hash`.
+fn pss_padding<R: 'static + RngCore + CryptoRng + Copy>(
+  hash: &str,
+  rng: R,
+  salt_length: usize,
+) -> Result<PaddingScheme, WebCryptoError> {
+  match hash {
+    "SHA-1" => Ok(PaddingScheme::new_pss_with_salt::<Sha1, _>(
+      rng,
+      salt_length,
+    )),
+    "SHA-256" => Ok(PaddingScheme::new_pss_with_salt::<Sha256, _>(
+      rng,
+      salt_length,
+    )),
+    "SHA-384" => Ok(PaddingScheme::new_pss_with_salt::<Sha384, _>(
+      rng,
+      salt_length,
+    )),
+    "SHA-512" => Ok(PaddingScheme::new_pss_with_salt::<Sha512, _>(
+      rng,
+      salt_length,
+    )),
+    _ => Err(WebCryptoError::SyntaxError),
+  }
+}
+
+/// The hash of an RSA-OAEP `algorithm`.
+fn oaep_hash(algorithm: &Algorithm) -> &'static str {
+  match algorithm {
+    Algorithm::RsaHashedKeyAlgorithm(alg) => alg.hash.name,
+    _ => unreachable!(),
+  }
+}
+
+/// OAEP padding using `hash` for both the label digest and MGF1.
+fn hash_oaep_padding(hash: &str) -> Result<PaddingScheme, WebCryptoError> {
+  match hash {
+    "SHA-1" => Ok(PaddingScheme::new_oaep::<Sha1>()),
+    "SHA-256" => Ok(PaddingScheme::new_oaep::<Sha256>()),
+    "SHA-384" => Ok(PaddingScheme::new_oaep::<Sha384>()),
+    "SHA-512" => Ok(PaddingScheme::new_oaep::<Sha512>()),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// Parse RSA key material. Generated keys and imported private keys are
+/// stored as a PKCS#1 private key, imported public keys as a PKCS#1
+/// public key.
+fn rsa_public_key(
+  key_material: &KeyMaterial,
+) -> Result<RsaPublicKey, WebCryptoError> {
+  // Try the public key first: parsing a public key as a private one
+  // reads the modulus as the version number.
+  match RsaPublicKey::from_pkcs1_der(&key_material.0) {
+    Ok(public_key) => Ok(public_key),
+    Err(_) => Ok(
+      RsaPrivateKey::from_pkcs1_der(&key_material.0)
+        .map_err(|_| WebCryptoError::OperationError)?
+        .to_public_key(),
+    ),
+  }
+}
+
+/// The key of a "BLAKE3" keyed hash, used as a MAC.
+fn blake3_key<H>(
+  key: &CryptoKey<H>,
+  key_material: &KeyMaterial,
+) -> Result<[u8; 32], WebCryptoError> {
+  if key.type_ != KeyType::Secret || key.algorithm.name() != "BLAKE3" {
+    return Err(WebCryptoError::InvalidAccessError);
+  }
+
+  key_material
+    .0
+    .as_slice()
+    .try_into()
+    .map_err(|_| WebCryptoError::OperationError)
+}
+
+/// The AES key of an "AES-CMAC" `key`.
+fn aes_cmac_key<'a, H>(
+  key: &CryptoKey<H>,
+  key_material: &'a KeyMaterial,
+) -> Result<&'a [u8], WebCryptoError> {
+  if key.type_ != KeyType::Secret || key.algorithm.name() != "AES-CMAC" {
+    return Err(WebCryptoError::InvalidAccessError);
+  }
+
+  Ok(&key_material.0)
+}
+
+/// The HKDF info prefix of record keys, separating them from other keys
+/// derived from the same master key.
+const RECORD_KEY_CONTEXT: &[u8] = b"webcrypto 2024 record key\0";
+
+/// The HKDF parameters of the key of the record `record_id`.
+fn record_key_params<'a, H>(record_id: &[u8]) -> DeriveParams<'a, H> {
+  let mut info = RECORD_KEY_CONTEXT.to_vec();
+  info.extend_from_slice(record_id);
+
+  DeriveParams::HkdfParams(HkdfParams {
+    name: "HKDF",
+    hash: HashAlgorithmIdentifer { name: "SHA-256" },
+    salt: vec![],
+    info,
+  })
+}
+
+/// The length in bits of keys generated with `algorithm`, and the
+/// parameters that import them.
+fn derived_key_params(
+  algorithm: KeyGenParams,
+) -> Result<(usize, ImportParams), WebCryptoError> {
+  match algorithm {
+    KeyGenParams::AesKeyGenParams(params) => {
+      if !AES_KEY_LENGTHS.contains(&params.length) {
+        return Err(WebCryptoError::OperationError);
+      }
+      Ok((
+        params.length,
+        ImportParams::AlgorithmIdentifer(AlgorithmIdentifer {
+          name: params.name,
+        }),
+      ))
+    }
+    KeyGenParams::HmacKeyGenParams(params) => Ok((
+      match (params.length, hmac_block_size(params.hash.name)?) {
+        (Some(0), _) => return Err(WebCryptoError::OperationError),
+        (Some(length), _) => length,
+        (None, block_size) => block_size,
+      },
+      ImportParams::HmacImportParams(HmacImportParams {
+        name: params.name,
+        hash: params.hash,
+        length: params.length,
+      }),
+    )),
+    KeyGenParams::AlgorithmIdentifer(AlgorithmIdentifer {
+      name: name @ ("ChaCha20-Poly1305" | "XChaCha20-Poly1305" | "BLAKE3"),
+    }) => Ok((
+      256,
+      ImportParams::AlgorithmIdentifer(AlgorithmIdentifer { name }),
+    )),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+impl From<KeyGenParams> for Algorithm {
+  fn from(params: KeyGenParams) -> Self {
+    match params {
+      KeyGenParams::RsaKeyGenParams(params) => params.into(),
+      KeyGenParams::RsaHashedKeyGenParams(params) => params.into(),
+      KeyGenParams::EcKeyGenParams(params) => params.into(),
+      KeyGenParams::AesKeyGenParams(params) => params.into(),
+      KeyGenParams::HmacKeyGenParams(params) => params.into(),
+      KeyGenParams::AlgorithmIdentifer(params) => {
+        Algorithm::KeyAlgorithm(KeyAlgorithm { name: params.name })
+      }
+    }
+  }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum KeyUsage {
+  Encrypt,
+  Decrypt,
+  Sign,
+  Verify,
+  WrapKey,
+  UnwrapKey,
+  DeriveKey,
+  DeriveBits,
+  EncapsulateBits,
+  DecapsulateBits,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum KeyType {
+  Public,
+  Private,
+  Secret,
+}
+
+pub struct CryptoKey<H> {
+  /// Determine whether the key can be exportable or not.
+  ///
+  /// A false value **does not** mean that the key material
+  /// is completely secure.
+  /// It is **your responsibility** to ensure that in your
+  /// key storage.
+  pub extractable: bool,
+  pub usages: Vec<KeyUsage>,
+  pub type_: KeyType,
+  pub algorithm: Algorithm,
+
+  handle: H,
+  /// Bytes encrypted with this key by AES-GCM.
+  encrypted: AtomicU64,
+  /// Whether the key material is deleted after its first successful
+  /// use.
+  consume_once: bool,
+}
+
+impl<H: Copy> CryptoKey<H> {
+  /// The `KeyStorage` handle of the key material.
+  pub fn handle(&self) -> H {
+    self.handle
+  }
+
+  /// The number of plaintext bytes this key has encrypted with AES-GCM,
+  /// counted against the rekey threshold of
+  /// `SubtleCrypto::set_gcm_rekey_threshold`.
+  pub fn encrypted_bytes(&self) -> u64 {
+    self.encrypted.load(Ordering::Relaxed)
+  }
+
+  /// Make this a consume-once key: `SubtleCrypto` deletes its key
+  /// material from the storage right after the first operation that
+  /// succeeds with it, eg. a one-time unwrap or a single-shot signature,
+  /// and every later operation fails. The mark cannot be removed.
+  ///
+  /// Operations that cannot consume a key, like `blind_index`, refuse
+  /// consume-once keys, and so does every operation when the storage
+  /// cannot delete keys (see `KeyStorage::delete`).
+  pub fn mark_consume_once(&mut self) {
+    self.consume_once = true;
+  }
+
+  pub fn is_consume_once(&self) -> bool {
+    self.consume_once
+  }
+}
+
+pub struct CryptoKeyPair<H> {
+  pub private_key: CryptoKey<H>,
+  pub public_key: CryptoKey<H>,
+}
+
+pub enum CryptoKeyOrPair<H> {
+  CryptoKey(CryptoKey<H>),
+  CryptoKeyPair(CryptoKeyPair<H>),
+}
+
+/// What an import hook sees of a key being imported: its parsed
+/// parameters, but no secret key material.
+pub struct ImportedKey<'a> {
+  pub format: &'a str,
+  pub type_: &'a KeyType,
+  pub algorithm: &'a Algorithm,
+  /// The big-endian modulus of RSA keys.
+  pub modulus: Option<Vec<u8>>,
+}
+
+/// Decides whether a key may be imported, see
+/// `SubtleCrypto::add_import_hook`.
+pub type ImportHook =
+  Box<dyn Fn(&ImportedKey) -> Result<(), WebCryptoError> + Send + Sync>;
+
+/// Which behaviour `SubtleCrypto` follows, see
+/// `SubtleCrypto::set_api_mode`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ApiMode {
+  /// The algorithms, parameters and key formats of this crate, the
+  /// default.
+  Extended,
+  /// Only what browsers support, for runtimes promising browser parity.
+  Strict,
+}
+
+pub struct SubtleCrypto<R: RngCore + CryptoRng, S: KeyStorage> {
+  pub(crate) rng: R,
+  storage: S,
+  state: State,
+}
+
+/// Everything a context holds but its RNG and storage: its policies and
+/// the bookkeeping of the key material it stored, handed whole to the
+/// context `with_rng` builds.
+struct State {
+  api_mode: ApiMode,
+  gcm_rekey_threshold: Option<u64>,
+  min_rsa_modulus_length: Option<usize>,
+  require_f4_exponent: bool,
+  reject_legacy_hashes: bool,
+  import_hooks: Vec<ImportHook>,
+  screen_ec_imports: bool,
+  key_material_bytes: usize,
+  key_material_budget: Option<usize>,
+  key_cache: key_cache::KeyCache,
+  downgrade_protection: bool,
+  #[cfg(feature = "recording")]
+  recorder: Option<&'static crate::recording::Recorder>,
+}
+
+impl Default for State {
+  fn default() -> Self {
+    State {
+      api_mode: ApiMode::Extended,
+      gcm_rekey_threshold: None,
+      min_rsa_modulus_length: None,
+      require_f4_exponent: false,
+      reject_legacy_hashes: false,
+      import_hooks: Vec::new(),
+      screen_ec_imports: false,
+      key_material_bytes: 0,
+      key_material_budget: None,
+      key_cache: key_cache::KeyCache::default(),
+      downgrade_protection: false,
+      #[cfg(feature = "recording")]
+      recorder: None,
+    }
+  }
+}
+
+/// Hands the state `with_rng` lent back to its context once the
+/// operation returns, or panics.
+struct Lent<'a, R: RngCore + CryptoRng, S: KeyStorage> {
+  home: &'a mut State,
+  subtle: SubtleCrypto<R, S>,
+}
+
+impl<R: RngCore + CryptoRng, S: KeyStorage> Drop for Lent<'_, R, S> {
+  fn drop(&mut self) {
+    std::mem::swap(self.home, &mut self.subtle.state);
+  }
+}
+
+impl<R: RngCore + CryptoRng, S: KeyStorage> SubtleCrypto<R, S> {
+  pub fn new(rng: R, storage: S) -> Self {
+    SubtleCrypto {
+      rng,
+      storage,
+      state: State::default(),
+    }
+  }
+
+  /// In `ApiMode::Strict`, every method fails with `NotSupportedError`
+  /// on anything a browser does not support: other algorithms, named
+  /// curves, key formats and parameter extensions, eg.
+  /// "ChaCha20-Poly1305", secp256k1, "raw-private" or deterministic
+  /// ECDSA. Methods beyond the Web Crypto API, like `seal`, remain
+  /// available with keys of the algorithms browsers support.
+  pub fn set_api_mode(&mut self, mode: ApiMode) {
+    self.state.api_mode = mode;
+  }
+
+  pub fn api_mode(&self) -> ApiMode {
+    self.state.api_mode
+  }
+
+  /// Refuse AES-GCM encryption once a key would have encrypted more than
+  /// `bytes` in total, so long-lived keys are rotated before reaching the
+  /// safety margins of GCM. `None`, the default, disables the limit.
+  pub fn set_gcm_rekey_threshold(&mut self, bytes: Option<u64>) {
+    self.state.gcm_rekey_threshold = bytes;
+  }
+
+  /// Refuse to generate or import RSA keys with a modulus shorter than
+  /// `bits`, eg. 2048 as recommended by NIST SP 800-131A. `None`, the
+  /// default, accepts any supported length.
+  pub fn set_min_rsa_modulus_length(&mut self, bits: Option<usize>) {
+    self.state.min_rsa_modulus_length = bits;
+  }
+
+  /// Refuse to generate or import RSA keys whose public exponent is not
+  /// 65537 (F4), as many compliance baselines mandate. Small exponents
+  /// like 3 make padding mistakes exploitable. Off by default; pass
+  /// `false` to accept any exponent again.
+  pub fn set_require_f4_exponent(&mut self, required: bool) {
+    self.state.require_f4_exponent = required;
+  }
+
+  /// Refuse to hash with SHA-1 or MD5 where the hash is picked by the
+  /// caller rather than by a key: `digest` and the name-based UUIDs of
+  /// `Context::uuid_v3` and `Context::uuid_v5`. Off by default.
+  pub fn set_reject_legacy_hashes(&mut self, rejected: bool) {
+    self.state.reject_legacy_hashes = rejected;
+  }
+
+  /// Refuse to generate, import, derive or unwrap keys once the resident
+  /// key material, see `key_material_bytes`, would exceed `bytes`, so
+  /// embedders with little memory can bound it. Cached parsed keys are
+  /// evicted before a key is refused. `None`, the default, disables the
+  /// limit.
+  pub fn set_key_material_budget(&mut self, bytes: Option<usize>) {
+    self.state.key_material_budget = bytes;
+    if let Some(max) = bytes {
+      self
+        .state
+        .key_cache
+        .evict(max.saturating_sub(self.stored_key_bytes()));
+    }
+  }
+
+  /// Refuse operations asking for weaker parameters than their key was
+  /// created with: ECDSA digests shorter than the curve calls for, eg.
+  /// SHA-1 with a P-256 key, RSA-PSS salts shorter than the digest,
+  /// truncated AES-GCM tags, and wrapping an AES key under a shorter
+  /// one. Off by default.
+  pub fn set_downgrade_protection(&mut self, enabled: bool) {
+    self.state.downgrade_protection = enabled;
+  }
+
+  /// The length in bytes of the key material resident in memory: the
+  /// keys the storage holds, including those it held before this context
+  /// used it, and the parsed keys this context caches. Storages that
+  /// cannot tell what they hold count the keys this context stored.
+  pub fn key_material_bytes(&self) -> usize {
+    self.stored_key_bytes() + self.state.key_cache.bytes()
+  }
+
+  /// The length in bytes of the key material held by the storage.
+  fn stored_key_bytes(&self) -> usize {
+    self
+      .storage
+      .resident_bytes()
+      .unwrap_or(self.state.key_material_bytes)
+  }
+
+  /// Store `key_material`, counting it against the key material budget.
+  fn store_key(
+    &mut self,
+    key_material: KeyMaterial,
+  ) -> Result<S::Handle, WebCryptoError> {
+    let length = key_material.0.len();
+    if let Some(max) = self.state.key_material_budget {
+      let stored = self.stored_key_bytes() + length;
+      // Parsed keys can be parsed again: they give way first.
+      self.state.key_cache.evict(max.saturating_sub(stored));
+      if stored > max {
+        return Err(WebCryptoError::QuotaExceededError);
+      }
+    }
+    let handle = self
+      .storage
+      .try_store(key_material)
+      .map_err(|_| WebCryptoError::OperationError)?;
+    self.state.key_material_bytes += length;
+
+    Ok(handle)
+  }
+
+  /// The RSA private key of the PKCS #1 `key_material`, parsed once and
+  /// cached while the key material budget leaves room for it.
+  fn rsa_private_key(
+    &self,
+    key_material: &KeyMaterial,
+  ) -> Result<Arc<RsaPrivateKey>, WebCryptoError> {
+    let room = match self.state.key_material_budget {
+      Some(max) => max.saturating_sub(self.stored_key_bytes()),
+      None => usize::MAX,
+    };
+    self.state.key_cache.rsa_private_key(&key_material.0, room)
+  }
+
+  /// Delete the key material of a consume-once `key` once `result`, an
+  /// operation with it, succeeded. The operation fails when the storage
+  /// cannot delete the key, so the key is never used twice.
+  fn consume<T>(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    result: Result<T, WebCryptoError>,
+  ) -> Result<T, WebCryptoError> {
+    if result.is_err() || !key.consume_once {
+      return result;
+    }
+
+    if !self.delete_key(key.handle) {
+      return Err(WebCryptoError::OperationError);
+    }
+    result
+  }
+
+  /// Delete the key material of `handle` from the storage, no longer
+  /// counting it against the key material budget. Returns whether the
+  /// storage deleted it.
+  pub(crate) fn delete_key(&mut self, handle: S::Handle) -> bool {
+    let length = match self.storage.get(handle) {
+      Some(key_material) => {
+        self.state.key_cache.remove(&key_material.0);
+        key_material.0.len()
+      }
+      // Held by the storage itself, see `KeyStorage::generate`.
+      None => 0,
+    };
+    if !self.storage.delete(handle) {
+      return false;
+    }
+    self.state.key_material_bytes =
+      self.state.key_material_bytes.saturating_sub(length);
+    true
+  }
+
+  /// Check the parameters of an RSA key against the context policy,
+  /// saying why a weak key is refused.
+  fn check_rsa_policy(
+    &self,
+    modulus_length: usize,
+    public_exponent: PublicExponent,
+  ) -> Result<(), WebCryptoError> {
+    if matches!(self.state.min_rsa_modulus_length, Some(bits) if modulus_length < bits)
+    {
+      return Err(WebCryptoError::WeakRsaKey(WeakRsaKey::SmallModulus {
+        modulus_length,
+      }));
+    }
+
+    if self.state.require_f4_exponent && public_exponent != PublicExponent::F4 {
+      if public_exponent.0 < PublicExponent::F4.0 {
+        return Err(WebCryptoError::WeakRsaKey(WeakRsaKey::SmallExponent {
+          exponent: public_exponent.0,
+        }));
+      }
+      return Err(WebCryptoError::NotSupportedError);
+    }
+
+    Ok(())
+  }
+
+  /// Check the digest algorithm `name` against the context policy.
+  fn check_sign_downgrade(
+    &self,
+    algorithm: &SignParams,
+    key_algorithm: &Algorithm,
+  ) -> Result<(), WebCryptoError> {
+    if self.state.downgrade_protection
+      && is_sign_downgrade(algorithm, key_algorithm)?
+    {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+
+    Ok(())
+  }
+
+  fn check_encrypt_downgrade(
+    &self,
+    algorithm: &EncryptParams,
+  ) -> Result<(), WebCryptoError> {
+    if let EncryptParams::AesGcmParams(AesGcmParams {
+      tag_length: Some(tag_length),
+      ..
+    }) = algorithm
+    {
+      if self.state.downgrade_protection && *tag_length < 128 {
+        return Err(WebCryptoError::InvalidAccessError);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Fail in strict mode on the algorithm `name`, the key `format`, or
+  /// parameters that are an `extension`.
+  fn check_api_mode(
+    &self,
+    name: &str,
+    format: Option<&str>,
+    extension: bool,
+  ) -> Result<(), WebCryptoError> {
+    if self.state.api_mode == ApiMode::Strict
+      && (extension
+        || !SPEC_ALGORITHM_NAMES.contains(&name)
+        || format.is_some_and(|format| !SPEC_FORMATS.contains(&format)))
+    {
+      return Err(WebCryptoError::NotSupportedError);
+    }
+
+    Ok(())
+  }
+
+  pub(crate) fn check_hash_policy(
+    &self,
+    name: &str,
+  ) -> Result<(), WebCryptoError> {
+    if self.state.reject_legacy_hashes && matches!(name, "SHA-1" | "MD5") {
+      return Err(WebCryptoError::NotSupportedError);
+    }
+
+    Ok(())
+  }
+
+  /// Run `hook` on every imported key, after it is parsed and before it
+  /// is stored. The import fails when a hook returns an error, so
+  /// deployments can enforce their own rules, eg. approved curves or a
+  /// list of known-compromised moduli.
+  pub fn add_import_hook(
+    &mut self,
+    hook: impl Fn(&ImportedKey) -> Result<(), WebCryptoError>
+      + Send
+      + Sync
+      + 'static,
+  ) {
+    self.state.import_hooks.push(Box::new(hook));
+  }
+
+  /// Reject imported RSA keys that `screen` flags, failing with
+  /// `WebCryptoError::WeakRsaKey` saying why.
+  pub fn screen_rsa_imports(&mut self, screen: RsaScreen) {
+    self.add_import_hook(move |key| {
+      screen.check_key(key).map_err(WebCryptoError::WeakRsaKey)
+    });
+  }
+
+  /// Reject imported EC private keys with an obviously structured scalar,
+  /// see `check_ec_private_key`.
+  pub fn screen_ec_imports(&mut self) {
+    self.state.screen_ec_imports = true;
+  }
+
+  /// Run `op` with `rng` in place of the context RNG, eg. a DRBG for a
+  /// FIPS path or a `ReplayRng` replaying a single operation, without
+  /// building another context. `op` gets a context sharing the storage
+  /// and the policies of this one: keys it creates are usable here, and
+  /// count towards the key material budget.
+  pub fn with_rng<'a, Q: RngCore + CryptoRng, T>(
+    &'a mut self,
+    rng: Q,
+    op: impl FnOnce(&mut SubtleCrypto<Q, &'a mut S>) -> T,
+  ) -> T {
+    let state = std::mem::take(&mut self.state);
+    let mut lent = Lent {
+      home: &mut self.state,
+      subtle: SubtleCrypto {
+        rng,
+        storage: &mut self.storage,
+        state,
+      },
+    };
+    op(&mut lent.subtle)
+  }
+
+  /// Log every operation of this context to `recorder`, or stop logging
+  /// with `None`.
+  #[cfg(feature = "recording")]
+  pub fn set_recorder(
+    &mut self,
+    recorder: Option<&'static crate::recording::Recorder>,
+  ) {
+    self.state.recorder = recorder;
+  }
+
+  /// A copy of `data` to log as the input of an in-place operation,
+  /// taken only while a recorder is attached.
+  #[cfg_attr(not(feature = "recording"), allow(unused_variables))]
+  fn record_input(&self, data: &[u8]) -> Option<Vec<u8>> {
+    #[cfg(feature = "recording")]
+    if self.state.recorder.is_some() {
+      return Some(data.to_vec());
+    }
+    None
+  }
+
+  /// Log an operation to the attached recorder, if any. `output` is `None`
+  /// when the operation failed.
+  #[cfg_attr(not(feature = "recording"), allow(unused_variables))]
+  fn record(&self, operation: &str, inputs: &[&[u8]], output: Option<&[u8]>) {
+    #[cfg(feature = "recording")]
+    if let Some(recorder) = self.state.recorder {
+      recorder.record(operation, inputs, output);
+    }
+  }
+}
+
+impl<R: RngCore + CryptoRng, S: KeyStorage> SubtleCrypto<R, S> {
+  pub fn generate_key(
+    &mut self,
+    mut algorithm: KeyGenParams,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKeyOrPair<S::Handle>, WebCryptoError> {
+    algorithm.normalize();
+    self.check_api_mode(algorithm.name(), None, algorithm.is_extension())?;
+
+    let result = self.generate(algorithm, extractable, usages);
+    self.record("generateKey", &[], result.as_ref().ok().map(|_| &[][..]));
+    result
+  }
+
+  fn generate(
+    &mut self,
+    algorithm: KeyGenParams,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKeyOrPair<S::Handle>, WebCryptoError> {
+    check_generate_usages(algorithm.name(), &usages)?;
+    self.check_generate_params(&algorithm)?;
+
+    // Keys held by the storage never leave it, so only non-extractable
+    // keys are generated there.
+    if !extractable {
+      if let Some(generated) = self.storage.generate(&algorithm) {
+        let generated = generated?;
+        let handle = generated.handle;
+        let result = self.generated_key(algorithm, generated, usages);
+        // Do not leave a key no caller holds in the storage.
+        if result.is_err() {
+          self.delete_key(handle);
+        }
+        return result;
+      }
+    }
+
+    match algorithm {
+      KeyGenParams::RsaHashedKeyGenParams(ref rsa_alg) => {
+        match rsa_alg.name {
+          "RSASSA-PKCS1-v1_5" | "RSA-PSS" | "RSA-OAEP" | "RSA-KEM" => {
+            // 1. eg. encrypt and wrapKey for the public key of RSA-OAEP,
+            // decrypt and unwrapKey for the private one.
+            // Checked by `check_generate_usages`.
+            let name = rsa_alg.algorithm_name()?;
+            let private_usages = permitted_usages(name, KeyType::Private);
+            let public_usages = permitted_usages(name, KeyType::Public);
+
+            // 2. Checked by `check_generate_params`.
+            let exp = rsa_alg.public_exponent.to_biguint();
+            let p_key = RsaPrivateKey::new_with_exp(
+              &mut self.rng,
+              rsa_alg.modulus_length,
+              &exp,
+            )
+            .map_err(|_| WebCryptoError::OperationError)?;
+
+            let pkcs1 = p_key
+              .to_pkcs1_der()
+              .map_err(|_| WebCryptoError::OperationError)?;
+            // The public key is stored on its own, so its handle never
+            // leads to the private key.
+            let public_pkcs1 = p_key
+              .to_public_key()
+              .to_pkcs1_der()
+              .map_err(|_| WebCryptoError::OperationError)?;
+
+            let key_pair = CryptoKeyPair {
+              private_key: CryptoKey {
+                extractable,
+                usages: usages
+                  .iter()
+                  .filter(|usage| private_usages.contains(usage))
+                  .cloned()
+                  .collect(),
+                handle: self.store_key(KeyMaterial(pkcs1.as_ref().to_vec()))?,
+                type_: KeyType::Private,
+                algorithm: algorithm.into(),
+                encrypted: AtomicU64::new(0),
+                consume_once: false,
+              },
+              // 3. The public key is always extractable.
+              public_key: CryptoKey {
+                extractable: true,
+                usages: usages
+                  .into_iter()
+                  .filter(|usage| public_usages.contains(usage))
+                  .collect(),
+                handle: self
+                  .store_key(KeyMaterial(public_pkcs1.as_ref().to_vec()))?,
+                type_: KeyType::Public,
+                algorithm: algorithm.into(),
+                encrypted: AtomicU64::new(0),
+                consume_once: false,
+              },
+            };
+
+            Ok(CryptoKeyOrPair::CryptoKeyPair(key_pair))
+          }
+          _ => Err(WebCryptoError::NotSupportedError),
+        }
+      }
+      KeyGenParams::EcKeyGenParams(ref ec_alg) => {
+        // 1. Checked by `check_generate_usages`.
+        let name = match ec_alg.name {
+          "ECDSA" | "ECDH" => ec_alg.algorithm_name()?,
+          _ => {
+            return Err(WebCryptoError::NotSupportedError);
+          }
+        };
+        let private_usages = permitted_usages(name, KeyType::Private);
+        let public_usages = permitted_usages(name, KeyType::Public);
+
+        // 2.
+        let (private_key, public_key) =
+          ec::generate(ec_alg.named_curve, &mut self.rng);
+
+        let key_pair = CryptoKeyPair {
+          private_key: CryptoKey {
+            extractable,
+            usages: usages
+              .iter()
+              .filter(|usage| private_usages.contains(usage))
+              .cloned()
+              .collect(),
+            handle: self.store_key(KeyMaterial(private_key))?,
+            type_: KeyType::Private,
+            algorithm: algorithm.into(),
+            encrypted: AtomicU64::new(0),
+            consume_once: false,
+          },
+          // 3. The public key is always extractable.
+          public_key: CryptoKey {
+            extractable: true,
+            usages: usages
+              .iter()
+              .filter(|usage| public_usages.contains(usage))
+              .cloned()
+              .collect(),
+            handle: self.store_key(KeyMaterial(public_key))?,
+            type_: KeyType::Public,
+            algorithm: algorithm.into(),
+            encrypted: AtomicU64::new(0),
+            consume_once: false,
+          },
+        };
+
+        Ok(CryptoKeyOrPair::CryptoKeyPair(key_pair))
+      }
+      KeyGenParams::AesKeyGenParams(ref aes_alg) => match aes_alg.name {
+        "AES-CTR" | "AES-CBC" | "AES-GCM" | "AES-KW" | "AES-KWP"
+        | "AES-CMAC" | "AES-FF1" => {
+          // The length is checked by `check_generate_params`.
+          let mut key_data = vec![0u8; aes_alg.length / 8];
+          self.rng.fill_bytes(&mut key_data);
+          let handle = self.store_key(KeyMaterial(key_data))?;
+
+          let key = CryptoKey {
+            extractable,
+            usages,
+            handle,
+            type_: KeyType::Secret,
+            algorithm: algorithm.into(),
+            encrypted: AtomicU64::new(0),
+            consume_once: false,
+          };
+
+          Ok(CryptoKeyOrPair::CryptoKey(key))
+        }
+        _ => Err(WebCryptoError::NotSupportedError),
+      },
+      KeyGenParams::HmacKeyGenParams(ref hmac_alg) => {
+        match hmac_alg.name {
+          "HMAC" => {
+            // 2. Checked by `check_generate_params`.
+            let block_size = hmac_block_size(hmac_alg.hash.name)?;
+            let length = hmac_alg.length.unwrap_or(block_size);
+
+            // 3.
+            let mut key_data = vec![0u8; length.div_ceil(8)];
+            self.rng.fill_bytes(&mut key_data);
+            truncate_bits(&mut key_data, length);
+
+            let handle = self.store_key(KeyMaterial(key_data))?;
+
+            let key = CryptoKey {
+              extractable,
+              usages,
+              handle,
+              type_: KeyType::Secret,
+              algorithm: algorithm.into(),
+              encrypted: AtomicU64::new(0),
+              consume_once: false,
+            };
+
+            Ok(CryptoKeyOrPair::CryptoKey(key))
+          }
+          _ => Err(WebCryptoError::NotSupportedError),
+        }
+      }
+      KeyGenParams::AlgorithmIdentifer(alg) => match alg.name {
+        "ChaCha20-Poly1305" | "XChaCha20-Poly1305" | "BLAKE3" => {
+          let mut key_data = vec![0u8; 32];
+          self.rng.fill_bytes(&mut key_data);
+          let handle = self.store_key(KeyMaterial(key_data))?;
+
+          let key = CryptoKey {
+            extractable,
+            usages,
+            handle,
+            type_: KeyType::Secret,
+            algorithm: algorithm.into(),
+            encrypted: AtomicU64::new(0),
+            consume_once: false,
+          };
+
+          Ok(CryptoKeyOrPair::CryptoKey(key))
+        }
+        "Ed25519" | "Ed448" | "X448" => {
+          // 1. Checked by `check_generate_usages`.
+          let name = alg.algorithm_name()?;
+          let private_usages = permitted_usages(name, KeyType::Private);
+          let public_usages = permitted_usages(name, KeyType::Public);
+
+          // 2.
+          let (private_key, public_key) =
+            okp::generate(alg.name, &mut self.rng)?;
+
+          let key_pair = CryptoKeyPair {
+            private_key: CryptoKey {
+              extractable,
+              usages: usages
+                .iter()
+                .filter(|usage| private_usages.contains(usage))
+                .cloned()
+                .collect(),
+              handle: self.store_key(KeyMaterial(private_key))?,
+              type_: KeyType::Private,
+              algorithm: algorithm.into(),
+              encrypted: AtomicU64::new(0),
+              consume_once: false,
+            },
+            // 3. The public key is always extractable.
+            public_key: CryptoKey {
+              extractable: true,
+              usages: usages
+                .into_iter()
+                .filter(|usage| public_usages.contains(usage))
+                .collect(),
+              handle: self.store_key(KeyMaterial(public_key))?,
+              type_: KeyType::Public,
+              algorithm: algorithm.into(),
+              encrypted: AtomicU64::new(0),
+              consume_once: false,
+            },
+          };
+
+          Ok(CryptoKeyOrPair::CryptoKeyPair(key_pair))
+        }
+        #[cfg(feature = "ml-kem")]
+        "X-Wing" => {
+          // 1. Checked by `check_generate_usages`.
+          let name = alg.algorithm_name()?;
+          let private_usages = permitted_usages(name, KeyType::Private);
+          let public_usages = permitted_usages(name, KeyType::Public);
+
+          // 2.
+          let (private_key, public_key) = xwing::generate(&mut self.rng);
+
+          let key_pair = CryptoKeyPair {
+            private_key: CryptoKey {
+              extractable,
+              usages: usages
+                .iter()
+                .filter(|usage| private_usages.contains(usage))
+                .cloned()
+                .collect(),
+              handle: self.store_key(KeyMaterial(private_key))?,
+              type_: KeyType::Private,
+              algorithm: algorithm.into(),
+              encrypted: AtomicU64::new(0),
+              consume_once: false,
+            },
+            // 3. The public key is always extractable.
+            public_key: CryptoKey {
+              extractable: true,
+              usages: usages
+                .into_iter()
+                .filter(|usage| public_usages.contains(usage))
+                .collect(),
+              handle: self.store_key(KeyMaterial(public_key))?,
+              type_: KeyType::Public,
+              algorithm: algorithm.into(),
+              encrypted: AtomicU64::new(0),
+              consume_once: false,
+            },
+          };
+
+          Ok(CryptoKeyOrPair::CryptoKeyPair(key_pair))
+        }
+        _ => Err(WebCryptoError::NotSupportedError),
+      },
+      _ => Err(WebCryptoError::NotSupportedError),
+    }
+  }
+
+  pub fn encrypt(
+    &mut self,
+    mut algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    algorithm.normalize();
+    // 8. and 9.
+    check_key_use(key, algorithm.name(), Operation::Encrypt)?;
+
+    self.check_api_mode(
+      key.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+
+    // 10.
+    let result = self.encrypt_with(&algorithm, key, data);
+    let result = self.consume(key, result);
+    self.record("encrypt", &[data], result.as_deref().ok());
+    result
+  }
+
+  pub fn decrypt(
+    &mut self,
+    mut algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    algorithm.normalize();
+    // 8. and 9.
+    check_key_use(key, algorithm.name(), Operation::Decrypt)?;
+
+    self.check_api_mode(
+      key.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+
+    // 10.
+    let result = self.decrypt_with(&algorithm, key, data);
+    let result = self.consume(key, result);
+    self.record("decrypt", &[data], result.as_deref().ok());
+    result
+  }
+
+  /// Encrypt `buffer` in place, like `encrypt`. AES-GCM and the
+  /// ChaCha20-Poly1305 variants reuse the allocation of `buffer`, growing
+  /// it by the tag, so a server can encrypt every message into the same
+  /// buffer.
+  pub fn encrypt_in_place(
+    &mut self,
+    mut algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    buffer: &mut Vec<u8>,
+  ) -> Result<(), WebCryptoError> {
+    algorithm.normalize();
+    // 8. and 9.
+    check_key_use(key, algorithm.name(), Operation::Encrypt)?;
+
+    self.check_api_mode(
+      key.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+
+    // 10.
+    let input = self.record_input(buffer);
+    let result = self.encrypt_in_place_with(&algorithm, key, buffer);
+    let result = self.consume(key, result);
+    let output = result.map(|_| &buffer[..]).ok();
+    self.record("encrypt", &[input.as_deref().unwrap_or_default()], output);
+    result
+  }
+
+  /// Decrypt `buffer` in place, like `decrypt`. On failure, `buffer` is
+  /// zeroed and left empty, so unauthenticated plaintext is never
+  /// released.
+  pub fn decrypt_in_place(
+    &mut self,
+    mut algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    buffer: &mut Vec<u8>,
+  ) -> Result<(), WebCryptoError> {
+    algorithm.normalize();
+    // 8. and 9.
+    check_key_use(key, algorithm.name(), Operation::Decrypt)?;
+
+    self.check_api_mode(
+      key.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+
+    // 10.
+    let input = self.record_input(buffer);
+    let result = self.decrypt_in_place_with(&algorithm, key, buffer);
+    let result = self.consume(key, result);
+    if result.is_err() {
+      buffer.fill(0);
+      buffer.clear();
+    }
+    let output = result.map(|_| &buffer[..]).ok();
+    self.record("decrypt", &[input.as_deref().unwrap_or_default()], output);
+    result
+  }
+
+  /// Encrypt `data` into `out`, replacing its contents but keeping its
+  /// allocation.
+  pub fn encrypt_into(
+    &mut self,
+    algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    data: &[u8],
+    out: &mut Vec<u8>,
+  ) -> Result<(), WebCryptoError> {
+    out.clear();
+    out.extend_from_slice(data);
+    self.encrypt_in_place(algorithm, key, out)
+  }
+
+  /// Decrypt `data` into `out`, replacing its contents but keeping its
+  /// allocation.
+  pub fn decrypt_into(
+    &mut self,
+    algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    data: &[u8],
+    out: &mut Vec<u8>,
+  ) -> Result<(), WebCryptoError> {
+    out.clear();
+    out.extend_from_slice(data);
+    self.decrypt_in_place(algorithm, key, out)
+  }
+
+  /// Encrypt `data` like `encrypt`, also returning the effective
+  /// parameters: the IV and the tag length of AES-GCM, defaulting to 128
+  /// bits.
+  pub fn encrypt_detailed(
+    &mut self,
+    mut algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    data: &[u8],
+  ) -> Result<EncryptOutput, WebCryptoError> {
+    algorithm.normalize();
+    let mut params = algorithm.clone();
+    if let EncryptParams::AesGcmParams(params) = &mut params {
+      params.tag_length.get_or_insert(128);
+    }
+
+    let ciphertext = self.encrypt(algorithm, key, data)?;
+    Ok(EncryptOutput { ciphertext, params })
+  }
+
+  /// Encrypt `data` under a fresh random IV, ignoring the IV of
+  /// `algorithm`. Returns the IV and the ciphertext; the IV has to be
+  /// sent along for decryption.
+  ///
+  /// Random 96-bit IVs are safe for up to 2^32 messages per key, use a
+  /// `NonceSequence` beyond that.
+  pub fn encrypt_with_random_iv(
+    &mut self,
+    mut algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    data: &[u8],
+  ) -> Result<(Vec<u8>, Vec<u8>), WebCryptoError> {
+    // NotSupportedError when the algorithm takes no IV.
+    let (iv, length) = algorithm
+      .iv_mut()
+      .ok_or(WebCryptoError::NotSupportedError)?;
+    *iv = vec![0; length];
+    self.rng.fill_bytes(iv);
+    let iv = iv.clone();
+
+    let ciphertext = self.encrypt(algorithm, key, data)?;
+    Ok((iv, ciphertext))
+  }
+
+  /// A `NonceSequence` for `algorithm`, with a fixed field drawn from
+  /// the context RNG.
+  pub fn nonce_sequence(
+    &mut self,
+    algorithm: &EncryptParams,
+  ) -> Result<NonceSequence, WebCryptoError> {
+    // NotSupportedError when the algorithm takes no IV.
+    let (_, length) = algorithm
+      .clone()
+      .iv_mut()
+      .ok_or(WebCryptoError::NotSupportedError)?;
+    let mut fixed = vec![0; length - 8];
+    self.rng.fill_bytes(&mut fixed);
+
+    Ok(NonceSequence::new(&fixed))
+  }
+
+  /// Encrypt `data` under the next nonce of `nonces`, ignoring the IV of
+  /// `algorithm`. Returns the nonce and the ciphertext.
+  pub fn encrypt_with_nonce_sequence(
+    &mut self,
+    mut algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    nonces: &mut NonceSequence,
+    data: &[u8],
+  ) -> Result<(Vec<u8>, Vec<u8>), WebCryptoError> {
+    // NotSupportedError when the algorithm takes no IV.
+    let (iv, length) = algorithm
+      .iv_mut()
+      .ok_or(WebCryptoError::NotSupportedError)?;
+    if nonces.nonce_length() != length {
+      return Err(WebCryptoError::OperationError);
+    }
+    *iv = nonces.next()?;
+    let iv = iv.clone();
+
+    let ciphertext = self.encrypt(algorithm, key, data)?;
+    Ok((iv, ciphertext))
+  }
+
+  /// Check the parameters of a key to generate, before the storage is
+  /// asked to generate it: the storage then never holds a key this
+  /// context would have refused.
+  fn check_generate_params(
+    &self,
+    algorithm: &KeyGenParams,
+  ) -> Result<(), WebCryptoError> {
+    match algorithm {
+      KeyGenParams::RsaHashedKeyGenParams(rsa_alg) => {
+        if rsa_alg.modulus_length % 8 != 0
+          || !(MIN_RSA_MODULUS_LENGTH..=MAX_RSA_MODULUS_LENGTH)
+            .contains(&rsa_alg.modulus_length)
+        {
+          return Err(WebCryptoError::OperationError);
+        }
+        // Not a `DataError`: there is no key data yet.
+        self
+          .check_rsa_policy(rsa_alg.modulus_length, rsa_alg.public_exponent)
+          .map_err(|_| WebCryptoError::NotSupportedError)
+      }
+      KeyGenParams::AesKeyGenParams(aes_alg) => {
+        if !AES_KEY_LENGTHS.contains(&aes_alg.length) {
+          return Err(WebCryptoError::OperationError);
+        }
+        Ok(())
+      }
+      KeyGenParams::HmacKeyGenParams(hmac_alg) => {
+        let block_size = hmac_block_size(hmac_alg.hash.name)?;
+        if hmac_alg.length.unwrap_or(block_size) == 0 {
+          return Err(WebCryptoError::OperationError);
+        }
+        Ok(())
+      }
+      _ => Ok(()),
+    }
+  }
+
+  /// The non-extractable key the storage generated for `algorithm`. The
+  /// public key of a pair is imported, so it is screened like any other
+  /// and its algorithm, eg. the modulus length, is the one of the key
+  /// actually held.
+  fn generated_key(
+    &mut self,
+    algorithm: KeyGenParams,
+    generated: GeneratedKey<S::Handle>,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKeyOrPair<S::Handle>, WebCryptoError> {
+    let spki = match generated.public_key {
+      Some(spki) => spki,
+      None => {
+        return Ok(CryptoKeyOrPair::CryptoKey(CryptoKey {
+          extractable: false,
+          usages,
+          handle: generated.handle,
+          type_: KeyType::Secret,
+          algorithm: algorithm.into(),
+          encrypted: AtomicU64::new(0),
+          consume_once: false,
+        }));
+      }
+    };
+
+    let name = algorithm.name().parse::<AlgorithmName>()?;
+    let private_usages = permitted_usages(name, KeyType::Private);
+    let public_usages = permitted_usages(name, KeyType::Public);
+    let params = match algorithm {
+      KeyGenParams::RsaHashedKeyGenParams(params) => {
+        ImportParams::RsaHashedImportParams(RsaHashedImportParams {
+          name: params.name,
+          hash: params.hash,
+        })
+      }
+      KeyGenParams::EcKeyGenParams(params) => {
+        ImportParams::EcKeyImportParams(EcKeyImportParams {
+          name: params.name,
+          named_curve: params.named_curve,
+        })
+      }
+      _ => ImportParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: algorithm.name(),
+      }),
+    };
+    let public_key = self.import_key_data(
+      "spki",
+      &spki,
+      params,
+      true,
+      usages
+        .iter()
+        .filter(|usage| public_usages.contains(usage))
+        .cloned()
+        .collect(),
+    )?;
+
+    Ok(CryptoKeyOrPair::CryptoKeyPair(CryptoKeyPair {
+      private_key: CryptoKey {
+        extractable: false,
+        usages: usages
+          .into_iter()
+          .filter(|usage| private_usages.contains(usage))
+          .collect(),
+        handle: generated.handle,
+        type_: KeyType::Private,
+        algorithm: public_key.algorithm,
+        encrypted: AtomicU64::new(0),
+        consume_once: false,
+      },
+      public_key,
+    }))
+  }
+
+  /// `StreamNonces` for `algorithm`, with a prefix drawn from the context
+  /// RNG.
+  pub fn stream_nonces(
+    &mut self,
+    algorithm: &EncryptParams,
+  ) -> Result<StreamNonces, WebCryptoError> {
+    // NotSupportedError when the algorithm takes no IV.
+    let (_, length) = algorithm
+      .clone()
+      .iv_mut()
+      .ok_or(WebCryptoError::NotSupportedError)?;
+    let mut prefix = vec![0; length - 5];
+    self.rng.fill_bytes(&mut prefix);
+
+    Ok(StreamNonces::new(&prefix))
+  }
+
+  /// Encrypt one chunk of a stream under the next nonce of `nonces`,
+  /// ignoring the IV of `algorithm`. `last` marks the final chunk, after
+  /// which the stream cannot be extended.
+  ///
+  /// Consume-once keys are refused with `InvalidAccessError`: a stream
+  /// uses its key once per chunk. `seal_stream` consumes them once the
+  /// whole stream is sealed.
+  pub fn encrypt_chunk(
+    &mut self,
+    mut algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    nonces: &mut StreamNonces,
+    chunk: &[u8],
+    last: bool,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    if key.consume_once {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    // NotSupportedError when the algorithm takes no IV.
+    let (iv, length) = algorithm
+      .iv_mut()
+      .ok_or(WebCryptoError::NotSupportedError)?;
+    if nonces.nonce_length() != length {
+      return Err(WebCryptoError::OperationError);
+    }
+    *iv = nonces.next(last)?;
+
+    self.encrypt(algorithm, key, chunk)
+  }
+
+  /// Decrypt one chunk of a stream, the counterpart of `encrypt_chunk`.
+  /// `last` must be set on the final chunk only, or decryption fails.
+  /// A failed chunk ends the stream.
+  pub fn decrypt_chunk(
+    &mut self,
+    mut algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    nonces: &mut StreamNonces,
+    chunk: &[u8],
+    last: bool,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    if key.consume_once {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    // NotSupportedError when the algorithm takes no IV.
+    let (iv, length) = algorithm
+      .iv_mut()
+      .ok_or(WebCryptoError::NotSupportedError)?;
+    if nonces.nonce_length() != length {
+      return Err(WebCryptoError::OperationError);
+    }
+    *iv = nonces.next(last)?;
+
+    self.decrypt(algorithm, key, chunk)
+  }
+
+  /// Encrypt all of `reader` to `writer` in chunks of `chunk_length`
+  /// bytes, holding two chunks in memory at most. The output starts
+  /// with the random nonce prefix, followed by the encrypted chunks.
+  pub fn encrypt_stream<Rd: Read, W: Write>(
+    &mut self,
+    algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    chunk_length: usize,
+    reader: &mut Rd,
+    writer: &mut W,
+  ) -> Result<(), WebCryptoError> {
+    if key.consume_once {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    if chunk_length == 0 {
+      return Err(WebCryptoError::OperationError);
+    }
+    let mut nonces = self.stream_nonces(&algorithm)?;
+    writer
+      .write_all(nonces.prefix())
+      .map_err(|_| WebCryptoError::OperationError)?;
+
+    let mut chunk = vec![0; chunk_length];
+    let mut next = vec![0; chunk_length];
+    let mut length = stream::read_full(reader, &mut chunk)?;
+    loop {
+      // Only a full chunk can be followed by another.
+      let next_length = if length == chunk_length {
+        stream::read_full(reader, &mut next)?
+      } else {
+        0
+      };
+      let last = next_length == 0;
+
+      let ciphertext = self.encrypt_chunk(
+        algorithm.clone(),
+        key,
+        &mut nonces,
+        &chunk[..length],
+        last,
+      )?;
+      writer
+        .write_all(&ciphertext)
+        .map_err(|_| WebCryptoError::OperationError)?;
+      if last {
+        return Ok(());
+      }
+
+      std::mem::swap(&mut chunk, &mut next);
+      length = next_length;
+    }
+  }
+
+  /// Decrypt the output of `encrypt_stream` from `reader` to `writer`,
+  /// with the same `chunk_length`.
+  ///
+  /// Chunks are written as soon as they are authenticated, so on error
+  /// everything written so far has to be discarded.
+  pub fn decrypt_stream<Rd: Read, W: Write>(
+    &mut self,
+    algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    chunk_length: usize,
+    reader: &mut Rd,
+    writer: &mut W,
+  ) -> Result<(), WebCryptoError> {
+    if key.consume_once {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    // NotSupportedError when the algorithm takes no IV.
+    let (_, length) = algorithm
+      .clone()
+      .iv_mut()
+      .ok_or(WebCryptoError::NotSupportedError)?;
+    let mut prefix = vec![0; length - 5];
+    if chunk_length == 0
+      || stream::read_full(reader, &mut prefix)? != prefix.len()
+    {
+      return Err(WebCryptoError::OperationError);
+    }
+    let mut nonces = StreamNonces::new(&prefix);
+
+    let chunk_length = chunk_length + stream::overhead(&algorithm)?;
+    let mut chunk = vec![0; chunk_length];
+    let mut next = vec![0; chunk_length];
+    let mut length = stream::read_full(reader, &mut chunk)?;
+    loop {
+      let next_length = if length == chunk_length {
+        stream::read_full(reader, &mut next)?
+      } else {
+        0
+      };
+      let last = next_length == 0;
+
+      let plaintext = self.decrypt_chunk(
+        algorithm.clone(),
+        key,
+        &mut nonces,
+        &chunk[..length],
+        last,
+      )?;
+      writer
+        .write_all(&plaintext)
+        .map_err(|_| WebCryptoError::OperationError)?;
+      if last {
+        return Ok(());
+      }
+
+      std::mem::swap(&mut chunk, &mut next);
+      length = next_length;
+    }
+  }
+
+  /// Decrypt the output of `encrypt_stream` with the same `chunk_length`
+  /// as it is read from `reader`. Ciphertext is only read from `reader`
+  /// once the plaintext before it was read, so about two chunks are
+  /// buffered at most.
+  #[cfg(feature = "tokio")]
+  pub fn decrypt_reader<'a, Rd: tokio::io::AsyncRead + Unpin>(
+    &'a mut self,
+    algorithm: EncryptParams,
+    key: &'a CryptoKey<S::Handle>,
+    chunk_length: usize,
+    reader: Rd,
+  ) -> Result<DecryptReader<'a, R, S, Rd>, WebCryptoError> {
+    let decryption =
+      async_stream::Decryption::new(algorithm, key, chunk_length)?;
+    Ok(DecryptReader::new(self, decryption, reader))
+  }
+
+  /// Encrypt what is written in chunks of `chunk_length` bytes to
+  /// `writer`, like `encrypt_stream`. Plaintext is only taken once the
+  /// ciphertext before it was written to `writer`, so about two chunks
+  /// are buffered at most.
+  #[cfg(feature = "tokio")]
+  pub fn encrypt_writer<'a, W: tokio::io::AsyncWrite + Unpin>(
+    &'a mut self,
+    algorithm: EncryptParams,
+    key: &'a CryptoKey<S::Handle>,
+    chunk_length: usize,
+    writer: W,
+  ) -> Result<EncryptWriter<'a, R, S, W>, WebCryptoError> {
+    let nonces = self.stream_nonces(&algorithm)?;
+    let encryption =
+      async_stream::Encryption::new(algorithm, key, chunk_length, nonces)?;
+    Ok(EncryptWriter::new(self, encryption, writer))
+  }
+
+  /// Decrypt the output of `encrypt_stream` from `reader` and encrypt it
+  /// again with `new_algorithm` under `new_key` to `writer`, both in
+  /// chunks of `chunk_length` bytes, eg. in a proxy. Each chunk is
+  /// written once it is read, as fast as `writer` takes it.
+  ///
+  /// Like with `decrypt_stream`, on error everything written so far has
+  /// to be discarded.
+  #[cfg(feature = "tokio")]
+  #[allow(clippy::too_many_arguments)]
+  pub async fn reencrypt_stream<Rd, W>(
+    &mut self,
+    algorithm: EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    new_algorithm: EncryptParams,
+    new_key: &CryptoKey<S::Handle>,
+    chunk_length: usize,
+    mut reader: Rd,
+    mut writer: W,
+  ) -> Result<(), WebCryptoError>
+  where
+    Rd: tokio::io::AsyncRead + Unpin,
+    W: tokio::io::AsyncWrite + Unpin,
+  {
+    use std::future::poll_fn;
+    use std::pin::Pin;
+
+    let mut decryption =
+      async_stream::Decryption::new(algorithm, key, chunk_length)?;
+    let nonces = self.stream_nonces(&new_algorithm)?;
+    let mut encryption = async_stream::Encryption::new(
+      new_algorithm,
+      new_key,
+      chunk_length,
+      nonces,
+    )?;
+
+    loop {
+      let chunk =
+        poll_fn(|cx| decryption.poll_chunk(cx, self, Pin::new(&mut reader)))
+          .await
+          .map_err(async_stream::crypto_error)?;
+      let Some(chunk) = chunk else {
+        break;
+      };
+
+      let mut chunk = &chunk[..];
+      while !chunk.is_empty() {
+        let written = poll_fn(|cx| {
+          encryption.poll_write(cx, self, Pin::new(&mut writer), chunk)
+        })
+        .await
+        .map_err(async_stream::crypto_error)?;
+        chunk = &chunk[written..];
+      }
+    }
+
+    poll_fn(|cx| encryption.poll_shutdown(cx, self, Pin::new(&mut writer)))
+      .await
+      .map_err(async_stream::crypto_error)
+  }
+
+  /// Encrypt `data` under the AES-GCM or ChaCha20-Poly1305 `key` into an
+  /// envelope, a ciphertext carrying the algorithm, key ID, random nonce
+  /// and `additional_data` it was sealed with. ChaCha20-Poly1305 keys
+  /// seal with XChaCha20-Poly1305.
+  pub fn seal(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    additional_data: &[u8],
+    data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let result = self.seal_data(key, additional_data, data);
+    let result = self.consume(key, result);
+    self.record("seal", &[additional_data, data], result.as_deref().ok());
+    result
+  }
+
+  fn seal_data(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    additional_data: &[u8],
+    data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    // The algorithm of the envelope is that of the key.
+    check_key_use(key, key.algorithm.name(), Operation::Seal)?;
+    self.check_api_mode(key.algorithm.name(), None, false)?;
+
+    let algorithm = envelope::seal_algorithm(key.algorithm.name())?;
+    let mut nonce = vec![0; envelope::nonce_length(algorithm)];
+    self.rng.fill_bytes(&mut nonce);
+    let header = EnvelopeHeader {
+      version: ENVELOPE_VERSION,
+      algorithm,
+      key_id: self.envelope_key_id(key)?,
+      nonce,
+      additional_data: additional_data.to_vec(),
+      chunk_length: None,
+    };
+    let mut sealed = envelope::encode(&header)?;
+
+    let mut buffer = data.to_vec();
+    self.envelope_cipher(
+      header.algorithm,
+      &header.nonce,
+      &sealed,
+      key,
+      &mut buffer,
+      true,
+    )?;
+    sealed.extend(buffer);
+    Ok(sealed)
+  }
+
+  /// Decrypt an envelope sealed by `seal` under `key`, which must have
+  /// the key ID of its header. The additional data is in the header,
+  /// see `EnvelopeHeader::from_envelope`.
+  pub fn open(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    sealed: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let result = self.open_data(key, sealed);
+    let result = self.consume(key, result);
+    self.record("open", &[sealed], result.as_deref().ok());
+    result
+  }
+
+  fn open_data(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    sealed: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    check_key_use(key, key.algorithm.name(), Operation::Open)?;
+    self.check_api_mode(key.algorithm.name(), None, false)?;
+
+    let (header, header_length) = envelope::decode(sealed)?;
+    if envelope::key_algorithm(header.algorithm) != key.algorithm.name()
+      || header.key_id != self.envelope_key_id(key)?
+    {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    if header.version != ENVELOPE_VERSION {
+      return Err(WebCryptoError::NotSupportedError);
+    }
+
+    let (header_bytes, ciphertext) = sealed.split_at(header_length);
+    let mut buffer = ciphertext.to_vec();
+    self.envelope_cipher(
+      header.algorithm,
+      &header.nonce,
+      header_bytes,
+      key,
+      &mut buffer,
+      false,
+    )?;
+    Ok(buffer)
+  }
+
+  /// Seal all of `reader` under `key` into a streamed envelope written to
+  /// `writer`, like `seal` but in chunks of `chunk_length` bytes, at most
+  /// `MAX_CHUNK_LENGTH`, holding two chunks in memory at most.
+  pub fn seal_stream<Rd: Read, W: Write>(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    additional_data: &[u8],
+    chunk_length: u32,
+    reader: &mut Rd,
+    writer: &mut W,
+  ) -> Result<(), WebCryptoError> {
+    let result =
+      self.seal_stream_data(key, additional_data, chunk_length, reader, writer);
+    self.consume(key, result)
+  }
+
+  fn seal_stream_data<Rd: Read, W: Write>(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    additional_data: &[u8],
+    chunk_length: u32,
+    reader: &mut Rd,
+    writer: &mut W,
+  ) -> Result<(), WebCryptoError> {
+    let mut sealer =
+      self.stream_sealer(key, additional_data, chunk_length, writer)?;
+    let mut chunk = vec![0; sealer.chunk_length];
+    loop {
+      let length = stream::read_full(reader, &mut chunk)?;
+      self.seal_chunks(key, &mut sealer, &chunk[..length], false, writer)?;
+      if length < chunk.len() {
+        return self.seal_chunks(key, &mut sealer, &[], true, writer);
+      }
+    }
+  }
+
+  /// Start a streamed envelope under `key`, writing its header.
+  fn stream_sealer<W: Write>(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    additional_data: &[u8],
+    chunk_length: u32,
+    writer: &mut W,
+  ) -> Result<envelope::StreamSealer, WebCryptoError> {
+    // The algorithm of the envelope is that of the key.
+    check_key_use(key, key.algorithm.name(), Operation::Seal)?;
+    self.check_api_mode(key.algorithm.name(), None, false)?;
+
+    let algorithm = envelope::seal_algorithm(key.algorithm.name())?;
+    let mut prefix = vec![0; envelope::nonce_length(algorithm) - 5];
+    self.rng.fill_bytes(&mut prefix);
+    let header = EnvelopeHeader {
+      version: envelope::STREAM_ENVELOPE_VERSION,
+      algorithm,
+      key_id: self.envelope_key_id(key)?,
+      nonce: prefix,
+      additional_data: additional_data.to_vec(),
+      chunk_length: Some(chunk_length),
+    };
+    let header_bytes = envelope::encode(&header)?;
+    writer
+      .write_all(&header_bytes)
+      .map_err(|_| WebCryptoError::OperationError)?;
+
+    Ok(envelope::StreamSealer {
+      algorithm,
+      header_bytes,
+      nonces: StreamNonces::new(&header.nonce),
+      chunk_length: chunk_length as usize,
+      pending: vec![],
+    })
+  }
+
+  /// Add `data` to the streamed envelope of `sealer`, writing the chunks
+  /// it completes. `last` seals what remains as the last chunk.
+  fn seal_chunks<W: Write>(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    sealer: &mut envelope::StreamSealer,
+    data: &[u8],
+    last: bool,
+    writer: &mut W,
+  ) -> Result<(), WebCryptoError> {
+    sealer.pending.extend_from_slice(data);
+    // Only a full chunk can be followed by another.
+    while sealer.pending.len() > sealer.chunk_length || last {
+      let rest = if sealer.pending.len() > sealer.chunk_length {
+        sealer.pending.split_off(sealer.chunk_length)
+      } else {
+        vec![]
+      };
+      let mut chunk = std::mem::replace(&mut sealer.pending, rest);
+      let is_last = last && sealer.pending.is_empty();
+
+      let nonce = sealer.nonces.next(is_last)?;
+      self.envelope_cipher(
+        sealer.algorithm,
+        &nonce,
+        &sealer.header_bytes,
+        key,
+        &mut chunk,
+        true,
+      )?;
+      writer
+        .write_all(&chunk)
+        .map_err(|_| WebCryptoError::OperationError)?;
+      if is_last {
+        break;
+      }
+    }
+    Ok(())
+  }
+
+  /// Open a streamed envelope sealed by `seal_stream` under `key` from
+  /// `reader`, writing the data to `writer`.
+  ///
+  /// Chunks are written as soon as they are authenticated, so on error
+  /// everything written so far has to be discarded.
+  pub fn open_stream<Rd: Read, W: Write>(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    reader: &mut Rd,
+    writer: &mut W,
+  ) -> Result<(), WebCryptoError> {
+    let result = self.open_stream_data(key, reader, |_, _, data, _| {
+      writer
+        .write_all(data)
+        .map_err(|_| WebCryptoError::OperationError)
+    });
+    self.consume(key, result)
+  }
+
+  /// Open the streamed envelope of `reader`, handing each authenticated
+  /// chunk and whether it is the last to `sink`.
+  fn open_stream_data<Rd: Read>(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    reader: &mut Rd,
+    mut sink: impl FnMut(
+      &mut Self,
+      &EnvelopeHeader,
+      &[u8],
+      bool,
+    ) -> Result<(), WebCryptoError>,
+  ) -> Result<(), WebCryptoError> {
+    check_key_use(key, key.algorithm.name(), Operation::Open)?;
+    self.check_api_mode(key.algorithm.name(), None, false)?;
+
+    let (header, header_bytes) = envelope::read_stream_header(reader)?;
+    if envelope::key_algorithm(header.algorithm) != key.algorithm.name()
+      || header.key_id != self.envelope_key_id(key)?
+    {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    if header.nonce.len() + 5 != envelope::nonce_length(header.algorithm) {
+      return Err(WebCryptoError::DataError);
+    }
+    let mut nonces = StreamNonces::new(&header.nonce);
+
+    let chunk_length =
+      header.chunk_length.unwrap_or(0) as usize + envelope::CHUNK_OVERHEAD;
+    let mut chunk = vec![0; chunk_length];
+    let mut next = vec![0; chunk_length];
+    let mut length = stream::read_full(reader, &mut chunk)?;
+    loop {
+      let next_length = if length == chunk_length {
+        stream::read_full(reader, &mut next)?
+      } else {
+        0
+      };
+      let last = next_length == 0;
+
+      let mut data = chunk[..length].to_vec();
+      let nonce = nonces.next(last)?;
+      self.envelope_cipher(
+        header.algorithm,
+        &nonce,
+        &header_bytes,
+        key,
+        &mut data,
+        false,
+      )?;
+      sink(self, &header, &data, last)?;
+      if last {
+        return Ok(());
+      }
+
+      std::mem::swap(&mut chunk, &mut next);
+      length = next_length;
+    }
+  }
+
+  /// Open the streamed envelope of `reader` under `old_key` and seal its
+  /// data again as `new_params` asks, see `Context::reencrypt`.
+  pub(crate) fn reseal_stream<Rd: Read, W: Write>(
+    &mut self,
+    reader: &mut Rd,
+    writer: &mut W,
+    old_key: &CryptoKey<S::Handle>,
+    new_params: ReencryptParams<S::Handle>,
+  ) -> Result<(), WebCryptoError> {
+    let key = new_params.key;
+    let mut sealer = None;
+    let result =
+      self.open_stream_data(old_key, reader, |subtle, header, data, last| {
+        let sealer = match &mut sealer {
+          Some(sealer) => sealer,
+          None => sealer.insert(subtle.stream_sealer(
+            key,
+            &header.additional_data,
+            new_params.chunk_length,
+            writer,
+          )?),
+        };
+        subtle.seal_chunks(key, sealer, data, last, writer)
+      });
+    let result = self.consume(old_key, result);
+    self.consume(key, result)
+  }
+
+  /// The ID of the secret `key` in envelope headers, derived from its key
+  /// material.
+  pub fn envelope_key_id(
+    &self,
+    key: &CryptoKey<S::Handle>,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    if key.type_ != KeyType::Secret {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+    Ok(envelope::key_id(&key_material.0))
+  }
+
+  /// Encrypt or decrypt `buffer` in place with the envelope `algorithm`
+  /// and `nonce`, authenticating the encoded header `header_bytes`.
+  fn envelope_cipher(
+    &mut self,
+    algorithm: &str,
+    nonce: &[u8],
+    header_bytes: &[u8],
+    key: &CryptoKey<S::Handle>,
+    buffer: &mut Vec<u8>,
+    encrypt: bool,
+  ) -> Result<(), WebCryptoError> {
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+
+    match (algorithm, encrypt) {
+      ("AES-GCM", _) => {
+        let params = EncryptParams::AesGcmParams(AesGcmParams {
+          name: "AES-GCM",
+          iv: nonce.to_vec(),
+          additional_data: header_bytes.to_vec(),
+          tag_length: None,
+          key_commitment: false,
+        });
+        if encrypt {
+          self.encrypt_in_place_with(&params, key, buffer)
+        } else {
+          self.decrypt_in_place_with(&params, key, buffer)
+        }
+      }
+      ("ChaCha20-Poly1305", true) => chacha20_poly1305::encrypt_in_place(
+        &key_material.0,
+        nonce,
+        header_bytes,
+        buffer,
+      ),
+      ("ChaCha20-Poly1305", false) => chacha20_poly1305::decrypt_in_place(
+        &key_material.0,
+        nonce,
+        header_bytes,
+        buffer,
+      ),
+      ("XChaCha20-Poly1305", true) => chacha20_poly1305::xencrypt_in_place(
+        &key_material.0,
+        nonce,
+        header_bytes,
+        buffer,
+      ),
+      ("XChaCha20-Poly1305", false) => chacha20_poly1305::xdecrypt_in_place(
+        &key_material.0,
+        nonce,
+        header_bytes,
+        buffer,
+      ),
+      _ => Err(WebCryptoError::NotSupportedError),
+    }
+  }
+
+  /// The encrypt operation of `algorithm`, without usage checks.
+  fn encrypt_with(
+    &mut self,
+    algorithm: &EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let operation = KeyOperation::Encrypt {
+      params: algorithm,
+      data,
+    };
+    if let Some(result) =
+      self.storage.perform(key.handle, &key.algorithm, operation)
+    {
+      return result;
+    }
+
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+
+    match algorithm {
+      EncryptParams::AesGcmParams(_)
+      | EncryptParams::ChaCha20Poly1305Params(_)
+      | EncryptParams::XChaCha20Poly1305Params(_) => {
+        let mut buffer = data.to_vec();
+        self.encrypt_in_place_with(algorithm, key, &mut buffer)?;
+        Ok(buffer)
+      }
+      EncryptParams::Ff1Params(params) => {
+        ff1::encrypt(&key_material.0, &params.tweak, &params.alphabet, data)
+      }
+      EncryptParams::RsaOaepParams(params) => {
+        if key.type_ != KeyType::Public {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        let public_key = rsa_public_key(key_material)?;
+        let hash = oaep_hash(&key.algorithm);
+        if !params.label.is_empty() {
+          return oaep::encrypt(
+            &mut self.rng,
+            &public_key,
+            hash,
+            &params.label,
+            data,
+          );
+        }
+
+        public_key
+          .encrypt(&mut self.rng, hash_oaep_padding(hash)?, data)
+          .map_err(|_| WebCryptoError::OperationError)
+      }
+      EncryptParams::AlgorithmIdentifer(_) => {
+        Err(WebCryptoError::NotSupportedError)
+      }
+    }
+  }
+
+  /// The decrypt operation of `algorithm`, without usage checks.
+  fn decrypt_with(
+    &mut self,
+    algorithm: &EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let operation = KeyOperation::Decrypt {
+      params: algorithm,
+      data,
+    };
+    if let Some(result) =
+      self.storage.perform(key.handle, &key.algorithm, operation)
+    {
+      return result;
+    }
+
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+
+    match algorithm {
+      EncryptParams::AesGcmParams(_)
+      | EncryptParams::ChaCha20Poly1305Params(_)
+      | EncryptParams::XChaCha20Poly1305Params(_) => {
+        let mut buffer = data.to_vec();
+        self.decrypt_in_place_with(algorithm, key, &mut buffer)?;
+        Ok(buffer)
+      }
+      EncryptParams::Ff1Params(params) => {
+        ff1::decrypt(&key_material.0, &params.tweak, &params.alphabet, data)
+      }
+      EncryptParams::RsaOaepParams(params) => {
+        if key.type_ != KeyType::Private {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        let private_key = self.rsa_private_key(key_material)?;
+        let hash = oaep_hash(&key.algorithm);
+        if !params.label.is_empty() {
+          return oaep::decrypt(
+            &mut self.rng,
+            &private_key,
+            hash,
+            &params.label,
+            data,
+          );
+        }
+
+        private_key
+          .decrypt_blinded(&mut self.rng, hash_oaep_padding(hash)?, data)
+          .map_err(|_| WebCryptoError::OperationError)
+      }
+      EncryptParams::AlgorithmIdentifer(_) => {
+        Err(WebCryptoError::NotSupportedError)
+      }
+    }
+  }
+
+  /// The encrypt operation of `algorithm` on `buffer`, without usage
+  /// checks. The AEADs encrypt in place, other algorithms replace the
+  /// contents of `buffer` with the ciphertext.
+  fn encrypt_in_place_with(
+    &mut self,
+    algorithm: &EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    buffer: &mut Vec<u8>,
+  ) -> Result<(), WebCryptoError> {
+    self.check_encrypt_downgrade(algorithm)?;
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+
+    match algorithm {
+      EncryptParams::AesGcmParams(params) => {
+        let encrypted =
+          key.encrypted_bytes().saturating_add(buffer.len() as u64);
+        if matches!(self.state.gcm_rekey_threshold, Some(max) if encrypted > max)
+        {
+          return Err(WebCryptoError::OperationError);
+        }
+
+        let encrypt = if params.key_commitment {
+          aes_gcm::encrypt_committing_in_place
+        } else {
+          aes_gcm::encrypt_in_place
+        };
+        encrypt(
+          &key_material.0,
+          &params.iv,
+          &params.additional_data,
+          params.tag_length,
+          buffer,
+        )?;
+        key.encrypted.store(encrypted, Ordering::Relaxed);
+        Ok(())
+      }
+      EncryptParams::ChaCha20Poly1305Params(params) => {
+        chacha20_poly1305::encrypt_in_place(
+          &key_material.0,
+          &params.iv,
+          b"",
+          buffer,
+        )
+      }
+      EncryptParams::XChaCha20Poly1305Params(params) => {
+        chacha20_poly1305::xencrypt_in_place(
+          &key_material.0,
+          &params.iv,
+          b"",
+          buffer,
+        )
+      }
+      _ => {
+        let ciphertext = self.encrypt_with(algorithm, key, buffer)?;
+        buffer.clear();
+        buffer.extend_from_slice(&ciphertext);
+        Ok(())
+      }
+    }
+  }
+
+  /// The decrypt operation of `algorithm` on `buffer`, without usage
+  /// checks, like `encrypt_in_place_with`.
+  fn decrypt_in_place_with(
+    &mut self,
+    algorithm: &EncryptParams,
+    key: &CryptoKey<S::Handle>,
+    buffer: &mut Vec<u8>,
+  ) -> Result<(), WebCryptoError> {
+    self.check_encrypt_downgrade(algorithm)?;
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+
+    match algorithm {
+      EncryptParams::AesGcmParams(params) => {
+        let decrypt = if params.key_commitment {
+          aes_gcm::decrypt_committing_in_place
+        } else {
+          aes_gcm::decrypt_in_place
+        };
+        decrypt(
+          &key_material.0,
+          &params.iv,
+          &params.additional_data,
+          params.tag_length,
+          buffer,
+        )
+      }
+      EncryptParams::ChaCha20Poly1305Params(params) => {
+        chacha20_poly1305::decrypt_in_place(
+          &key_material.0,
+          &params.iv,
+          b"",
+          buffer,
+        )
+      }
+      EncryptParams::XChaCha20Poly1305Params(params) => {
+        chacha20_poly1305::xdecrypt_in_place(
+          &key_material.0,
+          &params.iv,
+          b"",
+          buffer,
+        )
+      }
+      _ => {
+        let plaintext = self.decrypt_with(algorithm, key, buffer)?;
+        buffer.clear();
+        buffer.extend_from_slice(&plaintext);
+        Ok(())
+      }
+    }
+  }
+
+  pub fn wrap_key(
+    &mut self,
+    format: &str,
+    key: &CryptoKey<S::Handle>,
+    wrapping_key: &CryptoKey<S::Handle>,
+    mut wrap_algorithm: EncryptParams,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    wrap_algorithm.normalize();
+    // 9. and 10.
+    check_key_use(wrapping_key, wrap_algorithm.name(), Operation::WrapKey)?;
+
+    self.check_api_mode(
+      wrapping_key.algorithm.name(),
+      Some(format),
+      wrap_algorithm.is_extension(),
+    )?;
+    self.check_api_mode(key.algorithm.name(), None, false)?;
+
+    if let (
+      Algorithm::AesKeyAlgorithm(alg),
+      Algorithm::AesKeyAlgorithm(wrapping_alg),
+    ) = (&key.algorithm, &wrapping_key.algorithm)
+    {
+      if self.state.downgrade_protection && alg.length > wrapping_alg.length {
+        return Err(WebCryptoError::InvalidAccessError);
+      }
+    }
+
+    // 12. and 13.
+    let bytes = self.export_extractable(format, key)?;
+
+    // 14.
+    let result = match wrap_algorithm {
+      EncryptParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "AES-KW",
+      }) => {
+        let kek = self
+          .storage
+          .get(wrapping_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        aes_kw::wrap(&kek.0, &bytes)
+      }
+      EncryptParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "AES-KWP",
+      }) => {
+        let kek = self
+          .storage
+          .get(wrapping_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        aes_kw::wrap_with_padding(&kek.0, &bytes)
+      }
+      _ => self.encrypt_with(&wrap_algorithm, wrapping_key, &bytes),
+    };
+    let result = self.consume(wrapping_key, result);
+    self.record("wrapKey", &[format.as_bytes()], result.as_deref().ok());
+    result
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  pub fn unwrap_key(
+    &mut self,
+    format: &str,
+    wrapped_key: &[u8],
+    unwrapping_key: &CryptoKey<S::Handle>,
+    mut unwrap_algorithm: EncryptParams,
+    mut unwrapped_key_algorithm: ImportParams,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKey<S::Handle>, WebCryptoError> {
+    unwrap_algorithm.normalize();
+    unwrapped_key_algorithm.normalize();
+    // 11. and 12.
+    check_key_use(
+      unwrapping_key,
+      unwrap_algorithm.name(),
+      Operation::UnwrapKey,
+    )?;
+
+    self.check_api_mode(
+      unwrapping_key.algorithm.name(),
+      Some(format),
+      unwrap_algorithm.is_extension(),
+    )?;
+    self.check_api_mode(
+      unwrapped_key_algorithm.name(),
+      None,
+      unwrapped_key_algorithm.is_extension(),
+    )?;
+
+    // 13.
+    let key_data = match unwrap_algorithm {
+      EncryptParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "AES-KW",
+      }) => {
+        let kek = self
+          .storage
+          .get(unwrapping_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        aes_kw::unwrap(&kek.0, wrapped_key)?
+      }
+      EncryptParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "AES-KWP",
+      }) => {
+        let kek = self
+          .storage
+          .get(unwrapping_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        aes_kw::unwrap_with_padding(&kek.0, wrapped_key)?
+      }
+      _ => self.decrypt_with(&unwrap_algorithm, unwrapping_key, wrapped_key)?,
+    };
+
+    // 14.
+    let result = self.import_key(
+      format,
+      &key_data,
+      unwrapped_key_algorithm,
+      extractable,
+      usages,
+    );
+    self.consume(unwrapping_key, result)
+  }
+
+  /// Hash `data` with `algorithm`. "BLAKE3" is an extension and produces
+  /// its default 256-bit output.
+  pub fn digest(
+    &self,
+    mut algorithm: AlgorithmIdentifer,
+    data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    normalize_name(&mut algorithm.name);
+    self.check_api_mode(algorithm.name, None, false)?;
+
+    let result = self.check_hash_policy(algorithm.name).and_then(|_| {
+      match algorithm.name {
+        "BLAKE3" => Ok(blake3::hash(data).as_bytes().to_vec()),
+        name => digest(name, data),
+      }
+    });
+    self.record(
+      "digest",
+      &[algorithm.name.as_bytes(), data],
+      result.as_deref().ok(),
+    );
+    result
+  }
+
+  /// The blind index of `value` in the column of `params`, a truncated
+  /// MAC under the HMAC or BLAKE3 `key`, for equality search over an
+  /// encrypted column. The key needs the Sign usage and must not be used
+  /// for anything else.
+  ///
+  /// Indexes reveal which rows hold equal values and how often, from
+  /// which low-entropy values can be guessed. Short indexes collide on
+  /// purpose so each matches several values, and lookups decrypt the
+  /// matching rows to filter them.
+  pub fn blind_index(
+    &self,
+    params: BlindIndexParams,
+    key: &CryptoKey<S::Handle>,
+    value: &str,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let result = self.blind_index_with(params, key, value);
+    self.record("blindIndex", &[value.as_bytes()], result.as_deref().ok());
+    result
+  }
+
+  fn blind_index_with(
+    &self,
+    params: BlindIndexParams,
+    key: &CryptoKey<S::Handle>,
+    value: &str,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    check_key_use(key, key.algorithm.name(), Operation::BlindIndex)?;
+    self.check_api_mode(key.algorithm.name(), None, false)?;
+    if key.consume_once {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+
+    let (input, length) = blind_index::mac_input(&params, value)?;
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+    let mut index = match key.algorithm {
+      Algorithm::HmacKeyAlgorithm(alg) => {
+        hmac(alg.hash.name, &key_material.0, &input)?
+      }
+      Algorithm::KeyAlgorithm(KeyAlgorithm { name: "BLAKE3" }) => {
+        let key = blake3_key(key, key_material)?;
+        blake3::keyed_hash(&key, &input).as_bytes().to_vec()
+      }
+      _ => {
+        return Err(WebCryptoError::InvalidAccessError);
+      }
+    };
+    if length > index.len() {
+      return Err(WebCryptoError::OperationError);
+    }
+    index.truncate(length);
+
+    Ok(index)
+  }
+
+  /// Derive `length` bits from `base_key`, or all the bits the algorithm
+  /// produces when `length` is `None`.
+  pub fn derive_bits(
+    &mut self,
+    mut algorithm: DeriveParams<S::Handle>,
+    base_key: &CryptoKey<S::Handle>,
+    length: Option<usize>,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    algorithm.normalize();
+    check_key_use(base_key, algorithm.name(), Operation::DeriveBits)?;
+
+    self.check_api_mode(
+      base_key.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+
+    let result = self.derive_bits_with(algorithm, base_key, length);
+    let result = self.consume(base_key, result);
+    self.record("deriveBits", &[], result.as_deref().ok());
+    result
+  }
+
+  /// Derive a key of type `derived_key_type` from `base_key`.
+  pub fn derive_key(
+    &mut self,
+    mut algorithm: DeriveParams<S::Handle>,
+    base_key: &CryptoKey<S::Handle>,
+    mut derived_key_type: KeyGenParams,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKey<S::Handle>, WebCryptoError> {
+    algorithm.normalize();
+    derived_key_type.normalize();
+    // 11.
+    check_key_use(base_key, algorithm.name(), Operation::DeriveKey)?;
+
+    self.check_api_mode(
+      base_key.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+    self.check_api_mode(
+      derived_key_type.name(),
+      None,
+      derived_key_type.is_extension(),
+    )?;
+
+    // 12.
+    let (length, import_algorithm) = derived_key_params(derived_key_type)?;
+
+    // 13.
+    let secret = self.derive_bits_with(algorithm, base_key, Some(length))?;
+
+    // 14.
+    let key =
+      self.import_key("raw", &secret, import_algorithm, extractable, usages)?;
+
+    // 15.
+    if key.usages.is_empty() {
+      return Err(WebCryptoError::SyntaxError);
+    }
+
+    self.consume(base_key, Ok(key))
+  }
+
+  /// Derive the AES-GCM key of the record `record_id`, eg. a database
+  /// row, from the "HKDF" key `master`. The same record always gets the
+  /// same key, and keys of different records are independent.
+  ///
+  /// The key is stored like any other. To encrypt many records without
+  /// growing the storage, use `seal_record` instead.
+  pub fn derive_record_key(
+    &mut self,
+    master: &CryptoKey<S::Handle>,
+    record_id: &[u8],
+  ) -> Result<CryptoKey<S::Handle>, WebCryptoError> {
+    self.derive_key(
+      record_key_params(record_id),
+      master,
+      KeyGenParams::AesKeyGenParams(AesKeyGenParams {
+        name: "AES-GCM",
+        length: 256,
+      }),
+      false,
+      vec![KeyUsage::Encrypt, KeyUsage::Decrypt],
+    )
+  }
+
+  /// Encrypt `data` with AES-GCM under the key of the record `record_id`
+  /// (see `derive_record_key`), without storing the key. The record ID is
+  /// authenticated, so a sealed record does not open as another one.
+  ///
+  /// Returns the random 96-bit IV followed by the ciphertext.
+  pub fn seal_record(
+    &mut self,
+    master: &CryptoKey<S::Handle>,
+    record_id: &[u8],
+    data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let key = self.record_key(master, record_id)?;
+
+    let mut sealed = vec![0; 12];
+    self.rng.fill_bytes(&mut sealed);
+    let ciphertext = aes_gcm::encrypt(&key, &sealed, record_id, None, data)?;
+    sealed.extend(ciphertext);
+    Ok(sealed)
+  }
+
+  /// Decrypt a record sealed by `seal_record`.
+  pub fn open_record(
+    &mut self,
+    master: &CryptoKey<S::Handle>,
+    record_id: &[u8],
+    sealed: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    if sealed.len() < 12 {
+      return Err(WebCryptoError::OperationError);
+    }
+    let key = self.record_key(master, record_id)?;
+
+    let (iv, ciphertext) = sealed.split_at(12);
+    aes_gcm::decrypt(&key, iv, record_id, None, ciphertext)
+  }
+
+  /// The AES-256 key of the record `record_id`, as raw bytes.
+  fn record_key(
+    &self,
+    master: &CryptoKey<S::Handle>,
+    record_id: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let algorithm = record_key_params(record_id);
+    check_key_use(master, algorithm.name(), Operation::DeriveRecordKey)?;
+    self.check_api_mode(
+      master.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+    if master.consume_once {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+
+    self.derive_bits_with(algorithm, master, Some(256))
+  }
+
+  /// The derive bits operation of `algorithm`, without usage checks.
+  fn derive_bits_with(
+    &self,
+    algorithm: DeriveParams<S::Handle>,
+    base_key: &CryptoKey<S::Handle>,
+    length: Option<usize>,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let mut bits = match algorithm {
+      // X448 reuses the ECDH parameters, like X25519 in browsers.
+      #[cfg(feature = "x448")]
+      DeriveParams::EcdhKeyDeriveParams(params) if params.name == "X448" => {
+        let public = params.public;
+        if base_key.type_ != KeyType::Private
+          || public.type_ != KeyType::Public
+          || base_key.algorithm.name() != "X448"
+          || public.algorithm.name() != "X448"
+        {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        let private_key = self
+          .storage
+          .get(base_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        let public_key = self
+          .storage
+          .get(public.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+
+        x448::derive(&private_key.0, &public_key.0)?
+      }
+      DeriveParams::EcdhKeyDeriveParams(params) => {
+        let public = params.public;
+        if base_key.type_ != KeyType::Private || public.type_ != KeyType::Public
+        {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        let named_curve = match (base_key.algorithm, public.algorithm) {
+          (
+            Algorithm::EcKeyAlgorithm(private_alg),
+            Algorithm::EcKeyAlgorithm(public_alg),
+          ) if private_alg.name == params.name
+            && public_alg.name == params.name
+            && private_alg.named_curve == public_alg.named_curve =>
+          {
+            private_alg.named_curve
+          }
+          _ => {
+            return Err(WebCryptoError::InvalidAccessError);
+          }
+        };
+
+        let private_key = self
+          .storage
+          .get(base_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        let public_key = self
+          .storage
+          .get(public.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+
+        ec::derive(named_curve, &private_key.0, &public_key.0)?
+      }
+      DeriveParams::DhKeyDeriveParams(params) => {
+        let public = params.public;
+        if base_key.type_ != KeyType::Private || public.type_ != KeyType::Public
+        {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        let group = match (base_key.algorithm, public.algorithm) {
+          (
+            Algorithm::DhKeyAlgorithm(private_alg),
+            Algorithm::DhKeyAlgorithm(public_alg),
+          ) if private_alg.name == params.name
+            && public_alg.name == params.name
+            && private_alg.group == public_alg.group =>
+          {
+            private_alg.group
+          }
+          _ => {
+            return Err(WebCryptoError::InvalidAccessError);
+          }
+        };
+
+        let private_key = self
+          .storage
+          .get(base_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        let public_key = self
+          .storage
+          .get(public.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+
+        dh::derive(group, &private_key.0, &public_key.0)?
+      }
+      DeriveParams::HkdfParams(params) => {
+        if base_key.algorithm.name() != params.name {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        let length = match length {
+          Some(length) if length % 8 == 0 => length / 8,
+          _ => {
+            return Err(WebCryptoError::OperationError);
+          }
+        };
+
+        let key = self
+          .storage
+          .get(base_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        kdf::hkdf(
+          params.hash.name,
+          Some(&params.salt),
+          &key.0,
+          &params.info,
+          length,
+        )?
+      }
+      DeriveParams::HkdfExpandParams(params) => {
+        if base_key.algorithm.name() != params.name {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        let length = match length {
+          Some(length) if length % 8 == 0 => length / 8,
+          _ => {
+            return Err(WebCryptoError::OperationError);
+          }
+        };
+
+        let prk = self
+          .storage
+          .get(base_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        kdf::hkdf(params.hash.name, None, &prk.0, &params.info, length)?
+      }
+      DeriveParams::ConcatKdfParams(params) => {
+        if base_key.algorithm.name() != params.name {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        let length = match length {
+          Some(length) if length % 8 == 0 => length / 8,
+          _ => {
+            return Err(WebCryptoError::OperationError);
+          }
+        };
+
+        let secret = self
+          .storage
+          .get(base_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        kdf::concat_kdf(
+          params.hash.name,
+          &secret.0,
+          &params.other_info,
+          length,
+        )?
+      }
+      DeriveParams::X963KdfParams(params) => {
+        if base_key.algorithm.name() != params.name {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        let length = match length {
+          Some(length) if length % 8 == 0 => length / 8,
+          _ => {
+            return Err(WebCryptoError::OperationError);
+          }
+        };
+
+        let secret = self
+          .storage
+          .get(base_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        kdf::x963_kdf(params.hash.name, &secret.0, &params.shared_info, length)?
+      }
+      DeriveParams::Argon2Params(params) => {
+        if base_key.algorithm.name() != params.name {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        // The output has no natural length.
+        let length = match length {
+          Some(length) if length % 8 == 0 => length / 8,
+          _ => {
+            return Err(WebCryptoError::OperationError);
+          }
+        };
+
+        let password = self
+          .storage
+          .get(base_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        kdf::argon2id(
+          &password.0,
+          &params.salt,
+          params.memory,
+          params.iterations,
+          params.parallelism,
+          length,
+        )?
+      }
+      DeriveParams::ScryptParams(params) => {
+        if base_key.algorithm.name() != params.name {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        let length = match length {
+          Some(length) if length % 8 == 0 => length / 8,
+          _ => {
+            return Err(WebCryptoError::OperationError);
+          }
+        };
+
+        let password = self
+          .storage
+          .get(base_key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        kdf::scrypt(
+          &password.0,
+          &params.salt,
+          params.n,
+          params.r,
+          params.p,
+          length,
+        )?
+      }
+    };
+
+    if let Some(length) = length {
+      if length > bits.len() * 8 {
+        return Err(WebCryptoError::OperationError);
+      }
+
+      bits.truncate(length.div_ceil(8));
+      if length % 8 != 0 {
+        let last = bits.len() - 1;
+        bits[last] &= 0xff << (8 - length % 8);
+      }
+    }
+
+    Ok(bits)
+  }
+
+  /// Create a fresh shared secret for the holder of the private half of
+  /// `encapsulation_key`.
+  pub fn encapsulate_bits(
+    &mut self,
+    mut algorithm: EncapsulateParams,
+    encapsulation_key: &CryptoKey<S::Handle>,
+  ) -> Result<EncapsulatedBits, WebCryptoError> {
+    algorithm.normalize();
+    check_key_use(
+      encapsulation_key,
+      algorithm.name(),
+      Operation::EncapsulateBits,
+    )?;
+    self.check_api_mode(encapsulation_key.algorithm.name(), None, false)?;
+
+    let key_material = self
+      .storage
+      .get(encapsulation_key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+    let (shared_key, ciphertext) = match algorithm {
+      EncapsulateParams::RsaKemParams(params) => {
+        let hash = match encapsulation_key.algorithm {
+          Algorithm::RsaHashedKeyAlgorithm(alg) if alg.name == params.name => {
+            alg.hash.name
+          }
+          _ => {
+            return Err(WebCryptoError::InvalidAccessError);
+          }
+        };
+
+        let public_key = rsa_public_key(key_material)?;
+        rsa_kem::encapsulate(&mut self.rng, &public_key, hash, params.length)?
+      }
+      #[cfg(feature = "ml-kem")]
+      EncapsulateParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "X-Wing",
+      }) => {
+        if encapsulation_key.algorithm.name() != "X-Wing" {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        xwing::encapsulate(&mut self.rng, &key_material.0)?
+      }
+      EncapsulateParams::AlgorithmIdentifer(_) => {
+        return Err(WebCryptoError::NotSupportedError);
+      }
+    };
+
+    let result = Ok(EncapsulatedBits {
+      shared_key,
+      ciphertext,
+    });
+    self.consume(encapsulation_key, result)
+  }
+
+  /// Recover the shared secret of `ciphertext`, as created by
+  /// `encapsulate_bits`.
+  pub fn decapsulate_bits(
+    &mut self,
+    mut algorithm: EncapsulateParams,
+    decapsulation_key: &CryptoKey<S::Handle>,
+    ciphertext: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    algorithm.normalize();
+    check_key_use(
+      decapsulation_key,
+      algorithm.name(),
+      Operation::DecapsulateBits,
+    )?;
+    self.check_api_mode(decapsulation_key.algorithm.name(), None, false)?;
+
+    let key_material = self
+      .storage
+      .get(decapsulation_key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+    let result = match algorithm {
+      EncapsulateParams::RsaKemParams(params) => {
+        let hash = match decapsulation_key.algorithm {
+          Algorithm::RsaHashedKeyAlgorithm(alg) if alg.name == params.name => {
+            alg.hash.name
+          }
+          _ => {
+            return Err(WebCryptoError::InvalidAccessError);
+          }
+        };
+
+        let private_key = self.rsa_private_key(key_material)?;
+        rsa_kem::decapsulate(
+          &mut self.rng,
+          &private_key,
+          hash,
+          ciphertext,
+          params.length,
+        )
+      }
+      #[cfg(feature = "ml-kem")]
+      EncapsulateParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "X-Wing",
+      }) => {
+        if decapsulation_key.algorithm.name() != "X-Wing" {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        xwing::decapsulate(&key_material.0, ciphertext)
+      }
+      EncapsulateParams::AlgorithmIdentifer(_) => {
+        Err(WebCryptoError::NotSupportedError)
+      }
+    };
+    self.consume(decapsulation_key, result)
+  }
+
+  pub fn export_key(
+    &self,
+    format: &str,
+    key: &CryptoKey<S::Handle>,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    self.check_api_mode(key.algorithm.name(), Some(format), false)?;
+
+    // 6.
+    let result = self.export_extractable(format, key);
+    self.record("exportKey", &[format.as_bytes()], result.as_deref().ok());
+    result
+  }
+
+  /// Export `key` as PEM: private keys as PKCS#8 ("PRIVATE KEY") and
+  /// public keys as SPKI ("PUBLIC KEY").
+  pub fn export_key_pem(
+    &self,
+    key: &CryptoKey<S::Handle>,
+  ) -> Result<String, WebCryptoError> {
+    let (format, label) = pem::format(&key.type_)?;
+    let der = self.export_key(format, key)?;
+
+    pem::encode(label, &der)
+  }
+
+  /// Bundle the private `key` with its certificate `chain`, leaf first,
+  /// into a PKCS#12 blob protected by `password`.
+  ///
+  /// The key must be extractable and exportable as "pkcs8".
+  pub fn export_pkcs12(
+    &self,
+    key: &CryptoKey<S::Handle>,
+    chain: &[Certificate],
+    alias: &str,
+    password: &str,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let pkcs8 = self.export_key("pkcs8", key)?;
+
+    pkcs12::encode(alias, &pkcs8, chain, password)
+  }
+
+  /// Describe `key` as a JSON string that `deserialize_key` turns back
+  /// into the same key, eg. in another service sharing the storage
+  /// backend. The descriptor holds the attributes of the key and its
+  /// handle encoded by `codec`, but no key material.
+  ///
+  /// Descriptors are not authenticated: whoever can alter one can change
+  /// the usages and extractability of the key. Only accept them from
+  /// trusted parties, or sign them.
+  pub fn serialize_key<C: HandleCodec<S::Handle>>(
+    &self,
+    codec: &C,
+    key: &CryptoKey<S::Handle>,
+  ) -> String {
+    descriptor::serialize(key, codec.encode(key.handle))
+  }
+
+  /// Reconstruct a key from a descriptor of `serialize_key`. Descriptors
+  /// of a newer version, naming unknown algorithms or usages, or whose
+  /// handle `codec` rejects or the storage does not hold are refused.
+  pub fn deserialize_key<C: HandleCodec<S::Handle>>(
+    &self,
+    codec: &C,
+    descriptor: &str,
+  ) -> Result<CryptoKey<S::Handle>, WebCryptoError> {
+    descriptor::deserialize(descriptor, |handle| {
+      let handle = codec.decode(handle).ok_or(WebCryptoError::DataError)?;
+      let key_material = self
+        .storage
+        .get(handle)
+        .ok_or(WebCryptoError::OperationError)?;
+      Ok((handle, key_material.0.len()))
+    })
+  }
+
+  /// Serialize `key` into `format` for the caller, `InvalidAccessError`
+  /// unless it is extractable. Every export, `export_key`, `wrap_key` and
+  /// those built on them, goes through here; `export_key_data` is only
+  /// for using the material internally, eg. the SPKI of a key verifying a
+  /// certificate.
+  fn export_extractable(
+    &self,
+    format: &str,
+    key: &CryptoKey<S::Handle>,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    if !key.extractable {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    self.export_key_data(format, key)
+  }
+
+  /// Serialize `key` into `format`.
+  fn export_key_data(
+    &self,
+    format: &str,
+    key: &CryptoKey<S::Handle>,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+
+    match format {
+      "raw" => match (&key.type_, key.algorithm) {
+        // Secret keys are stored as their raw bytes and EC public keys as
+        // an uncompressed SEC1 point, the format browsers export. DH
+        // public keys are their big-endian public value, and X-Wing ones
+        // their encoding from the draft.
+        (KeyType::Secret, _)
+        | (KeyType::Public, Algorithm::EcKeyAlgorithm(_))
+        | (KeyType::Public, Algorithm::DhKeyAlgorithm(_))
+        | (KeyType::Public, Algorithm::KeyAlgorithm(_)) => {
+          Ok(key_material.0.clone())
+        }
+        _ => Err(WebCryptoError::InvalidAccessError),
+      },
+      // An extension for protocols sending compressed points.
+      "raw-compressed" => match (&key.type_, key.algorithm) {
+        (KeyType::Public, Algorithm::EcKeyAlgorithm(alg)) => {
+          ec::export_compressed(alg.named_curve, &key_material.0)
+        }
+        _ => Err(WebCryptoError::InvalidAccessError),
+      },
+      "raw-private" => match (&key.type_, key.algorithm) {
+        // The big-endian private exponent or scalar.
+        (KeyType::Private, Algorithm::DhKeyAlgorithm(_))
+        | (KeyType::Private, Algorithm::EcKeyAlgorithm(_)) => {
+          Ok(key_material.0.clone())
+        }
+        _ => Err(WebCryptoError::InvalidAccessError),
+      },
+      "pkcs8" => {
+        if key.type_ != KeyType::Private {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        match key.algorithm {
+          Algorithm::RsaHashedKeyAlgorithm(_) => {
+            let private_key = RsaPrivateKey::from_pkcs1_der(&key_material.0)
+              .map_err(|_| WebCryptoError::OperationError)?;
+            let pkcs8 = private_key
+              .to_pkcs8_der()
+              .map_err(|_| WebCryptoError::OperationError)?;
+
+            Ok(pkcs8.as_ref().to_vec())
+          }
+          Algorithm::EcKeyAlgorithm(alg) => {
+            ec::export_pkcs8(alg.named_curve, &key_material.0)
+          }
+          Algorithm::KeyAlgorithm(alg) if okp::is_okp(alg.name) => {
+            okp::export_pkcs8(alg.name, &key_material.0)
+          }
+          _ => Err(WebCryptoError::NotSupportedError),
+        }
+      }
+      "spki" => {
+        if key.type_ != KeyType::Public {
+          return Err(WebCryptoError::InvalidAccessError);
+        }
+
+        match key.algorithm {
+          Algorithm::RsaHashedKeyAlgorithm(_) => {
+            let public_key = rsa_public_key(key_material)?;
+            let spki = public_key
+              .to_public_key_der()
+              .map_err(|_| WebCryptoError::OperationError)?;
+
+            Ok(spki.as_ref().to_vec())
+          }
+          Algorithm::EcKeyAlgorithm(alg) => {
+            ec::export_spki(alg.named_curve, &key_material.0)
+          }
+          Algorithm::KeyAlgorithm(alg) if okp::is_okp(alg.name) => {
+            okp::export_spki(alg.name, &key_material.0)
+          }
+          _ => Err(WebCryptoError::NotSupportedError),
+        }
+      }
+      "jwk" => {
+        let mut jwk = match key.algorithm {
+          Algorithm::AesKeyAlgorithm(alg) => {
+            Jwk::oct(&key_material.0, jwk::aes_alg(alg.name, alg.length))
+          }
+          Algorithm::HmacKeyAlgorithm(alg) => Jwk::oct(
+            &key_material.0,
+            jwk::hmac_alg(alg.hash.name).map(String::from),
+          ),
+          Algorithm::RsaHashedKeyAlgorithm(alg) => {
+            let jwk_alg = jwk::rsa_alg(alg.name, alg.hash.name);
+            match key.type_ {
+              KeyType::Private => {
+                let private_key =
+                  RsaPrivateKey::from_pkcs1_der(&key_material.0)
+                    .map_err(|_| WebCryptoError::OperationError)?;
+                Jwk::rsa_private(&private_key, jwk_alg)?
+              }
+              _ => Jwk::rsa_public(&rsa_public_key(key_material)?, jwk_alg),
+            }
+          }
+          Algorithm::EcKeyAlgorithm(alg) => match key.type_ {
+            KeyType::Private => Jwk::ec_private(
+              alg.named_curve,
+              &ec::public_key(alg.named_curve, &key_material.0)?,
+              &key_material.0,
+            ),
+            _ => Jwk::ec_public(alg.named_curve, &key_material.0),
+          },
+          Algorithm::KeyAlgorithm(alg) if okp::is_okp(alg.name) => {
+            match key.type_ {
+              KeyType::Private => Jwk::okp_private(
+                alg.name,
+                &okp::public_key(alg.name, &key_material.0)?,
+                &key_material.0,
+              ),
+              _ => Jwk::okp_public(alg.name, &key_material.0),
+            }
+          }
+          _ => {
+            return Err(WebCryptoError::NotSupportedError);
+          }
+        };
+        jwk.set_key_ops(&key.usages, key.extractable);
+
+        jwk.to_vec()
+      }
+      _ => Err(WebCryptoError::NotSupportedError),
+    }
+  }
+
+  /// The public key of the private `key`, computed from its key material
+  /// and stored, eg. after importing a PKCS#8 private key. It is
+  /// extractable like every public key, and `usages` must be public key
+  /// usages of the algorithm.
+  pub fn get_public_key(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKey<S::Handle>, WebCryptoError> {
+    let result = self.public_key_of(key, usages);
+    self.record("getPublicKey", &[], result.as_ref().ok().map(|_| &[][..]));
+    result
+  }
+
+  fn public_key_of(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKey<S::Handle>, WebCryptoError> {
+    if key.type_ != KeyType::Private {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    self.check_api_mode(key.algorithm.name(), None, false)?;
+
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+    let public_key = match key.algorithm {
+      Algorithm::RsaHashedKeyAlgorithm(_) => {
+        let public_key = RsaPrivateKey::from_pkcs1_der(&key_material.0)
+          .map_err(|_| WebCryptoError::OperationError)?
+          .to_public_key();
+        let pkcs1 = public_key
+          .to_pkcs1_der()
+          .map_err(|_| WebCryptoError::OperationError)?;
+        pkcs1.as_ref().to_vec()
+      }
+      Algorithm::EcKeyAlgorithm(alg) => {
+        ec::public_key(alg.named_curve, &key_material.0)?
+      }
+      Algorithm::KeyAlgorithm(alg) if okp::is_okp(alg.name) => {
+        okp::public_key(alg.name, &key_material.0)?
+      }
+      _ => {
+        return Err(WebCryptoError::NotSupportedError);
+      }
+    };
+    let allowed =
+      permitted_usages(key.algorithm.name().parse()?, KeyType::Public);
+    if usages.iter().any(|usage| !allowed.contains(usage)) {
+      return Err(WebCryptoError::SyntaxError);
+    }
+
+    Ok(CryptoKey {
+      extractable: true,
+      usages,
+      handle: self.store_key(KeyMaterial(public_key))?,
+      type_: KeyType::Public,
+      algorithm: key.algorithm,
+      encrypted: AtomicU64::new(0),
+      consume_once: false,
+    })
+  }
+
+  pub fn import_key(
+    &mut self,
+    format: &str,
+    key_data: &[u8],
+    mut algorithm: ImportParams,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKey<S::Handle>, WebCryptoError> {
+    algorithm.normalize();
+    self.check_api_mode(
+      algorithm.name(),
+      Some(format),
+      algorithm.is_extension(),
+    )?;
+
+    let result =
+      self.import_key_data(format, key_data, algorithm, extractable, usages);
+    self.record(
+      "importKey",
+      &[format.as_bytes(), key_data],
+      result.as_ref().ok().map(|_| &[][..]),
+    );
+    result
+  }
+
+  fn import_key_data(
+    &mut self,
+    format: &str,
+    key_data: &[u8],
+    algorithm: ImportParams,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKey<S::Handle>, WebCryptoError> {
+    let (type_, algorithm, key_material) = match format {
+      "raw-private" => match algorithm {
+        ImportParams::DhKeyImportParams(dh_alg) => {
+          if usages.iter().any(|usage| {
+            !(usage == &KeyUsage::DeriveKey || usage == &KeyUsage::DeriveBits)
+          }) {
+            return Err(WebCryptoError::SyntaxError);
+          }
+
+          // DataError when the value is out of range.
+          dh::check(dh_alg.group, key_data)?;
+
+          let algorithm = Algorithm::DhKeyAlgorithm(DhKeyAlgorithm {
+            name: dh_alg.name,
+            group: dh_alg.group,
+          });
+          (KeyType::Private, algorithm, key_data.to_vec())
+        }
+        // The bare big-endian scalar `d`, as HSMs and wallets hand out
+        // keys.
+        ImportParams::EcKeyImportParams(ec_alg) => {
+          let allowed: &[KeyUsage] = match ec_alg.name {
+            "ECDSA" => &[KeyUsage::Sign],
+            "ECDH" => &[KeyUsage::DeriveKey, KeyUsage::DeriveBits],
+            _ => {
+              return Err(WebCryptoError::NotSupportedError);
+            }
+          };
+          if usages.iter().any(|usage| !allowed.contains(usage)) {
+            return Err(WebCryptoError::SyntaxError);
+          }
+
+          // DataError when the scalar is out of range.
+          let private_key = ec::import_scalar(ec_alg.named_curve, key_data)?;
+
+          let algorithm = Algorithm::EcKeyAlgorithm(EcKeyAlgorithm {
+            name: ec_alg.name,
+            named_curve: ec_alg.named_curve,
+          });
+          (KeyType::Private, algorithm, private_key)
+        }
+        _ => {
+          return Err(WebCryptoError::NotSupportedError);
+        }
+      },
+      "raw" => {
+        let algorithm = match algorithm {
+          ImportParams::AlgorithmIdentifer(AlgorithmIdentifer { name }) => {
+            match name {
+              "AES-CTR" | "AES-CBC" | "AES-GCM" | "AES-KW" | "AES-KWP"
+              | "AES-CMAC" | "AES-FF1" => {
+                let allowed: &[KeyUsage] = match name {
+                  "AES-KW" | "AES-KWP" => {
+                    &[KeyUsage::WrapKey, KeyUsage::UnwrapKey]
+                  }
+                  "AES-CMAC" => &[KeyUsage::Sign, KeyUsage::Verify],
+                  "AES-FF1" => &[KeyUsage::Encrypt, KeyUsage::Decrypt],
+                  _ => &[
+                    KeyUsage::Encrypt,
+                    KeyUsage::Decrypt,
+                    KeyUsage::WrapKey,
+                    KeyUsage::UnwrapKey,
+                  ],
+                };
+                if usages.iter().any(|usage| !allowed.contains(usage)) {
+                  return Err(WebCryptoError::SyntaxError);
+                }
+
+                if ![16, 24, 32].contains(&key_data.len()) {
+                  return Err(WebCryptoError::DataError);
+                }
+
+                Algorithm::AesKeyAlgorithm(AesKeyAlgorithm {
+                  name,
+                  length: key_data.len() * 8,
+                })
+              }
+              "ChaCha20-Poly1305" | "XChaCha20-Poly1305" | "BLAKE3" => {
+                let allowed: &[KeyUsage] = if name == "BLAKE3" {
+                  &[KeyUsage::Sign, KeyUsage::Verify]
+                } else {
+                  &[
+                    KeyUsage::Encrypt,
+                    KeyUsage::Decrypt,
+                    KeyUsage::WrapKey,
+                    KeyUsage::UnwrapKey,
+                  ]
+                };
+                if usages.iter().any(|usage| !allowed.contains(usage)) {
+                  return Err(WebCryptoError::SyntaxError);
+                }
+
+                if key_data.len() != 32 {
+                  return Err(WebCryptoError::DataError);
+                }
+
+                Algorithm::KeyAlgorithm(KeyAlgorithm { name })
+              }
+              #[cfg(feature = "ml-kem")]
+              "X-Wing" => {
+                if usages
+                  .iter()
+                  .any(|usage| usage != &KeyUsage::EncapsulateBits)
+                {
+                  return Err(WebCryptoError::SyntaxError);
+                }
+
+                if key_data.len() != xwing::PUBLIC_KEY_LENGTH {
+                  return Err(WebCryptoError::DataError);
+                }
+
+                Algorithm::KeyAlgorithm(KeyAlgorithm { name })
+              }
+              "Ed25519" | "Ed448" | "X448" => {
+                check_import_usages(name, KeyType::Public, &usages)?;
+
+                // DataError when the point is invalid.
+                okp::check_public_key(name, key_data)?;
+
+                Algorithm::KeyAlgorithm(KeyAlgorithm { name })
+              }
+              "HKDF" | "PBKDF2" | "ConcatKDF" | "X963KDF" | "Argon2id"
+              | "scrypt" => {
+                // 1.
+                if usages.iter().any(|usage| {
+                  !(usage == &KeyUsage::DeriveKey
+                    || usage == &KeyUsage::DeriveBits)
+                }) {
+                  return Err(WebCryptoError::SyntaxError);
+                }
+
+                // 2.
+                if extractable {
+                  return Err(WebCryptoError::SyntaxError);
+                }
+
+                Algorithm::KeyAlgorithm(KeyAlgorithm { name })
+              }
+              _ => {
+                return Err(WebCryptoError::NotSupportedError);
+              }
+            }
+          }
+          ImportParams::HmacImportParams(hmac_alg) => {
+            if usages.iter().any(|usage| {
+              !(usage == &KeyUsage::Sign || usage == &KeyUsage::Verify)
+            }) {
+              return Err(WebCryptoError::SyntaxError);
+            }
+
+            Algorithm::HmacKeyAlgorithm(HmacKeyAlgorithm {
+              name: hmac_alg.name,
+              hash: hmac_alg.hash,
+              length: hmac_import_length(key_data, hmac_alg.length)?,
+            })
+          }
+          ImportParams::DhKeyImportParams(dh_alg) => {
+            if !usages.is_empty() {
+              return Err(WebCryptoError::SyntaxError);
+            }
+
+            // DataError when the value is out of range.
+            dh::check(dh_alg.group, key_data)?;
+
+            Algorithm::DhKeyAlgorithm(DhKeyAlgorithm {
+              name: dh_alg.name,
+              group: dh_alg.group,
+            })
+          }
+          ImportParams::EcKeyImportParams(ec_alg) => {
+            let allowed: &[KeyUsage] = match ec_alg.name {
+              "ECDSA" => &[KeyUsage::Verify],
+              "ECDH" => &[],
+              _ => {
+                return Err(WebCryptoError::NotSupportedError);
+              }
+            };
+            if usages.iter().any(|usage| !allowed.contains(usage)) {
+              return Err(WebCryptoError::SyntaxError);
+            }
+
+            Algorithm::EcKeyAlgorithm(EcKeyAlgorithm {
+              name: ec_alg.name,
+              named_curve: ec_alg.named_curve,
+            })
+          }
+          ImportParams::RsaHashedImportParams(_) => {
+            return Err(WebCryptoError::NotSupportedError);
+          }
+        };
+
+        // EC, DH, X-Wing and OKP public keys are the only public keys
+        // imported as "raw".
+        let type_ = match algorithm {
+          Algorithm::EcKeyAlgorithm(_) | Algorithm::DhKeyAlgorithm(_) => {
+            KeyType::Public
+          }
+          Algorithm::KeyAlgorithm(KeyAlgorithm {
+            name: "X-Wing" | "Ed25519" | "Ed448" | "X448",
+          }) => KeyType::Public,
+          _ => KeyType::Secret,
+        };
+        // EC points may be compressed, and are stored uncompressed.
+        let key_material = match algorithm {
+          // DataError when the point is not on the curve.
+          Algorithm::EcKeyAlgorithm(alg) => {
+            ec::import_raw(alg.named_curve, key_data)?
+          }
+          Algorithm::HmacKeyAlgorithm(alg) => {
+            let mut key_data = key_data.to_vec();
+            truncate_bits(&mut key_data, alg.length);
+            key_data
+          }
+          _ => key_data.to_vec(),
+        };
+        (type_, algorithm, key_material)
+      }
+      "jwk" => {
+        let jwk = Jwk::from_slice(key_data)?;
+
+        match algorithm {
+          ImportParams::AlgorithmIdentifer(AlgorithmIdentifer { name })
+            if okp::is_okp(name) =>
+          {
+            jwk.check("OKP", name, jwk::okp_alg(name), extractable, &usages)?;
+
+            let (type_, key_material) = jwk.okp_key_material(name)?;
+            check_import_usages(name, type_, &usages)?;
+
+            let algorithm = Algorithm::KeyAlgorithm(KeyAlgorithm { name });
+            (type_, algorithm, key_material)
+          }
+          ImportParams::AlgorithmIdentifer(AlgorithmIdentifer { name }) => {
+            let key_data = jwk.secret()?;
+            let alg = match jwk::aes_alg(name, key_data.len() * 8) {
+              Some(alg) => alg,
+              None => {
+                return Err(WebCryptoError::NotSupportedError);
+              }
+            };
+            check_import_usages(name, KeyType::Secret, &usages)?;
+            jwk.check("oct", name, Some(&alg), extractable, &usages)?;
+
+            if ![16, 24, 32].contains(&key_data.len()) {
+              return Err(WebCryptoError::DataError);
+            }
+
+            let algorithm = Algorithm::AesKeyAlgorithm(AesKeyAlgorithm {
+              name,
+              length: key_data.len() * 8,
+            });
+            (KeyType::Secret, algorithm, key_data)
+          }
+          ImportParams::HmacImportParams(hmac_alg) => {
+            check_import_usages(hmac_alg.name, KeyType::Secret, &usages)?;
+            let alg = jwk::hmac_alg(hmac_alg.hash.name);
+            jwk.check("oct", hmac_alg.name, alg, extractable, &usages)?;
+
+            let mut key_data = jwk.secret()?;
+            let length = hmac_import_length(&key_data, hmac_alg.length)?;
+            truncate_bits(&mut key_data, length);
+
+            let algorithm = Algorithm::HmacKeyAlgorithm(HmacKeyAlgorithm {
+              name: hmac_alg.name,
+              hash: hmac_alg.hash,
+              length,
+            });
+            (KeyType::Secret, algorithm, key_data)
+          }
+          ImportParams::RsaHashedImportParams(rsa_alg) => {
+            let alg = jwk::rsa_alg(rsa_alg.name, rsa_alg.hash.name);
+            jwk.check("RSA", rsa_alg.name, alg, extractable, &usages)?;
+
+            // SyntaxError for eg. a public key to sign with.
+            let (type_, key_material) = jwk.rsa_key_material()?;
+            check_import_usages(rsa_alg.name, type_, &usages)?;
+            let key_material = KeyMaterial(key_material);
+            let public_key = rsa_public_key(&key_material)?;
+            let public_exponent =
+              PublicExponent::from_be_bytes(&public_key.e().to_bytes_be())?;
+
+            let algorithm =
+              Algorithm::RsaHashedKeyAlgorithm(RsaHashedKeyAlgorithm {
+                name: rsa_alg.name,
+                hash: rsa_alg.hash,
+                modulus_length: public_key.n().bits(),
+                public_exponent,
+              });
+            (type_, algorithm, key_material.0)
+          }
+          ImportParams::EcKeyImportParams(ec_alg) => {
+            // The `alg` of ECDH keys, eg. "ECDH-ES", is not checked.
+            let alg = match ec_alg.name {
+              "ECDSA" => jwk::ec_alg(ec_alg.name, ec_alg.named_curve),
+              "ECDH" => jwk.alg.as_deref(),
+              _ => return Err(WebCryptoError::NotSupportedError),
+            };
+            jwk.check("EC", ec_alg.name, alg, extractable, &usages)?;
+
+            // DataError when the key is for another curve or the point is
+            // not on it.
+            let (type_, key_material) =
+              jwk.ec_key_material(ec_alg.named_curve)?;
+            check_import_usages(ec_alg.name, type_, &usages)?;
+
+            let algorithm = Algorithm::EcKeyAlgorithm(EcKeyAlgorithm {
+              name: ec_alg.name,
+              named_curve: ec_alg.named_curve,
+            });
+            (type_, algorithm, key_material)
+          }
+          ImportParams::DhKeyImportParams(_) => {
+            return Err(WebCryptoError::NotSupportedError)
+          }
+        }
+      }
+      "pkcs8" => match algorithm {
+        ImportParams::RsaHashedImportParams(rsa_alg) => {
+          // 2.
+          let allowed: &[KeyUsage] = match rsa_alg.name {
+            "RSA-OAEP" => &[KeyUsage::Decrypt, KeyUsage::UnwrapKey],
+            "RSA-KEM" => &[KeyUsage::DecapsulateBits],
+            _ => &[KeyUsage::Sign],
+          };
+          if usages.iter().any(|usage| !allowed.contains(usage)) {
+            return Err(WebCryptoError::SyntaxError);
+          }
+
+          let private_key = RsaPrivateKey::from_pkcs8_der(key_data)
+            .map_err(|_| WebCryptoError::DataError)?;
+          let public_exponent =
+            PublicExponent::from_be_bytes(&private_key.e().to_bytes_be())?;
+          let pkcs1 = private_key
+            .to_pkcs1_der()
+            .map_err(|_| WebCryptoError::DataError)?;
+
+          let algorithm =
+            Algorithm::RsaHashedKeyAlgorithm(RsaHashedKeyAlgorithm {
+              name: rsa_alg.name,
+              hash: rsa_alg.hash,
+              modulus_length: private_key.n().bits(),
+              public_exponent,
+            });
+
+          (KeyType::Private, algorithm, pkcs1.as_ref().to_vec())
+        }
+        ImportParams::EcKeyImportParams(ec_alg) => {
+          // 2.
+          let allowed: &[KeyUsage] = match ec_alg.name {
+            "ECDSA" => &[KeyUsage::Sign],
+            "ECDH" => &[KeyUsage::DeriveKey, KeyUsage::DeriveBits],
+            _ => {
+              return Err(WebCryptoError::NotSupportedError);
+            }
+          };
+          if usages.iter().any(|usage| !allowed.contains(usage)) {
+            return Err(WebCryptoError::SyntaxError);
+          }
+
+          // DataError when the key is on another curve.
+          let private_key = ec::import_pkcs8(ec_alg.named_curve, key_data)?;
+
+          let algorithm = Algorithm::EcKeyAlgorithm(EcKeyAlgorithm {
+            name: ec_alg.name,
+            named_curve: ec_alg.named_curve,
+          });
+
+          (KeyType::Private, algorithm, private_key)
+        }
+        ImportParams::AlgorithmIdentifer(AlgorithmIdentifer { name })
+          if okp::is_okp(name) =>
+        {
+          check_import_usages(name, KeyType::Private, &usages)?;
+
+          // DataError when the key is for another curve.
+          let private_key = okp::import_pkcs8(name, key_data)?;
+
+          let algorithm = Algorithm::KeyAlgorithm(KeyAlgorithm { name });
+          (KeyType::Private, algorithm, private_key)
+        }
+        _ => {
+          return Err(WebCryptoError::NotSupportedError);
+        }
+      },
+      "spki" => match algorithm {
+        ImportParams::RsaHashedImportParams(rsa_alg) => {
+          // 2.
+          let allowed: &[KeyUsage] = match rsa_alg.name {
+            "RSA-OAEP" => &[KeyUsage::Encrypt, KeyUsage::WrapKey],
+            "RSA-KEM" => &[KeyUsage::EncapsulateBits],
+            _ => &[KeyUsage::Verify],
+          };
+          if usages.iter().any(|usage| !allowed.contains(usage)) {
+            return Err(WebCryptoError::SyntaxError);
+          }
+
+          let public_key = RsaPublicKey::from_public_key_der(key_data)
+            .map_err(|_| WebCryptoError::DataError)?;
+          let public_exponent =
+            PublicExponent::from_be_bytes(&public_key.e().to_bytes_be())?;
+          let pkcs1 = public_key
+            .to_pkcs1_der()
+            .map_err(|_| WebCryptoError::DataError)?;
+
+          let algorithm =
+            Algorithm::RsaHashedKeyAlgorithm(RsaHashedKeyAlgorithm {
+              name: rsa_alg.name,
+              hash: rsa_alg.hash,
+              modulus_length: public_key.n().bits(),
+              public_exponent,
+            });
+
+          (KeyType::Public, algorithm, pkcs1.as_ref().to_vec())
+        }
+        ImportParams::EcKeyImportParams(ec_alg) => {
+          // 2.
+          let allowed: &[KeyUsage] = match ec_alg.name {
+            "ECDSA" => &[KeyUsage::Verify],
+            "ECDH" => &[],
+            _ => {
+              return Err(WebCryptoError::NotSupportedError);
+            }
+          };
+          if usages.iter().any(|usage| !allowed.contains(usage)) {
+            return Err(WebCryptoError::SyntaxError);
+          }
+
+          // DataError when the key is on another curve.
+          let public_key = ec::import_spki(ec_alg.named_curve, key_data)?;
+
+          let algorithm = Algorithm::EcKeyAlgorithm(EcKeyAlgorithm {
+            name: ec_alg.name,
+            named_curve: ec_alg.named_curve,
+          });
+
+          (KeyType::Public, algorithm, public_key)
+        }
+        ImportParams::AlgorithmIdentifer(AlgorithmIdentifer { name })
+          if okp::is_okp(name) =>
+        {
+          check_import_usages(name, KeyType::Public, &usages)?;
+
+          // DataError when the key is for another curve.
+          let public_key = okp::import_spki(name, key_data)?;
+
+          let algorithm = Algorithm::KeyAlgorithm(KeyAlgorithm { name });
+          (KeyType::Public, algorithm, public_key)
+        }
+        _ => {
+          return Err(WebCryptoError::NotSupportedError);
+        }
+      },
+      _ => return Err(WebCryptoError::NotSupportedError),
+    };
+    let key_material = KeyMaterial(key_material);
+
+    if let Algorithm::RsaHashedKeyAlgorithm(alg) = algorithm {
+      self.check_rsa_policy(alg.modulus_length, alg.public_exponent)?;
+    }
+    if self.state.screen_ec_imports
+      && type_ == KeyType::Private
+      && matches!(algorithm, Algorithm::EcKeyAlgorithm(_))
+    {
+      check_ec_private_key(&key_material.0)
+        .map_err(|_| WebCryptoError::DataError)?;
+    }
+
+    if !self.state.import_hooks.is_empty() {
+      let modulus = match algorithm {
+        Algorithm::RsaKeyAlgorithm(_) | Algorithm::RsaHashedKeyAlgorithm(_) => {
+          Some(rsa_public_key(&key_material)?.n().to_bytes_be())
+        }
+        _ => None,
+      };
+      let imported = ImportedKey {
+        format,
+        type_: &type_,
+        algorithm: &algorithm,
+        modulus,
+      };
+      for hook in &self.state.import_hooks {
+        hook(&imported)?;
+      }
+    }
+
+    let handle = self.store_key(key_material)?;
+
+    Ok(CryptoKey {
+      extractable,
+      usages,
+      handle,
+      type_,
+      algorithm,
+      encrypted: AtomicU64::new(0),
+      consume_once: false,
+    })
+  }
+
+  /// Import a PEM encoded key.
+  ///
+  /// The format is picked from the PEM label: "PRIVATE KEY" is imported as
+  /// "pkcs8" and "PUBLIC KEY" as "spki". PKCS#1 ("RSA PRIVATE KEY",
+  /// "RSA PUBLIC KEY") and SEC1 ("EC PRIVATE KEY") keys are converted
+  /// first.
+  pub fn import_key_pem(
+    &mut self,
+    pem: &str,
+    algorithm: ImportParams,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<CryptoKey<S::Handle>, WebCryptoError> {
+    let (format, key_data) = pem::decode(pem, &algorithm)?;
+
+    self.import_key(format, &key_data, algorithm, extractable, usages)
+  }
+
+  /// Import the private keys of a password protected PKCS#12 (.p12 or
+  /// .pfx) bundle.
+  ///
+  /// Every key is imported as "pkcs8" with `algorithm`, so a bundle
+  /// mixing key types has to be imported once per algorithm.
+  pub fn import_pkcs12(
+    &mut self,
+    data: &[u8],
+    password: &str,
+    algorithm: ImportParams,
+    extractable: bool,
+    usages: Vec<KeyUsage>,
+  ) -> Result<Pkcs12<S::Handle>, WebCryptoError> {
+    let (entries, certificates) = pkcs12::decode(data, password)?;
+
+    let mut keys = Vec::with_capacity(entries.len());
+    for (alias, key_data, chain) in entries {
+      let key = self.import_key(
+        "pkcs8",
+        &key_data,
+        algorithm,
+        extractable,
+        usages.clone(),
+      )?;
+      keys.push(Pkcs12Key { alias, key, chain });
+    }
+
+    Ok(Pkcs12 { keys, certificates })
+  }
+
+  /// Encrypt `content` as a CMS (PKCS#7) EnvelopedData for `recipients`.
+  ///
+  /// The content is encrypted with AES-256-CBC under a random key, which
+  /// is transported to RSA recipients with RSA-OAEP (SHA-256) and to EC
+  /// recipients with ephemeral-static ECDH and AES-256 key wrap.
+  pub fn cms_encrypt(
+    &mut self,
+    recipients: &[Certificate],
+    content: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    cms::encrypt_enveloped(&mut self.rng, recipients, content)
+  }
+
+  /// Decrypt a CMS (PKCS#7) EnvelopedData addressed to `certificate`
+  /// with its private `key`.
+  ///
+  /// `key` is an RSA-OAEP key with the "decrypt" or "unwrapKey" usage, or
+  /// an ECDH key with the "deriveBits" or "deriveKey" usage.
+  pub fn cms_decrypt(
+    &mut self,
+    key: &CryptoKey<S::Handle>,
+    certificate: &Certificate,
+    enveloped_data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    if key.type_ != KeyType::Private {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    self.check_api_mode(key.algorithm.name(), None, false)?;
+
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+    let (recipient_key, usages) = match key.algorithm {
+      Algorithm::RsaHashedKeyAlgorithm(alg) if alg.name == "RSA-OAEP" => (
+        cms::RecipientKey::RsaOaep(&key_material.0),
+        [KeyUsage::Decrypt, KeyUsage::UnwrapKey],
+      ),
+      Algorithm::EcKeyAlgorithm(alg) if alg.name == "ECDH" => (
+        cms::RecipientKey::Ecdh(alg.named_curve, &key_material.0),
+        [KeyUsage::DeriveBits, KeyUsage::DeriveKey],
+      ),
+      _ => {
+        return Err(WebCryptoError::InvalidAccessError);
+      }
+    };
+    if !usages.iter().any(|usage| key.usages.contains(usage)) {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+
+    let result = cms::decrypt_enveloped(
+      &mut self.rng,
+      recipient_key,
+      certificate,
+      enveloped_data,
+    );
+    self.consume(key, result)
+  }
+
+  /// Validate the certificate path from `leaf` to one of `trust_anchors`,
+  /// through any of `intermediates`, at `time`. Returns the path, leaf
+  /// first and ending with the trust anchor.
+  ///
+  /// Signatures over the certificates as received, validity periods,
+  /// basic constraints, the "keyCertSign" key usage and name constraints
+  /// are checked, and certificates with a critical extension that is not
+  /// processed are rejected. When an issuer leads nowhere, the other
+  /// certificates with its name are tried. Policies and revocation are
+  /// not checked.
+  pub fn verify_chain(
+    &self,
+    leaf: &Certificate,
+    intermediates: &[Certificate],
+    trust_anchors: &[Certificate],
+    time: SystemTime,
+  ) -> Result<Vec<Certificate>, ChainError> {
+    x509::verify_chain(leaf, intermediates, trust_anchors, time)
+  }
+
+  /// Verify the signature of a DER encoded CRL with the public key of its
+  /// issuer.
+  ///
+  /// `issuer` is an RSASSA-PKCS1-v1_5 or ECDSA public key with the
+  /// "verify" usage, eg. imported from the issuer certificate's SPKI.
+  pub fn verify_crl(
+    &self,
+    crl: &[u8],
+    issuer: &CryptoKey<S::Handle>,
+  ) -> Result<bool, WebCryptoError> {
+    let signed = x509::crl_signature(crl)?;
+
+    self.verify_signed(signed, issuer)
+  }
+
+  /// Verify the signature of a DER encoded OCSP response with the public
+  /// key of its signer, the issuer of the certificate in question or its
+  /// delegated OCSP responder.
+  ///
+  /// `issuer` is an RSASSA-PKCS1-v1_5 or ECDSA public key with the
+  /// "verify" usage.
+  pub fn verify_ocsp_response(
+    &self,
+    response: &[u8],
+    issuer: &CryptoKey<S::Handle>,
+  ) -> Result<bool, WebCryptoError> {
+    let signed = x509::ocsp_signature(response)?;
+
+    self.verify_signed(signed, issuer)
+  }
+
+  /// Verify the signature of a signed X.509 object with `key`.
+  fn verify_signed(
+    &self,
+    (signature_algorithm, data, signature): x509::SignedParts,
+    key: &CryptoKey<S::Handle>,
+  ) -> Result<bool, WebCryptoError> {
+    check_key_use(key, key.algorithm.name(), Operation::Verify)?;
+    self.check_api_mode(key.algorithm.name(), None, false)?;
+
+    let hash = cms::signature_hash(&signature_algorithm)?;
+    let spki = self.export_key_data("spki", key)?;
+    let spki = SubjectPublicKeyInfoOwned::from_der(&spki)
+      .map_err(|_| WebCryptoError::OperationError)?;
+
+    cms::verify_spki_signature(&spki, hash, &data, &signature)
+  }
+}
+
+impl<R: 'static + RngCore + CryptoRng + Copy, S: KeyStorage>
+  SubtleCrypto<R, S>
+{
   pub fn sign(
+    &mut self,
+    mut algorithm: SignParams,
+    key: &CryptoKey<S::Handle>,
+    data: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    algorithm.normalize();
+    self.check_api_mode(
+      key.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+    check_key_use(key, algorithm.name(), Operation::Sign)?;
+
+    let result = self.sign_data(algorithm, key, data);
+    let result = self.consume(key, result);
+    self.record("sign", &[data], result.as_deref().ok());
+    result
+  }
+
+  /// Sign `data` like `sign`, also returning the effective parameters:
+  /// the RSA-PSS salt length, defaulting to the digest length.
+  pub fn sign_detailed(
+    &mut self,
+    mut algorithm: SignParams,
+    key: &CryptoKey<S::Handle>,
+    data: &[u8],
+  ) -> Result<SignOutput, WebCryptoError> {
+    algorithm.normalize();
+    let params = match algorithm {
+      SignParams::RsaPssParams(params) => {
+        let hash = prehash(&algorithm, &key.algorithm)?;
+        let salt_length = match params.salt_length {
+          Some(salt_length) => salt_length,
+          None => digest(hash, &[])?.len(),
+        };
+        SignParams::RsaPssParams(RsaPssParams {
+          salt_length: Some(salt_length),
+          ..params
+        })
+      }
+      params => params,
+    };
+
+    let signature = self.sign(algorithm, key, data)?;
+    Ok(SignOutput { signature, params })
+  }
+
+  fn sign_data(
     &mut self,
     algorithm: SignParams,
     key: &CryptoKey<S::Handle>,
     data: &[u8],
-  ) -> Result<Vec<u8>, ()> {
+  ) -> Result<Vec<u8>, WebCryptoError> {
     match algorithm {
       SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
         name: "RSASSA-PKCS1-v1_5",
+      })
+      | SignParams::RsaPssParams(RsaPssParams {
+        name: "RSA-PSS", ..
+      })
+      | SignParams::EcdsaParams(_) => {
+        let digest = digest(prehash(&algorithm, &key.algorithm)?, data)?;
+        self.sign_digest(algorithm, key, &digest)
+      }
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer { name: "BLAKE3" }) => {
+        let key = blake3_key(
+          key,
+          self
+            .storage
+            .get(key.handle)
+            .ok_or(WebCryptoError::OperationError)?,
+        )?;
+
+        Ok(blake3::keyed_hash(&key, data).as_bytes().to_vec())
+      }
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "AES-CMAC",
       }) => {
-        if key.type_ != KeyType::Private {
-          // InvalidAccessError.
+        let key = aes_cmac_key(
+          key,
+          self
+            .storage
+            .get(key.handle)
+            .ok_or(WebCryptoError::OperationError)?,
+        )?;
+        aes_cmac::sign(key, data)
+      }
+      #[cfg(feature = "ed448")]
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer { name: "Ed448" }) => {
+        let seed = self.okp_key(key, KeyType::Private, "Ed448")?;
+        ed448::sign(seed, data)
+      }
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "Ed25519",
+      }) => {
+        let seed = self.okp_key(key, KeyType::Private, "Ed25519")?;
+        ed25519::sign(seed, b"", data)
+      }
+      SignParams::Ed25519Params(params) if params.prehash => {
+        self.sign_ed25519ph(params, key, ed25519::prehash(data))
+      }
+      SignParams::Ed25519Params(params) => {
+        let seed = self.okp_key(key, KeyType::Private, params.name)?;
+        ed25519::sign(seed, params.context, data)
+      }
+      _ => Err(WebCryptoError::NotSupportedError),
+    }
+  }
+
+  pub fn verify(
+    &mut self,
+    mut algorithm: SignParams,
+    key: &CryptoKey<S::Handle>,
+    signature: &[u8],
+    data: &[u8],
+  ) -> Result<bool, WebCryptoError> {
+    algorithm.normalize();
+    self.check_api_mode(
+      key.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+    check_key_use(key, algorithm.name(), Operation::Verify)?;
+
+    let result = self.verify_data(algorithm, key, signature, data);
+    let result = self.consume(key, result);
+    self.record(
+      "verify",
+      &[signature, data],
+      result
+        .ok()
+        .map(|valid| [valid as u8])
+        .as_ref()
+        .map(|valid| &valid[..]),
+    );
+    result
+  }
+
+  fn verify_data(
+    &mut self,
+    algorithm: SignParams,
+    key: &CryptoKey<S::Handle>,
+    signature: &[u8],
+    data: &[u8],
+  ) -> Result<bool, WebCryptoError> {
+    match algorithm {
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "RSASSA-PKCS1-v1_5",
+      })
+      | SignParams::RsaPssParams(RsaPssParams {
+        name: "RSA-PSS", ..
+      })
+      | SignParams::EcdsaParams(_) => {
+        let digest = digest(prehash(&algorithm, &key.algorithm)?, data)?;
+        self.verify_digest(algorithm, key, signature, &digest)
+      }
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer { name: "BLAKE3" }) => {
+        let key = blake3_key(
+          key,
+          self
+            .storage
+            .get(key.handle)
+            .ok_or(WebCryptoError::OperationError)?,
+        )?;
+        let signature: [u8; 32] = match signature.try_into() {
+          Ok(signature) => signature,
+          Err(_) => return Ok(false),
+        };
+
+        // Hash comparisons are constant time.
+        Ok(blake3::keyed_hash(&key, data) == blake3::Hash::from(signature))
+      }
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "AES-CMAC",
+      }) => {
+        let key = aes_cmac_key(
+          key,
+          self
+            .storage
+            .get(key.handle)
+            .ok_or(WebCryptoError::OperationError)?,
+        )?;
+        aes_cmac::verify(key, signature, data)
+      }
+      #[cfg(feature = "ed448")]
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer { name: "Ed448" }) => {
+        let public_key = self.okp_key(key, KeyType::Public, "Ed448")?;
+        ed448::verify(public_key, signature, data)
+      }
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "Ed25519",
+      }) => {
+        let public_key = self.okp_key(key, KeyType::Public, "Ed25519")?;
+        ed25519::verify(
+          &ed25519::verifying_key(public_key)?,
+          b"",
+          signature,
+          data,
+        )
+      }
+      SignParams::Ed25519Params(params) if params.prehash => {
+        self.verify_ed25519ph(params, key, signature, ed25519::prehash(data))
+      }
+      SignParams::Ed25519Params(params) => {
+        let public_key = self.okp_key(key, KeyType::Public, params.name)?;
+        ed25519::verify(
+          &ed25519::verifying_key(public_key)?,
+          params.context,
+          signature,
+          data,
+        )
+      }
+      _ => Err(WebCryptoError::NotSupportedError),
+    }
+  }
+
+  /// Parse the public `keys` into a pool verifying signatures without
+  /// `SubtleCrypto`, eg. from many threads. Every key needs the Verify
+  /// usage.
+  pub fn verifier_pool(
+    &self,
+    keys: &[&CryptoKey<S::Handle>],
+  ) -> Result<VerifierPool, WebCryptoError> {
+    let keys = keys
+      .iter()
+      .map(|key| {
+        check_key_use(key, key.algorithm.name(), Operation::Verify)?;
+        self.check_api_mode(key.algorithm.name(), None, false)?;
+        if key.consume_once {
+          return Err(WebCryptoError::InvalidAccessError);
         }
 
-        let key_material = self.storage.get(key.handle).unwrap();
-        let private_key =
-          RsaPrivateKey::from_pkcs1_der(&key_material.0).map_err(|_| ())?;
-        let (padding, digest_in) = match key.algorithm {
-          Algorithm::RsaHashedKeyAlgorithm(alg) => {
-            match alg.hash.name {
-              "SHA-1" => {
-                let mut hasher = Sha1::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::PKCS1v15Sign {
-                    hash: Some(rsa::hash::Hash::SHA1),
-                  },
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-256" => {
-                let mut hasher = Sha256::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::PKCS1v15Sign {
-                    hash: Some(rsa::hash::Hash::SHA2_256),
-                  },
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-384" => {
-                let mut hasher = Sha384::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::PKCS1v15Sign {
-                    hash: Some(rsa::hash::Hash::SHA2_384),
-                  },
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-512" => {
-                let mut hasher = Sha512::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::PKCS1v15Sign {
-                    hash: Some(rsa::hash::Hash::SHA2_512),
-                  },
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              _ => {
-                // SyntaxError.
-                return Err(());
-              }
-            }
+        let key_material = self
+          .storage
+          .get(key.handle)
+          .ok_or(WebCryptoError::OperationError)?;
+        let verifier = match key.algorithm {
+          Algorithm::RsaHashedKeyAlgorithm(_) => {
+            verifier_pool::Verifier::Rsa(rsa_public_key(key_material)?)
+          }
+          Algorithm::EcKeyAlgorithm(alg) => verifier_pool::Verifier::Ecdsa(
+            ec::VerifyingKey::from_sec1(alg.named_curve, &key_material.0)?,
+          ),
+          Algorithm::KeyAlgorithm(alg) if alg.name == "Ed25519" => {
+            verifier_pool::Verifier::Ed25519(ed25519::verifying_key(
+              &key_material.0,
+            )?)
+          }
+          #[cfg(feature = "ed448")]
+          Algorithm::KeyAlgorithm(alg) if alg.name == "Ed448" => {
+            verifier_pool::Verifier::Ed448(key_material.0.clone())
+          }
+          _ => {
+            return Err(WebCryptoError::NotSupportedError);
           }
-          _ => unreachable!(),
         };
-        let sig = private_key.sign(padding, &digest_in).map_err(|_| ())?;
+        Ok(verifier_pool::PooledKey {
+          algorithm: key.algorithm,
+          verifier,
+        })
+      })
+      .collect::<Result<_, WebCryptoError>>()?;
+
+    Ok(VerifierPool {
+      keys,
+      downgrade_protection: self.state.downgrade_protection,
+    })
+  }
+
+  /// Start signing data given in parts with `SignContext::update`, for
+  /// RSASSA-PKCS1-v1_5, RSA-PSS, ECDSA, Ed25519ph and BLAKE3. The
+  /// signature is the one `sign` produces for the whole data.
+  pub fn sign_context<'a>(
+    &self,
+    mut algorithm: SignParams,
+    key: &'a CryptoKey<S::Handle>,
+  ) -> Result<SignContext<'a, S::Handle>, WebCryptoError> {
+    algorithm.normalize();
+    self.check_api_mode(
+      key.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+    check_key_use(key, algorithm.name(), Operation::Sign)?;
+
+    Ok(SignContext {
+      algorithm,
+      key,
+      hasher: self.multipart_hasher(&algorithm, key)?,
+    })
+  }
 
-        Ok(sig)
+  pub fn sign_finish(
+    &mut self,
+    context: SignContext<S::Handle>,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let SignContext {
+      algorithm,
+      key,
+      hasher,
+    } = context;
+    let result = match (hasher, algorithm) {
+      (multipart::Hasher::Blake3(hasher), _) => {
+        Ok(hasher.finalize().as_bytes().to_vec())
       }
-      SignParams::RsaPssParams(RsaPssParams {
-        name: "RSA-PSS",
-        salt_length,
-        ..
-      }) => {
-        if key.type_ != KeyType::Private {
-          // InvalidAccessError.
+      (
+        multipart::Hasher::Ed25519ph(hasher),
+        SignParams::Ed25519Params(params),
+      ) => self.sign_ed25519ph(params, key, hasher),
+      (hasher, _) => self.sign_digest(algorithm, key, &hasher.finalize()),
+    };
+    let result = self.consume(key, result);
+    self.record("sign", &[], result.as_deref().ok());
+    result
+  }
+
+  /// Start verifying a signature over data given in parts with
+  /// `VerifyContext::update`, like `sign_context`.
+  pub fn verify_context<'a>(
+    &self,
+    mut algorithm: SignParams,
+    key: &'a CryptoKey<S::Handle>,
+  ) -> Result<VerifyContext<'a, S::Handle>, WebCryptoError> {
+    algorithm.normalize();
+    self.check_api_mode(
+      key.algorithm.name(),
+      None,
+      algorithm.is_extension(),
+    )?;
+    check_key_use(key, algorithm.name(), Operation::Verify)?;
+
+    Ok(VerifyContext {
+      algorithm,
+      key,
+      hasher: self.multipart_hasher(&algorithm, key)?,
+    })
+  }
+
+  pub fn verify_finish(
+    &mut self,
+    context: VerifyContext<S::Handle>,
+    signature: &[u8],
+  ) -> Result<bool, WebCryptoError> {
+    let VerifyContext {
+      algorithm,
+      key,
+      hasher,
+    } = context;
+    let result = match (hasher, algorithm) {
+      // Hash comparisons are constant time.
+      (multipart::Hasher::Blake3(hasher), _) => Ok(matches!(
+        <[u8; 32]>::try_from(signature),
+        Ok(signature) if hasher.finalize() == blake3::Hash::from(signature)
+      )),
+      (
+        multipart::Hasher::Ed25519ph(hasher),
+        SignParams::Ed25519Params(params),
+      ) => self.verify_ed25519ph(params, key, signature, hasher),
+      (hasher, _) => {
+        self.verify_digest(algorithm, key, signature, &hasher.finalize())
+      }
+    };
+    let result = self.consume(key, result);
+    self.record(
+      "verify",
+      &[signature],
+      result
+        .ok()
+        .map(|valid| [valid as u8])
+        .as_ref()
+        .map(|valid| &valid[..]),
+    );
+    result
+  }
+
+  /// The hasher of data signed in parts with `algorithm` and `key`.
+  fn multipart_hasher(
+    &self,
+    algorithm: &SignParams,
+    key: &CryptoKey<S::Handle>,
+  ) -> Result<multipart::Hasher, WebCryptoError> {
+    match algorithm {
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer { name: "BLAKE3" }) => {
+        let key = blake3_key(
+          key,
+          self
+            .storage
+            .get(key.handle)
+            .ok_or(WebCryptoError::OperationError)?,
+        )?;
+        Ok(multipart::Hasher::blake3(&key))
+      }
+      SignParams::Ed25519Params(params) if params.prehash => {
+        Ok(multipart::Hasher::ed25519ph())
+      }
+      SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+        name: "RSASSA-PKCS1-v1_5",
+      })
+      | SignParams::RsaPssParams(_)
+      | SignParams::EcdsaParams(_) => {
+        multipart::Hasher::new(prehash(algorithm, &key.algorithm)?)
+      }
+      _ => Err(WebCryptoError::NotSupportedError),
+    }
+  }
+
+  /// Sign the `digest` of the data with the RSA or ECDSA `key`.
+  fn sign_digest(
+    &mut self,
+    algorithm: SignParams,
+    key: &CryptoKey<S::Handle>,
+    digest: &[u8],
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    if key.type_ != KeyType::Private {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+    self.check_sign_downgrade(&algorithm, &key.algorithm)?;
+    let hash = prehash(&algorithm, &key.algorithm)?;
+    let operation = KeyOperation::Sign {
+      params: &algorithm,
+      hash,
+      digest,
+    };
+    if let Some(result) =
+      self.storage.perform(key.handle, &key.algorithm, operation)
+    {
+      return match (algorithm, key.algorithm) {
+        (SignParams::EcdsaParams(params), Algorithm::EcKeyAlgorithm(alg))
+          if params.low_s =>
+        {
+          ec::normalize_s(alg.named_curve, &result?)
         }
+        _ => result,
+      };
+    }
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
 
-        let key_material = self.storage.get(key.handle).unwrap();
-        let private_key =
-          RsaPrivateKey::from_pkcs1_der(&key_material.0).map_err(|_| ())?;
+    match algorithm {
+      SignParams::AlgorithmIdentifer(_) => {
+        let private_key = self.rsa_private_key(key_material)?;
+        let padding = pkcs1v15_padding(hash)?;
 
-        let (padding, digest_in) = match key.algorithm {
-          Algorithm::RsaHashedKeyAlgorithm(alg) => {
-            match alg.hash.name {
-              "SHA-1" => {
-                let mut hasher = Sha1::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::new_pss_with_salt::<Sha1, _>(
-                    self.rng,
-                    salt_length,
-                  ),
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-256" => {
-                let mut hasher = Sha256::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::new_pss_with_salt::<Sha256, _>(
-                    self.rng,
-                    salt_length,
-                  ),
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-384" => {
-                let mut hasher = Sha384::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::new_pss_with_salt::<Sha384, _>(
-                    self.rng,
-                    salt_length,
-                  ),
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-512" => {
-                let mut hasher = Sha512::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::new_pss_with_salt::<Sha512, _>(
-                    self.rng,
-                    salt_length,
-                  ),
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              _ => {
-                // SyntaxError.
-                return Err(());
-              }
-            }
+        private_key
+          .sign(padding, digest)
+          .map_err(|_| WebCryptoError::OperationError)
+      }
+      SignParams::RsaPssParams(params) => {
+        let private_key = self.rsa_private_key(key_material)?;
+        let salt_length =
+          pss_salt_length(hash, params.salt_length, private_key.n().bits())?;
+        let padding = pss_padding(hash, self.rng, salt_length)?;
+
+        private_key
+          .sign(padding, digest)
+          .map_err(|_| WebCryptoError::OperationError)
+      }
+      SignParams::EcdsaParams(params) => {
+        let named_curve = match key.algorithm {
+          Algorithm::EcKeyAlgorithm(alg) if alg.name == params.name => {
+            alg.named_curve
+          }
+          _ => {
+            return Err(WebCryptoError::InvalidAccessError);
           }
-          _ => unreachable!(),
         };
-        let sig = private_key.sign(padding, &digest_in).map_err(|_| ())?;
 
-        Ok(sig)
+        let signature = if params.deterministic {
+          ec::sign_deterministic(named_curve, &key_material.0, digest)?
+        } else {
+          ec::sign(named_curve, &key_material.0, digest, &mut self.rng)?
+        };
+        if params.low_s {
+          ec::normalize_s(named_curve, &signature)
+        } else {
+          Ok(signature)
+        }
       }
-      _ => todo!(),
+      SignParams::Ed25519Params(_) => Err(WebCryptoError::InvalidAccessError),
     }
   }
 
-  pub fn verify(
+  /// Verify `signature` over the `digest` of the data with the RSA or
+  /// ECDSA `key`.
+  fn verify_digest(
     &mut self,
     algorithm: SignParams,
     key: &CryptoKey<S::Handle>,
     signature: &[u8],
-    data: &[u8],
-  ) -> Result<bool, ()> {
+    digest: &[u8],
+  ) -> Result<bool, WebCryptoError> {
+    self.check_sign_downgrade(&algorithm, &key.algorithm)?;
+    let hash = prehash(&algorithm, &key.algorithm)?;
+    let key_material = self
+      .storage
+      .get(key.handle)
+      .ok_or(WebCryptoError::OperationError)?;
+
     match algorithm {
-      SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
-        name: "RSASSA-PKCS1-v1_5",
-      }) => {
+      SignParams::AlgorithmIdentifer(_) => {
+        let public_key = rsa_public_key(key_material)?;
+        let padding = pkcs1v15_padding(hash)?;
+
+        Ok(public_key.verify(padding, digest, signature).is_ok())
+      }
+      SignParams::RsaPssParams(params) => {
+        let public_key = rsa_public_key(key_material)?;
+        let salt_length =
+          pss_salt_length(hash, params.salt_length, public_key.n().bits())?;
+        let padding = pss_padding(hash, self.rng, salt_length)?;
+
+        Ok(public_key.verify(padding, digest, signature).is_ok())
+      }
+      SignParams::EcdsaParams(params) => {
+        // 2.
         if key.type_ != KeyType::Public {
-          // InvalidAccessError.
+          return Err(WebCryptoError::InvalidAccessError);
         }
 
-        let key_material = self.storage.get(key.handle).unwrap();
-        let private_key =
-          RsaPrivateKey::from_pkcs1_der(&key_material.0).map_err(|_| ())?;
-        let (padding, digest_in) = match key.algorithm {
-          Algorithm::RsaHashedKeyAlgorithm(alg) => {
-            match alg.hash.name {
-              "SHA-1" => {
-                let mut hasher = Sha1::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::PKCS1v15Sign {
-                    hash: Some(rsa::hash::Hash::SHA1),
-                  },
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-256" => {
-                let mut hasher = Sha256::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::PKCS1v15Sign {
-                    hash: Some(rsa::hash::Hash::SHA2_256),
-                  },
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-384" => {
-                let mut hasher = Sha384::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::PKCS1v15Sign {
-                    hash: Some(rsa::hash::Hash::SHA2_384),
-                  },
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-512" => {
-                let mut hasher = Sha512::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::PKCS1v15Sign {
-                    hash: Some(rsa::hash::Hash::SHA2_512),
-                  },
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              _ => {
-                // SyntaxError.
-                return Err(());
-              }
-            }
+        let named_curve = match key.algorithm {
+          Algorithm::EcKeyAlgorithm(alg) if alg.name == params.name => {
+            alg.named_curve
+          }
+          _ => {
+            return Err(WebCryptoError::InvalidAccessError);
           }
-          _ => unreachable!(),
         };
-        let verify = private_key.verify(padding, &digest_in, signature).is_ok();
 
-        Ok(verify)
-      }
-      SignParams::RsaPssParams(RsaPssParams {
-        name: "RSA-PSS",
-        salt_length,
-        ..
-      }) => {
-        if key.type_ != KeyType::Private {
-          // InvalidAccessError.
+        if params.low_s && ec::is_high_s(named_curve, signature) {
+          return Ok(false);
         }
+        ec::verify(named_curve, &key.type_, &key_material.0, digest, signature)
+      }
+      SignParams::Ed25519Params(_) => Err(WebCryptoError::InvalidAccessError),
+    }
+  }
 
-        let key_material = self.storage.get(key.handle).unwrap();
-        let private_key =
-          RsaPrivateKey::from_pkcs1_der(&key_material.0).map_err(|_| ())?;
+  /// Sign the Ed25519ph `prehash` of the data with the Ed25519 `key`.
+  fn sign_ed25519ph(
+    &self,
+    params: Ed25519Params,
+    key: &CryptoKey<S::Handle>,
+    prehash: ed25519::Prehash,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let seed = self.okp_key(key, KeyType::Private, params.name)?;
+    ed25519::sign_prehashed(seed, params.context, prehash)
+  }
 
-        let (padding, digest_in) = match key.algorithm {
-          Algorithm::RsaHashedKeyAlgorithm(alg) => {
-            match alg.hash.name {
-              "SHA-1" => {
-                let mut hasher = Sha1::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::new_pss_with_salt::<Sha1, _>(
-                    self.rng,
-                    salt_length,
-                  ),
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-256" => {
-                let mut hasher = Sha256::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::new_pss_with_salt::<Sha256, _>(
-                    self.rng,
-                    salt_length,
-                  ),
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-384" => {
-                let mut hasher = Sha384::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::new_pss_with_salt::<Sha384, _>(
-                    self.rng,
-                    salt_length,
-                  ),
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              "SHA-512" => {
-                let mut hasher = Sha512::new();
-                hasher.update(&data);
-                (
-                  PaddingScheme::new_pss_with_salt::<Sha512, _>(
-                    self.rng,
-                    salt_length,
-                  ),
-                  hasher.finalize()[..].to_vec(),
-                )
-              }
-              _ => {
-                // SyntaxError.
-                return Err(());
-              }
-            }
-          }
-          _ => unreachable!(),
-        };
-        let verify = private_key.verify(padding, &digest_in, signature).is_ok();
+  /// Verify `signature` over the Ed25519ph `prehash` of the data with the
+  /// Ed25519 `key`.
+  fn verify_ed25519ph(
+    &self,
+    params: Ed25519Params,
+    key: &CryptoKey<S::Handle>,
+    signature: &[u8],
+    prehash: ed25519::Prehash,
+  ) -> Result<bool, WebCryptoError> {
+    let public_key = self.okp_key(key, KeyType::Public, params.name)?;
+    ed25519::verify_prehashed(
+      &ed25519::verifying_key(public_key)?,
+      params.context,
+      signature,
+      prehash,
+    )
+  }
 
-        Ok(verify)
-      }
-      _ => todo!(),
+  /// The seed or public key of the OKP `key`, which has to be of type
+  /// `type_`, for parameters named `name`.
+  fn okp_key(
+    &self,
+    key: &CryptoKey<S::Handle>,
+    type_: KeyType,
+    name: &str,
+  ) -> Result<&[u8], WebCryptoError> {
+    if key.type_ != type_ || key.algorithm.name() != name {
+      return Err(WebCryptoError::InvalidAccessError);
     }
+
+    Ok(
+      &self
+        .storage
+        .get(key.handle)
+        .ok_or(WebCryptoError::OperationError)?
+        .0,
+    )
+  }
+
+  /// Sign `content` as a CMS (PKCS#7) SignedData with the private `key`.
+  ///
+  /// `certificates` holds the signer's certificate first, followed by
+  /// any intermediates to embed. Only RSASSA-PKCS1-v1_5 and ECDSA keys
+  /// are supported. A `detached` signature does not embed `content`.
+  pub fn cms_sign(
+    &mut self,
+    algorithm: SignParams,
+    key: &CryptoKey<S::Handle>,
+    certificates: &[Certificate],
+    content: &[u8],
+    detached: bool,
+  ) -> Result<Vec<u8>, WebCryptoError> {
+    let (scheme, hash) = match (algorithm, key.algorithm) {
+      (
+        SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+          name: "RSASSA-PKCS1-v1_5",
+        }),
+        Algorithm::RsaHashedKeyAlgorithm(alg),
+      ) => (cms::Scheme::RsassaPkcs1v15, alg.hash.name),
+      (SignParams::EcdsaParams(params), Algorithm::EcKeyAlgorithm(alg)) => {
+        (cms::Scheme::Ecdsa(alg.named_curve), params.hash.name)
+      }
+      _ => {
+        return Err(WebCryptoError::NotSupportedError);
+      }
+    };
+
+    let signed_attributes = cms::signed_attributes(hash, content)?;
+    let signature = self.sign(
+      algorithm,
+      key,
+      &signed_attributes
+        .to_der()
+        .map_err(|_| WebCryptoError::OperationError)?,
+    )?;
+
+    cms::encode(
+      scheme,
+      hash,
+      certificates,
+      if detached { None } else { Some(content) },
+      signed_attributes,
+      &signature,
+    )
+  }
+
+  /// Verify a CMS (PKCS#7) SignedData against the certificates it
+  /// embeds.
+  ///
+  /// Returns the certificates of the signers when every signature is
+  /// valid and `None` otherwise. `content` is required for detached
+  /// signatures. The certificates themselves are not validated.
+  pub fn cms_verify(
+    &self,
+    signed_data: &[u8],
+    content: Option<&[u8]>,
+  ) -> Result<Option<Vec<Certificate>>, WebCryptoError> {
+    cms::verify(signed_data, content)
   }
 }
@@ -0,0 +1,102 @@
+use sha1::Sha1;
+use sha2::Digest;
+use sha2::Sha256;
+use sha2::Sha384;
+use sha2::Sha512;
+
+use super::ed25519;
+use super::CryptoKey;
+use super::SignParams;
+use crate::WebCryptoError;
+
+/// The running hash of data signed in parts.
+#[derive(Clone)]
+pub(crate) enum Hasher {
+  Sha1(Sha1),
+  Sha256(Sha256),
+  Sha384(Sha384),
+  Sha512(Sha512),
+  /// The prehash of Ed25519ph.
+  Ed25519ph(ed25519::Prehash),
+  /// A keyed BLAKE3 hash, the MAC itself.
+  Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+  /// A hasher for the digest algorithm `name`.
+  pub(crate) fn new(name: &str) -> Result<Self, WebCryptoError> {
+    match name {
+      "SHA-1" => Ok(Hasher::Sha1(Sha1::new())),
+      "SHA-256" => Ok(Hasher::Sha256(Sha256::new())),
+      "SHA-384" => Ok(Hasher::Sha384(Sha384::new())),
+      "SHA-512" => Ok(Hasher::Sha512(Sha512::new())),
+      _ => Err(WebCryptoError::NotSupportedError),
+    }
+  }
+
+  pub(crate) fn ed25519ph() -> Self {
+    Hasher::Ed25519ph(ed25519::Prehash::default())
+  }
+
+  pub(crate) fn blake3(key: &[u8; 32]) -> Self {
+    Hasher::Blake3(Box::new(blake3::Hasher::new_keyed(key)))
+  }
+
+  pub(crate) fn update(&mut self, data: &[u8]) {
+    match self {
+      Hasher::Sha1(hasher) => hasher.update(data),
+      Hasher::Sha256(hasher) => hasher.update(data),
+      Hasher::Sha384(hasher) => hasher.update(data),
+      Hasher::Sha512(hasher) => hasher.update(data),
+      Hasher::Ed25519ph(hasher) => ed25519_dalek::Digest::update(hasher, data),
+      Hasher::Blake3(hasher) => {
+        hasher.update(data);
+      }
+    }
+  }
+
+  pub(crate) fn finalize(self) -> Vec<u8> {
+    match self {
+      Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+      Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+      Hasher::Sha384(hasher) => hasher.finalize().to_vec(),
+      Hasher::Sha512(hasher) => hasher.finalize().to_vec(),
+      Hasher::Ed25519ph(hasher) => {
+        ed25519_dalek::Digest::finalize(hasher).to_vec()
+      }
+      Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+    }
+  }
+}
+
+/// Signs data given in parts, eg. a file too large to buffer. Created by
+/// `SubtleCrypto::sign_context` and finished by
+/// `SubtleCrypto::sign_finish`.
+pub struct SignContext<'a, H> {
+  pub(crate) algorithm: SignParams,
+  pub(crate) key: &'a CryptoKey<H>,
+  pub(crate) hasher: Hasher,
+}
+
+impl<H> SignContext<'_, H> {
+  /// Append `data` to the data signed.
+  pub fn update(&mut self, data: &[u8]) {
+    self.hasher.update(data);
+  }
+}
+
+/// Verifies a signature over data given in parts. Created by
+/// `SubtleCrypto::verify_context` and finished by
+/// `SubtleCrypto::verify_finish`.
+pub struct VerifyContext<'a, H> {
+  pub(crate) algorithm: SignParams,
+  pub(crate) key: &'a CryptoKey<H>,
+  pub(crate) hasher: Hasher,
+}
+
+impl<H> VerifyContext<'_, H> {
+  /// Append `data` to the data verified.
+  pub fn update(&mut self, data: &[u8]) {
+    self.hasher.update(data);
+  }
+}
@@ -0,0 +1,49 @@
+use crate::WebCryptoError;
+
+/// Unique nonces for many encryptions under one key: a fixed field
+/// followed by a 64-bit big-endian counter, the deterministic
+/// construction of NIST SP 800-38D.
+///
+/// The fixed field tells senders sharing a key apart, so each of them
+/// needs its own. Nonces are never repeated: `next` fails once the
+/// counter is exhausted.
+///
+/// ```
+/// use webcrypto::subtle::NonceSequence;
+///
+/// let mut nonces = NonceSequence::new(&[0, 0, 0, 7]);
+/// assert_eq!(nonces.next().unwrap(), [0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0]);
+/// assert_eq!(nonces.next().unwrap(), [0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 1]);
+/// ```
+#[derive(Debug)]
+pub struct NonceSequence {
+  fixed: Vec<u8>,
+  counter: Option<u64>,
+}
+
+impl NonceSequence {
+  /// A sequence of `fixed.len() + 8` byte nonces, eg. a 4-byte fixed
+  /// field for the 96-bit nonces of AES-GCM.
+  pub fn new(fixed: &[u8]) -> Self {
+    NonceSequence {
+      fixed: fixed.to_vec(),
+      counter: Some(0),
+    }
+  }
+
+  /// The length in bytes of the nonces.
+  pub fn nonce_length(&self) -> usize {
+    self.fixed.len() + 8
+  }
+
+  /// The next nonce, or an error once all were used.
+  #[allow(clippy::should_implement_trait)]
+  pub fn next(&mut self) -> Result<Vec<u8>, WebCryptoError> {
+    let counter = self.counter.ok_or(WebCryptoError::OperationError)?;
+    self.counter = counter.checked_add(1);
+
+    let mut nonce = self.fixed.clone();
+    nonce.extend_from_slice(&counter.to_be_bytes());
+    Ok(nonce)
+  }
+}
@@ -0,0 +1,146 @@
+// RSAES-OAEP (RFC 8017 section 7.1) with a label of arbitrary bytes. The
+// rsa crate takes the label as a string, so labeled encryption encodes
+// the message here and only borrows the raw RSA operation.
+
+use crypto_bigint::subtle::Choice;
+use crypto_bigint::subtle::ConditionallySelectable;
+use crypto_bigint::subtle::ConstantTimeEq;
+use rand::CryptoRng;
+use rand::RngCore;
+
+use rsa::internals;
+use rsa::BigUint;
+use rsa::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use rsa::RsaPublicKey;
+use sha1::Sha1;
+use sha2::Digest;
+use sha2::Sha256;
+use sha2::Sha384;
+use sha2::Sha512;
+
+use super::rsa_kem::i2osp;
+use crate::WebCryptoError;
+
+/// Encrypt `message` to `public_key` with OAEP using `hash` for both the
+/// label digest and MGF1.
+pub(crate) fn encrypt<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  public_key: &RsaPublicKey,
+  hash: &str,
+  label: &[u8],
+  message: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  match hash {
+    "SHA-1" => encrypt_with::<Sha1, _>(rng, public_key, label, message),
+    "SHA-256" => encrypt_with::<Sha256, _>(rng, public_key, label, message),
+    "SHA-384" => encrypt_with::<Sha384, _>(rng, public_key, label, message),
+    "SHA-512" => encrypt_with::<Sha512, _>(rng, public_key, label, message),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// Decrypt `ciphertext` with `private_key`, `OperationError` unless it
+/// was encrypted under `label`.
+pub(crate) fn decrypt<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  private_key: &RsaPrivateKey,
+  hash: &str,
+  label: &[u8],
+  ciphertext: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  match hash {
+    "SHA-1" => decrypt_with::<Sha1, _>(rng, private_key, label, ciphertext),
+    "SHA-256" => decrypt_with::<Sha256, _>(rng, private_key, label, ciphertext),
+    "SHA-384" => decrypt_with::<Sha384, _>(rng, private_key, label, ciphertext),
+    "SHA-512" => decrypt_with::<Sha512, _>(rng, private_key, label, ciphertext),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// XOR `out` with the MGF1 mask of `seed`.
+fn mgf1_xor<D: Digest>(out: &mut [u8], seed: &[u8]) {
+  for (counter, chunk) in out.chunks_mut(D::output_size()).enumerate() {
+    let mut digest = D::new();
+    digest.update(seed);
+    digest.update((counter as u32).to_be_bytes());
+    for (byte, mask) in chunk.iter_mut().zip(digest.finalize()) {
+      *byte ^= mask;
+    }
+  }
+}
+
+fn encrypt_with<D: Digest, R: RngCore + CryptoRng>(
+  rng: &mut R,
+  public_key: &RsaPublicKey,
+  label: &[u8],
+  message: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let k = public_key.size();
+  let h_len = D::output_size();
+  if message.len() + 2 * h_len + 2 > k {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  // EM = 0x00 || maskedSeed || maskedDB, where
+  // DB = lHash || PS || 0x01 || M.
+  let mut em = vec![0; k];
+  let (seed, db) = em[1..].split_at_mut(h_len);
+  rng.fill_bytes(seed);
+  let start = db.len() - message.len();
+  db[..h_len].copy_from_slice(&D::digest(label));
+  db[start - 1] = 1;
+  db[start..].copy_from_slice(message);
+
+  mgf1_xor::<D>(db, seed);
+  mgf1_xor::<D>(seed, db);
+
+  let c = internals::encrypt(public_key, &BigUint::from_bytes_be(&em));
+  Ok(i2osp(&c, k))
+}
+
+fn decrypt_with<D: Digest, R: RngCore + CryptoRng>(
+  rng: &mut R,
+  private_key: &RsaPrivateKey,
+  label: &[u8],
+  ciphertext: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let k = private_key.size();
+  let h_len = D::output_size();
+  if ciphertext.len() != k || k < 2 * h_len + 2 {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  let c = BigUint::from_bytes_be(ciphertext);
+  if &c >= private_key.n() {
+    return Err(WebCryptoError::OperationError);
+  }
+  // Blinded, so the timing does not depend on the private exponent.
+  let m = internals::decrypt(Some(rng), private_key, &c)
+    .map_err(|_| WebCryptoError::OperationError)?;
+  let mut em = i2osp(&m, k);
+
+  let (y, rest) = em.split_at_mut(1);
+  let (seed, db) = rest.split_at_mut(h_len);
+  mgf1_xor::<D>(seed, db);
+  mgf1_xor::<D>(db, seed);
+
+  // Every check runs in constant time, so that a failure does not tell
+  // which part of the padding was wrong.
+  let mut valid = y[0].ct_eq(&0) & db[..h_len].ct_eq(&D::digest(label)[..]);
+  let mut looking = Choice::from(1);
+  let mut index = 0u32;
+  for (i, byte) in db[h_len..].iter().enumerate() {
+    let zero = byte.ct_eq(&0);
+    let one = byte.ct_eq(&1);
+    index.conditional_assign(&(i as u32), looking & one);
+    valid &= !(looking & !zero & !one);
+    looking &= zero;
+  }
+  valid &= !looking;
+
+  if !bool::from(valid) {
+    return Err(WebCryptoError::OperationError);
+  }
+  Ok(db[h_len + index as usize + 1..].to_vec())
+}
@@ -0,0 +1,158 @@
+// Octet key pairs (RFC 8037): curves whose keys are plain byte strings,
+// like Ed25519, Ed448 and X448. Private keys are stored as their raw
+// bytes and public keys as the encoded point, so "raw" export needs no
+// conversion.
+//
+// PKCS#8 and SPKI encodings (RFC 8410) have a fixed layout for each
+// curve, and are built and checked as a prefix followed by the key.
+
+use rand::CryptoRng;
+use rand::RngCore;
+
+use super::ed25519;
+#[cfg(feature = "ed448")]
+use super::ed448;
+#[cfg(feature = "x448")]
+use super::x448;
+use crate::WebCryptoError;
+
+/// The last byte of the object identifier 1.3.101.x of `name`, and the
+/// length of its keys.
+fn curve(name: &str) -> Result<(u8, usize), WebCryptoError> {
+  match name {
+    #[cfg(feature = "x448")]
+    "X448" => Ok((111, x448::KEY_LENGTH)),
+    "Ed25519" => Ok((112, ed25519::KEY_LENGTH)),
+    #[cfg(feature = "ed448")]
+    "Ed448" => Ok((113, ed448::KEY_LENGTH)),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// Whether `name` is an OKP algorithm supported by this build.
+pub(crate) fn is_okp(name: &str) -> bool {
+  curve(name).is_ok()
+}
+
+/// The length of the keys of `name`.
+pub(crate) fn key_length(name: &str) -> Result<usize, WebCryptoError> {
+  Ok(curve(name)?.1)
+}
+
+/// Generate a key pair of `name`, returning the private and the public
+/// key.
+pub(crate) fn generate<R: RngCore + CryptoRng>(
+  name: &str,
+  rng: &mut R,
+) -> Result<(Vec<u8>, Vec<u8>), WebCryptoError> {
+  match name {
+    #[cfg(feature = "x448")]
+    "X448" => Ok(x448::generate(rng)),
+    "Ed25519" => Ok(ed25519::generate(rng)),
+    #[cfg(feature = "ed448")]
+    "Ed448" => Ok(ed448::generate(rng)),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The public key of the private key `private_key`.
+pub(crate) fn public_key(
+  name: &str,
+  private_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  match name {
+    #[cfg(feature = "x448")]
+    "X448" => x448::public_key(private_key),
+    "Ed25519" => ed25519::public_key(private_key),
+    #[cfg(feature = "ed448")]
+    "Ed448" => ed448::public_key(private_key),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// Check that `key_data` is a valid public key of `name`. Any string of
+/// the right length is an X448 public key, and Ed448 points are only
+/// decoded when verifying.
+pub(crate) fn check_public_key(
+  name: &str,
+  key_data: &[u8],
+) -> Result<(), WebCryptoError> {
+  if key_data.len() != key_length(name)? {
+    return Err(WebCryptoError::DataError);
+  }
+
+  match name {
+    "Ed25519" => ed25519::check_public_key(key_data),
+    _ => Ok(()),
+  }
+}
+
+fn algorithm_identifier(oid: u8) -> [u8; 7] {
+  [0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, oid]
+}
+
+fn spki_prefix(oid: u8, length: usize) -> Vec<u8> {
+  let mut prefix = vec![0x30, (7 + 2 + 1 + length) as u8];
+  prefix.extend_from_slice(&algorithm_identifier(oid));
+  prefix.extend_from_slice(&[0x03, (1 + length) as u8, 0x00]);
+  prefix
+}
+
+fn pkcs8_prefix(oid: u8, length: usize) -> Vec<u8> {
+  let mut prefix = vec![0x30, (3 + 7 + 4 + length) as u8, 0x02, 0x01, 0x00];
+  prefix.extend_from_slice(&algorithm_identifier(oid));
+  prefix.extend_from_slice(&[0x04, (2 + length) as u8, 0x04, length as u8]);
+  prefix
+}
+
+fn strip<'a>(
+  prefix: &[u8],
+  length: usize,
+  data: &'a [u8],
+) -> Result<&'a [u8], WebCryptoError> {
+  match data.strip_prefix(prefix) {
+    Some(key) if key.len() == length => Ok(key),
+    _ => Err(WebCryptoError::DataError),
+  }
+}
+
+pub(crate) fn export_spki(
+  name: &str,
+  public_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let (oid, length) = curve(name)?;
+  let mut spki = spki_prefix(oid, length);
+  spki.extend_from_slice(public_key);
+  Ok(spki)
+}
+
+pub(crate) fn export_pkcs8(
+  name: &str,
+  private_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let (oid, length) = curve(name)?;
+  let mut pkcs8 = pkcs8_prefix(oid, length);
+  pkcs8.extend_from_slice(private_key);
+  Ok(pkcs8)
+}
+
+/// The public key of a SPKI structure for `name`.
+pub(crate) fn import_spki(
+  name: &str,
+  spki: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let (oid, length) = curve(name)?;
+  let public_key = strip(&spki_prefix(oid, length), length, spki)?;
+  check_public_key(name, public_key)?;
+  Ok(public_key.to_vec())
+}
+
+/// The private key of a PKCS#8 structure for `name`. Keys embedding their
+/// public key (PKCS#8 v2) are not supported.
+pub(crate) fn import_pkcs8(
+  name: &str,
+  pkcs8: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let (oid, length) = curve(name)?;
+  Ok(strip(&pkcs8_prefix(oid, length), length, pkcs8)?.to_vec())
+}
@@ -0,0 +1,80 @@
+use der::pem::LineEnding;
+
+use rsa::pkcs1::FromRsaPrivateKey;
+use rsa::pkcs1::FromRsaPublicKey;
+use rsa::pkcs8::ToPrivateKey;
+use rsa::pkcs8::ToPublicKey;
+use rsa::RsaPrivateKey;
+use rsa::RsaPublicKey;
+
+use super::ec;
+use super::ImportParams;
+use super::KeyType;
+use crate::WebCryptoError;
+
+/// Decode a PEM document into the `import_key` format and DER of the key
+/// it holds.
+///
+/// "PRIVATE KEY" and "PUBLIC KEY" are passed through as "pkcs8" and
+/// "spki". PKCS#1 ("RSA PRIVATE KEY", "RSA PUBLIC KEY") and SEC1
+/// ("EC PRIVATE KEY") keys are converted to PKCS#8 or SPKI; SEC1 keys
+/// are read on the curve of `algorithm`.
+pub(crate) fn decode(
+  pem: &str,
+  algorithm: &ImportParams,
+) -> Result<(&'static str, Vec<u8>), WebCryptoError> {
+  let (label, der) = der::pem::decode_vec(pem.trim().as_bytes())
+    .map_err(|_| WebCryptoError::DataError)?;
+
+  match label {
+    "PRIVATE KEY" => Ok(("pkcs8", der)),
+    "PUBLIC KEY" => Ok(("spki", der)),
+    "RSA PRIVATE KEY" => {
+      let private_key = RsaPrivateKey::from_pkcs1_der(&der)
+        .map_err(|_| WebCryptoError::DataError)?;
+      let pkcs8 = private_key
+        .to_pkcs8_der()
+        .map_err(|_| WebCryptoError::DataError)?;
+
+      Ok(("pkcs8", pkcs8.as_ref().to_vec()))
+    }
+    "RSA PUBLIC KEY" => {
+      let public_key = RsaPublicKey::from_pkcs1_der(&der)
+        .map_err(|_| WebCryptoError::DataError)?;
+      let spki = public_key
+        .to_public_key_der()
+        .map_err(|_| WebCryptoError::DataError)?;
+
+      Ok(("spki", spki.as_ref().to_vec()))
+    }
+    "EC PRIVATE KEY" => match algorithm {
+      ImportParams::EcKeyImportParams(ec_alg) => {
+        let private_key = ec::import_sec1(ec_alg.named_curve, &der)?;
+
+        Ok(("pkcs8", ec::export_pkcs8(ec_alg.named_curve, &private_key)?))
+      }
+      _ => Err(WebCryptoError::DataError),
+    },
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// The export format and PEM label of a key of `type_`.
+pub(crate) fn format(
+  type_: &KeyType,
+) -> Result<(&'static str, &'static str), WebCryptoError> {
+  match type_ {
+    KeyType::Private => Ok(("pkcs8", "PRIVATE KEY")),
+    KeyType::Public => Ok(("spki", "PUBLIC KEY")),
+    KeyType::Secret => Err(WebCryptoError::InvalidAccessError),
+  }
+}
+
+/// Encode `der` as a PEM document with `label`.
+pub(crate) fn encode(
+  label: &str,
+  der: &[u8],
+) -> Result<String, WebCryptoError> {
+  der::pem::encode_string(label, LineEnding::LF, der)
+    .map_err(|_| WebCryptoError::DataError)
+}
@@ -0,0 +1,124 @@
+use p12_keystore::KeyStore;
+use p12_keystore::KeyStoreEntry;
+use p12_keystore::PrivateKeyChain;
+
+use sha1::Digest;
+use sha1::Sha1;
+
+use super::CryptoKey;
+use crate::WebCryptoError;
+
+/// An X.509 certificate from a PKCS#12 bundle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Certificate {
+  /// The DER encoded certificate.
+  pub der: Vec<u8>,
+  pub subject: String,
+  pub issuer: String,
+}
+
+impl Certificate {
+  /// Parse a DER encoded X.509 certificate.
+  pub fn from_der(der: &[u8]) -> Result<Self, WebCryptoError> {
+    let certificate = p12_keystore::Certificate::from_der(der)
+      .map_err(|_| WebCryptoError::DataError)?;
+    Ok(Certificate::from(&certificate))
+  }
+}
+
+impl From<&p12_keystore::Certificate> for Certificate {
+  fn from(certificate: &p12_keystore::Certificate) -> Self {
+    Certificate {
+      der: certificate.as_der().to_vec(),
+      subject: certificate.subject().to_string(),
+      issuer: certificate.issuer().to_string(),
+    }
+  }
+}
+
+/// A private key imported from a PKCS#12 bundle.
+pub struct Pkcs12Key<H> {
+  /// The bag's friendly name, or the subject of its certificate.
+  pub alias: String,
+  pub key: CryptoKey<H>,
+  /// The certificate chain of the key, leaf first.
+  pub chain: Vec<Certificate>,
+}
+
+/// The contents of a PKCS#12 bundle.
+pub struct Pkcs12<H> {
+  pub keys: Vec<Pkcs12Key<H>>,
+  /// Trusted certificates that do not belong to a private key.
+  pub certificates: Vec<Certificate>,
+}
+
+/// A private key entry of a decrypted bundle: its alias, PKCS#8 DER
+/// and certificate chain.
+pub(crate) type Entry = (String, Vec<u8>, Vec<Certificate>);
+
+/// Verify the MAC of a PKCS#12 bundle and decrypt its contents.
+pub(crate) fn decode(
+  data: &[u8],
+  password: &str,
+) -> Result<(Vec<Entry>, Vec<Certificate>), WebCryptoError> {
+  // DataError, also raised for a wrong password.
+  let keystore = KeyStore::from_pkcs12(data, password)
+    .map_err(|_| WebCryptoError::DataError)?;
+
+  let mut keys = vec![];
+  let mut certificates = vec![];
+  for (alias, entry) in keystore.entries() {
+    match entry {
+      KeyStoreEntry::PrivateKeyChain(chain) => keys.push((
+        alias.clone(),
+        chain.key().to_vec(),
+        chain.chain().iter().map(Certificate::from).collect(),
+      )),
+      KeyStoreEntry::Certificate(certificate) => {
+        certificates.push(Certificate::from(certificate))
+      }
+    }
+  }
+
+  Ok((keys, certificates))
+}
+
+/// Bundle the PKCS#8 private key `pkcs8` and its certificate `chain`
+/// into a PKCS#12 blob, using PBES2 with AES-256 and an HMAC-SHA256 MAC.
+pub(crate) fn encode(
+  alias: &str,
+  pkcs8: &[u8],
+  chain: &[Certificate],
+  password: &str,
+) -> Result<Vec<u8>, WebCryptoError> {
+  // The key is matched with its leaf certificate by `localKeyId`, so a
+  // key without a certificate could not be imported back.
+  let leaf = match chain.first() {
+    Some(leaf) => leaf,
+    None => {
+      return Err(WebCryptoError::DataError);
+    }
+  };
+  let local_key_id = Sha1::digest(&leaf.der);
+
+  let chain = chain
+    .iter()
+    .map(|certificate| p12_keystore::Certificate::from_der(&certificate.der))
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|_| WebCryptoError::DataError)?;
+
+  let mut keystore = KeyStore::new();
+  keystore.add_entry(
+    alias,
+    KeyStoreEntry::PrivateKeyChain(PrivateKeyChain::new(
+      pkcs8,
+      local_key_id,
+      chain,
+    )),
+  );
+
+  keystore
+    .writer(password)
+    .write()
+    .map_err(|_| WebCryptoError::OperationError)
+}
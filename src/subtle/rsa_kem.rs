@@ -0,0 +1,77 @@
+use rand::CryptoRng;
+use rand::RngCore;
+
+use rsa::internals;
+use rsa::BigUint;
+use rsa::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use rsa::RsaPublicKey;
+
+use super::cms::x963_kdf;
+use crate::WebCryptoError;
+
+/// Encode `value` big-endian in the `length` bytes of the modulus.
+pub(super) fn i2osp(value: &BigUint, length: usize) -> Vec<u8> {
+  let bytes = value.to_bytes_be();
+  let mut padded = vec![0; length - bytes.len()];
+  padded.extend(bytes);
+  padded
+}
+
+/// RSA-KEM (RFC 5990): encrypt a random integer below the modulus with
+/// `public_key` and derive `length` bytes from it with KDF2 using `hash`.
+/// Returns the shared secret and the ciphertext.
+pub(crate) fn encapsulate<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  public_key: &RsaPublicKey,
+  hash: &str,
+  length: usize,
+) -> Result<(Vec<u8>, Vec<u8>), WebCryptoError> {
+  let n = public_key.n();
+  let n_len = public_key.size();
+
+  // 1. Pick z uniformly in [0, n) by rejection sampling.
+  let z = loop {
+    let mut bytes = vec![0; n_len];
+    rng.fill_bytes(&mut bytes);
+    bytes[0] &= 0xff >> (n_len * 8 - n.bits());
+
+    let z = BigUint::from_bytes_be(&bytes);
+    if &z < n {
+      break z;
+    }
+  };
+
+  // 2.
+  let ciphertext = internals::encrypt(public_key, &z);
+
+  // 3.
+  let shared_key = x963_kdf(hash, &i2osp(&z, n_len), &[], length)?;
+
+  Ok((shared_key, i2osp(&ciphertext, n_len)))
+}
+
+/// Recover the shared secret of `ciphertext` with `private_key`.
+pub(crate) fn decapsulate<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  private_key: &RsaPrivateKey,
+  hash: &str,
+  ciphertext: &[u8],
+  length: usize,
+) -> Result<Vec<u8>, WebCryptoError> {
+  let n_len = private_key.size();
+  if ciphertext.len() != n_len {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  let c = BigUint::from_bytes_be(ciphertext);
+  if &c >= private_key.n() {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  // Blinded, so the timing does not depend on the private exponent.
+  let z = internals::decrypt(Some(rng), private_key, &c)
+    .map_err(|_| WebCryptoError::OperationError)?;
+
+  x963_kdf(hash, &i2osp(&z, n_len), &[], length)
+}
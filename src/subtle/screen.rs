@@ -0,0 +1,231 @@
+use std::fmt;
+use std::sync::Mutex;
+
+use rsa::BigUint;
+
+use sha1::Digest;
+use sha1::Sha1;
+
+use super::Algorithm;
+use super::ImportedKey;
+
+/// Primes whose residues identify ROCA moduli (CVE-2017-15361), as in the
+/// detector of the original paper.
+const ROCA_PRIMES: &[u32] = &[
+  3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73,
+  79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157,
+  163, 167,
+];
+
+/// Moduli are trial divided by the primes below this bound.
+const SMALL_FACTOR_BOUND: u32 = 1 << 16;
+
+/// Why an RSA key was rejected by an `RsaScreen` or the RSA policy of a
+/// context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeakRsaKey {
+  /// The modulus has the structure of keys generated by the vulnerable
+  /// Infineon library (ROCA, CVE-2017-15361) and can be factored.
+  Roca,
+  /// The modulus has a prime factor below 2^16.
+  SmallFactor { factor: u32 },
+  /// The modulus shares a prime with another modulus screened in this
+  /// session, so both can be factored.
+  SharedPrime,
+  /// The fingerprint of the modulus is on the blocklist, eg. one of the
+  /// keys generated by the broken Debian OpenSSL (CVE-2008-0166).
+  Blocklisted,
+  /// The modulus is shorter than the context requires, see
+  /// `SubtleCrypto::set_min_rsa_modulus_length`.
+  SmallModulus { modulus_length: usize },
+  /// The public exponent is below 65537 while the context requires 65537,
+  /// see `SubtleCrypto::set_require_f4_exponent`.
+  SmallExponent { exponent: u64 },
+}
+
+impl fmt::Display for WeakRsaKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      WeakRsaKey::Roca => f.write_str("the modulus is a ROCA modulus"),
+      WeakRsaKey::SmallFactor { factor } => {
+        write!(f, "the modulus has the small factor {}", factor)
+      }
+      WeakRsaKey::SharedPrime => {
+        f.write_str("the modulus shares a prime with another key")
+      }
+      WeakRsaKey::Blocklisted => f.write_str("the modulus is blocklisted"),
+      WeakRsaKey::SmallModulus { modulus_length } => {
+        write!(f, "the modulus is only {} bits long", modulus_length)
+      }
+      WeakRsaKey::SmallExponent { exponent } => {
+        write!(f, "the public exponent is only {}", exponent)
+      }
+    }
+  }
+}
+
+/// Why an EC private key was rejected by `check_ec_private_key`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WeakEcKey {
+  /// The scalar is below 2^64.
+  SmallScalar,
+  /// The scalar repeats a pattern of at most 8 bytes, eg. all zeros
+  /// but the last byte or `0xabab...`.
+  Repeating,
+  /// The bytes of the scalar count up or down, eg. `0x0102...20`.
+  Sequential,
+}
+
+/// Check an EC private scalar for structure no generated key has: keys
+/// like these were picked by hand or copied from test vectors.
+pub fn check_ec_private_key(scalar: &[u8]) -> Result<(), WeakEcKey> {
+  let significant = scalar.iter().skip_while(|&&byte| byte == 0).count();
+  if significant <= 8 {
+    return Err(WeakEcKey::SmallScalar);
+  }
+
+  if (1..=8)
+    .any(|period| scalar.iter().zip(&scalar[period..]).all(|(a, b)| a == b))
+  {
+    return Err(WeakEcKey::Repeating);
+  }
+
+  let step = scalar[1].wrapping_sub(scalar[0]);
+  if scalar
+    .windows(2)
+    .all(|pair| pair[1].wrapping_sub(pair[0]) == step)
+  {
+    return Err(WeakEcKey::Sequential);
+  }
+
+  Ok(())
+}
+
+/// Screens RSA moduli for trivially factorable and known-compromised
+/// keys.
+///
+/// Each screened modulus is kept in a session cache, so keys generated
+/// with a broken RNG and sharing a prime are caught once both have been
+/// seen.
+#[derive(Debug, Default)]
+pub struct RsaScreen {
+  moduli: Mutex<Vec<BigUint>>,
+  blocklist: Vec<String>,
+}
+
+/// The fingerprint of `modulus` in the format of the Debian
+/// openssl-blacklist: the last 20 hex digits of the SHA-1 of
+/// "Modulus=<uppercase hex>\n".
+fn fingerprint(modulus: &[u8]) -> String {
+  let mut line = String::from("Modulus=");
+  for byte in modulus.iter().skip_while(|&&byte| byte == 0) {
+    line.push_str(&format!("{:02X}", byte));
+  }
+  line.push('\n');
+
+  let digest = Sha1::digest(line.as_bytes());
+  digest[10..]
+    .iter()
+    .map(|byte| format!("{:02x}", byte))
+    .collect()
+}
+
+/// `n` mod `p`, with `n` in big-endian bytes.
+fn rem(n: &[u8], p: u32) -> u32 {
+  n.iter().fold(0, |r, &byte| {
+    ((r as u64 * 256 + byte as u64) % p as u64) as u32
+  })
+}
+
+/// Whether `n` mod `p` is a power of 65537, which holds for every prime
+/// of `ROCA_PRIMES` only for ROCA moduli.
+fn is_roca_residue(n: &[u8], p: u32) -> bool {
+  let residue = rem(n, p);
+  let generator = 65537 % p;
+  let mut power = 1;
+  loop {
+    if power == residue {
+      return true;
+    }
+    power = power * generator % p;
+    if power == 1 {
+      return false;
+    }
+  }
+}
+
+fn is_prime(p: u32) -> bool {
+  p >= 2
+    && (2..)
+      .take_while(|d| d * d <= p)
+      .all(|d| !p.is_multiple_of(d))
+}
+
+fn gcd(mut a: BigUint, mut b: BigUint) -> BigUint {
+  let zero = BigUint::default();
+  while b != zero {
+    let r = &a % &b;
+    a = b;
+    b = r;
+  }
+  a
+}
+
+impl RsaScreen {
+  pub fn new() -> Self {
+    RsaScreen::default()
+  }
+
+  /// Reject moduli with `fingerprint`, a line of a Debian
+  /// openssl-blacklist file.
+  pub fn block(mut self, fingerprint: &str) -> Self {
+    self.blocklist.push(fingerprint.trim().to_ascii_lowercase());
+    self
+  }
+
+  /// Check the big-endian `modulus`, adding it to the session cache.
+  pub fn check(&self, modulus: &[u8]) -> Result<(), WeakRsaKey> {
+    if !self.blocklist.is_empty()
+      && self.blocklist.contains(&fingerprint(modulus))
+    {
+      return Err(WeakRsaKey::Blocklisted);
+    }
+
+    if ROCA_PRIMES.iter().all(|&p| is_roca_residue(modulus, p)) {
+      return Err(WeakRsaKey::Roca);
+    }
+
+    if let Some(factor) =
+      (2..SMALL_FACTOR_BOUND).find(|&p| is_prime(p) && rem(modulus, p) == 0)
+    {
+      return Err(WeakRsaKey::SmallFactor { factor });
+    }
+
+    let n = BigUint::from_bytes_be(modulus);
+    let one = BigUint::from(1u8);
+    let mut moduli = self.moduli.lock().unwrap();
+    // The same key imported twice, eg. its public and private halves, is
+    // not a shared prime.
+    for other in moduli.iter().filter(|&other| other != &n) {
+      if gcd(n.clone(), other.clone()) != one {
+        return Err(WeakRsaKey::SharedPrime);
+      }
+    }
+    if !moduli.contains(&n) {
+      moduli.push(n);
+    }
+
+    Ok(())
+  }
+
+  /// Check an imported key, accepting keys of other algorithms.
+  pub fn check_key(&self, key: &ImportedKey) -> Result<(), WeakRsaKey> {
+    match (key.algorithm, &key.modulus) {
+      (
+        Algorithm::RsaKeyAlgorithm(_) | Algorithm::RsaHashedKeyAlgorithm(_),
+        Some(modulus),
+      ) => self.check(modulus),
+      _ => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,97 @@
+use std::io;
+use std::io::Read;
+
+use super::EncryptParams;
+use crate::WebCryptoError;
+
+/// The nonces of the STREAM construction (Hoang, Reyhanitabar, Rogaway
+/// and Vizár, "Online Authenticated-Encryption and its Nonce-Reuse
+/// Misuse-Resistance"), for encrypting a long message in chunks with
+/// bounded memory: a prefix, a 32-bit big-endian chunk counter and a
+/// byte set on the last chunk.
+///
+/// Each chunk is authenticated on its own, and the counter and the last
+/// chunk flag detect reordered, dropped and truncated chunks. The prefix
+/// has to be unique for each stream under a key.
+#[derive(Debug)]
+pub struct StreamNonces {
+  prefix: Vec<u8>,
+  counter: Option<u32>,
+  finished: bool,
+}
+
+impl StreamNonces {
+  /// The nonces of a stream with `prefix`, eg. 7 bytes for the 96-bit
+  /// nonces of AES-GCM.
+  pub fn new(prefix: &[u8]) -> Self {
+    StreamNonces {
+      prefix: prefix.to_vec(),
+      counter: Some(0),
+      finished: false,
+    }
+  }
+
+  pub fn prefix(&self) -> &[u8] {
+    &self.prefix
+  }
+
+  /// The length in bytes of the nonces.
+  pub fn nonce_length(&self) -> usize {
+    self.prefix.len() + 5
+  }
+
+  /// Whether the last chunk was processed. A stream is only complete
+  /// once it is.
+  pub fn is_finished(&self) -> bool {
+    self.finished
+  }
+
+  /// The nonce of the next chunk, or an error once the last chunk was
+  /// processed or the counter is exhausted.
+  pub(crate) fn next(&mut self, last: bool) -> Result<Vec<u8>, WebCryptoError> {
+    if self.finished {
+      return Err(WebCryptoError::OperationError);
+    }
+    let counter = self.counter.ok_or(WebCryptoError::OperationError)?;
+    self.counter = counter.checked_add(1);
+    self.finished = last;
+
+    let mut nonce = self.prefix.clone();
+    nonce.extend_from_slice(&counter.to_be_bytes());
+    nonce.push(last as u8);
+    Ok(nonce)
+  }
+}
+
+/// The bytes each chunk of `algorithm` grows by when encrypted.
+pub(crate) fn overhead(
+  algorithm: &EncryptParams,
+) -> Result<usize, WebCryptoError> {
+  match algorithm {
+    EncryptParams::AesGcmParams(params) => {
+      let commitment = if params.key_commitment { 32 } else { 0 };
+      Ok(params.tag_length.unwrap_or(128) / 8 + commitment)
+    }
+    EncryptParams::ChaCha20Poly1305Params(_)
+    | EncryptParams::XChaCha20Poly1305Params(_) => Ok(16),
+    _ => Err(WebCryptoError::NotSupportedError),
+  }
+}
+
+/// Read until `buffer` is full or `reader` ends, returning the number of
+/// bytes read.
+pub(crate) fn read_full<Rd: Read>(
+  reader: &mut Rd,
+  buffer: &mut [u8],
+) -> Result<usize, WebCryptoError> {
+  let mut filled = 0;
+  while filled < buffer.len() {
+    match reader.read(&mut buffer[filled..]) {
+      Ok(0) => break,
+      Ok(read) => filled += read,
+      Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+      Err(_) => return Err(WebCryptoError::OperationError),
+    }
+  }
+  Ok(filled)
+}
@@ -0,0 +1,58 @@
+use super::multipart::Hasher;
+use crate::WebCryptoError;
+
+/// A running hash of the messages of a protocol, eg. a handshake, to
+/// bind keys derived from it to everything both parties sent: pass its
+/// `digest` as the HKDF info.
+///
+/// Each message is absorbed under a label naming it. The label and the
+/// message are each prefixed with their length as a big-endian `u64`, so
+/// no two different sequences of messages hash alike.
+///
+/// ```
+/// use webcrypto::subtle::TranscriptHash;
+///
+/// let mut transcript = TranscriptHash::new("SHA-256").unwrap();
+/// transcript.absorb("client hello", b"...");
+/// transcript.absorb("server hello", b"...");
+/// let info = transcript.digest();
+/// assert_eq!(info.len(), 32);
+/// ```
+#[derive(Clone)]
+pub struct TranscriptHash {
+  hash: &'static str,
+  hasher: Hasher,
+}
+
+impl TranscriptHash {
+  /// A transcript hashed with `hash`, "SHA-256", "SHA-384" or "SHA-512".
+  pub fn new(hash: &'static str) -> Result<Self, WebCryptoError> {
+    if !matches!(hash, "SHA-256" | "SHA-384" | "SHA-512") {
+      return Err(WebCryptoError::NotSupportedError);
+    }
+
+    Ok(TranscriptHash {
+      hash,
+      hasher: Hasher::new(hash)?,
+    })
+  }
+
+  /// The name of the hash, eg. "SHA-256".
+  pub fn hash(&self) -> &'static str {
+    self.hash
+  }
+
+  /// Append the message `data` under `label`, eg. "server hello".
+  pub fn absorb(&mut self, label: &str, data: &[u8]) {
+    for bytes in [label.as_bytes(), data] {
+      self.hasher.update(&(bytes.len() as u64).to_be_bytes());
+      self.hasher.update(bytes);
+    }
+  }
+
+  /// The hash of the messages absorbed so far. The transcript can keep
+  /// absorbing messages afterwards.
+  pub fn digest(&self) -> Vec<u8> {
+    self.hasher.clone().finalize()
+  }
+}
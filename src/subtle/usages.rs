@@ -0,0 +1,118 @@
+//! The key usages of each algorithm and operation, as data, for
+//! embedders building permission prompts or bindings to present the
+//! options `SubtleCrypto` accepts.
+
+use super::AlgorithmName;
+use super::KeyType;
+use super::KeyUsage;
+
+/// An operation of `SubtleCrypto` using a key.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+  Encrypt,
+  Decrypt,
+  Sign,
+  Verify,
+  DeriveBits,
+  DeriveKey,
+  WrapKey,
+  UnwrapKey,
+  EncapsulateBits,
+  DecapsulateBits,
+  /// `SubtleCrypto::seal`, encrypting an envelope.
+  Seal,
+  /// `SubtleCrypto::open`, decrypting an envelope.
+  Open,
+  /// `SubtleCrypto::blind_index`, a keyed hash of a value.
+  BlindIndex,
+  /// `SubtleCrypto::derive_record_key` and the record encryption built
+  /// on it.
+  DeriveRecordKey,
+}
+
+impl Operation {
+  pub const ALL: &'static [Operation] = &[
+    Operation::Encrypt,
+    Operation::Decrypt,
+    Operation::Sign,
+    Operation::Verify,
+    Operation::DeriveBits,
+    Operation::DeriveKey,
+    Operation::WrapKey,
+    Operation::UnwrapKey,
+    Operation::EncapsulateBits,
+    Operation::DecapsulateBits,
+    Operation::Seal,
+    Operation::Open,
+    Operation::BlindIndex,
+    Operation::DeriveRecordKey,
+  ];
+
+  /// The usage the key of the operation must have.
+  pub fn required_usage(&self) -> KeyUsage {
+    match self {
+      Operation::Encrypt | Operation::Seal => KeyUsage::Encrypt,
+      Operation::Decrypt | Operation::Open => KeyUsage::Decrypt,
+      Operation::Sign | Operation::BlindIndex => KeyUsage::Sign,
+      Operation::Verify => KeyUsage::Verify,
+      Operation::DeriveBits => KeyUsage::DeriveBits,
+      Operation::DeriveKey | Operation::DeriveRecordKey => KeyUsage::DeriveKey,
+      Operation::WrapKey => KeyUsage::WrapKey,
+      Operation::UnwrapKey => KeyUsage::UnwrapKey,
+      Operation::EncapsulateBits => KeyUsage::EncapsulateBits,
+      Operation::DecapsulateBits => KeyUsage::DecapsulateBits,
+    }
+  }
+}
+
+/// The usages a key of `algorithm` and `type_` may have, empty when there
+/// are no such keys, eg. secret ECDSA keys or keys of a digest.
+///
+/// Generating a key pair takes the usages of both keys: each key gets
+/// those of its type.
+pub fn permitted_usages(
+  algorithm: AlgorithmName,
+  type_: KeyType,
+) -> &'static [KeyUsage] {
+  use AlgorithmName::*;
+
+  const SIGN: &[KeyUsage] = &[KeyUsage::Sign];
+  const VERIFY: &[KeyUsage] = &[KeyUsage::Verify];
+  const DERIVE: &[KeyUsage] = &[KeyUsage::DeriveKey, KeyUsage::DeriveBits];
+  const CIPHER: &[KeyUsage] = &[
+    KeyUsage::Encrypt,
+    KeyUsage::Decrypt,
+    KeyUsage::WrapKey,
+    KeyUsage::UnwrapKey,
+  ];
+
+  match (algorithm, type_) {
+    (RsassaPkcs1V1_5 | RsaPss | Ecdsa | Ed25519 | Ed448, KeyType::Private) => {
+      SIGN
+    }
+    (RsassaPkcs1V1_5 | RsaPss | Ecdsa | Ed25519 | Ed448, KeyType::Public) => {
+      VERIFY
+    }
+    (RsaOaep, KeyType::Private) => &[KeyUsage::Decrypt, KeyUsage::UnwrapKey],
+    (RsaOaep, KeyType::Public) => &[KeyUsage::Encrypt, KeyUsage::WrapKey],
+    (RsaKem | XWing, KeyType::Private) => &[KeyUsage::DecapsulateBits],
+    (RsaKem | XWing, KeyType::Public) => &[KeyUsage::EncapsulateBits],
+    // The public key is only an argument of the derivation.
+    (Ecdh | X448 | Dh, KeyType::Private) => DERIVE,
+    (AesCtr | AesCbc | AesGcm, KeyType::Secret) => CIPHER,
+    (ChaCha20Poly1305 | XChaCha20Poly1305, KeyType::Secret) => CIPHER,
+    (AesKw | AesKwp, KeyType::Secret) => {
+      &[KeyUsage::WrapKey, KeyUsage::UnwrapKey]
+    }
+    (AesFf1, KeyType::Secret) => &[KeyUsage::Encrypt, KeyUsage::Decrypt],
+    (AesCmac | Hmac | Blake3, KeyType::Secret) => {
+      &[KeyUsage::Sign, KeyUsage::Verify]
+    }
+    (
+      Hkdf | Pbkdf2 | ConcatKdf | X963Kdf | Argon2id | Scrypt,
+      KeyType::Secret,
+    ) => DERIVE,
+    _ => &[],
+  }
+}
@@ -0,0 +1,150 @@
+// Verification with a fixed set of public keys, eg. the keys of a JWKS or
+// of trusted signers, from many threads at once. The keys are parsed
+// once when the pool is built, so verifying neither reads the key storage
+// nor parses the key, and needs no exclusive access to `SubtleCrypto`.
+
+use rand::rngs::OsRng;
+
+use rsa::PublicKey;
+use rsa::PublicKeyParts;
+use rsa::RsaPublicKey;
+
+use super::digest;
+use super::ec;
+use super::ed25519;
+#[cfg(feature = "ed448")]
+use super::ed448;
+use super::is_sign_downgrade;
+use super::pkcs1v15_padding;
+use super::prehash;
+use super::pss_padding;
+use super::pss_salt_length;
+use super::Algorithm;
+use super::AlgorithmIdentifer;
+use super::RsaPssParams;
+use super::SignParams;
+use crate::WebCryptoError;
+
+pub(crate) enum Verifier {
+  Rsa(RsaPublicKey),
+  Ecdsa(ec::VerifyingKey),
+  Ed25519(ed25519_dalek::VerifyingKey),
+  #[cfg(feature = "ed448")]
+  Ed448(Vec<u8>),
+}
+
+pub(crate) struct PooledKey {
+  pub(crate) algorithm: Algorithm,
+  pub(crate) verifier: Verifier,
+}
+
+/// Public keys parsed for verification, created by
+/// `SubtleCrypto::verifier_pool`. A pool is `Send` and `Sync`: share it
+/// between threads, eg. in an `Arc`, and call `verify` concurrently.
+pub struct VerifierPool {
+  pub(crate) keys: Vec<PooledKey>,
+  /// `SubtleCrypto::set_downgrade_protection` when the pool was built.
+  pub(crate) downgrade_protection: bool,
+}
+
+impl VerifierPool {
+  /// The number of keys in the pool.
+  pub fn len(&self) -> usize {
+    self.keys.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.keys.is_empty()
+  }
+
+  /// Verify `signature` over `data` with the key at `index`, in the
+  /// order the keys were given to `SubtleCrypto::verifier_pool`. Supports
+  /// RSASSA-PKCS1-v1_5, RSA-PSS, ECDSA, Ed25519 and Ed448, with the same
+  /// results as `SubtleCrypto::verify`.
+  pub fn verify(
+    &self,
+    index: usize,
+    mut algorithm: SignParams,
+    signature: &[u8],
+    data: &[u8],
+  ) -> Result<bool, WebCryptoError> {
+    algorithm.normalize();
+    let key = self.keys.get(index).ok_or(WebCryptoError::OperationError)?;
+    if self.downgrade_protection
+      && is_sign_downgrade(&algorithm, &key.algorithm)?
+    {
+      return Err(WebCryptoError::InvalidAccessError);
+    }
+
+    match (&key.verifier, algorithm) {
+      (
+        Verifier::Rsa(public_key),
+        SignParams::AlgorithmIdentifer(AlgorithmIdentifer {
+          name: "RSASSA-PKCS1-v1_5",
+        }),
+      ) if key.algorithm.name() == "RSASSA-PKCS1-v1_5" => {
+        let hash = prehash(&algorithm, &key.algorithm)?;
+        let padding = pkcs1v15_padding(hash)?;
+
+        Ok(
+          public_key
+            .verify(padding, &digest(hash, data)?, signature)
+            .is_ok(),
+        )
+      }
+      (
+        Verifier::Rsa(public_key),
+        SignParams::RsaPssParams(RsaPssParams {
+          name: "RSA-PSS",
+          salt_length,
+        }),
+      ) if key.algorithm.name() == "RSA-PSS" => {
+        let hash = prehash(&algorithm, &key.algorithm)?;
+        let salt_length =
+          pss_salt_length(hash, salt_length, public_key.n().bits())?;
+        // Verification does not use the random generator of the padding.
+        let padding = pss_padding(hash, OsRng, salt_length)?;
+
+        Ok(
+          public_key
+            .verify(padding, &digest(hash, data)?, signature)
+            .is_ok(),
+        )
+      }
+      (Verifier::Ecdsa(verifying_key), SignParams::EcdsaParams(params))
+        if key.algorithm.name() == params.name =>
+      {
+        if params.low_s && ec::is_high_s(verifying_key.named_curve(), signature)
+        {
+          return Ok(false);
+        }
+        Ok(verifying_key.verify(&digest(params.hash.name, data)?, signature))
+      }
+      #[cfg(feature = "ed448")]
+      (
+        Verifier::Ed448(public_key),
+        SignParams::AlgorithmIdentifer(AlgorithmIdentifer { name: "Ed448" }),
+      ) => ed448::verify(public_key, signature, data),
+      (
+        Verifier::Ed25519(public_key),
+        SignParams::AlgorithmIdentifer(AlgorithmIdentifer { name: "Ed25519" }),
+      ) => ed25519::verify(public_key, b"", signature, data),
+      (Verifier::Ed25519(public_key), SignParams::Ed25519Params(params))
+        if params.name == "Ed25519" =>
+      {
+        if params.prehash {
+          let prehash = ed25519::prehash(data);
+          ed25519::verify_prehashed(
+            public_key,
+            params.context,
+            signature,
+            prehash,
+          )
+        } else {
+          ed25519::verify(public_key, params.context, signature, data)
+        }
+      }
+      _ => Err(WebCryptoError::InvalidAccessError),
+    }
+  }
+}
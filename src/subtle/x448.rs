@@ -0,0 +1,55 @@
+use openssl::derive::Deriver;
+use openssl::pkey::Id;
+use openssl::pkey::PKey;
+use rand::CryptoRng;
+use rand::RngCore;
+
+use crate::WebCryptoError;
+
+// X448 key agreement (RFC 7748), on OpenSSL. Private keys are stored as
+// the 56-byte scalar and public keys as the u-coordinate.
+
+pub(crate) const KEY_LENGTH: usize = 56;
+
+/// The public key of `private_key`.
+pub(crate) fn public_key(
+  private_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  if private_key.len() != KEY_LENGTH {
+    return Err(WebCryptoError::DataError);
+  }
+  PKey::private_key_from_raw_bytes(private_key, Id::X448)
+    .and_then(|key| key.raw_public_key())
+    .map_err(|_| WebCryptoError::DataError)
+}
+
+/// Generate a key pair, returning the private and the public key.
+pub(crate) fn generate<R: RngCore + CryptoRng>(
+  rng: &mut R,
+) -> (Vec<u8>, Vec<u8>) {
+  let mut private_key = vec![0; KEY_LENGTH];
+  rng.fill_bytes(&mut private_key);
+  let public_key = public_key(&private_key).unwrap();
+  (private_key, public_key)
+}
+
+/// The shared secret of `private_key` and the other party's
+/// `public_key`.
+pub(crate) fn derive(
+  private_key: &[u8],
+  public_key: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  let private_key = PKey::private_key_from_raw_bytes(private_key, Id::X448)
+    .map_err(|_| WebCryptoError::OperationError)?;
+  let public_key = PKey::public_key_from_raw_bytes(public_key, Id::X448)
+    .map_err(|_| WebCryptoError::OperationError)?;
+
+  // OpenSSL fails on a low order public key, whose secret is zero and
+  // contributes nothing.
+  let mut deriver =
+    Deriver::new(&private_key).map_err(|_| WebCryptoError::OperationError)?;
+  deriver
+    .set_peer(&public_key)
+    .and_then(|_| deriver.derive_to_vec())
+    .map_err(|_| WebCryptoError::OperationError)
+}
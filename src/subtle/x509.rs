@@ -0,0 +1,549 @@
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use std::mem::discriminant;
+
+use der::asn1::BitString;
+use der::asn1::ObjectIdentifier;
+use der::asn1::OctetString;
+use der::oid::AssociatedOid;
+use der::Any;
+use der::Decode;
+use der::Header;
+use der::Reader;
+use der::Sequence;
+use der::SliceReader;
+use der::Tag;
+
+use x509_cert::crl::CertificateList;
+use x509_cert::ext::pkix::constraints::name::GeneralSubtree;
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::AuthorityKeyIdentifier;
+use x509_cert::ext::pkix::BasicConstraints;
+use x509_cert::ext::pkix::ExtendedKeyUsage;
+use x509_cert::ext::pkix::KeyUsage;
+use x509_cert::ext::pkix::NameConstraints;
+use x509_cert::ext::pkix::SubjectAltName;
+use x509_cert::ext::pkix::SubjectKeyIdentifier;
+use x509_cert::spki::AlgorithmIdentifierOwned;
+
+use super::cms::parse_certificate;
+use super::cms::signature_hash;
+use super::cms::verify_signature;
+use super::Certificate;
+use crate::WebCryptoError;
+
+/// Longest path, in issuers above the leaf, that is searched before
+/// giving up.
+const MAX_PATH_LENGTH: usize = 16;
+
+/// Most issuer signatures checked while searching a path, bounding the
+/// backtracking over certificates sharing a name.
+const MAX_SIGNATURES: usize = 64;
+
+/// The extensions processed when validating a path, which may be
+/// critical. The extended key usage is left to the caller.
+const UNDERSTOOD_EXTENSIONS: &[ObjectIdentifier] = &[
+  BasicConstraints::OID,
+  KeyUsage::OID,
+  NameConstraints::OID,
+  SubjectAltName::OID,
+  SubjectKeyIdentifier::OID,
+  AuthorityKeyIdentifier::OID,
+  ExtendedKeyUsage::OID,
+];
+
+const ID_PKIX_OCSP_BASIC: ObjectIdentifier =
+  ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.48.1.1");
+
+/// A SIGNED{} structure (RFC 5280), keeping the signed data as received.
+#[derive(Sequence)]
+struct Signed {
+  tbs: Any,
+  signature_algorithm: AlgorithmIdentifierOwned,
+  signature: BitString,
+}
+
+/// OCSPResponse (RFC 6960).
+#[derive(Sequence)]
+struct OcspResponse {
+  /// An ENUMERATED OCSPResponseStatus, 0 for "successful".
+  response_status: Any,
+  #[asn1(context_specific = "0", tag_mode = "EXPLICIT", optional = "true")]
+  response_bytes: Option<ResponseBytes>,
+}
+
+#[derive(Sequence)]
+struct ResponseBytes {
+  response_type: ObjectIdentifier,
+  response: OctetString,
+}
+
+/// BasicOCSPResponse (RFC 6960), keeping the signed response data as
+/// received.
+#[derive(Sequence)]
+struct BasicOcspResponse {
+  tbs_response_data: Any,
+  signature_algorithm: AlgorithmIdentifierOwned,
+  signature: BitString,
+  #[asn1(context_specific = "0", tag_mode = "EXPLICIT", optional = "true")]
+  certs: Option<Vec<x509_cert::Certificate>>,
+}
+
+/// The signature algorithm, the signed bytes and the signature of a
+/// signed object.
+pub(crate) type SignedParts = (ObjectIdentifier, Vec<u8>, Vec<u8>);
+
+/// Why a certificate chain failed to validate. Certificates are named by
+/// their subject.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChainError {
+  /// A certificate is not a valid DER encoded X.509 certificate.
+  Malformed,
+  /// No intermediate or trust anchor has the issuer name of `subject`.
+  IssuerNotFound { subject: String },
+  /// None of the certificates named as the issuer of `subject` verify
+  /// its signature.
+  BadSignature { subject: String },
+  /// The signature algorithm of `subject` is not supported.
+  UnsupportedAlgorithm { subject: String },
+  /// `subject` is not yet valid at the validation time.
+  NotYetValid { subject: String },
+  /// `subject` has expired at the validation time.
+  Expired { subject: String },
+  /// `subject` issues a certificate, but its basic constraints or key
+  /// usage do not allow it to.
+  NotCa { subject: String },
+  /// More CA certificates follow `subject` than its path length
+  /// constraint allows.
+  PathLengthExceeded { subject: String },
+  /// `subject` has a critical extension this crate does not process.
+  UnknownCriticalExtension { subject: String },
+  /// A name of `subject` is outside the name constraints of an issuer.
+  NameNotPermitted { subject: String },
+  /// The path is longer than this crate is willing to search.
+  PathTooLong,
+}
+
+/// A certificate, parsed, with its encoding as received.
+struct Parsed<'a> {
+  original: &'a Certificate,
+  certificate: x509_cert::Certificate,
+}
+
+/// The encoding of the first element of the DER SEQUENCE `data`, as
+/// received: the signed data of a SIGNED{} structure. Signatures are
+/// checked over these bytes, never over a re-encoding.
+fn signed_data(data: &[u8]) -> Option<&[u8]> {
+  let mut reader = SliceReader::new(data).ok()?;
+  Header::decode(&mut reader)
+    .ok()?
+    .tag
+    .assert_eq(Tag::Sequence)
+    .ok()?;
+  let start = reader.position();
+  let header = Header::decode(&mut reader).ok()?;
+  let end = (reader.position() + header.length).ok()?;
+  data.get(usize::try_from(start).ok()?..usize::try_from(end).ok()?)
+}
+
+fn subject(certificate: &x509_cert::Certificate) -> String {
+  certificate.tbs_certificate.subject.to_string()
+}
+
+fn extension<T: AssociatedOid + for<'a> Decode<'a>>(
+  certificate: &x509_cert::Certificate,
+) -> Result<Option<T>, ChainError> {
+  let extension = certificate
+    .tbs_certificate
+    .extensions
+    .iter()
+    .flatten()
+    .find(|extension| extension.extn_id == T::OID);
+  match extension {
+    Some(extension) => T::from_der(extension.extn_value.as_bytes())
+      .map(Some)
+      .map_err(|_| ChainError::Malformed),
+    None => Ok(None),
+  }
+}
+
+fn check_validity(
+  certificate: &x509_cert::Certificate,
+  time: SystemTime,
+) -> Result<(), ChainError> {
+  let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+  let validity = &certificate.tbs_certificate.validity;
+
+  if time < validity.not_before.to_unix_duration() {
+    return Err(ChainError::NotYetValid {
+      subject: subject(certificate),
+    });
+  }
+  if time > validity.not_after.to_unix_duration() {
+    return Err(ChainError::Expired {
+      subject: subject(certificate),
+    });
+  }
+
+  Ok(())
+}
+
+/// Check that `issuer` may sign CA certificates, with `below` CA
+/// certificates between it and the leaf.
+fn check_ca(
+  issuer: &x509_cert::Certificate,
+  below: usize,
+) -> Result<(), ChainError> {
+  let basic_constraints = extension::<BasicConstraints>(issuer)?;
+  let key_usage = extension::<KeyUsage>(issuer)?;
+
+  let constraints = match basic_constraints {
+    Some(constraints) if constraints.ca => constraints,
+    _ => {
+      return Err(ChainError::NotCa {
+        subject: subject(issuer),
+      })
+    }
+  };
+  if matches!(key_usage, Some(key_usage) if !key_usage.key_cert_sign()) {
+    return Err(ChainError::NotCa {
+      subject: subject(issuer),
+    });
+  }
+  if matches!(constraints.path_len_constraint, Some(max) if below > max as usize)
+  {
+    return Err(ChainError::PathLengthExceeded {
+      subject: subject(issuer),
+    });
+  }
+
+  Ok(())
+}
+
+/// Reject certificates with critical extensions that are not processed.
+fn check_extensions(
+  certificate: &x509_cert::Certificate,
+) -> Result<(), ChainError> {
+  let unknown =
+    certificate
+      .tbs_certificate
+      .extensions
+      .iter()
+      .flatten()
+      .any(|extension| {
+        extension.critical
+          && !UNDERSTOOD_EXTENSIONS.contains(&extension.extn_id)
+      });
+  if unknown {
+    return Err(ChainError::UnknownCriticalExtension {
+      subject: subject(certificate),
+    });
+  }
+
+  Ok(())
+}
+
+/// Whether the DNS name `name` is within the subtree `constraint`: the
+/// domain itself and its subdomains, or only the subdomains of a
+/// constraint starting with a dot.
+fn dns_name_matches(constraint: &str, name: &str) -> bool {
+  let constraint = constraint.to_ascii_lowercase();
+  let name = name.to_ascii_lowercase();
+  if constraint.is_empty() || constraint.starts_with('.') {
+    return name.ends_with(&constraint);
+  }
+  name == constraint || name.ends_with(&format!(".{}", constraint))
+}
+
+/// Whether the email address `name` is within the subtree `constraint`:
+/// a mailbox, a host, or the subdomains of a domain starting with a dot.
+fn email_matches(constraint: &str, name: &str) -> bool {
+  let constraint = constraint.to_ascii_lowercase();
+  let name = name.to_ascii_lowercase();
+  if constraint.contains('@') {
+    return name == constraint;
+  }
+  match name.rsplit_once('@') {
+    Some((_, host)) if constraint.starts_with('.') => {
+      host.ends_with(&constraint)
+    }
+    Some((_, host)) => host == constraint,
+    None => false,
+  }
+}
+
+/// Whether `name` is within the subtree `base`, or `None` for subtrees
+/// this crate does not process.
+fn name_matches(base: &GeneralName, name: &GeneralName) -> Option<bool> {
+  match (base, name) {
+    (GeneralName::DnsName(base), GeneralName::DnsName(name)) => {
+      Some(dns_name_matches(base.as_str(), name.as_str()))
+    }
+    (GeneralName::Rfc822Name(base), GeneralName::Rfc822Name(name)) => {
+      Some(email_matches(base.as_str(), name.as_str()))
+    }
+    (GeneralName::IpAddress(base), GeneralName::IpAddress(name)) => {
+      // An address followed by its mask.
+      let (address, mask) = base.as_bytes().split_at(base.as_bytes().len() / 2);
+      let name = name.as_bytes();
+      Some(
+        address.len() == name.len()
+          && (0..name.len()).all(|i| name[i] & mask[i] == address[i] & mask[i]),
+      )
+    }
+    (GeneralName::DirectoryName(base), GeneralName::DirectoryName(name)) => {
+      Some(name.0.starts_with(&base.0))
+    }
+    _ => None,
+  }
+}
+
+/// Check the names of the certificates in `path` against the name
+/// constraints of their issuer `issuer`.
+fn check_name_constraints(
+  issuer: &x509_cert::Certificate,
+  path: &[&Parsed],
+) -> Result<(), ChainError> {
+  let constraints = match extension::<NameConstraints>(issuer)? {
+    Some(constraints) => constraints,
+    None => return Ok(()),
+  };
+  let permitted = constraints.permitted_subtrees.unwrap_or_default();
+  let excluded = constraints.excluded_subtrees.unwrap_or_default();
+
+  for (index, parsed) in path.iter().enumerate() {
+    let certificate = &parsed.certificate;
+    let tbs_certificate = &certificate.tbs_certificate;
+    // Self-issued intermediates are exempt (RFC 5280, section 6.1.4).
+    if index > 0 && tbs_certificate.subject == tbs_certificate.issuer {
+      continue;
+    }
+
+    let mut names = extension::<SubjectAltName>(certificate)?
+      .map(|names| names.0)
+      .unwrap_or_default();
+    if !tbs_certificate.subject.0.is_empty() {
+      names.push(GeneralName::DirectoryName(tbs_certificate.subject.clone()));
+    }
+
+    for name in &names {
+      let of_kind = |subtrees: &[GeneralSubtree]| {
+        subtrees
+          .iter()
+          .filter(|subtree| discriminant(&subtree.base) == discriminant(name))
+          .map(|subtree| name_matches(&subtree.base, name))
+          .collect::<Vec<_>>()
+      };
+      let excluded = of_kind(&excluded);
+      let permitted = of_kind(&permitted);
+      // Subtrees that are not processed fail the names they would cover.
+      let allowed = excluded.iter().all(|matches| *matches == Some(false))
+        && (permitted.is_empty() || permitted.contains(&Some(true)));
+      if !allowed {
+        return Err(ChainError::NameNotPermitted {
+          subject: subject(certificate),
+        });
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Searches a path from a leaf to a trust anchor.
+struct PathSearch<'a> {
+  trust_anchors: &'a [Parsed<'a>],
+  intermediates: &'a [Parsed<'a>],
+  time: SystemTime,
+  /// Issuer signatures left to check.
+  signatures: usize,
+}
+
+impl<'a> PathSearch<'a> {
+  /// Complete `path` up to a trust anchor, trying every certificate named
+  /// as the issuer of its last certificate until one leads to a trust
+  /// anchor.
+  fn extend(
+    &mut self,
+    path: &mut Vec<&'a Parsed<'a>>,
+  ) -> Result<(), ChainError> {
+    let certificate = &path[path.len() - 1].certificate;
+    check_validity(certificate, self.time)?;
+
+    // A trusted leaf is its own path.
+    if path.len() == 1
+      && self
+        .trust_anchors
+        .iter()
+        .any(|anchor| anchor.original.der == path[0].original.der)
+    {
+      return Ok(());
+    }
+    check_extensions(certificate)?;
+    if path.len() > MAX_PATH_LENGTH {
+      return Err(ChainError::PathTooLong);
+    }
+
+    let subject = || subject(certificate);
+    let hash = signature_hash(&certificate.signature_algorithm.oid)
+      .map_err(|_| ChainError::UnsupportedAlgorithm { subject: subject() })?;
+    let tbs_certificate = signed_data(&path[path.len() - 1].original.der)
+      .ok_or(ChainError::Malformed)?;
+    let signature = certificate.signature.raw_bytes();
+
+    let candidates = self
+      .trust_anchors
+      .iter()
+      .map(|candidate| (candidate, true))
+      .chain(
+        self
+          .intermediates
+          .iter()
+          .map(|candidate| (candidate, false)),
+      );
+
+    // The error of the last issuer whose signature verified, if any.
+    let mut error = ChainError::IssuerNotFound { subject: subject() };
+    let mut verified = false;
+    for (candidate, trusted) in candidates {
+      // A certificate cannot appear twice in a path.
+      if candidate.certificate.tbs_certificate.subject
+        != certificate.tbs_certificate.issuer
+        || path
+          .iter()
+          .any(|parsed| parsed.original.der == candidate.original.der)
+      {
+        continue;
+      }
+      if self.signatures == 0 {
+        return Err(ChainError::PathTooLong);
+      }
+      self.signatures -= 1;
+
+      let signature_error = match verify_signature(
+        &candidate.certificate,
+        hash,
+        tbs_certificate,
+        signature,
+      ) {
+        Ok(true) => None,
+        Ok(false) => Some(ChainError::BadSignature { subject: subject() }),
+        Err(_) => Some(ChainError::UnsupportedAlgorithm { subject: subject() }),
+      };
+      if let Some(signature_error) = signature_error {
+        if !verified {
+          error = signature_error;
+        }
+        continue;
+      }
+
+      verified = true;
+      // Trust anchors are only trusted for their key and name, and their
+      // name constraints.
+      let result = if trusted {
+        check_name_constraints(&candidate.certificate, path)
+      } else {
+        check_ca(&candidate.certificate, path.len() - 1)
+          .and_then(|_| check_name_constraints(&candidate.certificate, path))
+      };
+      path.push(candidate);
+      match result
+        .and_then(|_| if trusted { Ok(()) } else { self.extend(path) })
+      {
+        Ok(()) => return Ok(()),
+        Err(path_error) => {
+          path.truncate(path.len() - 1);
+          error = path_error;
+        }
+      }
+    }
+
+    Err(error)
+  }
+}
+
+fn parse(certificates: &[Certificate]) -> Result<Vec<Parsed<'_>>, ChainError> {
+  certificates
+    .iter()
+    .map(|certificate| {
+      Ok(Parsed {
+        original: certificate,
+        certificate: parse_certificate(certificate)
+          .map_err(|_| ChainError::Malformed)?,
+      })
+    })
+    .collect()
+}
+
+/// Build and validate a path from `leaf` to one of `trust_anchors`
+/// through `intermediates` at `time`, returning it leaf first.
+pub(crate) fn verify_chain(
+  leaf: &Certificate,
+  intermediates: &[Certificate],
+  trust_anchors: &[Certificate],
+  time: SystemTime,
+) -> Result<Vec<Certificate>, ChainError> {
+  let intermediates = parse(intermediates)?;
+  let trust_anchors = parse(trust_anchors)?;
+  let leaf = parse(std::slice::from_ref(leaf))?;
+
+  let mut search = PathSearch {
+    trust_anchors: &trust_anchors,
+    intermediates: &intermediates,
+    time,
+    signatures: MAX_SIGNATURES,
+  };
+  let mut path = vec![&leaf[0]];
+  search.extend(&mut path)?;
+
+  Ok(path.iter().map(|parsed| parsed.original.clone()).collect())
+}
+
+/// Split a DER encoded CRL into its signed parts.
+pub(crate) fn crl_signature(
+  data: &[u8],
+) -> Result<SignedParts, WebCryptoError> {
+  CertificateList::from_der(data).map_err(|_| WebCryptoError::DataError)?;
+  let crl =
+    Signed::from_der(data).map_err(|_| WebCryptoError::OperationError)?;
+  let tbs = signed_data(data).ok_or(WebCryptoError::OperationError)?;
+
+  Ok((
+    crl.signature_algorithm.oid,
+    tbs.to_vec(),
+    crl.signature.raw_bytes().to_vec(),
+  ))
+}
+
+/// Split a DER encoded OCSP response into the signed parts of its
+/// BasicOCSPResponse.
+pub(crate) fn ocsp_signature(
+  data: &[u8],
+) -> Result<SignedParts, WebCryptoError> {
+  let response =
+    OcspResponse::from_der(data).map_err(|_| WebCryptoError::DataError)?;
+  // Unsuccessful responses are not signed.
+  let response_bytes = match response.response_bytes {
+    Some(response_bytes) if response.response_status.value() == [0] => {
+      response_bytes
+    }
+    _ => {
+      return Err(WebCryptoError::DataError);
+    }
+  };
+  if response_bytes.response_type != ID_PKIX_OCSP_BASIC {
+    return Err(WebCryptoError::NotSupportedError);
+  }
+
+  let response = response_bytes.response.as_bytes();
+  let basic = BasicOcspResponse::from_der(response)
+    .map_err(|_| WebCryptoError::OperationError)?;
+  let tbs = signed_data(response).ok_or(WebCryptoError::OperationError)?;
+
+  Ok((
+    basic.signature_algorithm.oid,
+    tbs.to_vec(),
+    basic.signature.raw_bytes().to_vec(),
+  ))
+}
@@ -0,0 +1,129 @@
+use ml_kem::array::Array;
+use ml_kem::kem::Decapsulate;
+use ml_kem::kem::Encapsulate;
+use ml_kem::EncodedSizeUser;
+use ml_kem::KemCore;
+use ml_kem::MlKem768;
+
+use rand::CryptoRng;
+use rand::RngCore;
+
+use sha3::Digest;
+use sha3::Sha3_256;
+
+use crate::WebCryptoError;
+use x25519_dalek::x25519;
+use x25519_dalek::X25519_BASEPOINT_BYTES;
+
+// X-Wing (draft-connolly-cfrg-xwing-kem): ML-KEM-768 and X25519, with
+// both shared secrets bound to the X25519 ciphertext and public key by
+// SHA3-256.
+//
+// Public keys are the ML-KEM-768 encapsulation key followed by the X25519
+// public key. Private keys are stored expanded, as the ML-KEM-768
+// decapsulation key, the X25519 secret and the X25519 public key.
+
+type EncapsulationKey = <MlKem768 as KemCore>::EncapsulationKey;
+type DecapsulationKey = <MlKem768 as KemCore>::DecapsulationKey;
+
+const ML_KEM_PUBLIC_KEY_LENGTH: usize = 1184;
+const ML_KEM_PRIVATE_KEY_LENGTH: usize = 2400;
+const ML_KEM_CIPHERTEXT_LENGTH: usize = 1088;
+
+pub(crate) const PUBLIC_KEY_LENGTH: usize = ML_KEM_PUBLIC_KEY_LENGTH + 32;
+const PRIVATE_KEY_LENGTH: usize = ML_KEM_PRIVATE_KEY_LENGTH + 64;
+const CIPHERTEXT_LENGTH: usize = ML_KEM_CIPHERTEXT_LENGTH + 32;
+
+/// `\.//^\`
+const LABEL: &[u8] = b"\\.//^\\";
+
+fn combiner(ss_m: &[u8], ss_x: &[u8], ct_x: &[u8], pk_x: &[u8]) -> Vec<u8> {
+  let mut hasher = Sha3_256::new();
+  hasher.update(ss_m);
+  hasher.update(ss_x);
+  hasher.update(ct_x);
+  hasher.update(pk_x);
+  hasher.update(LABEL);
+  hasher.finalize().to_vec()
+}
+
+fn x25519_key(bytes: &[u8]) -> [u8; 32] {
+  bytes.try_into().unwrap()
+}
+
+/// Generate a key pair, returning the private and the public key.
+pub(crate) fn generate<R: RngCore + CryptoRng>(
+  rng: &mut R,
+) -> (Vec<u8>, Vec<u8>) {
+  let (dk_m, ek_m) = MlKem768::generate(rng);
+  let mut sk_x = [0; 32];
+  rng.fill_bytes(&mut sk_x);
+  let pk_x = x25519(sk_x, X25519_BASEPOINT_BYTES);
+
+  let mut private_key = dk_m.as_bytes().to_vec();
+  private_key.extend_from_slice(&sk_x);
+  private_key.extend_from_slice(&pk_x);
+
+  let mut public_key = ek_m.as_bytes().to_vec();
+  public_key.extend_from_slice(&pk_x);
+
+  (private_key, public_key)
+}
+
+/// Create a shared secret for `public_key`, returning it and the
+/// ciphertext.
+pub(crate) fn encapsulate<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  public_key: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>), WebCryptoError> {
+  if public_key.len() != PUBLIC_KEY_LENGTH {
+    return Err(WebCryptoError::DataError);
+  }
+  let (pk_m, pk_x) = public_key.split_at(ML_KEM_PUBLIC_KEY_LENGTH);
+
+  let ek_m = EncapsulationKey::from_bytes(
+    &Array::try_from(pk_m).map_err(|_| WebCryptoError::OperationError)?,
+  );
+  let (ct_m, ss_m) = ek_m
+    .encapsulate(rng)
+    .map_err(|_| WebCryptoError::OperationError)?;
+
+  let mut ek_x = [0; 32];
+  rng.fill_bytes(&mut ek_x);
+  let ct_x = x25519(ek_x, X25519_BASEPOINT_BYTES);
+  let ss_x = x25519(ek_x, x25519_key(pk_x));
+
+  let shared_key = combiner(&ss_m, &ss_x, &ct_x, pk_x);
+
+  let mut ciphertext = ct_m.to_vec();
+  ciphertext.extend_from_slice(&ct_x);
+
+  Ok((shared_key, ciphertext))
+}
+
+/// Recover the shared secret of `ciphertext` with `private_key`.
+pub(crate) fn decapsulate(
+  private_key: &[u8],
+  ciphertext: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  if private_key.len() != PRIVATE_KEY_LENGTH
+    || ciphertext.len() != CIPHERTEXT_LENGTH
+  {
+    return Err(WebCryptoError::OperationError);
+  }
+  let (dk_m, x) = private_key.split_at(ML_KEM_PRIVATE_KEY_LENGTH);
+  let (sk_x, pk_x) = x.split_at(32);
+  let (ct_m, ct_x) = ciphertext.split_at(ML_KEM_CIPHERTEXT_LENGTH);
+
+  let dk_m = DecapsulationKey::from_bytes(
+    &Array::try_from(dk_m).map_err(|_| WebCryptoError::OperationError)?,
+  );
+  let ss_m = dk_m
+    .decapsulate(
+      &Array::try_from(ct_m).map_err(|_| WebCryptoError::OperationError)?,
+    )
+    .map_err(|_| WebCryptoError::OperationError)?;
+  let ss_x = x25519(x25519_key(sk_x), x25519_key(ct_x));
+
+  Ok(combiner(&ss_m, &ss_x, ct_x, pk_x))
+}
@@ -0,0 +1,54 @@
+//! Small helpers applications otherwise write around the crate, and
+//! easily get subtly wrong: comparing secrets, one-shot MACs and random
+//! tokens.
+
+use rand::CryptoRng;
+use rand::RngCore;
+
+use crate::subtle;
+use crate::WebCryptoError;
+
+/// Compare `a` and `b` in time independent of their contents, eg. a
+/// received tag against the expected one. Only the lengths may leak.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len()
+    && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The HMAC of `data` under the raw `key` with the digest algorithm
+/// `hash`, eg. "SHA-256", without importing the key.
+pub fn hmac_tag(
+  hash: &str,
+  key: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, WebCryptoError> {
+  subtle::hmac(hash, key, data)
+}
+
+/// Check the HMAC `tag` of `data` in constant time, see `hmac_tag`.
+pub fn hmac_verify(
+  hash: &str,
+  key: &[u8],
+  data: &[u8],
+  tag: &[u8],
+) -> Result<bool, WebCryptoError> {
+  let expected = hmac_tag(hash, key, data)?;
+  Ok(constant_time_eq(&expected, tag))
+}
+
+/// A random token of `length` bytes from `rng`, base64url encoded
+/// without padding so it fits in URLs and file names, eg. for session
+/// IDs or password reset links. Tokens shorter than 16 bytes are guessable
+/// and rejected.
+pub fn random_token<R: RngCore + CryptoRng>(
+  rng: &mut R,
+  length: usize,
+) -> Result<String, WebCryptoError> {
+  if length < 16 {
+    return Err(WebCryptoError::OperationError);
+  }
+
+  let mut bytes = vec![0; length];
+  rng.fill_bytes(&mut bytes);
+  Ok(base64::encode_config(bytes, base64::URL_SAFE_NO_PAD))
+}